@@ -86,6 +86,8 @@ pub async fn seed(runtime: &Arc<KuiperRuntime>) -> anyhow::Result<()> {
         activity_id: Uuid::new_v4(),
         caller_id: None,
         is_internal: true,
+        defer_observers: false,
+        write_batch: None,
         cancellation_token: CancellationToken::new(),
     };
 
@@ -5,7 +5,9 @@
 //! * **Built-in** `VirtualMachineCluster` `ResourceDefinition` (seeded on startup).
 //! * **In-process mutating admission** — injects spec defaults before persisting.
 //! * **In-process validating admission** — enforces invariants after mutation.
-//! * **Background cleanup loop** — reconciles soft-deleted resources every 30 s.
+//! * **Cleanup controller** — hard-deletes soft-deleted clusters as soon as
+//!   their finalizers are gone, run under a `ServiceHost` on the replica that
+//!   holds the `vmc-control-plane` leader lease.
 //! * **HTTP API** — identical surface to `resource-server` (REST + WebSocket).
 //!
 //! # Running
@@ -20,18 +22,21 @@ mod cleanup;
 
 use std::{sync::Arc, thread};
 
-use actix_web::{middleware::Logger, App, HttpServer};
+use actix_web::{middleware::Logger, web, App, HttpServer};
 use dashmap::DashMap;
 use kuiper_runtime::{data::file_system_store::FileSystemStore, KuiperConfig};
 use resource_server::{
     commands::observer::{DeleteObserverCommand, SetObserverCommand},
     configure_app, SubscriberMap, SubscriptionMap,
 };
-use resource_server_runtime::KuiperRuntimeBuilder;
+use resource_server_runtime::{
+    services::{LeaderElectionOptions, LeaderElector},
+    KuiperRuntimeBuilder,
+};
 
 use admission::{VmcMutatingAdmission, VmcValidatingAdmission};
-use cleanup::VmcCleanupService;
-use kuiper_runtime::service::HostedService;
+use cleanup::VmcCleanupReconciler;
+use kuiper_runtime::service::{shutdown_signal, ServiceHost, Singleton};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -64,7 +69,8 @@ async fn main() -> std::io::Result<()> {
     //   3. AdmissionWebhookCommand(Validator, priority 1) — call webhook policies
     //   4. SchemaValidationCommand(Validator, priority 1) — JSON Schema check
     //   5. SetCommand             (Internal,  priority 2) — write to store
-    //   6. SetObserverCommand     (Observer,  priority 4) — notify WS clients
+    //   6. SetObserverCommand     (Observer,  priority 4) — notify WS clients,
+    //                                                       delivered via the outbox
     //
     let mut builder = KuiperRuntimeBuilder::new(shared_store.clone());
 
@@ -95,9 +101,37 @@ async fn main() -> std::io::Result<()> {
             subscription_map.clone(),
         )),
     );
+    builder.register_handler(
+        "set_status",
+        Arc::new(SetObserverCommand::new(
+            shared_store.clone(),
+            subscribers.clone(),
+            subscription_map.clone(),
+        )),
+    );
+    builder.register_handler(
+        "finalize",
+        Arc::new(SetObserverCommand::new(
+            shared_store.clone(),
+            subscribers.clone(),
+            subscription_map.clone(),
+        )),
+    );
 
-    // Reconcile command — needed by the embedded cleanup loop.
-    builder.with_reconciliation();
+    // Hard-delete soft-deleted clusters whose finalizers are gone.
+    builder.with_controller(
+        VmcCleanupReconciler::options(),
+        Arc::new(VmcCleanupReconciler),
+    );
+
+    // Deliver observers from the durable outbox rather than inline.
+    builder.with_outbox();
+
+    // Delete dependents of deleted clusters through their ownerReferences.
+    builder.with_garbage_collector();
+
+    // Rewrite stored clusters when their storage version changes.
+    builder.with_storage_migration();
 
     let runtime = Arc::new(builder.build());
 
@@ -117,14 +151,55 @@ async fn main() -> std::io::Result<()> {
         )
     })?;
 
-    // ── Background cleanup service ────────────────────────────────────────────
-    let cleanup = VmcCleanupService::new(runtime.clone());
-    cleanup.start().await.map_err(|e| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Failed to start cleanup service: {}", e),
-        )
-    })?;
+    // ── Hosted services ───────────────────────────────────────────────────────
+    //
+    // Controllers write through the runtime, so they start after outbox
+    // delivery and stop before it. With several replicas only the one holding
+    // the leader lease runs them.
+    let elector = LeaderElector::new(
+        shared_store.clone(),
+        LeaderElectionOptions::new("vmc-control-plane"),
+    );
+    let mut host = ServiceHost::new();
+    host.register(
+        "outbox",
+        runtime
+            .outbox_service()
+            .expect("outbox is enabled on the runtime builder"),
+    );
+    host.register("leader-election", elector.clone());
+    for controller in runtime.controllers() {
+        let name = controller.name().to_string();
+        host.register(&name, Singleton::new(elector.clone(), controller))
+            .depends_on("outbox")
+            .depends_on("leader-election");
+    }
+    host.register(
+        "garbage-collector",
+        Singleton::new(
+            elector.clone(),
+            runtime
+                .garbage_collector()
+                .expect("garbage collector is enabled on the runtime builder"),
+        ),
+    )
+    .depends_on("outbox")
+    .depends_on("leader-election");
+    host.register(
+        "storage-migration",
+        Singleton::new(
+            elector.clone(),
+            runtime
+                .storage_migrator()
+                .expect("storage migration is enabled on the runtime builder"),
+        ),
+    )
+    .depends_on("leader-election");
+
+    let host = Arc::new(host);
+    host.start()
+        .await
+        .map_err(|e| std::io::Error::other(format!("Failed to start hosted services: {}", e)))?;
 
     // ── HTTP server ───────────────────────────────────────────────────────────
     let port: u16 = std::env::var("VMC_PORT")
@@ -135,12 +210,14 @@ async fn main() -> std::io::Result<()> {
 
     tracing::warn!(">> Listening on {}:{}", ip, port);
 
+    let host_data = host.clone();
     let server = HttpServer::new(move || {
         let rt = runtime.clone();
         let subs = subscribers.clone();
         let sub_map = subscription_map.clone();
 
         App::new()
+            .app_data(web::Data::new(host_data.clone()))
             .configure(move |cfg| configure_app(cfg, rt.clone(), subs.clone(), sub_map.clone()))
             .wrap(
                 actix_web::middleware::DefaultHeaders::new()
@@ -154,19 +231,24 @@ async fn main() -> std::io::Result<()> {
             .wrap(resource_server::middleware::catch_panic::CatchPanic::default())
     })
     .workers(count)
+    .disable_signals()
     .bind((ip, port))?;
 
     tracing::warn!(">> vmc-control-plane v{} ready", env!("CARGO_PKG_VERSION"),);
 
-    server.run().await?;
+    let server = server.run();
+    let server_handle = server.handle();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::warn!(">> Shutting down...");
+        server_handle.stop(true).await;
+    });
+    server.await?;
 
     // ── Graceful shutdown ─────────────────────────────────────────────────────
-    cleanup.stop().await.map_err(|e| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Cleanup service shutdown error: {}", e),
-        )
-    })?;
+    host.stop()
+        .await
+        .map_err(|e| std::io::Error::other(format!("Hosted service shutdown error: {}", e)))?;
 
     Ok(())
 }
@@ -7,7 +7,7 @@ use kuiper_runtime::{
     },
     data::InMemoryStore,
 };
-use resource_server_runtime::KuiperRuntimeBuilder;
+use resource_server_runtime::{KuiperRuntime, KuiperRuntimeBuilder};
 use serde_json::json;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -91,6 +91,14 @@ impl TestResult {
     }
 }
 
+/// A runtime that accepts objects of kinds no `ResourceDefinition`
+/// registers, as the resource tests below write.
+fn unregistered_kinds_runtime(store: Arc<RwLock<InMemoryStore>>) -> KuiperRuntime {
+    let mut builder = KuiperRuntimeBuilder::new(store);
+    builder.with_strict_mode(false);
+    builder.build()
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -127,6 +135,8 @@ async fn test_command_context_creation() -> TestResult {
         caller_id: None,
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let passed = ctx.command_name == "test" && !ctx.activity_id.is_nil();
@@ -153,6 +163,8 @@ async fn test_echo_command() -> TestResult {
 
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let result = runtime.execute(&mut ctx).await;
@@ -174,7 +186,7 @@ async fn test_set_command() -> TestResult {
     let start = std::time::Instant::now();
 
     let store = Arc::new(RwLock::new(InMemoryStore::new()));
-    let runtime = KuiperRuntimeBuilder::new(store).build();
+    let runtime = unregistered_kinds_runtime(store);
 
     let resource_json = json!({
         "apiVersion": "v1",
@@ -208,6 +220,8 @@ async fn test_set_command() -> TestResult {
         caller_id: None,
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let result = runtime.execute(&mut ctx).await;
@@ -225,7 +239,7 @@ async fn test_set_then_get() -> TestResult {
     let start = std::time::Instant::now();
 
     let store = Arc::new(RwLock::new(InMemoryStore::new()));
-    let runtime = KuiperRuntimeBuilder::new(store).build();
+    let runtime = unregistered_kinds_runtime(store);
 
     let test_data = json!({
         "apiVersion": "v1",
@@ -255,6 +269,8 @@ async fn test_set_then_get() -> TestResult {
         caller_id: None,
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let set_result = runtime.execute(&mut set_ctx).await;
@@ -287,6 +303,8 @@ async fn test_set_then_get() -> TestResult {
         caller_id: None,
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let get_result = runtime.execute(&mut get_ctx).await;
@@ -324,6 +342,8 @@ async fn test_version_command() -> TestResult {
         caller_id: None,
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let result = runtime.execute(&mut ctx).await;
@@ -363,6 +383,8 @@ async fn test_list_empty() -> TestResult {
         caller_id: None,
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let result = runtime.execute(&mut ctx).await;
@@ -390,7 +412,7 @@ async fn test_delete_command() -> TestResult {
     let start = std::time::Instant::now();
 
     let store = Arc::new(RwLock::new(InMemoryStore::new()));
-    let runtime = KuiperRuntimeBuilder::new(store).build();
+    let runtime = unregistered_kinds_runtime(store);
 
     let test_data = json!({
         "apiVersion": "v1",
@@ -419,6 +441,8 @@ async fn test_delete_command() -> TestResult {
         caller_id: None,
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let _ = runtime.execute(&mut set_ctx).await;
@@ -444,6 +468,8 @@ async fn test_delete_command() -> TestResult {
 
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let delete_result = runtime.execute(&mut delete_ctx).await;
@@ -471,6 +497,8 @@ async fn test_nonexistent_command() -> TestResult {
         caller_id: None,
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let result = runtime.execute(&mut ctx).await;
@@ -499,6 +527,8 @@ async fn test_activity_id_tracking() -> TestResult {
         caller_id: None,
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let _ = runtime.execute(&mut ctx).await;
@@ -516,7 +546,7 @@ async fn test_multiple_resources_list() -> TestResult {
     let start = std::time::Instant::now();
 
     let store = Arc::new(RwLock::new(InMemoryStore::new()));
-    let runtime = KuiperRuntimeBuilder::new(store).build();
+    let runtime = unregistered_kinds_runtime(store);
 
     // Create multiple resources
     for i in 0..3 {
@@ -549,6 +579,8 @@ async fn test_multiple_resources_list() -> TestResult {
             caller_id: None,
             cancellation_token: CancellationToken::new(),
             is_internal: false,
+            defer_observers: false,
+            write_batch: None,
         };
         let _ = runtime.execute(&mut ctx).await;
     }
@@ -570,6 +602,8 @@ async fn test_multiple_resources_list() -> TestResult {
         caller_id: None,
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     let result = runtime.execute(&mut list_ctx).await;
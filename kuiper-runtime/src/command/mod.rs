@@ -1,11 +1,17 @@
+pub mod typed;
+
+pub use typed::{parse_response, respond, short_type_name, CommandRequest, Typed, TypedCommand};
+
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 use kuiper_types::model::security::UserId;
 
+use crate::data::WriteBatch;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct CommandContext {
     pub command_name: String,
@@ -20,6 +26,18 @@ pub struct CommandContext {
     #[serde(skip)]
     pub is_internal: bool,
 
+    /// Set by the executor when the command's observers are delivered from the
+    /// durable outbox instead of inline. Handlers that write the store stage an
+    /// [`OutboxEntry`](crate::outbox::OutboxEntry) in the same transaction.
+    #[serde(skip)]
+    pub defer_observers: bool,
+
+    /// Set when the command runs as part of a batch. Store writes are staged
+    /// into the batch and committed together once every item succeeded;
+    /// observers run after that commit.
+    #[serde(skip)]
+    pub write_batch: Option<WriteBatch>,
+
     #[serde(skip)]
     pub cancellation_token: CancellationToken,
 }
@@ -59,6 +77,34 @@ pub trait CommandDispatcher: Send + Sync {
     async fn dispatch(&self, ctx: &mut CommandContext) -> CommandResult;
 }
 
+/// A cross-cutting step wrapped around every dispatched command, in the order
+/// stages were registered. Stages see the command before any handler runs and
+/// its final outcome afterwards; call [`Next::run`] to continue the pipeline.
+#[async_trait]
+pub trait PipelineStage: Send + Sync {
+    async fn handle(&self, ctx: &mut CommandContext, next: Next<'_>) -> CommandResult;
+}
+
+/// The remainder of the pipeline after the current [`PipelineStage`].
+pub struct Next<'a> {
+    stages: &'a [Arc<dyn PipelineStage>],
+    endpoint: &'a dyn CommandDispatcher,
+}
+
+impl<'a> Next<'a> {
+    /// Builds a pipeline that runs `stages` in order and then `endpoint`.
+    pub fn new(stages: &'a [Arc<dyn PipelineStage>], endpoint: &'a dyn CommandDispatcher) -> Self {
+        Self { stages, endpoint }
+    }
+
+    pub async fn run(self, ctx: &mut CommandContext) -> CommandResult {
+        match self.stages.split_first() {
+            Some((stage, rest)) => stage.handle(ctx, Next::new(rest, self.endpoint)).await,
+            None => self.endpoint.dispatch(ctx).await,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CommandType {
     /// Mutator commands are responsible for changing the state of the system.
@@ -117,6 +163,12 @@ pub trait ExecutableCommand: Send + Sync {
 pub trait CommandHandler: Send + Sync {
     fn get_type(&self) -> CommandType;
 
+    /// Name used for the handler's tracing span and metrics. Defaults to the
+    /// implementing type's name without its module path.
+    fn name(&self) -> &'static str {
+        short_type_name::<Self>()
+    }
+
     fn as_validator(&self) -> Option<&dyn ValidationCommand> {
         None
     }
@@ -128,4 +180,17 @@ pub trait CommandHandler: Send + Sync {
     fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
         None
     }
+
+    /// Returns `true` when the handler stages an outbox entry alongside its
+    /// store write whenever `CommandContext::defer_observers` is set. The
+    /// executor only defers observers for commands whose handlers do so.
+    fn writes_outbox(&self) -> bool {
+        false
+    }
+
+    /// JSON Schema of the command parameters this handler reads, published by
+    /// command introspection. Usually provided by the `Internal` handler.
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        None
+    }
 }
@@ -0,0 +1,178 @@
+//! Typed command inputs and outputs.
+//!
+//! A command's inputs travel as `CommandContext::parameters`, with the target
+//! namespace held separately in the trusted `metadata`. The helpers here map
+//! between that representation and plain serde structs, so handlers and
+//! callers never touch the maps directly and input errors read the same for
+//! every command.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use kuiper_types::error::KuiperError;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+use super::{CommandContext, CommandHandler, CommandResult, CommandType, ExecutableCommand};
+
+/// Parameter carried in `CommandContext::metadata` rather than `parameters`.
+const NAMESPACE: &str = "namespace";
+
+/// The typed inputs of a command, and the type of its result.
+pub trait CommandRequest: Serialize + DeserializeOwned + Send + Sync {
+    /// Name the command is registered under.
+    const COMMAND: &'static str;
+
+    type Response: DeserializeOwned + Send;
+}
+
+/// Strips the module path (and generic arguments) from a type name.
+pub fn short_type_name<T: ?Sized>() -> &'static str {
+    let full = std::any::type_name::<T>();
+    let path = full.split('<').next().unwrap_or(full);
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+impl CommandContext {
+    /// Builds the context for `request`. A `namespace` field is moved into
+    /// the metadata; every other field becomes a parameter.
+    pub fn from_request<R: CommandRequest>(request: &R) -> anyhow::Result<Self> {
+        let mut ctx = CommandContext {
+            command_name: R::COMMAND.to_string(),
+            activity_id: uuid::Uuid::new_v4(),
+            ..Default::default()
+        };
+
+        let Value::Object(fields) = serde_json::to_value(request)? else {
+            anyhow::bail!("Command request must serialize to an object");
+        };
+
+        for (name, value) in fields {
+            match (name.as_str(), value) {
+                (NAMESPACE, Value::String(namespace)) => {
+                    ctx.metadata.insert(NAMESPACE.to_string(), namespace);
+                }
+                (NAMESPACE, Value::Null) => {}
+                (_, value) => {
+                    ctx.parameters.insert(name, value);
+                }
+            }
+        }
+
+        Ok(ctx)
+    }
+
+    /// Deserializes the command's inputs into `T`. The parameters are merged
+    /// with the `namespace` from the metadata, which takes precedence over a
+    /// parameter of the same name.
+    pub fn request<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        let mut fields: Map<String, Value> = self
+            .parameters
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+
+        if let Some(namespace) = self.metadata.get(NAMESPACE) {
+            fields.insert(NAMESPACE.to_string(), Value::String(namespace.clone()));
+        }
+
+        serde_json::from_value(Value::Object(fields)).map_err(|e| {
+            KuiperError::Invalid(format!("command '{}': {}", self.command_name, e)).into()
+        })
+    }
+
+    /// Deserializes a single parameter. When it is absent, `T` is built from
+    /// `null`, so `Option<_>` parameters are simply `None`.
+    pub fn param<T: DeserializeOwned>(&self, name: &str) -> anyhow::Result<T> {
+        match self.parameters.get(name) {
+            Some(value) => T::deserialize(value).map_err(|e| {
+                KuiperError::Invalid(format!(
+                    "command '{}', parameter '{}': {}",
+                    self.command_name, name, e
+                ))
+                .into()
+            }),
+            None => T::deserialize(&Value::Null).map_err(|_| {
+                KuiperError::Invalid(format!(
+                    "command '{}': missing field `{}`",
+                    self.command_name, name
+                ))
+                .into()
+            }),
+        }
+    }
+}
+
+/// Converts a typed response into a [`CommandResult`]. `null` becomes `None`.
+pub fn respond<T: Serialize>(response: &T) -> CommandResult {
+    match serde_json::to_value(response)? {
+        Value::Null => Ok(None),
+        value => Ok(Some(value)),
+    }
+}
+
+/// Converts a command's result back into its typed response.
+pub fn parse_response<T: DeserializeOwned>(result: Option<Value>) -> anyhow::Result<T> {
+    serde_json::from_value(result.unwrap_or(Value::Null))
+        .map_err(|e| anyhow::anyhow!("Unexpected command response: {}", e))
+}
+
+/// A command handler with declared, typed inputs and output. Register it
+/// wrapped in [`Typed`]; the request is extracted with
+/// [`CommandContext::request`] before [`run`](Self::run) is called.
+#[async_trait]
+pub trait TypedCommand: Send + Sync {
+    type Request: DeserializeOwned + Send;
+    type Response: Serialize + Send;
+
+    fn get_type(&self) -> CommandType {
+        CommandType::Internal
+    }
+
+    /// JSON Schema of [`Request`](Self::Request), if the command publishes one.
+    fn input_schema(&self) -> Option<Value> {
+        None
+    }
+
+    async fn run(
+        &self,
+        ctx: &CommandContext,
+        request: Self::Request,
+    ) -> anyhow::Result<Self::Response>;
+}
+
+/// Adapts a [`TypedCommand`] to a [`CommandHandler`].
+pub struct Typed<C>(pub C);
+
+impl<C: TypedCommand + 'static> Typed<C> {
+    pub fn handler(command: C) -> Arc<dyn CommandHandler> {
+        Arc::new(Self(command))
+    }
+}
+
+impl<C: TypedCommand> CommandHandler for Typed<C> {
+    fn get_type(&self) -> CommandType {
+        self.0.get_type()
+    }
+
+    fn name(&self) -> &'static str {
+        short_type_name::<C>()
+    }
+
+    fn input_schema(&self) -> Option<Value> {
+        self.0.input_schema()
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl<C: TypedCommand> ExecutableCommand for Typed<C> {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let request = ctx.request::<C::Request>()?;
+        let response = self.0.run(ctx, request).await?;
+        respond(&response)
+    }
+}
@@ -7,6 +7,20 @@ pub struct KuiperConfig {
     /// Target database name inside the DocumentDB cluster.
     /// Set via `KUIPER_DOCUMENTDB_DATABASE`; defaults to `"kuiper"`.
     pub documentdb_database: String,
+    /// File that audit records are appended to as JSON lines.
+    /// Set via `KUIPER_AUDIT_LOG_PATH`; file auditing is disabled when unset.
+    pub audit_log_path: Option<String>,
+    /// Days audit records are kept in the store before they are purged.
+    /// Set via `KUIPER_AUDIT_RETENTION_DAYS`; defaults to `30`.
+    pub audit_retention_days: u64,
+    /// Whether writes of unregistered kinds and versions, or of kinds outside
+    /// their scope, are rejected.
+    /// Set via `KUIPER_STRICT_MODE`; on unless set to `false` or `0`.
+    pub strict_mode: bool,
+    /// Whether `POST /admin/rebuild-indexes` is served. It rescans the whole
+    /// store and requires no authentication.
+    /// Set via `KUIPER_INDEX_ADMIN`; off unless set to `true` or `1`.
+    pub index_admin: bool,
 }
 
 impl Default for KuiperConfig {
@@ -25,6 +39,18 @@ impl KuiperConfig {
                 .filter(|s| !s.is_empty()),
             documentdb_database: std::env::var("KUIPER_DOCUMENTDB_DATABASE")
                 .unwrap_or_else(|_| "kuiper".to_string()),
+            audit_log_path: std::env::var("KUIPER_AUDIT_LOG_PATH")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            audit_retention_days: std::env::var("KUIPER_AUDIT_RETENTION_DAYS")
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(30),
+            strict_mode: std::env::var("KUIPER_STRICT_MODE")
+                .map(|s| !matches!(s.trim(), "false" | "0"))
+                .unwrap_or(true),
+            index_admin: std::env::var("KUIPER_INDEX_ADMIN")
+                .is_ok_and(|s| matches!(s.trim(), "true" | "1")),
         }
     }
 }
@@ -12,12 +12,16 @@
 //! is converted back to a `serde_json::Value` and serialised to JSON bytes,
 //! giving a lossless round-trip for all types used by the resource model.
 //!
+//! Every write also stamps the document with a fresh `_etag`, which
+//! `compare_and_swap` makes its replace conditional on.
+//!
 //! MongoDB-backed persistent store for Kuiper resources.
 
 use anyhow::Context;
 use async_trait::async_trait;
 use mongodb::{
     bson::{self, doc, Document},
+    error::{ErrorKind, WriteFailure},
     options::FindOptions,
     Client, Collection, Database,
 };
@@ -26,20 +30,29 @@ use super::{StoreContainer, StoreKey, StoreOperation, StoreResult, StoreValue, T
 
 // ── Helpers ───────────────────────────────────────────────────────────────────
 
-/// Parse JSON bytes into a native BSON document and set `_id` to `key`.
-/// Every field in the original JSON becomes a queryable BSON field.
+/// Field holding the document's version, changed by every write.
+const ETAG_FIELD: &str = "_etag";
+
+/// MongoDB's error code for a duplicate `_id`.
+const DUPLICATE_KEY: i32 = 11000;
+
+/// Parse JSON bytes into a native BSON document, set `_id` to `key` and
+/// stamp a new `_etag`. Every field in the original JSON becomes a queryable
+/// BSON field.
 fn make_doc(key: &str, value: &StoreValue) -> anyhow::Result<Document> {
     let json: serde_json::Value =
         serde_json::from_slice(value).context("Store value is not valid JSON")?;
     let mut doc = bson::to_document(&json).context("Failed to convert JSON value to BSON")?;
     doc.insert("_id", key);
+    doc.insert(ETAG_FIELD, uuid::Uuid::new_v4().to_string());
     Ok(doc)
 }
 
-/// Convert a raw BSON document back to JSON bytes, stripping the `_id` field
-/// that was injected by [`make_doc`].
+/// Convert a raw BSON document back to JSON bytes, stripping the `_id` and
+/// `_etag` fields that were injected by [`make_doc`].
 fn doc_to_value(mut doc: Document) -> anyhow::Result<StoreValue> {
     doc.remove("_id");
+    doc.remove(ETAG_FIELD);
     let json: serde_json::Value =
         bson::from_document(doc).context("Failed to convert BSON document to JSON")?;
     serde_json::to_vec(&json).context("Failed to serialise JSON value")
@@ -263,6 +276,60 @@ impl TransactionalKeyValueStore for DocumentDbStore {
         Ok(value)
     }
 
+    /// Inserts when `expected` is `None`, losing to a concurrent insert on
+    /// the duplicate `_id`. Otherwise compares the stored value as JSON and
+    /// replaces the document only while its `_etag` is the one read.
+    async fn compare_and_swap(
+        &self,
+        container: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        value: StoreValue,
+    ) -> StoreResult<bool> {
+        let collection = self.collection(container);
+        let doc = make_doc(key, &value)?;
+
+        let Some(expected) = expected else {
+            return match collection.insert_one(doc).await {
+                Ok(_) => Ok(true),
+                Err(e) => match &*e.kind {
+                    ErrorKind::Write(WriteFailure::WriteError(w)) if w.code == DUPLICATE_KEY => {
+                        Ok(false)
+                    }
+                    _ => Err(e).with_context(|| {
+                        format!("Failed to insert '{}' into '{}'", key, container)
+                    }),
+                },
+            };
+        };
+
+        let Some(current) = collection
+            .find_one(doc! { "_id": key })
+            .await
+            .with_context(|| format!("Failed to get '{}' from '{}'", key, container))?
+        else {
+            return Ok(false);
+        };
+
+        // Documents written before etags were stamped match on its absence.
+        let filter = match current.get(ETAG_FIELD) {
+            Some(etag) => doc! { "_id": key, ETAG_FIELD: etag.clone() },
+            None => doc! { "_id": key, ETAG_FIELD: { "$exists": false } },
+        };
+        let current: serde_json::Value = serde_json::from_slice(&doc_to_value(current)?)?;
+        let expected: serde_json::Value =
+            serde_json::from_slice(expected).context("Expected value is not valid JSON")?;
+        if current != expected {
+            return Ok(false);
+        }
+
+        let result = collection
+            .replace_one(filter, doc)
+            .await
+            .with_context(|| format!("Failed to swap '{}' in '{}'", key, container))?;
+        Ok(result.matched_count == 1)
+    }
+
     async fn delete(&self, container: &str, key: &str) -> StoreResult<()> {
         self.collection(container)
             .delete_one(doc! { "_id": key })
@@ -163,6 +163,43 @@ impl TransactionalKeyValueStore for FileSystemStore {
         Ok(value)
     }
 
+    /// Holds an OS lock on `{root}/.lock` while it reads and replaces the
+    /// key, so processes sharing the directory swap one at a time. The new
+    /// value is written beside the root and renamed into place.
+    async fn compare_and_swap(
+        &self,
+        container: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        value: StoreValue,
+    ) -> StoreResult<bool> {
+        let _guard = self.lock.lock().await;
+        let lock = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.root.join(".lock"))?;
+        lock.lock()?;
+
+        let path = self.key_path(container, key);
+        let current = match fs::read(&path) {
+            Ok(bytes) => Some(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        if current.as_deref() != expected {
+            return Ok(false);
+        }
+
+        fs::create_dir_all(path.parent().unwrap())?;
+        let staged = self
+            .root
+            .join(format!(".swap-{}", uuid::Uuid::new_v4().simple()));
+        fs::write(&staged, &value)?;
+        fs::rename(&staged, &path)?;
+        Ok(true)
+    }
+
     async fn delete(&self, container: &str, key: &str) -> StoreResult<()> {
         let container_path_buf = self.container_path(&container);
         let container_path = container_path_buf.as_path();
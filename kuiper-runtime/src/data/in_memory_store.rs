@@ -60,6 +60,22 @@ impl TransactionalKeyValueStore for InMemoryStore {
             .unwrap_or_default())
     }
 
+    async fn compare_and_swap(
+        &self,
+        container: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        value: StoreValue,
+    ) -> StoreResult<bool> {
+        let mut data = self.data.lock().unwrap();
+        let container_map = data.entry(container.to_string()).or_default();
+        if container_map.get(key).map(Vec::as_slice) != expected {
+            return Ok(false);
+        }
+        container_map.insert(key.to_string(), value);
+        Ok(true)
+    }
+
     async fn delete(&self, container: &str, key: &str) -> StoreResult<()> {
         let mut data = self.data.lock().unwrap();
         if let Some(container_map) = data.get_mut(container) {
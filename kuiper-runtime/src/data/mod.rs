@@ -1,9 +1,11 @@
 pub mod document_db_store;
 pub mod file_system_store;
 pub mod in_memory_store;
+pub mod write_batch;
 
 pub use document_db_store::DocumentDbStore;
 pub use in_memory_store::InMemoryStore;
+pub use write_batch::WriteBatch;
 
 use async_trait::async_trait;
 
@@ -34,6 +36,35 @@ pub trait TransactionalKeyValueStore: Send + Sync {
     async fn list_containers(&self) -> StoreResult<Vec<StoreContainer>>;
     async fn rename_container(&self, old: &str, new: &str) -> StoreResult<()>;
     async fn clear_container(&self, container: &str) -> StoreResult<()>;
+
+    /// Creates `container` if it does not exist yet.
+    async fn ensure_container(&self, container: &str) -> StoreResult<()> {
+        if !self.container_exists(container).await? {
+            self.new_container(container).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes `value` only if the stored value of `key` is still `expected`
+    /// (`None`: the key must not exist). Returns `false`, without writing,
+    /// when it is not. Must be atomic across every process sharing the store,
+    /// not only under the caller's lock: leader election and idempotency keys
+    /// rely on it. Stores that cannot guarantee that keep the default, which
+    /// fails.
+    async fn compare_and_swap(
+        &self,
+        container: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        value: StoreValue,
+    ) -> StoreResult<bool> {
+        let _ = (expected, value);
+        anyhow::bail!(
+            "This store does not support compare_and_swap (of {}/{})",
+            container,
+            key
+        )
+    }
 }
 
 pub struct Transaction<'a> {
@@ -62,6 +93,7 @@ impl<'a> Transaction<'a> {
 
     pub async fn commit(mut self) -> StoreResult<()> {
         if self.committed || self.staged_ops.is_empty() {
+            self.committed = true;
             return Ok(());
         }
 
@@ -72,6 +104,19 @@ impl<'a> Transaction<'a> {
         Ok(())
     }
 
+    /// Commits the transaction, or hands its operations to `batch` when the
+    /// command is part of a [`WriteBatch`] that commits them later.
+    pub async fn commit_or_stage(mut self, batch: Option<&WriteBatch>) -> StoreResult<()> {
+        match batch {
+            Some(batch) => {
+                batch.stage(std::mem::take(&mut self.staged_ops));
+                self.committed = true;
+                Ok(())
+            }
+            None => self.commit().await,
+        }
+    }
+
     pub fn rollback(mut self) {
         self.staged_ops.clear();
         self.committed = true;
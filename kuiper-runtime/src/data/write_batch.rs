@@ -0,0 +1,133 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use kuiper_types::error::KuiperError;
+
+use super::{StoreKey, StoreOperation, StoreResult, StoreValue, TransactionalKeyValueStore};
+
+/// Work run once a [`WriteBatch`] has committed.
+pub type AfterCommit = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+#[derive(Default)]
+struct WriteBatchState {
+    ops: Vec<StoreOperation>,
+
+    /// The stored value of every key at the time the batch first read it.
+    /// Re-checked at commit so concurrent writers cause a conflict.
+    reads: Vec<(String, StoreKey, Option<StoreValue>)>,
+
+    after_commit: Vec<AfterCommit>,
+}
+
+/// Store writes collected across several commands and committed as one
+/// transaction. Reads through the batch see its own staged writes.
+#[derive(Clone, Default)]
+pub struct WriteBatch {
+    state: Arc<Mutex<WriteBatchState>>,
+}
+
+impl std::fmt::Debug for WriteBatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let len = self.state.lock().map(|s| s.ops.len()).unwrap_or_default();
+        f.debug_struct("WriteBatch").field("ops", &len).finish()
+    }
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, WriteBatchState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the value of `key` as the batch would leave it: the latest
+    /// staged write if there is one, otherwise the stored value. `None` means
+    /// the key does not exist (or was deleted earlier in the batch).
+    pub async fn get(
+        &self,
+        store: &dyn TransactionalKeyValueStore,
+        container: &str,
+        key: &str,
+    ) -> Option<StoreValue> {
+        {
+            let state = self.state();
+            let staged = state.ops.iter().rev().find_map(|op| match op {
+                StoreOperation::Put(c, k, v) if c == container && k == key => Some(Some(v.clone())),
+                StoreOperation::Delete(c, k) if c == container && k == key => Some(None),
+                _ => None,
+            });
+            if let Some(value) = staged {
+                return value;
+            }
+
+            if let Some((_, _, value)) = state
+                .reads
+                .iter()
+                .find(|(c, k, _)| c == container && k == key)
+            {
+                return value.clone();
+            }
+        }
+
+        let value = store.get(container, key).await.ok();
+        self.state()
+            .reads
+            .push((container.to_string(), key.to_string(), value.clone()));
+        value
+    }
+
+    pub fn stage(&self, ops: Vec<StoreOperation>) {
+        self.state().ops.extend(ops);
+    }
+
+    /// Queues `work` to run after a successful commit. Dropped on rollback.
+    pub fn after_commit(&self, work: AfterCommit) {
+        self.state().after_commit.push(work);
+    }
+
+    /// Verifies that nothing the batch read has changed since, then commits
+    /// every staged write in a single store transaction. Fails with
+    /// [`KuiperError::Conflict`] on a concurrent modification, in which case
+    /// nothing is written. Hold the store's write lock across the call.
+    pub async fn commit(&self, store: &dyn TransactionalKeyValueStore) -> StoreResult<()> {
+        let (ops, reads) = {
+            let mut state = self.state();
+            (
+                std::mem::take(&mut state.ops),
+                std::mem::take(&mut state.reads),
+            )
+        };
+
+        for (container, key, expected) in reads {
+            let current = store.get(&container, &key).await.ok();
+            if current != expected {
+                return Err(KuiperError::Conflict(format!(
+                    "'{}' was modified concurrently; batch not applied",
+                    key
+                ))
+                .into());
+            }
+        }
+
+        if !ops.is_empty() {
+            store.commit_transaction(ops).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs the work queued with [`after_commit`](Self::after_commit). Call
+    /// after a successful [`commit`](Self::commit), once the store lock has
+    /// been released.
+    pub async fn run_after_commit(&self) {
+        let work = std::mem::take(&mut self.state().after_commit);
+        for item in work {
+            item.await;
+        }
+    }
+}
@@ -1,10 +1,12 @@
 pub mod command;
 mod config;
 pub mod data;
+pub mod outbox;
+pub mod scheduler;
 pub mod service;
 
 #[cfg(test)]
 mod tests;
 
 pub use config::KuiperConfig;
-pub use service::HostedService;
+pub use service::{HostedService, ServiceHost};
@@ -0,0 +1,150 @@
+//! Durable outbox for post-commit observer delivery.
+//!
+//! Handlers that write the store stage an [`OutboxEntry`] in the same
+//! [`Transaction`] as the change itself. A background delivery service later
+//! replays each entry through the command's observers and removes it once they
+//! succeed, so side effects are at-least-once and survive a process crash.
+//!
+//! Every entry records the [replica](replica_id) that staged it, which is the
+//! one that delivers it: observers may keep state of their own process, such
+//! as its WebSocket subscriptions. Deliverers claim an entry with a
+//! compare-and-swap before running its observers, so replicas sharing a store
+//! do not deliver it twice.
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use kuiper_types::model::security::UserId;
+
+use crate::command::CommandContext;
+use crate::data::{StoreKey, StoreResult, Transaction};
+
+/// Container holding entries that are waiting to be delivered.
+pub const OUTBOX_CONTAINER: &str = "outbox";
+
+/// Container holding entries that exhausted their delivery attempts.
+pub const OUTBOX_DEAD_LETTER_CONTAINER: &str = "outbox-dead-letter";
+
+/// Identifies this process among the replicas sharing a store.
+pub fn replica_id() -> &'static str {
+    static REPLICA_ID: OnceLock<String> = OnceLock::new();
+    REPLICA_ID.get_or_init(|| Uuid::new_v4().to_string())
+}
+
+/// A snapshot of a committed command, waiting to be handed to its observers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub id: Uuid,
+
+    #[serde(rename = "commandName")]
+    pub command_name: String,
+
+    #[serde(rename = "activityId")]
+    pub activity_id: Uuid,
+
+    #[serde(rename = "callerId", skip_serializing_if = "Option::is_none")]
+    pub caller_id: Option<UserId>,
+
+    /// Whether the originating command was internal. Entries are only ever
+    /// written by the runtime itself, so persisting the flag is safe.
+    #[serde(rename = "internal", default)]
+    pub is_internal: bool,
+
+    /// Command parameters as observers would have seen them inline, i.e. with
+    /// `value` replaced by the result of the write.
+    pub parameters: HashMap<String, Value>,
+
+    pub metadata: HashMap<String, String>,
+
+    /// Microseconds since the Unix epoch at which the entry was staged.
+    #[serde(rename = "enqueuedAt")]
+    pub enqueued_at: i64,
+
+    /// Number of failed delivery attempts so far.
+    #[serde(default)]
+    pub attempts: u32,
+
+    /// Microseconds since the Unix epoch before which the entry is not retried.
+    #[serde(rename = "nextAttemptAt")]
+    pub next_attempt_at: i64,
+
+    #[serde(rename = "lastError", skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+
+    /// The [replica](replica_id) that staged the entry.
+    #[serde(rename = "replica", default, skip_serializing_if = "Option::is_none")]
+    pub replica: Option<String>,
+
+    /// The replica delivering the entry right now.
+    #[serde(rename = "claimedBy", default, skip_serializing_if = "Option::is_none")]
+    pub claimed_by: Option<String>,
+
+    /// Microseconds since the Unix epoch until which the claim holds; after
+    /// that the entry may be claimed again.
+    #[serde(
+        rename = "claimedUntil",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub claimed_until: Option<i64>,
+}
+
+impl OutboxEntry {
+    /// Builds an entry for `ctx`, carrying `result` as the observed `value`.
+    pub fn from_context(ctx: &CommandContext, result: Option<&Value>) -> Self {
+        let mut parameters = ctx.parameters.clone();
+        if let Some(value) = result {
+            parameters.insert("value".to_string(), value.clone());
+        }
+
+        let now = chrono::Utc::now().timestamp_micros();
+
+        Self {
+            id: Uuid::new_v4(),
+            command_name: ctx.command_name.clone(),
+            activity_id: ctx.activity_id,
+            caller_id: ctx.caller_id.clone(),
+            is_internal: ctx.is_internal,
+            parameters,
+            metadata: ctx.metadata.clone(),
+            enqueued_at: now,
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+            replica: Some(replica_id().to_string()),
+            claimed_by: None,
+            claimed_until: None,
+        }
+    }
+
+    /// Store key: `{enqueuedAt}-{id}`, zero-padded so keys sort in enqueue order.
+    pub fn key(&self) -> StoreKey {
+        format!("{:020}-{}", self.enqueued_at, self.id)
+    }
+
+    /// Rebuilds the command context the observers are invoked with.
+    pub fn to_context(&self) -> CommandContext {
+        CommandContext {
+            command_name: self.command_name.clone(),
+            parameters: self.parameters.clone(),
+            metadata: self.metadata.clone(),
+            activity_id: self.activity_id,
+            caller_id: self.caller_id.clone(),
+            is_internal: self.is_internal,
+            defer_observers: false,
+            write_batch: None,
+            cancellation_token: CancellationToken::new(),
+        }
+    }
+
+    /// Stages the entry into `tx` so it commits atomically with the change.
+    pub fn stage(&self, tx: &mut Transaction<'_>) -> StoreResult<()> {
+        let bytes = serde_json::to_vec(self)?;
+        tx.put(OUTBOX_CONTAINER.to_string(), self.key(), bytes);
+        Ok(())
+    }
+}
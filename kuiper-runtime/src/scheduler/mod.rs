@@ -0,0 +1,665 @@
+//! Periodic command execution.
+//!
+//! [`Scheduler`] is a [`HostedService`] that dispatches [`ScheduledJob`]s on a
+//! cron expression or a fixed interval. Each run is delayed by a random jitter
+//! of up to the job's `jitter`, and a job whose previous run is still going is
+//! skipped rather than run twice unless it allows overlap. The last and next
+//! run of every job are kept in the `schedules` container, so a restart
+//! resumes the schedule instead of starting it over; runs missed while the
+//! process was down collapse into a single run on startup.
+//!
+//! Jobs are added in code with [`Scheduler::add_job`], or read from a
+//! [`JobSource`] that the scheduler resyncs periodically.
+
+mod schedule;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+pub use schedule::{CronSchedule, Schedule};
+
+use crate::{
+    command::{CommandContext, CommandDispatcher},
+    data::TransactionalKeyValueStore,
+    service::{HostedService, ServiceTask},
+};
+
+/// Container holding the persisted [`JobState`] of every job, keyed by job name.
+pub const SCHEDULER_CONTAINER: &str = "schedules";
+
+/// A command dispatched on a schedule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledJob {
+    /// Unique name; also the key of the job's persisted state.
+    pub name: String,
+
+    pub command: String,
+
+    /// Namespace passed in the command's metadata.
+    pub namespace: Option<String>,
+
+    pub parameters: HashMap<String, Value>,
+
+    pub schedule: Schedule,
+
+    /// Upper bound of the random delay added to every run.
+    pub jitter: Duration,
+
+    /// Start a run even when the previous one has not finished.
+    pub allow_overlap: bool,
+}
+
+impl ScheduledJob {
+    pub fn new(name: &str, command: &str, schedule: Schedule) -> Self {
+        Self {
+            name: name.to_string(),
+            command: command.to_string(),
+            namespace: None,
+            parameters: HashMap::new(),
+            schedule,
+            jitter: Duration::ZERO,
+            allow_overlap: false,
+        }
+    }
+
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    pub fn with_parameter(mut self, name: &str, value: Value) -> Self {
+        self.parameters.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_overlap(mut self, allow: bool) -> Self {
+        self.allow_overlap = allow;
+        self
+    }
+
+    /// The next run after `after`, including jitter.
+    fn next_run(&self, after: DateTime<Utc>) -> Option<i64> {
+        let next = self.schedule.next_after(after)?.timestamp_micros();
+        let jitter = self.jitter.as_micros();
+        let offset = match jitter {
+            0 => 0,
+            _ => (Uuid::new_v4().as_u128() % jitter) as i64,
+        };
+        Some(next + offset)
+    }
+}
+
+/// Outcome of a job's most recent occurrence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobOutcome {
+    Succeeded,
+    Failed,
+    /// Not run because the previous run was still in progress.
+    Skipped,
+}
+
+/// Persisted progress of a job. Times are microseconds since the Unix epoch.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobState {
+    /// The schedule the state was computed for; a changed schedule resets
+    /// `next_run_at`.
+    pub schedule: String,
+
+    #[serde(rename = "lastRunAt", skip_serializing_if = "Option::is_none")]
+    pub last_run_at: Option<i64>,
+
+    #[serde(rename = "lastCompletedAt", skip_serializing_if = "Option::is_none")]
+    pub last_completed_at: Option<i64>,
+
+    #[serde(rename = "lastOutcome", skip_serializing_if = "Option::is_none")]
+    pub last_outcome: Option<JobOutcome>,
+
+    #[serde(rename = "lastError", skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+
+    #[serde(rename = "nextRunAt", skip_serializing_if = "Option::is_none")]
+    pub next_run_at: Option<i64>,
+
+    /// Runs started since the job was first scheduled.
+    #[serde(default)]
+    pub runs: u64,
+}
+
+/// A job together with its live state, as reported by [`Scheduler::jobs`].
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub name: String,
+    pub command: String,
+    pub schedule: String,
+    pub running: bool,
+    pub state: JobState,
+}
+
+/// Supplies jobs defined outside code, such as stored resources. The
+/// scheduler adds, updates and removes these jobs to match on every resync.
+#[async_trait]
+pub trait JobSource: Send + Sync {
+    async fn jobs(&self) -> anyhow::Result<Vec<ScheduledJob>>;
+}
+
+/// Tuning knobs for [`Scheduler`].
+#[derive(Debug, Clone)]
+pub struct SchedulerOptions {
+    /// How often due jobs are checked for.
+    pub tick: Duration,
+
+    /// How often the [`JobSource`] is re-read.
+    pub resync_interval: Duration,
+}
+
+impl Default for SchedulerOptions {
+    fn default() -> Self {
+        Self {
+            tick: Duration::from_secs(1),
+            resync_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+struct JobEntry {
+    job: ScheduledJob,
+    state: Mutex<JobState>,
+    /// Runs in progress.
+    running: Arc<AtomicUsize>,
+    /// Whether the job came from the [`JobSource`] rather than code.
+    sourced: bool,
+}
+
+/// Runs [`ScheduledJob`]s through a [`CommandDispatcher`].
+pub struct Scheduler {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    dispatcher: Arc<dyn CommandDispatcher>,
+    options: SchedulerOptions,
+    source: Option<Arc<dyn JobSource>>,
+    jobs: Mutex<HashMap<String, Arc<JobEntry>>>,
+    /// Replaced on every start, so the scheduler can be started again after
+    /// it was stopped (e.g. when it only runs while leader).
+    stop: Mutex<CancellationToken>,
+    task: ServiceTask,
+}
+
+impl Scheduler {
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        dispatcher: Arc<dyn CommandDispatcher>,
+        options: SchedulerOptions,
+    ) -> Self {
+        Self {
+            store,
+            dispatcher,
+            options,
+            source: None,
+            jobs: Mutex::new(HashMap::new()),
+            stop: Mutex::new(CancellationToken::new()),
+            task: ServiceTask::default(),
+        }
+    }
+
+    pub fn with_source(mut self, source: Arc<dyn JobSource>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Schedules `job`, replacing any job of the same name.
+    pub async fn add_job(&self, job: ScheduledJob) -> anyhow::Result<()> {
+        self.insert(job, false).await
+    }
+
+    /// Unschedules the named job. A run already in progress is not interrupted.
+    pub fn remove_job(&self, name: &str) -> bool {
+        self.jobs.lock().unwrap().remove(name).is_some()
+    }
+
+    /// Every scheduled job, sorted by name.
+    pub fn jobs(&self) -> Vec<JobStatus> {
+        let mut jobs: Vec<JobStatus> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| JobStatus {
+                name: entry.job.name.clone(),
+                command: entry.job.command.clone(),
+                schedule: entry.job.schedule.to_string(),
+                running: entry.running.load(Ordering::SeqCst) > 0,
+                state: entry.state.lock().unwrap().clone(),
+            })
+            .collect();
+        jobs.sort_by(|a, b| a.name.cmp(&b.name));
+        jobs
+    }
+
+    async fn insert(&self, job: ScheduledJob, sourced: bool) -> anyhow::Result<()> {
+        let schedule = job.schedule.to_string();
+
+        // Keep the running flag of a job that is being updated in place, so a
+        // run in progress still blocks an overlapping one.
+        let running = self
+            .jobs
+            .lock()
+            .unwrap()
+            .get(&job.name)
+            .map(|existing| existing.running.clone())
+            .unwrap_or_default();
+
+        let mut state = self.load_state(&job.name).await?.unwrap_or_default();
+        if state.schedule != schedule || state.next_run_at.is_none() {
+            state.schedule = schedule;
+            state.next_run_at = job.next_run(Utc::now());
+            self.save_state(&job.name, &state).await?;
+        }
+
+        tracing::debug!(
+            job = %job.name,
+            "Scheduled '{}' ({})",
+            job.command,
+            state.schedule
+        );
+
+        let entry = Arc::new(JobEntry {
+            job,
+            state: Mutex::new(state),
+            running,
+            sourced,
+        });
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(entry.job.name.clone(), entry);
+        Ok(())
+    }
+
+    /// Brings the jobs from the [`JobSource`] in line with what it returns now.
+    pub async fn resync(&self) -> anyhow::Result<()> {
+        let Some(source) = &self.source else {
+            return Ok(());
+        };
+
+        let wanted = source
+            .jobs()
+            .await
+            .context("Failed to read scheduled jobs")?;
+        let names: HashSet<String> = wanted.iter().map(|j| j.name.clone()).collect();
+
+        let stale: Vec<String> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|e| e.sourced && !names.contains(&e.job.name))
+            .map(|e| e.job.name.clone())
+            .collect();
+        for name in stale {
+            self.remove_job(&name);
+            self.delete_state(&name).await;
+        }
+
+        for job in wanted {
+            let unchanged = self
+                .jobs
+                .lock()
+                .unwrap()
+                .get(&job.name)
+                .is_some_and(|e| e.job == job);
+            if !unchanged {
+                self.insert(job, true).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Starts every job that is due. Returns the number of runs started.
+    pub async fn run_due(&self) -> usize {
+        let now = chrono::Utc::now().timestamp_micros();
+        let due: Vec<Arc<JobEntry>> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|e| {
+                e.state
+                    .lock()
+                    .unwrap()
+                    .next_run_at
+                    .is_some_and(|next| next <= now)
+            })
+            .cloned()
+            .collect();
+
+        let mut started = 0;
+        for entry in due {
+            let next_run_at = entry.job.next_run(Utc::now());
+            let overlapping = !entry.job.allow_overlap && entry.running.load(Ordering::SeqCst) > 0;
+
+            let state = {
+                let mut state = entry.state.lock().unwrap();
+                state.next_run_at = next_run_at;
+                if overlapping {
+                    state.last_outcome = Some(JobOutcome::Skipped);
+                } else {
+                    state.last_run_at = Some(now);
+                    state.runs += 1;
+                }
+                state.clone()
+            };
+            self.persist(&entry.job.name, &state).await;
+
+            if overlapping {
+                tracing::warn!(
+                    job = %entry.job.name,
+                    "Skipping scheduled run; the previous run is still in progress"
+                );
+                continue;
+            }
+
+            entry.running.fetch_add(1, Ordering::SeqCst);
+            self.spawn_run(entry);
+            started += 1;
+        }
+
+        started
+    }
+
+    fn spawn_run(&self, entry: Arc<JobEntry>) {
+        let dispatcher = self.dispatcher.clone();
+        let store = self.store.clone();
+        let cancellation_token = self.stop.lock().unwrap().child_token();
+
+        tokio::spawn(async move {
+            let job = &entry.job;
+            let mut ctx = CommandContext {
+                command_name: job.command.clone(),
+                parameters: job.parameters.clone(),
+                metadata: job
+                    .namespace
+                    .iter()
+                    .map(|ns| ("namespace".to_string(), ns.clone()))
+                    .collect(),
+                activity_id: Uuid::new_v4(),
+                caller_id: None,
+                is_internal: true,
+                defer_observers: false,
+                write_batch: None,
+                cancellation_token,
+            };
+
+            tracing::debug!(job = %job.name, activity_id = %ctx.activity_id, "Running scheduled command '{}'", job.command);
+            let result = dispatcher.dispatch(&mut ctx).await;
+
+            let state = {
+                let mut state = entry.state.lock().unwrap();
+                state.last_completed_at = Some(chrono::Utc::now().timestamp_micros());
+                match &result {
+                    Ok(_) => {
+                        state.last_outcome = Some(JobOutcome::Succeeded);
+                        state.last_error = None;
+                    }
+                    Err(e) => {
+                        tracing::warn!(job = %job.name, "Scheduled command '{}' failed: {}", job.command, e);
+                        state.last_outcome = Some(JobOutcome::Failed);
+                        state.last_error = Some(e.to_string());
+                    }
+                }
+                state.clone()
+            };
+            entry.running.fetch_sub(1, Ordering::SeqCst);
+
+            if let Err(e) = save_state(&store, &job.name, &state).await {
+                tracing::warn!(job = %job.name, "Failed to persist job state: {}", e);
+            }
+        });
+    }
+
+    async fn persist(&self, name: &str, state: &JobState) {
+        if let Err(e) = self.save_state(name, state).await {
+            tracing::warn!(job = %name, "Failed to persist job state: {}", e);
+        }
+    }
+
+    async fn load_state(&self, name: &str) -> anyhow::Result<Option<JobState>> {
+        let store = self.store.read().await;
+        if !store
+            .container_exists(SCHEDULER_CONTAINER)
+            .await
+            .context("Failed to check scheduler container")?
+        {
+            return Ok(None);
+        }
+
+        let Ok(bytes) = store.get(SCHEDULER_CONTAINER, name).await else {
+            return Ok(None);
+        };
+        Ok(serde_json::from_slice(&bytes).ok())
+    }
+
+    async fn save_state(&self, name: &str, state: &JobState) -> anyhow::Result<()> {
+        save_state(&self.store, name, state).await
+    }
+
+    async fn delete_state(&self, name: &str) {
+        let store = self.store.write().await;
+        if let Err(e) = store.delete(SCHEDULER_CONTAINER, name).await {
+            tracing::debug!(job = %name, "Failed to delete job state: {}", e);
+        }
+    }
+
+    fn any_running(&self) -> bool {
+        self.jobs
+            .lock()
+            .unwrap()
+            .values()
+            .any(|e| e.running.load(Ordering::SeqCst) > 0)
+    }
+}
+
+async fn save_state(
+    store: &RwLock<dyn TransactionalKeyValueStore>,
+    name: &str,
+    state: &JobState,
+) -> anyhow::Result<()> {
+    let bytes = serde_json::to_vec(state)?;
+    let store = store.write().await;
+    store
+        .ensure_container(SCHEDULER_CONTAINER)
+        .await
+        .context("Failed to create scheduler container")?;
+    store
+        .put(SCHEDULER_CONTAINER, name, bytes)
+        .await
+        .context("Failed to write job state")?;
+    Ok(())
+}
+
+#[async_trait]
+impl HostedService for Scheduler {
+    async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        let scheduler = self.clone();
+        let stop = CancellationToken::new();
+        *self.stop.lock().unwrap() = stop.clone();
+
+        self.task.spawn(async move {
+            tracing::info!(
+                "Scheduler started (tick={}ms, resync={}s)",
+                scheduler.options.tick.as_millis(),
+                scheduler.options.resync_interval.as_secs()
+            );
+
+            let mut resync_at = tokio::time::Instant::now();
+
+            loop {
+                if tokio::time::Instant::now() >= resync_at {
+                    if let Err(e) = scheduler.resync().await {
+                        tracing::warn!("Scheduler resync failed: {}", e);
+                    }
+                    resync_at += scheduler.options.resync_interval;
+                }
+
+                scheduler.run_due().await;
+
+                tokio::select! {
+                    _ = tokio::time::sleep(scheduler.options.tick) => {}
+                    _ = stop.cancelled() => {
+                        tracing::info!("Scheduler stopping");
+                        return Ok(());
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Stops scheduling and waits for runs in progress, which see their
+    /// `cancellation_token` cancelled.
+    async fn stop(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.stop.lock().unwrap().cancel();
+        self.task.join().await?;
+
+        while self.any_running() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        tracing::info!("Scheduler stopped");
+        Ok(())
+    }
+
+    async fn join(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.task.join().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{command::CommandResult, data::InMemoryStore};
+
+    /// Runs `slow` for a while, to exercise overlap prevention, and any other
+    /// command at once.
+    struct TestDispatcher;
+
+    #[async_trait]
+    impl CommandDispatcher for TestDispatcher {
+        async fn dispatch(&self, ctx: &mut CommandContext) -> CommandResult {
+            if ctx.command_name == "slow" {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+            }
+            Ok(None)
+        }
+    }
+
+    fn scheduler(store: Arc<RwLock<InMemoryStore>>) -> Arc<Scheduler> {
+        Arc::new(Scheduler::new(
+            store,
+            Arc::new(TestDispatcher),
+            SchedulerOptions {
+                tick: Duration::from_millis(10),
+                ..Default::default()
+            },
+        ))
+    }
+
+    /// A job added in code runs on its interval and its state is persisted.
+    #[tokio::test]
+    async fn runs_job_and_persists_state() {
+        let store = Arc::new(RwLock::new(InMemoryStore::new()));
+        let scheduler = scheduler(store.clone());
+
+        scheduler
+            .add_job(
+                ScheduledJob::new("ping", "echo", Schedule::every(Duration::from_millis(30)))
+                    .with_parameter("message", json!("hi")),
+            )
+            .await
+            .unwrap();
+        scheduler.start().await.unwrap();
+
+        for _ in 0..100 {
+            if scheduler.jobs()[0].state.runs >= 2 && !scheduler.jobs()[0].running {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        scheduler.stop().await.unwrap();
+
+        let job = &scheduler.jobs()[0];
+        assert!(job.state.runs >= 2);
+        assert_eq!(job.state.last_outcome, Some(JobOutcome::Succeeded));
+        assert!(job.state.next_run_at > job.state.last_run_at);
+
+        let bytes = store
+            .read()
+            .await
+            .get(SCHEDULER_CONTAINER, "ping")
+            .await
+            .unwrap();
+        let persisted: JobState = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(persisted.schedule, "@every 30ms");
+        assert!(persisted.runs >= 2);
+
+        // A new scheduler over the same store resumes from the persisted state.
+        let restarted = self::scheduler(store.clone());
+        restarted
+            .add_job(ScheduledJob::new(
+                "ping",
+                "echo",
+                Schedule::every(Duration::from_millis(30)),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(restarted.jobs()[0].state.runs, persisted.runs);
+    }
+
+    /// A run is skipped, not overlapped, while the previous one is still going.
+    #[tokio::test]
+    async fn skips_overlapping_runs() {
+        let scheduler = scheduler(Arc::new(RwLock::new(InMemoryStore::new())));
+
+        scheduler
+            .add_job(ScheduledJob::new(
+                "slow",
+                "slow",
+                Schedule::every(Duration::from_millis(20)),
+            ))
+            .await
+            .unwrap();
+        scheduler.start().await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let job = scheduler.jobs().remove(0);
+        assert!(job.running);
+        assert_eq!(job.state.runs, 1);
+        assert_eq!(job.state.last_outcome, Some(JobOutcome::Skipped));
+
+        scheduler.stop().await.unwrap();
+        assert!(!scheduler.jobs()[0].running);
+    }
+}
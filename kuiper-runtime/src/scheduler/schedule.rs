@@ -0,0 +1,302 @@
+use std::{fmt, time::Duration};
+
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, TimeZone, Timelike, Utc};
+use kuiper_types::error::KuiperError;
+
+/// When a [`ScheduledJob`](super::ScheduledJob) runs.
+///
+/// Parsed from either a five-field cron expression (`minute hour
+/// day-of-month month day-of-week`, evaluated in UTC), one of the macros
+/// `@hourly`, `@daily`, `@weekly`, `@monthly`, `@yearly`, or a fixed
+/// interval written as `@every 30s` (units `ms`, `s`, `m`, `h`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    Cron(CronSchedule),
+    Interval(Duration),
+}
+
+impl Schedule {
+    pub fn every(interval: Duration) -> Self {
+        Self::Interval(interval)
+    }
+
+    pub fn cron(expression: &str) -> anyhow::Result<Self> {
+        CronSchedule::parse(expression).map(Self::Cron)
+    }
+
+    pub fn parse(expression: &str) -> anyhow::Result<Self> {
+        let expression = expression.trim();
+        match expression.strip_prefix("@every") {
+            Some(interval) => parse_interval(interval.trim()).map(Self::Interval),
+            None => Self::cron(expression),
+        }
+    }
+
+    /// The first run strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Cron(cron) => cron.next_after(after),
+            Self::Interval(interval) => {
+                after.checked_add_signed(ChronoDuration::from_std(*interval).ok()?)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cron(cron) => f.write_str(&cron.expression),
+            Self::Interval(interval) => {
+                let ms = interval.as_millis();
+                match ms {
+                    _ if ms % 3_600_000 == 0 => write!(f, "@every {}h", ms / 3_600_000),
+                    _ if ms % 60_000 == 0 => write!(f, "@every {}m", ms / 60_000),
+                    _ if ms % 1_000 == 0 => write!(f, "@every {}s", ms / 1_000),
+                    _ => write!(f, "@every {}ms", ms),
+                }
+            }
+        }
+    }
+}
+
+fn parse_interval(text: &str) -> anyhow::Result<Duration> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| KuiperError::Invalid(format!("Invalid interval '{}'", text)))?;
+    let too_long = || KuiperError::Invalid(format!("Interval '{}' is too long", text));
+
+    let interval = match unit {
+        "ms" => Duration::from_millis(value),
+        "s" => Duration::from_secs(value),
+        "m" => Duration::from_secs(value.checked_mul(60).ok_or_else(too_long)?),
+        "h" => Duration::from_secs(value.checked_mul(60 * 60).ok_or_else(too_long)?),
+        _ => {
+            return Err(KuiperError::Invalid(format!(
+                "Invalid interval unit in '{}'; expected ms, s, m or h",
+                text
+            ))
+            .into())
+        }
+    };
+
+    if interval.is_zero() {
+        return Err(KuiperError::Invalid("Interval must be greater than zero".to_string()).into());
+    }
+    // Runs are timed with chrono, whose durations are shorter than `Duration`'s.
+    if ChronoDuration::from_std(interval).is_err() {
+        return Err(too_long().into());
+    }
+    Ok(interval)
+}
+
+/// A parsed five-field cron expression. Each field is a set of allowed values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Cron matches a day when *either* day field matches, unless one of them starts with `*`.
+    day_of_month_any: bool,
+    day_of_week_any: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> anyhow::Result<Self> {
+        let expanded = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            anyhow::bail!(
+                "Invalid cron expression '{}': expected 5 fields, found {}",
+                expression,
+                fields.len()
+            );
+        };
+
+        let parse = |field: &str, min: u32, max: u32, name: &str| {
+            parse_field(field, min, max).map_err(|e| {
+                anyhow::anyhow!("Invalid cron expression '{}': {} {}", expression, name, e)
+            })
+        };
+
+        // Day-of-week accepts 7 as an alias for Sunday.
+        let mut days_of_week = parse(dow, 0, 7, "day-of-week")?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes: parse(minute, 0, 59, "minute")?,
+            hours: parse(hour, 0, 23, "hour")?,
+            days_of_month: parse(dom, 1, 31, "day-of-month")?,
+            months: parse(month, 1, 12, "month")?,
+            days_of_week,
+            day_of_month_any: dom.starts_with('*'),
+            day_of_week_any: dow.starts_with('*'),
+        })
+    }
+
+    fn day_matches(&self, t: &DateTime<Utc>) -> bool {
+        let dom = self.days_of_month & (1 << t.day()) != 0;
+        let dow = self.days_of_week & (1 << t.weekday().num_days_from_sunday()) != 0;
+
+        match (self.day_of_month_any, self.day_of_week_any) {
+            (false, false) => dom || dow,
+            _ => dom && dow,
+        }
+    }
+
+    /// The first matching minute strictly after `after`, searching up to
+    /// five years ahead (long enough for any February 29th).
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        let limit = after + ChronoDuration::days(5 * 366);
+
+        while t <= limit {
+            if self.months & (1 << t.month()) == 0 {
+                let (year, month) = match t.month() {
+                    12 => (t.year() + 1, 1),
+                    m => (t.year(), m + 1),
+                };
+                t = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+                continue;
+            }
+            if !self.day_matches(&t) {
+                t = (t + ChronoDuration::days(1)).with_hour(0)?.with_minute(0)?;
+                continue;
+            }
+            if self.hours & (1 << t.hour()) == 0 {
+                t = (t + ChronoDuration::hours(1)).with_minute(0)?;
+                continue;
+            }
+            if self.minutes & (1 << t.minute()) == 0 {
+                t += ChronoDuration::minutes(1);
+                continue;
+            }
+            return Some(t);
+        }
+
+        None
+    }
+}
+
+/// Parses one cron field (`*`, `5`, `1-5`, `*/15`, `10-40/10`, `1,15`) into a bitmask.
+fn parse_field(field: &str, min: u32, max: u32) -> anyhow::Result<u64> {
+    let mut mask = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("has an invalid step in '{}'", part))?;
+                if step == 0 {
+                    anyhow::bail!("has a zero step in '{}'", part);
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => {
+                let value = |s: &str| {
+                    s.parse::<u32>()
+                        .map_err(|_| anyhow::anyhow!("has an invalid value '{}'", s))
+                };
+                match range.split_once('-') {
+                    Some((start, end)) => (value(start)?, value(end)?),
+                    // `5/10` means every 10th value starting at 5.
+                    None if step > 1 => (value(range)?, max),
+                    None => (value(range)?, value(range)?),
+                }
+            }
+        };
+
+        if start < min || end > max || start > end {
+            anyhow::bail!("has '{}' outside {}-{}", part, min, max);
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cron expressions, macros and intervals parse and compute the next run in UTC.
+    #[test]
+    fn parse_and_next_run() {
+        // Mon 2024-01-01 17:50 UTC.
+        let at = Utc.with_ymd_and_hms(2024, 1, 1, 17, 50, 0).unwrap();
+
+        let weekdays = Schedule::parse("*/15 9-17 * * 1-5").unwrap();
+        assert_eq!(
+            weekdays.next_after(at),
+            Some(Utc.with_ymd_and_hms(2024, 1, 2, 9, 0, 0).unwrap())
+        );
+
+        let daily = Schedule::parse("@daily").unwrap();
+        assert_eq!(
+            daily.next_after(at),
+            Some(Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap())
+        );
+
+        // Both day fields restricted: either may match.
+        let either = Schedule::parse("0 0 13 * 5").unwrap();
+        assert_eq!(
+            either.next_after(at),
+            Some(Utc.with_ymd_and_hms(2024, 1, 5, 0, 0, 0).unwrap())
+        );
+
+        let every = Schedule::parse("@every 30s").unwrap();
+        assert_eq!(every, Schedule::every(Duration::from_secs(30)));
+        assert_eq!(every.to_string(), "@every 30s");
+        assert_eq!(
+            every.next_after(at),
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 17, 50, 30).unwrap())
+        );
+
+        assert!(Schedule::parse("* * *").is_err());
+        assert!(Schedule::parse("61 * * * *").is_err());
+        assert!(Schedule::parse("@every 0s").is_err());
+
+        // Intervals too long to schedule are rejected up front.
+        for too_long in [
+            "@every 18446744073709551615h",
+            "@every 9223372036854775807s",
+        ] {
+            let err = Schedule::parse(too_long).unwrap_err();
+            assert!(
+                matches!(
+                    err.downcast_ref::<KuiperError>(),
+                    Some(KuiperError::Invalid(_))
+                ),
+                "{}",
+                too_long
+            );
+        }
+    }
+}
@@ -0,0 +1,600 @@
+//! Supervision of [`HostedService`]s.
+//!
+//! [`ServiceHost`] starts its services in dependency order, restarts the ones
+//! whose task crashes (as reported by [`HostedService::join`]) with exponential
+//! backoff, and stops them in reverse order. [`ServiceHost::status`] reports
+//! the state and readiness of each service.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use super::HostedService;
+
+/// Object-safe view of a [`HostedService`], whose methods take `&Arc<Self>`.
+#[async_trait]
+trait Supervised: Send + Sync {
+    async fn start(&self) -> anyhow::Result<()>;
+    async fn stop(&self) -> anyhow::Result<()>;
+    async fn join(&self) -> anyhow::Result<()>;
+    fn is_ready(&self) -> bool;
+}
+
+struct Hosted<S>(Arc<S>);
+
+#[async_trait]
+impl<S: HostedService + 'static> Supervised for Hosted<S> {
+    async fn start(&self) -> anyhow::Result<()> {
+        self.0.start().await
+    }
+
+    async fn stop(&self) -> anyhow::Result<()> {
+        self.0.stop().await
+    }
+
+    async fn join(&self) -> anyhow::Result<()> {
+        self.0.join().await
+    }
+
+    fn is_ready(&self) -> bool {
+        self.0.is_ready()
+    }
+}
+
+/// How a crashed service is restarted.
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// Consecutive restarts before the service is left failed; `None`
+    /// retries forever.
+    pub max_restarts: Option<u32>,
+
+    /// Delay before the first restart; doubled on every further crash.
+    pub initial_backoff: Duration,
+
+    /// Upper bound for the restart delay.
+    pub max_backoff: Duration,
+
+    /// A service that runs at least this long has its backoff and restart
+    /// count reset.
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: None,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            reset_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Never restart; a crashed service is left failed.
+    pub fn never() -> Self {
+        Self {
+            max_restarts: Some(0),
+            ..Default::default()
+        }
+    }
+
+    /// Restart delay for the `attempt`-th consecutive crash.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServiceState {
+    Pending,
+    Starting,
+    Running,
+    /// Crashed and waiting for its backoff before being started again.
+    Restarting,
+    /// Crashed and out of restarts.
+    Failed,
+    Stopping,
+    Stopped,
+}
+
+/// Health of a single hosted service.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub state: ServiceState,
+    pub ready: bool,
+
+    /// Restarts since the service last ran for
+    /// [`RestartPolicy::reset_after`].
+    pub restarts: u32,
+
+    #[serde(rename = "lastError", skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// A service registered with a [`ServiceHost`].
+pub struct ServiceRegistration {
+    name: String,
+    service: Arc<dyn Supervised>,
+    depends_on: Vec<String>,
+    policy: RestartPolicy,
+    status: Arc<Mutex<ServiceStatus>>,
+}
+
+impl ServiceRegistration {
+    /// Starts this service only after `name`, and stops it before.
+    pub fn depends_on(&mut self, name: &str) -> &mut Self {
+        self.depends_on.push(name.to_string());
+        self
+    }
+
+    pub fn restart_policy(&mut self, policy: RestartPolicy) -> &mut Self {
+        self.policy = policy;
+        self
+    }
+}
+
+fn set_state(status: &Mutex<ServiceStatus>, state: ServiceState) {
+    status.lock().unwrap().state = state;
+}
+
+/// Starts, supervises and stops a set of [`HostedService`]s.
+///
+/// A host is started once; after [`stop`](Self::stop) it cannot be restarted.
+#[derive(Default)]
+pub struct ServiceHost {
+    services: Vec<ServiceRegistration>,
+    shutdown: CancellationToken,
+    supervisors: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl ServiceHost {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `service` under `name` with the default [`RestartPolicy`].
+    pub fn register<S: HostedService + 'static>(
+        &mut self,
+        name: &str,
+        service: Arc<S>,
+    ) -> &mut ServiceRegistration {
+        self.services.push(ServiceRegistration {
+            name: name.to_string(),
+            service: Arc::new(Hosted(service)),
+            depends_on: Vec::new(),
+            policy: RestartPolicy::default(),
+            status: Arc::new(Mutex::new(ServiceStatus {
+                name: name.to_string(),
+                state: ServiceState::Pending,
+                ready: false,
+                restarts: 0,
+                last_error: None,
+            })),
+        });
+        self.services.last_mut().unwrap()
+    }
+
+    /// Registration order, rearranged so every service follows its dependencies.
+    fn start_order(&self) -> anyhow::Result<Vec<usize>> {
+        let index: HashMap<&str, usize> = self
+            .services
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.name.as_str(), i))
+            .collect();
+
+        for service in &self.services {
+            for dependency in &service.depends_on {
+                if !index.contains_key(dependency.as_str()) {
+                    anyhow::bail!(
+                        "Service '{}' depends on unknown service '{}'",
+                        service.name,
+                        dependency
+                    );
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.services.len());
+        let mut placed = vec![false; self.services.len()];
+
+        while order.len() < self.services.len() {
+            let next = self.services.iter().enumerate().position(|(i, s)| {
+                !placed[i] && s.depends_on.iter().all(|d| placed[index[d.as_str()]])
+            });
+
+            let Some(next) = next else {
+                let cycle: Vec<&str> = self
+                    .services
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| !placed[*i])
+                    .map(|(_, s)| s.name.as_str())
+                    .collect();
+                anyhow::bail!("Service dependency cycle between {}", cycle.join(", "));
+            };
+
+            placed[next] = true;
+            order.push(next);
+        }
+
+        Ok(order)
+    }
+
+    /// Starts every service in dependency order and begins supervising them.
+    /// If a service fails to start, the ones already started are stopped.
+    pub async fn start(&self) -> anyhow::Result<()> {
+        let order = self.start_order()?;
+
+        for (started, &i) in order.iter().enumerate() {
+            let registration = &self.services[i];
+            set_state(&registration.status, ServiceState::Starting);
+
+            if let Err(e) = registration.service.start().await {
+                {
+                    let mut status = registration.status.lock().unwrap();
+                    status.state = ServiceState::Failed;
+                    status.last_error = Some(e.to_string());
+                }
+                for &j in order[..started].iter().rev() {
+                    self.stop_service(&self.services[j]).await.ok();
+                }
+                return Err(e).context(format!("Failed to start service '{}'", registration.name));
+            }
+
+            set_state(&registration.status, ServiceState::Running);
+            tracing::info!("Service '{}' started", registration.name);
+        }
+
+        let mut supervisors = self.supervisors.lock().unwrap();
+        for registration in &self.services {
+            supervisors.push(tokio::spawn(supervise(
+                registration.name.clone(),
+                registration.service.clone(),
+                registration.policy.clone(),
+                registration.status.clone(),
+                self.shutdown.clone(),
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Stops supervising and stops every running service in reverse
+    /// dependency order. Returns the first shutdown error, after trying all.
+    pub async fn stop(&self) -> anyhow::Result<()> {
+        self.shutdown.cancel();
+
+        let supervisors = std::mem::take(&mut *self.supervisors.lock().unwrap());
+        for supervisor in supervisors {
+            supervisor.await.ok();
+        }
+
+        let order = self.start_order()?;
+        let mut first_error = None;
+
+        for &i in order.iter().rev() {
+            let registration = &self.services[i];
+            if let Err(e) = self.stop_service(registration).await {
+                tracing::error!("Service '{}' failed to stop: {}", registration.name, e);
+                first_error.get_or_insert(e);
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    async fn stop_service(&self, registration: &ServiceRegistration) -> anyhow::Result<()> {
+        if registration.status.lock().unwrap().state != ServiceState::Running {
+            set_state(&registration.status, ServiceState::Stopped);
+            return Ok(());
+        }
+
+        set_state(&registration.status, ServiceState::Stopping);
+        let result = registration.service.stop().await;
+        set_state(&registration.status, ServiceState::Stopped);
+        result
+    }
+
+    /// The state of every service, in registration order.
+    pub fn status(&self) -> Vec<ServiceStatus> {
+        self.services
+            .iter()
+            .map(|registration| {
+                let mut status = registration.status.lock().unwrap().clone();
+                status.ready =
+                    status.state == ServiceState::Running && registration.service.is_ready();
+                status
+            })
+            .collect()
+    }
+
+    /// Whether every service is running and ready.
+    pub fn is_ready(&self) -> bool {
+        self.status().iter().all(|s| s.ready)
+    }
+}
+
+/// Watches a started service and restarts it when it crashes.
+async fn supervise(
+    name: String,
+    service: Arc<dyn Supervised>,
+    policy: RestartPolicy,
+    status: Arc<Mutex<ServiceStatus>>,
+    shutdown: CancellationToken,
+) {
+    let mut attempt = 0;
+
+    loop {
+        let running_since = Instant::now();
+
+        let exit = tokio::select! {
+            exit = service.join() => exit,
+            _ = shutdown.cancelled() => return,
+        };
+
+        let mut error = match exit {
+            Ok(()) => {
+                tracing::info!("Service '{}' exited", name);
+                set_state(&status, ServiceState::Stopped);
+                return;
+            }
+            Err(e) => e.to_string(),
+        };
+
+        if running_since.elapsed() >= policy.reset_after {
+            attempt = 0;
+            status.lock().unwrap().restarts = 0;
+        }
+
+        // Keep trying until the service starts again or runs out of restarts.
+        loop {
+            {
+                let mut status = status.lock().unwrap();
+                status.last_error = Some(error.clone());
+
+                if policy
+                    .max_restarts
+                    .is_some_and(|max| status.restarts >= max)
+                {
+                    status.state = ServiceState::Failed;
+                    tracing::error!(
+                        "Service '{}' crashed and will not be restarted: {}",
+                        name,
+                        error
+                    );
+                    return;
+                }
+
+                status.state = ServiceState::Restarting;
+                status.restarts += 1;
+            }
+
+            attempt += 1;
+            let delay = policy.backoff(attempt);
+            tracing::warn!(
+                "Service '{}' crashed; restarting in {}ms: {}",
+                name,
+                delay.as_millis(),
+                error
+            );
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutdown.cancelled() => return,
+            }
+
+            set_state(&status, ServiceState::Starting);
+            match service.start().await {
+                Ok(()) => {
+                    set_state(&status, ServiceState::Running);
+                    tracing::info!("Service '{}' restarted", name);
+                    break;
+                }
+                Err(e) => error = e.to_string(),
+            }
+        }
+    }
+}
+
+/// Resolves on Ctrl-C, or on `SIGTERM` where signals are available.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {}", e);
+                tokio::signal::ctrl_c().await.ok();
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::{service::ServiceTask, tests::LifecycleService};
+
+    /// Crashes every time after running for `runs_for`.
+    struct CrashingService {
+        runs_for: Duration,
+        starts: AtomicU32,
+        task: ServiceTask,
+    }
+
+    #[async_trait]
+    impl HostedService for CrashingService {
+        async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+            self.starts.fetch_add(1, Ordering::SeqCst);
+            let runs_for = self.runs_for;
+            self.task.spawn(async move {
+                tokio::time::sleep(runs_for).await;
+                anyhow::bail!("boom")
+            });
+            Ok(())
+        }
+
+        async fn stop(self: &Arc<Self>) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn join(self: &Arc<Self>) -> anyhow::Result<()> {
+            self.task.join().await
+        }
+    }
+
+    /// Polls the host until the named service reaches `state`.
+    async fn wait_for_state(host: &ServiceHost, name: &str, state: ServiceState) -> ServiceStatus {
+        for _ in 0..200 {
+            let status = host.status().into_iter().find(|s| s.name == name).unwrap();
+            if status.state == state {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("service '{}' never reached {:?}", name, state);
+    }
+
+    /// Services start in dependency order and stop in reverse.
+    #[tokio::test]
+    async fn dependency_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let mut host = ServiceHost::new();
+        host.register("api", LifecycleService::new("api", &log, 0))
+            .depends_on("store");
+        host.register("store", LifecycleService::new("store", &log, 0));
+
+        host.start().await.unwrap();
+        assert!(host.is_ready());
+        assert_eq!(host.status()[0].name, "api");
+        assert_eq!(host.status()[0].state, ServiceState::Running);
+
+        host.stop().await.unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["start store", "start api", "stop api", "stop store"]
+        );
+    }
+
+    /// Unknown dependencies and cycles are rejected before anything starts.
+    #[tokio::test]
+    async fn invalid_dependencies() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let mut host = ServiceHost::new();
+        host.register("api", LifecycleService::new("api", &log, 0))
+            .depends_on("store");
+        assert!(host.start().await.is_err());
+
+        host.register("store", LifecycleService::new("store", &log, 0))
+            .depends_on("api");
+        let err = host.start().await.unwrap_err();
+        assert!(err.to_string().contains("cycle"), "{err:#}");
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    /// A crashed service is restarted after its backoff; once out of restarts
+    /// it is left failed and the host is no longer ready.
+    #[tokio::test]
+    async fn restarts_crashed_service() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let fast = RestartPolicy {
+            initial_backoff: Duration::from_millis(10),
+            ..Default::default()
+        };
+
+        let mut host = ServiceHost::new();
+        host.register("flaky", LifecycleService::new("flaky", &log, 2))
+            .restart_policy(fast.clone());
+        host.register("broken", LifecycleService::new("broken", &log, 1))
+            .restart_policy(RestartPolicy {
+                max_restarts: Some(0),
+                ..fast
+            });
+        host.start().await.unwrap();
+
+        let broken = wait_for_state(&host, "broken", ServiceState::Failed).await;
+        assert!(broken.last_error.unwrap().contains("boom"));
+
+        for _ in 0..200 {
+            if host.status()[0].restarts == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let flaky = wait_for_state(&host, "flaky", ServiceState::Running).await;
+        assert_eq!(flaky.restarts, 2);
+        assert!(flaky.ready);
+        assert!(!host.is_ready());
+
+        host.stop().await.unwrap();
+        assert_eq!(host.status()[0].state, ServiceState::Stopped);
+    }
+
+    /// A service that crashes after a healthy run starts its restart count
+    /// over, so it is not left failed for crashes spread over its lifetime.
+    #[tokio::test]
+    async fn restarts_reset_after_a_healthy_run() {
+        let service = Arc::new(CrashingService {
+            runs_for: Duration::from_millis(60),
+            starts: AtomicU32::new(0),
+            task: ServiceTask::default(),
+        });
+
+        let mut host = ServiceHost::new();
+        host.register("crashy", service.clone())
+            .restart_policy(RestartPolicy {
+                max_restarts: Some(1),
+                initial_backoff: Duration::from_millis(10),
+                reset_after: Duration::from_millis(30),
+                ..Default::default()
+            });
+        host.start().await.unwrap();
+
+        for _ in 0..200 {
+            if service.starts.load(Ordering::SeqCst) >= 4 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let status = &host.status()[0];
+        assert!(service.starts.load(Ordering::SeqCst) >= 4);
+        assert_ne!(status.state, ServiceState::Failed);
+        assert_eq!(status.restarts, 1);
+        assert!(status.last_error.as_deref().unwrap().contains("boom"));
+
+        host.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+mod host;
+mod singleton;
+
+use std::{future::Future, sync::Arc};
+
+use async_trait::async_trait;
+use tokio::sync::watch;
+
+pub use host::{
+    shutdown_signal, RestartPolicy, ServiceHost, ServiceRegistration, ServiceState, ServiceStatus,
+};
+pub use singleton::{Leadership, LeadershipToken, Singleton};
+
+/// Trait for long-running background services that can be started and stopped
+/// gracefully. Implementations are typically held behind an `Arc` so that the
+/// handle can be shared between the lifecycle owner and the spawned task.
+#[async_trait]
+pub trait HostedService: Send + Sync {
+    /// Starts the background service, e.g. by spawning a Tokio task.
+    async fn start(self: &Arc<Self>) -> anyhow::Result<()>;
+
+    /// Signals the service to stop and waits for it to shut down cleanly.
+    async fn stop(self: &Arc<Self>) -> anyhow::Result<()>;
+
+    /// Resolves when the background work started by [`start`](Self::start)
+    /// ends on its own. An `Err` (including a panic) is treated as a crash by
+    /// the [`ServiceHost`]. The default never resolves, so services that do
+    /// not track their task are never restarted.
+    async fn join(self: &Arc<Self>) -> anyhow::Result<()> {
+        std::future::pending().await
+    }
+
+    /// Whether the service is ready to do work. Defaults to `true` while it
+    /// is running.
+    fn is_ready(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TaskState {
+    Idle,
+    Running,
+    /// The task finished; holds its error or panic message, if any.
+    Exited(Option<String>),
+}
+
+/// The background task of a [`HostedService`]. It records how the task
+/// ended, including panics, so `stop` and `join` can both wait on it.
+pub struct ServiceTask {
+    state: Arc<watch::Sender<TaskState>>,
+}
+
+impl Default for ServiceTask {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(watch::channel(TaskState::Idle).0),
+        }
+    }
+}
+
+impl ServiceTask {
+    /// Spawns `work`, replacing any task that has already exited.
+    pub fn spawn<F>(&self, work: F)
+    where
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.state.send_replace(TaskState::Running);
+
+        let state = self.state.clone();
+        let handle = tokio::spawn(work);
+
+        tokio::spawn(async move {
+            let error = match handle.await {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(e) if e.is_panic() => Some(format!("panicked: {}", panic_message(e))),
+                Err(e) => Some(e.to_string()),
+            };
+            state.send_replace(TaskState::Exited(error));
+        });
+    }
+
+    pub fn is_running(&self) -> bool {
+        *self.state.borrow() == TaskState::Running
+    }
+
+    /// Waits for the task to finish. Returns immediately if it is not running.
+    pub async fn join(&self) -> anyhow::Result<()> {
+        let mut state = self.state.subscribe();
+        let exited = state.wait_for(|s| *s != TaskState::Running).await?.clone();
+
+        match exited {
+            TaskState::Exited(Some(error)) => Err(anyhow::anyhow!(error)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn panic_message(error: tokio::task::JoinError) -> String {
+    let payload = error.into_panic();
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
@@ -0,0 +1,214 @@
+//! Running a [`HostedService`] in only one process at a time.
+//!
+//! A [`Leadership`] implementation (such as a lease-based leader elector)
+//! hands out a [`LeadershipToken`] while this process is the leader. The
+//! [`Singleton`] wrapper starts its service when a token is acquired and stops
+//! it as soon as the token is lost, then waits to be elected again.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
+
+use super::{HostedService, ServiceTask};
+
+/// Proof of leadership for one term. Clones share the same state; once
+/// [revoked](Self::revoke) the token stays invalid.
+#[derive(Debug, Clone)]
+pub struct LeadershipToken {
+    term: u64,
+    lost: CancellationToken,
+}
+
+impl LeadershipToken {
+    pub fn new(term: u64) -> Self {
+        Self {
+            term,
+            lost: CancellationToken::new(),
+        }
+    }
+
+    /// Increases every time leadership changes hands. Nothing written by a
+    /// [`Singleton`] service is fenced by it: a leader that lost the lease
+    /// may still finish a write before its service is stopped.
+    pub fn term(&self) -> u64 {
+        self.term
+    }
+
+    pub fn is_held(&self) -> bool {
+        !self.lost.is_cancelled()
+    }
+
+    /// Resolves once leadership is lost.
+    pub async fn lost(&self) {
+        self.lost.cancelled().await
+    }
+
+    pub fn revoke(&self) {
+        self.lost.cancel();
+    }
+}
+
+/// Source of leadership for [`Singleton`] services.
+#[async_trait]
+pub trait Leadership: Send + Sync {
+    /// Waits until this process is the leader and returns the current token.
+    async fn acquire(&self) -> LeadershipToken;
+
+    /// The current token, if this process is the leader.
+    fn current(&self) -> Option<LeadershipToken>;
+}
+
+/// Runs `service` only while `leadership` is held. The wrapped service must
+/// support being started again after it was stopped.
+pub struct Singleton<S> {
+    leadership: Arc<dyn Leadership>,
+    service: Arc<S>,
+    stop: CancellationToken,
+    task: ServiceTask,
+}
+
+impl<S: HostedService + 'static> Singleton<S> {
+    pub fn new(leadership: Arc<dyn Leadership>, service: Arc<S>) -> Arc<Self> {
+        Arc::new(Self {
+            leadership,
+            service,
+            stop: CancellationToken::new(),
+            task: ServiceTask::default(),
+        })
+    }
+
+    /// Whether the wrapped service is running, i.e. this process is leader.
+    pub fn is_active(&self) -> bool {
+        self.leadership.current().is_some() && self.task.is_running()
+    }
+
+    async fn run(&self) -> anyhow::Result<()> {
+        loop {
+            let token = tokio::select! {
+                token = self.leadership.acquire() => token,
+                _ = self.stop.cancelled() => return Ok(()),
+            };
+
+            tracing::info!(
+                term = token.term(),
+                "Leadership acquired; starting singleton service"
+            );
+            self.service.start().await?;
+
+            tokio::select! {
+                _ = token.lost() => {
+                    tracing::warn!(term = token.term(), "Leadership lost; stopping singleton service");
+                    self.service.stop().await?;
+                }
+                exit = self.service.join() => return exit,
+                _ = self.stop.cancelled() => {
+                    self.service.stop().await?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S: HostedService + 'static> HostedService for Singleton<S> {
+    async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        let singleton = self.clone();
+        self.task.spawn(async move { singleton.run().await });
+        Ok(())
+    }
+
+    async fn stop(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.stop.cancel();
+        self.task.join().await
+    }
+
+    async fn join(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.task.join().await
+    }
+
+    /// A standby replica is ready; only the leader runs the service.
+    fn is_ready(&self) -> bool {
+        self.leadership.current().is_none() || self.service.is_ready()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, time::Duration};
+
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::tests::LifecycleService;
+
+    /// Leadership handed out and taken away by the test.
+    struct ManualLeadership(watch::Sender<Option<LeadershipToken>>);
+
+    impl ManualLeadership {
+        fn new() -> Arc<Self> {
+            Arc::new(Self(watch::channel(None).0))
+        }
+
+        fn elect(&self, term: u64) {
+            self.0.send_replace(Some(LeadershipToken::new(term)));
+        }
+
+        fn revoke(&self) {
+            if let Some(token) = self.0.send_replace(None) {
+                token.revoke();
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Leadership for ManualLeadership {
+        async fn acquire(&self) -> LeadershipToken {
+            let mut current = self.0.subscribe();
+            let token = current.wait_for(Option::is_some).await.unwrap();
+            token.clone().unwrap()
+        }
+
+        fn current(&self) -> Option<LeadershipToken> {
+            self.0.borrow().clone()
+        }
+    }
+
+    async fn wait_for_log(log: &Mutex<Vec<String>>, expected: &[&str]) {
+        for _ in 0..100 {
+            if *log.lock().unwrap() == expected {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(*log.lock().unwrap(), expected);
+    }
+
+    /// A `Singleton` runs its service only while leadership is held.
+    #[tokio::test]
+    async fn runs_only_while_leader() {
+        let leadership = ManualLeadership::new();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let singleton = Singleton::new(
+            leadership.clone(),
+            LifecycleService::new("cleanup", &log, 0),
+        );
+        singleton.start().await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!singleton.is_active());
+        assert!(singleton.is_ready());
+        assert!(log.lock().unwrap().is_empty());
+
+        leadership.elect(1);
+        wait_for_log(&log, &["start cleanup"]).await;
+        assert!(singleton.is_active());
+
+        leadership.revoke();
+        wait_for_log(&log, &["start cleanup", "stop cleanup"]).await;
+        assert!(!singleton.is_active());
+
+        singleton.stop().await.unwrap();
+    }
+}
@@ -1 +1,73 @@
+//! Fixtures shared by the unit tests of this crate.
 
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, Mutex,
+};
+
+use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
+
+use crate::service::{HostedService, ServiceTask};
+
+/// A hosted service that records its lifecycle in `log` and panics on its
+/// first `crashes` runs.
+pub(crate) struct LifecycleService {
+    name: &'static str,
+    log: Arc<Mutex<Vec<String>>>,
+    crashes: AtomicU32,
+    stop: CancellationToken,
+    task: ServiceTask,
+}
+
+impl LifecycleService {
+    pub(crate) fn new(
+        name: &'static str,
+        log: &Arc<Mutex<Vec<String>>>,
+        crashes: u32,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            name,
+            log: log.clone(),
+            crashes: crashes.into(),
+            stop: Default::default(),
+            task: ServiceTask::default(),
+        })
+    }
+}
+
+#[async_trait]
+impl HostedService for LifecycleService {
+    async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("start {}", self.name));
+
+        let crash = self
+            .crashes
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        let stop = self.stop.clone();
+
+        self.task.spawn(async move {
+            if crash {
+                panic!("boom");
+            }
+            stop.cancelled().await;
+            Ok(())
+        });
+        Ok(())
+    }
+
+    async fn stop(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.stop.cancel();
+        self.task.join().await?;
+        self.log.lock().unwrap().push(format!("stop {}", self.name));
+        Ok(())
+    }
+
+    async fn join(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.task.join().await
+    }
+}
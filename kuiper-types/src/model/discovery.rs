@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+/// What a server serves: every group with its versions and kinds. Served at
+/// `/apis` and by the `discovery` command.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiDiscovery {
+    /// Sorted by name.
+    pub groups: Vec<ApiGroup>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiGroup {
+    pub name: String,
+    /// The enabled versions of the group's kinds, sorted.
+    pub versions: Vec<ApiGroupVersion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiGroupVersion {
+    pub version: String,
+    /// The kinds served at this version, sorted by kind.
+    pub resources: Vec<ApiResource>,
+}
+
+/// One kind at one version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiResource {
+    pub kind: String,
+    pub plural: String,
+    pub singular: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub short_names: Vec<String>,
+    /// `Namespace` or `System`.
+    pub scope: String,
+    /// Commands clients may run on objects of the kind, e.g. `get`, `set`
+    /// and `watch` (a WebSocket subscription).
+    pub verbs: Vec<String>,
+    /// Subresources served below an object's path, e.g. `status`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subresources: Vec<String>,
+}
+
+impl ApiDiscovery {
+    /// The kind named `kind` in `group` at `version`.
+    pub fn resource(&self, group: &str, version: &str, kind: &str) -> Option<&ApiResource> {
+        self.groups
+            .iter()
+            .find(|g| g.name.eq_ignore_ascii_case(group))?
+            .versions
+            .iter()
+            .find(|v| v.version.eq_ignore_ascii_case(version))?
+            .resources
+            .iter()
+            .find(|r| r.kind.eq_ignore_ascii_case(kind))
+    }
+}
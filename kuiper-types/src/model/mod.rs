@@ -1,5 +1,9 @@
+pub mod discovery;
+pub mod openapi;
 pub mod resource;
 pub mod security;
+pub mod selector;
+pub mod status;
 
 #[cfg(test)]
 mod tests;
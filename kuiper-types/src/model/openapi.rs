@@ -0,0 +1,133 @@
+use serde_json::{json, Map, Value};
+
+/// Reference to the component schema `name`.
+pub fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// OpenAPI 3 component schemas of the types every object shares:
+/// `ObjectMeta`, `OwnerReference`, `ManagedFieldsEntry`, `ObjectStatus` and
+/// `Condition`, plus the discovery document's `ApiDiscovery`, `ApiGroup`,
+/// `ApiGroupVersion` and `ApiResource`.
+pub fn component_schemas() -> Map<String, Value> {
+    let string_map = json!({ "type": "object", "additionalProperties": { "type": "string" } });
+    let strings = json!({ "type": "array", "items": { "type": "string" } });
+    let micros = |description: &str| {
+        let mut schema = json!({ "type": "integer", "format": "int64" });
+        schema["description"] = json!(description);
+        schema
+    };
+
+    let schemas = json!({
+        "ObjectMeta": {
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "type": "string" },
+                "namespace": { "type": "string" },
+                "uid": { "type": "string", "format": "uuid", "readOnly": true },
+                "creationTimestamp": micros("Microseconds since the Unix epoch."),
+                "deletionTimestamp": micros("Set once the object is being deleted."),
+                "resourceVersion": {
+                    "type": "string",
+                    "description": "Changes on every write; makes writes conditional."
+                },
+                "generation": {
+                    "type": "integer",
+                    "format": "int64",
+                    "readOnly": true,
+                    "description": "1 on create, incremented when the spec changes."
+                },
+                "selfLink": { "type": "string", "readOnly": true },
+                "labels": string_map,
+                "annotations": string_map,
+                "finalizers": strings,
+                "ownerReferences": { "type": "array", "items": schema_ref("OwnerReference") },
+                "managedFields": { "type": "array", "items": schema_ref("ManagedFieldsEntry") }
+            },
+            "additionalProperties": true
+        },
+        "OwnerReference": {
+            "type": "object",
+            "required": ["apiVersion", "kind", "name"],
+            "properties": {
+                "apiVersion": { "type": "string" },
+                "kind": { "type": "string" },
+                "name": { "type": "string" },
+                "uid": { "type": "string", "format": "uuid" },
+                "controller": { "type": "boolean" }
+            }
+        },
+        "ManagedFieldsEntry": {
+            "type": "object",
+            "required": ["manager"],
+            "properties": {
+                "manager": { "type": "string" },
+                "time": micros("When the manager last applied."),
+                "fields": strings
+            }
+        },
+        "ObjectStatus": {
+            "type": "object",
+            "properties": {
+                "observedGeneration": { "type": "integer", "format": "int64" },
+                "phase": { "type": "string" },
+                "conditions": { "type": "array", "items": schema_ref("Condition") }
+            },
+            "additionalProperties": true
+        },
+        "Condition": {
+            "type": "object",
+            "required": ["type", "status"],
+            "properties": {
+                "type": { "type": "string" },
+                "status": { "type": "string", "enum": ["True", "False", "Unknown"] },
+                "reason": { "type": "string" },
+                "message": { "type": "string" },
+                "lastTransitionTime": micros("When the status last changed."),
+                "observedGeneration": { "type": "integer", "format": "int64" }
+            }
+        },
+        "ApiDiscovery": {
+            "type": "object",
+            "required": ["groups"],
+            "properties": {
+                "groups": { "type": "array", "items": schema_ref("ApiGroup") }
+            }
+        },
+        "ApiGroup": {
+            "type": "object",
+            "required": ["name", "versions"],
+            "properties": {
+                "name": { "type": "string" },
+                "versions": { "type": "array", "items": schema_ref("ApiGroupVersion") }
+            }
+        },
+        "ApiGroupVersion": {
+            "type": "object",
+            "required": ["version", "resources"],
+            "properties": {
+                "version": { "type": "string" },
+                "resources": { "type": "array", "items": schema_ref("ApiResource") }
+            }
+        },
+        "ApiResource": {
+            "type": "object",
+            "required": ["kind", "plural", "singular", "scope", "verbs"],
+            "properties": {
+                "kind": { "type": "string" },
+                "plural": { "type": "string" },
+                "singular": { "type": "string" },
+                "shortNames": strings,
+                "scope": { "type": "string", "enum": ["Namespace", "System"] },
+                "verbs": strings,
+                "subresources": strings
+            }
+        }
+    });
+
+    match schemas {
+        Value::Object(schemas) => schemas,
+        _ => unreachable!(),
+    }
+}
@@ -3,6 +3,8 @@ use serde_json::Value;
 use std::collections::HashMap;
 use uuid::Uuid;
 
+use super::status::{deserialize_status, Condition, ObjectStatus};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SystemObject {
     #[serde(rename = "apiVersion")]
@@ -13,8 +15,12 @@ pub struct SystemObject {
 
     pub metadata: SystemObjectMetadata,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub status: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_status",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub status: Option<ObjectStatus>,
 
     #[serde(flatten)]
     pub extension_data: HashMap<String, Value>,
@@ -40,6 +46,11 @@ pub struct SystemObjectMetadata {
     #[serde(rename = "resourceVersion", skip_serializing_if = "Option::is_none")]
     pub resource_version: Option<String>,
 
+    /// Set by the server: 1 on create, incremented on every change outside
+    /// `metadata` and `status`.
+    #[serde(rename = "generation", skip_serializing_if = "Option::is_none")]
+    pub generation: Option<i64>,
+
     #[serde(rename = "selfLink", skip_serializing_if = "Option::is_none")]
     pub self_link: Option<String>,
 
@@ -52,6 +63,148 @@ pub struct SystemObjectMetadata {
     #[serde(rename = "finalizers", skip_serializing_if = "Option::is_none")]
     pub finalizers: Option<Vec<String>>,
 
+    /// Objects this one depends on. When every owner is gone, the garbage
+    /// collector deletes this object too.
+    #[serde(rename = "ownerReferences", skip_serializing_if = "Option::is_none")]
+    pub owner_references: Option<Vec<OwnerReference>>,
+
+    /// Fields set through server-side apply, by manager. A `set` that leaves
+    /// them out clears them.
+    #[serde(rename = "managedFields", skip_serializing_if = "Option::is_none")]
+    pub managed_fields: Option<Vec<ManagedFieldsEntry>>,
+
     #[serde(flatten)]
     pub extension_data: HashMap<String, Value>,
 }
+
+impl SystemObject {
+    /// The status, created empty if the object has none.
+    pub fn status_mut(&mut self) -> &mut ObjectStatus {
+        self.status.get_or_insert_with(ObjectStatus::default)
+    }
+
+    pub fn condition(&self, condition_type: &str) -> Option<&Condition> {
+        self.status.as_ref()?.condition(condition_type)
+    }
+
+    /// Whether the condition is present and `True`.
+    pub fn is_condition_true(&self, condition_type: &str) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|s| s.is_condition_true(condition_type))
+    }
+
+    /// Sets the condition for the current generation. See
+    /// [`ObjectStatus::set_condition`].
+    pub fn set_condition(&mut self, mut condition: Condition) -> bool {
+        if condition.observed_generation.is_none() {
+            condition.observed_generation = self.metadata.generation;
+        }
+        self.status_mut().set_condition(condition)
+    }
+
+    /// Records that the status reflects the current generation.
+    pub fn observe_generation(&mut self) {
+        self.status_mut().observed_generation = self.metadata.generation;
+    }
+
+    /// Whether the status reflects the current generation.
+    pub fn is_status_current(&self) -> bool {
+        self.status.as_ref().and_then(|s| s.observed_generation) >= self.metadata.generation
+    }
+}
+
+/// A link from a dependent object to its owner, which must exist in the
+/// dependent's namespace (or in `global`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnerReference {
+    #[serde(rename = "apiVersion")]
+    pub api_version: String,
+
+    #[serde(rename = "kind")]
+    pub kind: String,
+
+    #[serde(rename = "name")]
+    pub name: String,
+
+    /// Filled in from the owner when left empty.
+    #[serde(rename = "uid", default = "Uuid::nil")]
+    pub uid: Uuid,
+
+    /// Whether the owner is the managing controller of this object.
+    #[serde(rename = "controller", skip_serializing_if = "Option::is_none")]
+    pub controller: Option<bool>,
+}
+
+/// The fields of an object one manager last applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedFieldsEntry {
+    /// Name of the applier, e.g. a controller or a CLI user.
+    #[serde(rename = "manager")]
+    pub manager: String,
+
+    /// When the manager last applied, in microseconds since the Unix epoch.
+    #[serde(rename = "time", default)]
+    pub time: i64,
+
+    /// JSON pointers of the fields the manager owns, e.g. `/spec/replicas`.
+    /// Objects are descended into; arrays and other values are owned whole.
+    #[serde(rename = "fields", default)]
+    pub fields: Vec<String>,
+}
+
+/// Finalizer held by an object deleted with [`DeletionPropagation::Orphan`]
+/// until its dependents no longer reference it.
+pub const ORPHAN_FINALIZER: &str = "orphan";
+
+/// Finalizer held by an object deleted with
+/// [`DeletionPropagation::Foreground`] until its dependents are gone.
+pub const FOREGROUND_DELETION_FINALIZER: &str = "foregroundDeletion";
+
+/// Finalizer held by a `Namespace` being deleted until the objects in it are
+/// gone.
+pub const NAMESPACE_FINALIZER: &str = "namespace";
+
+/// What happens to an object's dependents when it is deleted.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, schemars::JsonSchema,
+)]
+pub enum DeletionPropagation {
+    /// Delete the owner now; the garbage collector deletes dependents after.
+    #[default]
+    Background,
+
+    /// Delete the dependents first; the owner stays, marked for deletion,
+    /// until they are gone.
+    Foreground,
+
+    /// Keep the dependents and remove their references to the owner.
+    Orphan,
+}
+
+impl DeletionPropagation {
+    /// The finalizer that holds the owner while the policy is carried out.
+    pub fn finalizer(&self) -> Option<&'static str> {
+        match self {
+            Self::Background => None,
+            Self::Foreground => Some(FOREGROUND_DELETION_FINALIZER),
+            Self::Orphan => Some(ORPHAN_FINALIZER),
+        }
+    }
+}
+
+impl std::str::FromStr for DeletionPropagation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "background" => Ok(Self::Background),
+            "foreground" => Ok(Self::Foreground),
+            "orphan" => Ok(Self::Orphan),
+            _ => Err(format!(
+                "unknown propagation policy '{}'; expected Background, Foreground or Orphan",
+                s
+            )),
+        }
+    }
+}
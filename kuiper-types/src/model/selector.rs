@@ -0,0 +1,341 @@
+use std::{fmt, str::FromStr};
+
+use serde_json::Value;
+
+use crate::error::KuiperError;
+
+use super::resource::SystemObject;
+
+/// Filters objects by their labels, e.g. `tier=gold,env in (dev,test),!legacy`.
+///
+/// Requirements are separated by commas and must all hold:
+///
+/// * `key=value`, `key==value` and `key!=value`
+/// * `key in (a,b)` and `key notin (a,b)`
+/// * `key` (the label exists) and `!key` (it does not)
+///
+/// `!=` and `notin` also match objects without the label.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelSelector {
+    pub requirements: Vec<LabelRequirement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelRequirement {
+    pub key: String,
+    pub operator: LabelOperator,
+    /// One value for `Equals` and `NotEquals`, any number for `In` and
+    /// `NotIn`, none otherwise.
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelOperator {
+    Equals,
+    NotEquals,
+    In,
+    NotIn,
+    Exists,
+    DoesNotExist,
+}
+
+impl LabelSelector {
+    /// Whether every requirement holds for `object`. An empty selector
+    /// matches everything.
+    pub fn matches(&self, object: &SystemObject) -> bool {
+        let labels = object.metadata.labels.as_ref();
+        self.requirements.iter().all(|requirement| {
+            let value = labels.and_then(|l| l.get(&requirement.key));
+            requirement.matches(value.map(String::as_str))
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+}
+
+impl LabelRequirement {
+    fn matches(&self, value: Option<&str>) -> bool {
+        let listed = |v: &str| self.values.iter().any(|x| x == v);
+        match self.operator {
+            LabelOperator::Equals | LabelOperator::In => value.is_some_and(listed),
+            LabelOperator::NotEquals | LabelOperator::NotIn => !value.is_some_and(listed),
+            LabelOperator::Exists => value.is_some(),
+            LabelOperator::DoesNotExist => value.is_none(),
+        }
+    }
+}
+
+impl FromStr for LabelSelector {
+    type Err = KuiperError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            |reason: &str| KuiperError::Invalid(format!("label selector '{}': {}", s, reason));
+
+        let mut requirements = Vec::new();
+        for term in split_terms(s).map_err(|e| invalid(&e))? {
+            let term = term.trim();
+            if term.is_empty() {
+                continue;
+            }
+
+            let requirement = if let Some(key) = term.strip_prefix('!') {
+                LabelRequirement {
+                    key: parse_key(key).map_err(|e| invalid(&e))?,
+                    operator: LabelOperator::DoesNotExist,
+                    values: Vec::new(),
+                }
+            } else if let Some((key, value)) = term.split_once("!=") {
+                LabelRequirement {
+                    key: parse_key(key).map_err(|e| invalid(&e))?,
+                    operator: LabelOperator::NotEquals,
+                    values: vec![parse_value(value).map_err(|e| invalid(&e))?],
+                }
+            } else if let Some((key, value)) =
+                term.split_once("==").or_else(|| term.split_once('='))
+            {
+                LabelRequirement {
+                    key: parse_key(key).map_err(|e| invalid(&e))?,
+                    operator: LabelOperator::Equals,
+                    values: vec![parse_value(value).map_err(|e| invalid(&e))?],
+                }
+            } else if let Some((key, operator, values)) = parse_set_term(term) {
+                let values = values
+                    .split(',')
+                    .map(parse_value)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| invalid(&e))?;
+                LabelRequirement {
+                    key: parse_key(key).map_err(|e| invalid(&e))?,
+                    operator,
+                    values,
+                }
+            } else {
+                LabelRequirement {
+                    key: parse_key(term).map_err(|e| invalid(&e))?,
+                    operator: LabelOperator::Exists,
+                    values: Vec::new(),
+                }
+            };
+            requirements.push(requirement);
+        }
+
+        Ok(Self { requirements })
+    }
+}
+
+impl fmt::Display for LabelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<String> = self
+            .requirements
+            .iter()
+            .map(|r| match r.operator {
+                LabelOperator::Equals => format!("{}={}", r.key, r.values[0]),
+                LabelOperator::NotEquals => format!("{}!={}", r.key, r.values[0]),
+                LabelOperator::In => format!("{} in ({})", r.key, r.values.join(",")),
+                LabelOperator::NotIn => format!("{} notin ({})", r.key, r.values.join(",")),
+                LabelOperator::Exists => r.key.clone(),
+                LabelOperator::DoesNotExist => format!("!{}", r.key),
+            })
+            .collect();
+        write!(f, "{}", terms.join(","))
+    }
+}
+
+/// Splits on the commas that separate requirements, not those in `(...)`.
+fn split_terms(s: &str) -> Result<Vec<&str>, String> {
+    let mut terms = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' if depth == 0 => depth = 1,
+            '(' => return Err("nested parentheses".to_string()),
+            ')' if depth == 1 => depth = 0,
+            ')' => return Err("unbalanced parentheses".to_string()),
+            ',' if depth == 0 => {
+                terms.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err("unbalanced parentheses".to_string());
+    }
+    terms.push(&s[start..]);
+    Ok(terms)
+}
+
+/// `key in (a,b)` or `key notin (a,b)`, as key, operator and `a,b`.
+fn parse_set_term(term: &str) -> Option<(&str, LabelOperator, &str)> {
+    let (head, values) = term.strip_suffix(')')?.split_once('(')?;
+    let mut words = head.split_whitespace();
+    let key = words.next()?;
+    let operator = match words.next()? {
+        "in" => LabelOperator::In,
+        "notin" => LabelOperator::NotIn,
+        _ => return None,
+    };
+    if words.next().is_some() {
+        return None;
+    }
+    Some((key, operator, values))
+}
+
+fn parse_key(key: &str) -> Result<String, String> {
+    let key = key.trim();
+    let valid = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'));
+    if valid {
+        Ok(key.to_string())
+    } else {
+        Err(format!("invalid label key '{}'", key))
+    }
+}
+
+fn parse_value(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    let valid = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(value.to_string())
+    } else {
+        Err(format!("invalid label value '{}'", value))
+    }
+}
+
+/// Filters objects by a few well-known fields, e.g.
+/// `metadata.name=web,status.phase!=Failed`.
+///
+/// Requirements are `field=value`, `field==value` or `field!=value`,
+/// separated by commas. The supported fields are `metadata.name`,
+/// `metadata.namespace`, `status.phase` and `metadata.deletionTimestamp`,
+/// plus any passed to [`FieldSelector::parse_with_fields`]. A missing field
+/// has the empty value, so `metadata.deletionTimestamp=` selects objects that
+/// are not being deleted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldSelector {
+    pub requirements: Vec<FieldRequirement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldRequirement {
+    pub field: String,
+    pub value: String,
+    /// `true` for `!=`.
+    pub negated: bool,
+}
+
+/// Fields every selector may use.
+pub const SELECTABLE_FIELDS: &[&str] = &[
+    "metadata.name",
+    "metadata.namespace",
+    "metadata.deletionTimestamp",
+    "status.phase",
+];
+
+impl FieldSelector {
+    /// Whether every requirement holds for `object`. An empty selector
+    /// matches everything.
+    pub fn matches(&self, object: &SystemObject) -> bool {
+        self.requirements.iter().all(|requirement| {
+            let value = field_value(object, &requirement.field).unwrap_or_default();
+            (value == requirement.value) != requirement.negated
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+
+    /// Parses `s`, accepting `fields` (dotted paths such as `spec.host`)
+    /// besides the [`SELECTABLE_FIELDS`].
+    pub fn parse_with_fields(s: &str, fields: &[String]) -> Result<Self, KuiperError> {
+        let mut requirements = Vec::new();
+        for term in s.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let (field, value, negated) = if let Some((f, v)) = term.split_once("!=") {
+                (f, v, true)
+            } else if let Some((f, v)) = term.split_once("==").or_else(|| term.split_once('=')) {
+                (f, v, false)
+            } else {
+                return Err(KuiperError::Invalid(format!(
+                    "field selector '{}': '{}' is not of the form field=value",
+                    s, term
+                )));
+            };
+            let field = field.trim();
+            if !SELECTABLE_FIELDS.contains(&field) && !fields.iter().any(|f| f == field) {
+                let supported: Vec<&str> = SELECTABLE_FIELDS
+                    .iter()
+                    .copied()
+                    .chain(fields.iter().map(String::as_str))
+                    .collect();
+                return Err(KuiperError::Invalid(format!(
+                    "field selector '{}': field '{}' is not supported; use one of {}",
+                    s,
+                    field,
+                    supported.join(", ")
+                )));
+            }
+            requirements.push(FieldRequirement {
+                field: field.to_string(),
+                value: value.trim().to_string(),
+                negated,
+            });
+        }
+
+        Ok(Self { requirements })
+    }
+}
+
+/// The value of the field at the dotted path `field` of `object`, as text.
+/// `None` when the field is missing, `null`, an object or an array.
+pub fn field_value(object: &SystemObject, field: &str) -> Option<String> {
+    let metadata = &object.metadata;
+    match field {
+        "metadata.name" => Some(metadata.name.clone()),
+        "metadata.namespace" => metadata.namespace.clone(),
+        "metadata.deletionTimestamp" => metadata.deletion_timestamp.map(|t| t.to_string()),
+        "status.phase" => object.status.as_ref().and_then(|s| s.phase.clone()),
+        _ => {
+            let pointer: String = field
+                .split('.')
+                .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+                .collect();
+            match serde_json::to_value(object).ok()?.pointer(&pointer)? {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                Value::Bool(b) => Some(b.to_string()),
+                _ => None,
+            }
+        }
+    }
+}
+
+impl FromStr for FieldSelector {
+    type Err = KuiperError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with_fields(s, &[])
+    }
+}
+
+impl fmt::Display for FieldSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<String> = self
+            .requirements
+            .iter()
+            .map(|r| {
+                let operator = if r.negated { "!=" } else { "=" };
+                format!("{}{}{}", r.field, operator, r.value)
+            })
+            .collect();
+        write!(f, "{}", terms.join(","))
+    }
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Observed state of an object, written by the controllers that act on it.
+///
+/// Besides `conditions` and `observedGeneration`, a status may carry any
+/// kind-specific fields, kept in `extension_data`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ObjectStatus {
+    /// The `metadata.generation` the controller last acted on. When it is
+    /// behind the object's generation, the rest of the status is stale.
+    #[serde(rename = "observedGeneration", skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
+
+    /// Short summary of the object's state, e.g. `Ready`. Objects written
+    /// before status was structured keep their status text here.
+    #[serde(rename = "phase", skip_serializing_if = "Option::is_none")]
+    pub phase: Option<String>,
+
+    #[serde(rename = "conditions", default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<Condition>,
+
+    #[serde(flatten)]
+    pub extension_data: HashMap<String, Value>,
+}
+
+/// One aspect of an object's state, e.g. whether it is `Ready`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Condition {
+    /// Name of the condition, in `CamelCase`. Unique within a status.
+    #[serde(rename = "type")]
+    pub condition_type: String,
+
+    #[serde(rename = "status")]
+    pub status: ConditionStatus,
+
+    /// Machine-readable `CamelCase` reason for the last transition.
+    #[serde(rename = "reason", default, skip_serializing_if = "String::is_empty")]
+    pub reason: String,
+
+    /// Human-readable details about the last transition.
+    #[serde(rename = "message", default, skip_serializing_if = "String::is_empty")]
+    pub message: String,
+
+    /// When `status` last changed, in microseconds since the Unix epoch.
+    #[serde(rename = "lastTransitionTime", default)]
+    pub last_transition_time: i64,
+
+    /// The `metadata.generation` the condition was set for.
+    #[serde(rename = "observedGeneration", skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConditionStatus {
+    True,
+    False,
+    Unknown,
+}
+
+impl Condition {
+    pub fn new(condition_type: &str, status: ConditionStatus, reason: &str, message: &str) -> Self {
+        Self {
+            condition_type: condition_type.to_string(),
+            status,
+            reason: reason.to_string(),
+            message: message.to_string(),
+            last_transition_time: 0,
+            observed_generation: None,
+        }
+    }
+
+    pub fn is_true(&self) -> bool {
+        self.status == ConditionStatus::True
+    }
+}
+
+impl ObjectStatus {
+    pub fn condition(&self, condition_type: &str) -> Option<&Condition> {
+        self.conditions
+            .iter()
+            .find(|c| c.condition_type == condition_type)
+    }
+
+    /// Whether the condition is present and `True`.
+    pub fn is_condition_true(&self, condition_type: &str) -> bool {
+        self.condition(condition_type)
+            .is_some_and(Condition::is_true)
+    }
+
+    /// Adds or updates the condition of the same type. `lastTransitionTime`
+    /// only moves when the condition's status changes; an unset time is taken
+    /// as now. Returns whether anything changed.
+    pub fn set_condition(&mut self, mut condition: Condition) -> bool {
+        match self
+            .conditions
+            .iter_mut()
+            .find(|c| c.condition_type == condition.condition_type)
+        {
+            Some(existing) => {
+                if existing.status == condition.status {
+                    condition.last_transition_time = existing.last_transition_time;
+                } else if condition.last_transition_time == 0 {
+                    condition.last_transition_time = chrono::Utc::now().timestamp_micros();
+                }
+                if *existing == condition {
+                    return false;
+                }
+                *existing = condition;
+            }
+            None => {
+                if condition.last_transition_time == 0 {
+                    condition.last_transition_time = chrono::Utc::now().timestamp_micros();
+                }
+                self.conditions.push(condition);
+            }
+        }
+        true
+    }
+
+    /// Returns whether the condition was present.
+    pub fn remove_condition(&mut self, condition_type: &str) -> bool {
+        let before = self.conditions.len();
+        self.conditions
+            .retain(|c| c.condition_type != condition_type);
+        self.conditions.len() != before
+    }
+}
+
+/// Reads `status` both as an [`ObjectStatus`] and as the plain string that
+/// objects stored before structured status used, which becomes the `phase`.
+pub(crate) fn deserialize_status<'de, D>(deserializer: D) -> Result<Option<ObjectStatus>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredStatus {
+        Text(String),
+        Structured(ObjectStatus),
+    }
+
+    Ok(
+        Option::<StoredStatus>::deserialize(deserializer)?.map(|status| match status {
+            StoredStatus::Text(phase) => ObjectStatus {
+                phase: Some(phase),
+                ..Default::default()
+            },
+            StoredStatus::Structured(status) => status,
+        }),
+    )
+}
@@ -1,4 +1,6 @@
 use crate::model::resource::{SystemObject, SystemObjectMetadata};
+use crate::model::selector::{FieldSelector, LabelSelector};
+use crate::model::status::{Condition, ConditionStatus, ObjectStatus};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -15,6 +17,7 @@ fn test_system_object_metadata_serialization() {
             creation_timestamp: Some(1682839600),
             deletion_timestamp: None,
             resource_version: Some("v1".to_string()),
+            generation: None,
             self_link: Some("/api/v1/namespaces/default/my-object".to_string()),
             labels: Some(HashMap::from([(
                 "env".to_string(),
@@ -25,6 +28,8 @@ fn test_system_object_metadata_serialization() {
                 "dev-team".to_string(),
             )])),
             finalizers: Some(vec!["cleanup".to_string()]),
+            owner_references: None,
+            managed_fields: None,
             extension_data: HashMap::new(),
         },
         status: None,
@@ -66,3 +71,131 @@ fn test_system_object_metadata_serialization() {
 
     assert_eq!(expected_json, actual_json);
 }
+
+#[test]
+fn test_legacy_string_status_migrates_to_phase() {
+    let legacy: SystemObject = serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": "MyObject",
+        "metadata": { "name": "legacy" },
+        "status": "Ready"
+    }))
+    .unwrap();
+
+    assert_eq!(
+        legacy.status,
+        Some(ObjectStatus {
+            phase: Some("Ready".to_string()),
+            ..Default::default()
+        })
+    );
+    assert_eq!(
+        serde_json::to_value(&legacy).unwrap()["status"],
+        json!({ "phase": "Ready" })
+    );
+
+    let missing: SystemObject = serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": "MyObject",
+        "metadata": { "name": "missing" }
+    }))
+    .unwrap();
+    assert!(missing.status.is_none());
+}
+
+#[test]
+fn test_set_condition_tracks_transitions() {
+    let mut object: SystemObject = serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": "MyObject",
+        "metadata": { "name": "conditions", "generation": 3 }
+    }))
+    .unwrap();
+    assert!(!object.is_status_current());
+
+    assert!(object.set_condition(Condition::new(
+        "Ready",
+        ConditionStatus::False,
+        "Provisioning",
+        "waiting for capacity"
+    )));
+    let first = object.condition("Ready").unwrap().clone();
+    assert!(first.last_transition_time > 0);
+    assert_eq!(first.observed_generation, Some(3));
+    assert!(!object.is_condition_true("Ready"));
+
+    // Same status: nothing changes, not even the transition time.
+    assert!(!object.set_condition(Condition::new(
+        "Ready",
+        ConditionStatus::False,
+        "Provisioning",
+        "waiting for capacity"
+    )));
+
+    // New reason only: the transition time is kept.
+    assert!(object.set_condition(Condition::new(
+        "Ready",
+        ConditionStatus::False,
+        "Scheduling",
+        ""
+    )));
+    assert_eq!(
+        object.condition("Ready").unwrap().last_transition_time,
+        first.last_transition_time
+    );
+
+    assert!(object.set_condition(Condition::new(
+        "Ready",
+        ConditionStatus::True,
+        "Running",
+        ""
+    )));
+    assert!(object.is_condition_true("Ready"));
+
+    object.observe_generation();
+    assert!(object.is_status_current());
+
+    let status = object.status_mut();
+    assert!(status.remove_condition("Ready"));
+    assert!(!status.remove_condition("Ready"));
+}
+
+#[test]
+fn test_label_and_field_selectors() {
+    let object: SystemObject = serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": "MyObject",
+        "metadata": {
+            "name": "web",
+            "namespace": "default",
+            "labels": { "tier": "gold", "env": "dev" }
+        },
+        "status": { "phase": "Running" }
+    }))
+    .unwrap();
+
+    let matches = |selector: &str| selector.parse::<LabelSelector>().unwrap().matches(&object);
+    assert!(matches(""));
+    assert!(matches("tier=gold"));
+    assert!(matches("tier==gold,env"));
+    assert!(matches("env in (dev, test),tier notin (bronze)"));
+    assert!(matches("!legacy,owner!=ops"));
+    assert!(!matches("tier!=gold"));
+    assert!(!matches("env in (prod),tier=gold"));
+    assert!(!matches("!env"));
+
+    let selector: LabelSelector = "env in (dev,test),!legacy".parse().unwrap();
+    assert_eq!(
+        selector.to_string().parse::<LabelSelector>().unwrap(),
+        selector
+    );
+    assert!("env in (dev".parse::<LabelSelector>().is_err());
+    assert!("tier=gold!".parse::<LabelSelector>().is_err());
+
+    let matches = |selector: &str| selector.parse::<FieldSelector>().unwrap().matches(&object);
+    assert!(matches("metadata.name=web,metadata.namespace==default"));
+    assert!(matches("status.phase!=Failed,metadata.deletionTimestamp="));
+    assert!(!matches("metadata.name=api"));
+    assert!("spec.size=1".parse::<FieldSelector>().is_err());
+    assert!("metadata.name".parse::<FieldSelector>().is_err());
+}
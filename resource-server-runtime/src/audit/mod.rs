@@ -0,0 +1,88 @@
+//! Command audit log.
+//!
+//! [`AuditStage`] is a [`PipelineStage`](kuiper_runtime::command::PipelineStage)
+//! that records every mutating command — who ran it, when, against which key,
+//! and whether it succeeded — to one or more [`AuditSink`]s. An [`AuditPolicy`]
+//! chooses how much of the object is captured per group/kind and which fields
+//! are redacted before a record leaves the process.
+
+pub mod policy;
+pub mod sink;
+pub mod stage;
+
+pub use policy::{AuditLevel, AuditPolicy, AuditRule};
+pub use sink::{AuditSink, JsonLinesFileSink, StoreAuditSink, TracingAuditSink};
+pub use stage::AuditStage;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use kuiper_types::model::security::UserId;
+
+/// The container used by [`StoreAuditSink`].
+pub const AUDIT_CONTAINER: &str = "audit";
+
+// ── AuditOutcome ──────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+// ── AuditRecord ───────────────────────────────────────────────────────────────
+
+/// A single audited command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub id: Uuid,
+
+    /// Microseconds since the Unix epoch at which the command completed.
+    pub timestamp: i64,
+
+    #[serde(rename = "activityId")]
+    pub activity_id: Uuid,
+
+    #[serde(rename = "callerId", skip_serializing_if = "Option::is_none")]
+    pub caller_id: Option<UserId>,
+
+    /// The command name, e.g. `set` or `delete`.
+    pub operation: String,
+
+    pub namespace: String,
+
+    /// Storage key of the target object: `{namespace}/{resource}`.
+    pub key: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+
+    pub level: AuditLevel,
+
+    pub outcome: AuditOutcome,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// The stored object before the command ran. Only captured at
+    /// [`AuditLevel::Full`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+
+    /// The object returned by the command. Only captured at
+    /// [`AuditLevel::Full`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+}
+
+impl AuditRecord {
+    /// Store key: `{timestamp}-{id}`, zero-padded so keys sort chronologically.
+    pub fn key(&self) -> String {
+        format!("{:020}-{}", self.timestamp, self.id)
+    }
+}
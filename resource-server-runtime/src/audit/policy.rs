@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Replacement written over redacted fields.
+pub const REDACTED: &str = "***";
+
+// ── AuditLevel ────────────────────────────────────────────────────────────────
+
+/// How much of a command is recorded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditLevel {
+    /// Nothing is recorded.
+    None,
+    /// Caller, time, key, operation and outcome.
+    #[default]
+    Metadata,
+    /// Metadata plus the object before and after the command.
+    Full,
+}
+
+// ── AuditRule ─────────────────────────────────────────────────────────────────
+
+/// Selects the audit level for a group and/or kind. An omitted `group` or
+/// `kind` matches any value; both are compared case-insensitively.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditRule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+
+    pub level: AuditLevel,
+
+    /// JSON pointers (e.g. `/spec/password`) blanked out of `before`/`after`,
+    /// in addition to the policy-wide list.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact: Vec<String>,
+}
+
+impl AuditRule {
+    fn matches(&self, group: Option<&str>, kind: Option<&str>) -> bool {
+        fn field_matches(rule: &Option<String>, value: Option<&str>) -> bool {
+            match rule {
+                None => true,
+                Some(expected) => value.is_some_and(|v| v.eq_ignore_ascii_case(expected)),
+            }
+        }
+
+        field_matches(&self.group, group) && field_matches(&self.kind, kind)
+    }
+}
+
+// ── AuditPolicy ───────────────────────────────────────────────────────────────
+
+/// Chooses the audit level per group/kind. Rules are evaluated in order and
+/// the first match wins; `default_level` applies when none match.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditPolicy {
+    #[serde(rename = "defaultLevel", default)]
+    pub default_level: AuditLevel,
+
+    #[serde(default)]
+    pub rules: Vec<AuditRule>,
+
+    /// JSON pointers redacted for every group/kind.
+    #[serde(default)]
+    pub redact: Vec<String>,
+}
+
+impl AuditPolicy {
+    pub fn new(default_level: AuditLevel) -> Self {
+        Self {
+            default_level,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_rule(mut self, rule: AuditRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn with_redaction(mut self, pointer: impl Into<String>) -> Self {
+        self.redact.push(pointer.into());
+        self
+    }
+
+    fn rule_for(&self, group: Option<&str>, kind: Option<&str>) -> Option<&AuditRule> {
+        self.rules.iter().find(|r| r.matches(group, kind))
+    }
+
+    /// The level that applies to `group`/`kind`.
+    pub fn level_for(&self, group: Option<&str>, kind: Option<&str>) -> AuditLevel {
+        self.rule_for(group, kind)
+            .map_or(self.default_level, |r| r.level)
+    }
+
+    /// Blanks every policy-wide and matching rule's redacted field in `value`.
+    pub fn redact(&self, group: Option<&str>, kind: Option<&str>, value: &mut Value) {
+        let rule_paths = self
+            .rule_for(group, kind)
+            .map(|r| r.redact.as_slice())
+            .unwrap_or_default();
+
+        for pointer in self.redact.iter().chain(rule_paths) {
+            if let Some(field) = value.pointer_mut(pointer) {
+                *field = Value::String(REDACTED.to_string());
+            }
+        }
+    }
+}
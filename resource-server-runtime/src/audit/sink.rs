@@ -0,0 +1,163 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::data::TransactionalKeyValueStore;
+use tokio::sync::RwLock;
+
+use super::{AuditRecord, AUDIT_CONTAINER};
+
+/// A destination for audit records. A failing sink is logged and never fails
+/// the audited command.
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    async fn write(&self, record: &AuditRecord) -> anyhow::Result<()>;
+
+    /// Deletes the records that outlived the sink's retention and returns how
+    /// many were deleted. Sinks that hand records off, or leave rotation to the
+    /// host, keep nothing to purge.
+    async fn purge_expired(&self) -> anyhow::Result<usize> {
+        Ok(0)
+    }
+}
+
+// ── JsonLinesFileSink ─────────────────────────────────────────────────────────
+
+/// Appends one JSON object per line to a file.
+pub struct JsonLinesFileSink {
+    file: Mutex<File>,
+}
+
+impl JsonLinesFileSink {
+    pub fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+#[async_trait]
+impl AuditSink for JsonLinesFileSink {
+    async fn write(&self, record: &AuditRecord) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Audit log file lock poisoned"))?;
+        file.write_all(&line)
+            .context("Failed to append audit record")?;
+        Ok(())
+    }
+}
+
+// ── StoreAuditSink ────────────────────────────────────────────────────────────
+
+/// Writes each record to the `audit` container of the shared store. Records
+/// older than the retention are deleted by [`purge_expired`](AuditSink::purge_expired).
+pub struct StoreAuditSink {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    retention: Duration,
+}
+
+impl StoreAuditSink {
+    /// How long records are kept unless [`with_retention`](Self::with_retention) says otherwise.
+    pub const DEFAULT_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+    pub fn new(store: Arc<RwLock<dyn TransactionalKeyValueStore>>) -> Self {
+        Self {
+            store,
+            retention: Self::DEFAULT_RETENTION,
+        }
+    }
+
+    /// Keeps records for `retention` instead of [`Self::DEFAULT_RETENTION`].
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+}
+
+#[async_trait]
+impl AuditSink for StoreAuditSink {
+    async fn write(&self, record: &AuditRecord) -> anyhow::Result<()> {
+        let bytes = serde_json::to_vec(record)?;
+        let store = self.store.write().await;
+        store
+            .ensure_container(AUDIT_CONTAINER)
+            .await
+            .context("Failed to create audit container")?;
+        store
+            .put(AUDIT_CONTAINER, &record.key(), bytes)
+            .await
+            .context("Failed to write audit record")?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> anyhow::Result<usize> {
+        let store = self.store.write().await;
+        if !store
+            .container_exists(AUDIT_CONTAINER)
+            .await
+            .context("Failed to check audit container")?
+        {
+            return Ok(0);
+        }
+
+        // Keys start with the record's timestamp, see `AuditRecord::key`.
+        let retention = i64::try_from(self.retention.as_micros()).unwrap_or(i64::MAX);
+        let cutoff = chrono::Utc::now()
+            .timestamp_micros()
+            .saturating_sub(retention);
+        let mut purged = 0;
+        for key in store.list_keys(AUDIT_CONTAINER, None).await? {
+            let expired = key
+                .split_once('-')
+                .and_then(|(timestamp, _)| timestamp.parse::<i64>().ok())
+                .is_some_and(|timestamp| timestamp < cutoff);
+            if expired {
+                store
+                    .delete(AUDIT_CONTAINER, &key)
+                    .await
+                    .context("Failed to delete audit record")?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+}
+
+// ── TracingAuditSink ──────────────────────────────────────────────────────────
+
+/// Emits each record as an event on the `kuiper::audit` tracing target.
+pub struct TracingAuditSink;
+
+#[async_trait]
+impl AuditSink for TracingAuditSink {
+    async fn write(&self, record: &AuditRecord) -> anyhow::Result<()> {
+        tracing::info!(
+            target: "kuiper::audit",
+            activity_id = %record.activity_id,
+            operation = %record.operation,
+            key = %record.key,
+            outcome = ?record.outcome,
+            "{}",
+            serde_json::to_string(record)?
+        );
+        Ok(())
+    }
+}
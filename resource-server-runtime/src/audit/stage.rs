@@ -0,0 +1,164 @@
+use std::{collections::HashSet, sync::Arc};
+
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{CommandContext, CommandResult, Next, PipelineStage},
+    data::TransactionalKeyValueStore,
+};
+use serde_json::Value;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::{AuditLevel, AuditOutcome, AuditPolicy, AuditRecord, AuditSink};
+use crate::constants::{resource_key, RESOURCE_CONTAINER};
+
+/// Commands audited by default.
+const MUTATING_COMMANDS: &[&str] = &["set", "set_status", "delete", "finalize"];
+
+/// Pipeline stage that records mutating commands to the configured sinks.
+pub struct AuditStage {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    policy: AuditPolicy,
+    sinks: Vec<Arc<dyn AuditSink>>,
+    commands: HashSet<String>,
+}
+
+impl AuditStage {
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        policy: AuditPolicy,
+        sinks: Vec<Arc<dyn AuditSink>>,
+    ) -> Self {
+        Self {
+            store,
+            policy,
+            sinks,
+            commands: MUTATING_COMMANDS.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    /// Audits `command` in addition to the built-in mutating commands.
+    pub fn with_command(mut self, command: &str) -> Self {
+        self.commands.insert(command.to_string());
+        self
+    }
+
+    /// The stored object, as seen through the batch when the command is part of one.
+    async fn load(&self, ctx: &CommandContext, key: &str) -> Option<Value> {
+        let store = self.store.read().await;
+        let bytes = match &ctx.write_batch {
+            Some(batch) => batch.get(&*store, RESOURCE_CONTAINER, key).await?,
+            None => store.get(RESOURCE_CONTAINER, key).await.ok()?,
+        };
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+async fn emit(sinks: &[Arc<dyn AuditSink>], record: &AuditRecord) {
+    for sink in sinks {
+        if let Err(e) = sink.write(record).await {
+            tracing::warn!(
+                activity_id = %record.activity_id,
+                "Failed to write audit record: {}",
+                e
+            );
+        }
+    }
+}
+
+/// Splits `apiVersion`/`kind` out of an object, if present.
+fn group_kind(obj: &Value) -> (Option<String>, Option<String>) {
+    let group = obj
+        .get("apiVersion")
+        .and_then(Value::as_str)
+        .map(|v| v.split('/').next().unwrap_or_default().to_string());
+    let kind = obj.get("kind").and_then(Value::as_str).map(str::to_string);
+    (group, kind)
+}
+
+#[async_trait]
+impl PipelineStage for AuditStage {
+    async fn handle(&self, ctx: &mut CommandContext, next: Next<'_>) -> CommandResult {
+        if !self.commands.contains(&ctx.command_name) {
+            return next.run(ctx).await;
+        }
+
+        let namespace = ctx
+            .metadata
+            .get("namespace")
+            .cloned()
+            .unwrap_or_default()
+            .to_lowercase();
+        let resource = ctx.get_string_param("resource").ok();
+        let key = resource_key(&namespace, resource.as_deref());
+
+        let before = self.load(ctx, &key).await;
+
+        // Prefer the submitted object, then the stored one, then the REST path
+        // shape `{group}/{kind}/{name}`.
+        let (group, kind) = match ctx.parameters.get("value").map(group_kind) {
+            Some((Some(g), Some(k))) => (Some(g), Some(k)),
+            _ => match before.as_ref().map(group_kind) {
+                Some((Some(g), Some(k))) => (Some(g), Some(k)),
+                _ => {
+                    let mut parts = resource.as_deref().unwrap_or_default().split('/');
+                    (
+                        parts.next().map(str::to_string),
+                        parts.next().map(str::to_string),
+                    )
+                }
+            },
+        };
+
+        let level = self.policy.level_for(group.as_deref(), kind.as_deref());
+        if level == AuditLevel::None {
+            return next.run(ctx).await;
+        }
+
+        let result = next.run(ctx).await;
+
+        let (outcome, error, after) = match &result {
+            Ok(value) => (AuditOutcome::Success, None, value.clone()),
+            Err(e) => (AuditOutcome::Failure, Some(e.to_string()), None),
+        };
+
+        let (before, after) = if level == AuditLevel::Full {
+            let redact = |mut v: Value| {
+                self.policy
+                    .redact(group.as_deref(), kind.as_deref(), &mut v);
+                v
+            };
+            (before.map(redact), after.map(redact))
+        } else {
+            (None, None)
+        };
+
+        let record = AuditRecord {
+            id: Uuid::new_v4(),
+            timestamp: chrono::Utc::now().timestamp_micros(),
+            activity_id: ctx.activity_id,
+            caller_id: ctx.caller_id.clone(),
+            operation: ctx.command_name.clone(),
+            namespace,
+            key,
+            group,
+            kind,
+            level,
+            outcome,
+            error,
+            before,
+            after,
+        };
+
+        // A batched write only happens once the whole batch commits.
+        match (&ctx.write_batch, &result) {
+            (Some(batch), Ok(_)) => {
+                let sinks = self.sinks.clone();
+                batch.after_commit(Box::pin(async move { emit(&sinks, &record).await }));
+            }
+            _ => emit(&self.sinks, &record).await,
+        }
+
+        result
+    }
+}
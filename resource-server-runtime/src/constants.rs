@@ -10,6 +10,9 @@ pub(crate) const SYSTEM_API_VERSION: &str = "v1alpha1";
 /// The pseudo-namespace used for system-scoped (cluster-wide) resources.
 pub(crate) const GLOBAL_NAMESPACE: &str = "global";
 
+/// The namespace that exists without a `Namespace` object.
+pub(crate) const DEFAULT_NAMESPACE: &str = "default";
+
 /// Constructs the storage key for a resource: `{namespace}/{resource}` (lower-cased).
 pub(crate) fn resource_key(namespace: &str, resource: Option<&str>) -> String {
     format!("{}/{}", namespace, resource.unwrap_or("")).to_lowercase()
@@ -0,0 +1,415 @@
+//! Controllers that reconcile one group/kind.
+//!
+//! Implement [`Reconciler`] for a kind and register it with
+//! [`KuiperRuntimeBuilder::with_controller`](crate::KuiperRuntimeBuilder::with_controller).
+//! The resulting [`Controller`] is a [`HostedService`] that lists every object
+//! of the kind on start and every `resync_interval`, and watches `set`,
+//! `set_status`, `delete` and `finalize` through an `Observer` handler. Changed keys go into a
+//! deduplicating [`WorkQueue`], from which workers hand one object at a time
+//! to the reconciler. Failures are retried with per-key exponential backoff.
+
+mod queue;
+
+pub use queue::{RateLimit, WorkQueue};
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{
+        parse_response, CommandContext, CommandDispatcher, CommandHandler, CommandRequest,
+        CommandResult, CommandType, ExecutableCommand,
+    },
+    data::TransactionalKeyValueStore,
+    service::{HostedService, ServiceTask},
+};
+use kuiper_types::model::{resource::SystemObject, status::ObjectStatus};
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    constants::{resource_key, RESOURCE_CONTAINER},
+    handlers::set_status::SetStatusRequest,
+};
+
+/// What the controller does with an object after reconciling it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Nothing to do until the object changes or the next resync.
+    AwaitChange,
+
+    /// Reconcile the object again after the delay, e.g. to poll progress.
+    RequeueAfter(Duration),
+}
+
+impl Action {
+    pub fn await_change() -> Self {
+        Self::AwaitChange
+    }
+
+    pub fn requeue(after: Duration) -> Self {
+        Self::RequeueAfter(after)
+    }
+}
+
+/// Drives objects of one group/kind towards their desired state.
+///
+/// Reconcilers must be idempotent: they are called again on every change, on
+/// every resync and after every failure, and see only the latest object. An
+/// `Err` requeues the object with backoff.
+#[async_trait]
+pub trait Reconciler: Send + Sync {
+    async fn reconcile(
+        &self,
+        object: SystemObject,
+        ctx: &ReconcileContext,
+    ) -> anyhow::Result<Action>;
+}
+
+/// Tuning knobs for a [`Controller`].
+#[derive(Debug, Clone)]
+pub struct ControllerOptions {
+    /// API group of the reconciled kind, e.g. `vm.example.dev`.
+    pub group: String,
+
+    /// Kind reconciled, e.g. `VirtualMachineCluster`.
+    pub kind: String,
+
+    /// Objects reconciled concurrently. A single object is never reconciled
+    /// by two workers at once.
+    pub workers: usize,
+
+    /// How often every object is queued again, even without changes.
+    pub resync_interval: Duration,
+
+    /// Backoff of objects whose reconcile failed.
+    pub rate_limit: RateLimit,
+}
+
+impl ControllerOptions {
+    pub fn new(group: &str, kind: &str) -> Self {
+        Self {
+            group: group.to_string(),
+            kind: kind.to_string(),
+            workers: 1,
+            resync_interval: Duration::from_secs(300),
+            rate_limit: RateLimit::default(),
+        }
+    }
+
+    /// Whether `object` is of the reconciled group/kind.
+    fn matches(&self, object: &SystemObject) -> bool {
+        let group = object
+            .api_version
+            .split_once('/')
+            .map_or("", |(group, _)| group);
+        group.eq_ignore_ascii_case(&self.group) && object.kind.eq_ignore_ascii_case(&self.kind)
+    }
+}
+
+/// Handed to [`Reconciler::reconcile`] with the object being reconciled.
+pub struct ReconcileContext {
+    dispatcher: Arc<dyn CommandDispatcher>,
+    namespace: String,
+    resource: String,
+    cancellation_token: CancellationToken,
+}
+
+impl ReconcileContext {
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Path of the object within its namespace, as used by `get`, `set` and
+    /// `delete`.
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    /// Cancelled when the controller stops.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation_token
+    }
+
+    /// Runs `request` as an internal command, e.g. a
+    /// [`DeleteRequest`](crate::handlers::delete::DeleteRequest).
+    pub async fn send<R: CommandRequest>(&self, request: &R) -> anyhow::Result<R::Response> {
+        let mut ctx = CommandContext::from_request(request)?;
+        ctx.is_internal = true;
+        ctx.cancellation_token = self.cancellation_token.child_token();
+        let result = self.dispatcher.dispatch(&mut ctx).await?;
+        parse_response(result)
+    }
+
+    /// Writes `status` to `object` through `set_status` unless it already has
+    /// it, and returns the stored object. The write is conditional on the
+    /// object's `resourceVersion`, so a stale object fails with a conflict and
+    /// is reconciled again.
+    pub async fn update_status(
+        &self,
+        mut object: SystemObject,
+        status: ObjectStatus,
+    ) -> anyhow::Result<SystemObject> {
+        if object.status.as_ref() == Some(&status) {
+            return Ok(object);
+        }
+
+        object.status = Some(status);
+
+        self.send(&SetStatusRequest {
+            namespace: self.namespace.clone(),
+            resource: self.resource.clone(),
+            value: object,
+        })
+        .await
+    }
+}
+
+/// Runs a [`Reconciler`] for every object of one group/kind.
+pub struct Controller {
+    name: String,
+    options: ControllerOptions,
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    dispatcher: Arc<dyn CommandDispatcher>,
+    reconciler: Arc<dyn Reconciler>,
+    queue: Arc<WorkQueue>,
+    /// Replaced on every start, so the controller can be started again after
+    /// it was stopped (e.g. when it only runs while leader).
+    stop: Mutex<CancellationToken>,
+    task: ServiceTask,
+}
+
+impl Controller {
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        dispatcher: Arc<dyn CommandDispatcher>,
+        options: ControllerOptions,
+        reconciler: Arc<dyn Reconciler>,
+        queue: Arc<WorkQueue>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            name: format!("{}-controller", options.kind.to_lowercase()),
+            options,
+            store,
+            dispatcher,
+            reconciler,
+            queue,
+            stop: Mutex::new(CancellationToken::new()),
+            task: ServiceTask::default(),
+        })
+    }
+
+    /// `{kind}-controller`, lower-cased.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn options(&self) -> &ControllerOptions {
+        &self.options
+    }
+
+    pub fn queue(&self) -> &Arc<WorkQueue> {
+        &self.queue
+    }
+
+    /// Queues every stored object of the kind. Returns how many were queued.
+    pub async fn resync(&self) -> anyhow::Result<usize> {
+        let store = self.store.read().await;
+        if !store
+            .container_exists(RESOURCE_CONTAINER)
+            .await
+            .context("Failed to check resource container")?
+        {
+            return Ok(0);
+        }
+
+        let keys = store
+            .list_keys(RESOURCE_CONTAINER, None)
+            .await
+            .context("Failed to list resources")?;
+
+        let mut queued = 0;
+        for key in keys {
+            let Ok(bytes) = store.get(RESOURCE_CONTAINER, &key).await else {
+                continue;
+            };
+            match serde_json::from_slice::<SystemObject>(&bytes) {
+                Ok(object) if self.options.matches(&object) => {
+                    self.queue.add(&key);
+                    queued += 1;
+                }
+                _ => continue,
+            }
+        }
+
+        Ok(queued)
+    }
+
+    /// Reconciles the object stored under `key`, then requeues it as the
+    /// reconciler asked or with backoff if it failed.
+    async fn process(&self, key: &str, stop: &CancellationToken) {
+        match self.reconcile_key(key, stop).await {
+            Ok(None) => self.queue.forget(key),
+            Ok(Some(Action::AwaitChange)) => self.queue.forget(key),
+            Ok(Some(Action::RequeueAfter(delay))) => {
+                self.queue.forget(key);
+                self.queue.add_after(key, delay);
+            }
+            Err(e) => {
+                let delay = self.queue.add_rate_limited(key);
+                tracing::warn!(
+                    controller = %self.name,
+                    key,
+                    retry_in_ms = delay.as_millis() as u64,
+                    "Reconcile failed: {}",
+                    e
+                );
+            }
+        }
+        self.queue.done(key);
+    }
+
+    /// `Ok(None)` when the object no longer exists.
+    async fn reconcile_key(
+        &self,
+        key: &str,
+        stop: &CancellationToken,
+    ) -> anyhow::Result<Option<Action>> {
+        let object = {
+            let store = self.store.read().await;
+            match store.get(RESOURCE_CONTAINER, key).await {
+                Ok(bytes) => serde_json::from_slice::<SystemObject>(&bytes)
+                    .context("Failed to parse stored value as SystemObject")?,
+                Err(_) => return Ok(None),
+            }
+        };
+
+        if !self.options.matches(&object) {
+            return Ok(None);
+        }
+
+        let (namespace, resource) = key.split_once('/').unwrap_or(("", key));
+        let ctx = ReconcileContext {
+            dispatcher: self.dispatcher.clone(),
+            namespace: namespace.to_string(),
+            resource: resource.to_string(),
+            cancellation_token: stop.clone(),
+        };
+
+        tracing::debug!(controller = %self.name, key, "Reconciling");
+        self.reconciler.reconcile(object, &ctx).await.map(Some)
+    }
+
+    async fn work(self: Arc<Self>, stop: CancellationToken) {
+        while let Some(key) = self.queue.get().await {
+            self.process(&key, &stop).await;
+        }
+    }
+}
+
+#[async_trait]
+impl HostedService for Controller {
+    async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        let stop = CancellationToken::new();
+        *self.stop.lock().unwrap() = stop.clone();
+        self.queue.resume();
+
+        let controller = self.clone();
+        self.task.spawn(async move {
+            tracing::info!(
+                controller = %controller.name,
+                "Controller started (workers={}, resync={}s)",
+                controller.options.workers,
+                controller.options.resync_interval.as_secs()
+            );
+
+            let workers: Vec<_> = (0..controller.options.workers.max(1))
+                .map(|_| tokio::spawn(controller.clone().work(stop.clone())))
+                .collect();
+
+            loop {
+                if let Err(e) = controller.resync().await {
+                    tracing::warn!(controller = %controller.name, "Resync failed: {}", e);
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(controller.options.resync_interval) => {}
+                    _ = stop.cancelled() => break,
+                }
+            }
+
+            controller.queue.shut_down();
+            for worker in workers {
+                worker.await?;
+            }
+
+            tracing::info!(controller = %controller.name, "Controller stopped");
+            Ok(())
+        });
+
+        Ok(())
+    }
+
+    async fn stop(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.stop.lock().unwrap().cancel();
+        self.task.join().await
+    }
+
+    async fn join(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.task.join().await
+    }
+}
+
+/// `Observer` of `set`, `set_status`, `delete` and `finalize` that queues changed objects of a
+/// controller's group/kind.
+pub struct ControllerWatch {
+    options: ControllerOptions,
+    queue: Arc<WorkQueue>,
+}
+
+impl ControllerWatch {
+    pub fn new(options: ControllerOptions, queue: Arc<WorkQueue>) -> Self {
+        Self { options, queue }
+    }
+}
+
+impl CommandHandler for ControllerWatch {
+    fn get_type(&self) -> CommandType {
+        CommandType::Observer
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for ControllerWatch {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let (Some(namespace), Ok(resource)) = (
+            ctx.metadata.get("namespace"),
+            ctx.param::<String>("resource"),
+        ) else {
+            return Ok(None);
+        };
+
+        // Objects removed outright leave no value; match on the path instead.
+        let matches = match ctx.param::<Option<SystemObject>>("value") {
+            Ok(Some(object)) => self.options.matches(&object),
+            _ => resource.to_lowercase().starts_with(&format!(
+                "{}/{}/",
+                self.options.group.to_lowercase(),
+                self.options.kind.to_lowercase()
+            )),
+        };
+
+        if matches {
+            self.queue.add(&resource_key(namespace, Some(&resource)));
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Mutex,
+    time::Duration,
+};
+
+use tokio::{sync::Notify, time::Instant};
+
+/// Per-key exponential backoff for [`WorkQueue::add_rate_limited`].
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    /// Delay after the first failure; doubled on every further failure.
+    pub initial_backoff: Duration,
+
+    /// Upper bound for the delay.
+    pub max_backoff: Duration,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RateLimit {
+    /// Delay before retrying a key that failed `failures` times in a row.
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Keys ready to be handed out, in order.
+    ready: VecDeque<String>,
+    /// Keys in `ready`, for deduplication.
+    queued: HashSet<String>,
+    /// Keys handed out and not yet marked [`done`](WorkQueue::done).
+    processing: HashSet<String>,
+    /// Keys added again while being processed; re-queued once done.
+    dirty: HashSet<String>,
+    /// Keys waiting for a delay, with the earliest time they become ready.
+    delayed: HashMap<String, Instant>,
+    /// Consecutive failures per key.
+    failures: HashMap<String, u32>,
+    shut_down: bool,
+}
+
+impl QueueState {
+    fn push(&mut self, key: String) {
+        if self.processing.contains(&key) {
+            self.dirty.insert(key);
+        } else if self.queued.insert(key.clone()) {
+            self.ready.push_back(key);
+        }
+    }
+
+    /// Moves delayed keys that are due into `ready`.
+    fn promote(&mut self, now: Instant) {
+        let due: Vec<String> = self
+            .delayed
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in due {
+            self.delayed.remove(&key);
+            self.push(key);
+        }
+    }
+}
+
+/// A deduplicating work queue of object keys.
+///
+/// A key is held at most once: adding a key that is already queued is a
+/// no-op, and adding one that is being processed queues it again once the
+/// worker calls [`done`](Self::done), so no two workers ever see the same
+/// key at once. Failed keys are retried with per-key exponential backoff.
+pub struct WorkQueue {
+    state: Mutex<QueueState>,
+    rate_limit: RateLimit,
+    changed: Notify,
+}
+
+impl WorkQueue {
+    pub fn new(rate_limit: RateLimit) -> Self {
+        Self {
+            state: Mutex::new(QueueState::default()),
+            rate_limit,
+            changed: Notify::new(),
+        }
+    }
+
+    pub fn add(&self, key: &str) {
+        self.state.lock().unwrap().push(key.to_string());
+        self.changed.notify_waiters();
+    }
+
+    /// Adds `key` once `delay` has passed. An earlier pending delay wins.
+    pub fn add_after(&self, key: &str, delay: Duration) {
+        if delay.is_zero() {
+            return self.add(key);
+        }
+
+        let at = Instant::now() + delay;
+        {
+            let mut state = self.state.lock().unwrap();
+            let entry = state.delayed.entry(key.to_string()).or_insert(at);
+            *entry = (*entry).min(at);
+        }
+        self.changed.notify_waiters();
+    }
+
+    /// Adds `key` after its backoff, counting one more failure.
+    pub fn add_rate_limited(&self, key: &str) -> Duration {
+        let delay = {
+            let mut state = self.state.lock().unwrap();
+            let failures = state.failures.entry(key.to_string()).or_default();
+            *failures += 1;
+            self.rate_limit.backoff(*failures)
+        };
+        self.add_after(key, delay);
+        delay
+    }
+
+    /// Clears the failure count of `key`.
+    pub fn forget(&self, key: &str) {
+        self.state.lock().unwrap().failures.remove(key);
+    }
+
+    pub fn failures(&self, key: &str) -> u32 {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .get(key)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Waits for the next ready key. Returns `None` once the queue is shut
+    /// down. Call [`done`](Self::done) when finished with the key.
+    pub async fn get(&self) -> Option<String> {
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+
+            let next_delayed = {
+                let mut state = self.state.lock().unwrap();
+                if state.shut_down {
+                    return None;
+                }
+
+                state.promote(Instant::now());
+                if let Some(key) = state.ready.pop_front() {
+                    state.queued.remove(&key);
+                    state.processing.insert(key.clone());
+                    return Some(key);
+                }
+                state.delayed.values().min().copied()
+            };
+
+            match next_delayed {
+                Some(at) => {
+                    tokio::select! {
+                        _ = changed => {}
+                        _ = tokio::time::sleep_until(at) => {}
+                    }
+                }
+                None => changed.await,
+            }
+        }
+    }
+
+    /// Marks `key` as processed, re-queuing it if it was added meanwhile.
+    pub fn done(&self, key: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.processing.remove(key);
+        if state.dirty.remove(key) {
+            state.push(key.to_string());
+            drop(state);
+            self.changed.notify_waiters();
+        }
+    }
+
+    /// Number of keys that are ready or waiting for a delay.
+    pub fn len(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.ready.len() + state.delayed.len() + state.dirty.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Wakes every waiting [`get`](Self::get) with `None`. Queued keys are
+    /// kept, so the queue can be [resumed](Self::resume).
+    pub fn shut_down(&self) {
+        self.state.lock().unwrap().shut_down = true;
+        self.changed.notify_waiters();
+    }
+
+    pub fn resume(&self) {
+        self.state.lock().unwrap().shut_down = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keys are deduplicated, re-queued when added during processing, and
+    /// retried with growing backoff.
+    #[tokio::test]
+    async fn dedup_and_backoff() {
+        let queue = WorkQueue::new(RateLimit {
+            initial_backoff: Duration::from_millis(20),
+            max_backoff: Duration::from_secs(1),
+        });
+
+        queue.add("a");
+        queue.add("a");
+        queue.add("b");
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.get().await.as_deref(), Some("a"));
+
+        // Added while processing: handed out again only once done.
+        queue.add("a");
+        assert_eq!(queue.get().await.as_deref(), Some("b"));
+        queue.done("b");
+        queue.done("a");
+        assert_eq!(queue.get().await.as_deref(), Some("a"));
+
+        assert_eq!(queue.add_rate_limited("a"), Duration::from_millis(20));
+        queue.done("a");
+        let started = Instant::now();
+        assert_eq!(queue.get().await.as_deref(), Some("a"));
+        assert!(started.elapsed() >= Duration::from_millis(15));
+        assert_eq!(queue.add_rate_limited("a"), Duration::from_millis(40));
+        assert_eq!(queue.failures("a"), 2);
+        queue.forget("a");
+        assert_eq!(queue.failures("a"), 0);
+
+        queue.shut_down();
+        assert_eq!(queue.get().await, None);
+    }
+}
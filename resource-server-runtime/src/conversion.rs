@@ -0,0 +1,377 @@
+//! Conversion of objects between the versions of their kind.
+//!
+//! A `ResourceDefinition` may mark one of its versions with `storage: true`.
+//! [`Converter`] converts objects written at any other version to it before
+//! they are stored, and converts stored objects to the version a read asks
+//! for. How fields change from one version to another is up to the
+//! definition's [`ResourceConversion`]: declared field mappings, or a webhook
+//! served by a `ServiceEndpoint`. Objects of kinds without a storage version
+//! are stored as written.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use kuiper_types::{error::KuiperError, model::resource::SystemObject};
+use reqwest::header::{HeaderValue, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::sync::RwLock;
+
+use crate::{
+    handlers::admission::build_auth_headers,
+    model::resource_definition::{ResourceConversion, VersionMapping},
+    registry::ResourceRegistry,
+};
+
+/// Body of a request to a conversion webhook.
+#[derive(Serialize)]
+struct ConversionRequest<'a> {
+    #[serde(rename = "desiredAPIVersion")]
+    desired_api_version: &'a str,
+    objects: Vec<&'a SystemObject>,
+}
+
+/// Body of a conversion webhook's response.
+#[derive(Deserialize)]
+struct ConversionResponse {
+    objects: Vec<SystemObject>,
+}
+
+/// Objects of one kind to send to its conversion webhook.
+struct WebhookBatch {
+    kind: String,
+    service_endpoint: String,
+    path: String,
+    timeout_seconds: Option<u32>,
+    api_version: String,
+    positions: Vec<usize>,
+}
+
+/// Converts objects between the versions of their kind.
+pub struct Converter {
+    registry: Arc<RwLock<ResourceRegistry>>,
+    http_client: reqwest::Client,
+}
+
+impl Converter {
+    pub fn new(registry: Arc<RwLock<ResourceRegistry>>) -> Self {
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("Failed to build HTTP client for Converter");
+
+        Self {
+            registry,
+            http_client,
+        }
+    }
+
+    /// `object` at `version`, or at the storage version of its kind when
+    /// `version` is `None`. See [`convert_all`](Self::convert_all).
+    pub async fn convert(
+        &self,
+        object: SystemObject,
+        version: Option<&str>,
+    ) -> anyhow::Result<SystemObject> {
+        let mut objects = self.convert_all(vec![object], version).await?;
+        Ok(objects.remove(0))
+    }
+
+    /// `stored`, an object as read from the store, at `version`, so changes
+    /// written against that version apply to it. Left as it is when
+    /// `version` is `None`.
+    pub(crate) async fn convert_stored(
+        &self,
+        stored: Value,
+        version: Option<&str>,
+    ) -> anyhow::Result<Value> {
+        let Some(version) = version else {
+            return Ok(stored);
+        };
+        let object: SystemObject = serde_json::from_value(stored)
+            .context("Failed to parse stored value as SystemObject")?;
+        let object = self.convert(object, Some(version)).await?;
+        serde_json::to_value(object).context("Failed to convert SystemObject to JSON")
+    }
+
+    /// `objects`, each at `version`, or at the storage version of its kind
+    /// when `version` is `None`. Objects of unregistered kinds, and of kinds
+    /// without a storage version when `version` is `None`, are left as they
+    /// are. A webhook is called once per kind.
+    pub async fn convert_all(
+        &self,
+        mut objects: Vec<SystemObject>,
+        version: Option<&str>,
+    ) -> anyhow::Result<Vec<SystemObject>> {
+        let mut webhooks: BTreeMap<String, WebhookBatch> = BTreeMap::new();
+
+        {
+            let registry = self.registry.read().await;
+            for (position, object) in objects.iter_mut().enumerate() {
+                let Some((group, from)) = object.api_version.split_once('/') else {
+                    continue;
+                };
+                let Some(definition) = registry.get_definition(group, &object.kind) else {
+                    continue;
+                };
+                let Some(to) = version.or(definition.storage_version()) else {
+                    continue;
+                };
+                let Some(to) = definition
+                    .spec
+                    .versions
+                    .iter()
+                    .find(|v| v.enabled && v.name.eq_ignore_ascii_case(to))
+                else {
+                    return Err(KuiperError::NotFound(format!(
+                        "{}/{} has no version '{}'",
+                        group, object.kind, to
+                    ))
+                    .into());
+                };
+                if from.eq_ignore_ascii_case(&to.name) {
+                    continue;
+                }
+
+                let api_version = format!("{}/{}", definition.spec.group, to.name);
+                match &definition.spec.conversion {
+                    Some(ResourceConversion::FieldMapping { mappings }) => {
+                        let steps = route(mappings, from, &to.name);
+                        if steps.is_empty() {
+                            return Err(KuiperError::Invalid(format!(
+                                "No conversion mapping of {}/{} leads from '{}' to '{}'",
+                                group, object.kind, from, to.name
+                            ))
+                            .into());
+                        }
+                        *object = map_fields(object, &steps, &api_version)?;
+                    }
+                    Some(ResourceConversion::Webhook {
+                        service_endpoint,
+                        path,
+                        timeout_seconds,
+                    }) => {
+                        webhooks
+                            .entry(definition.registry_key())
+                            .or_insert_with(|| WebhookBatch {
+                                kind: format!("{}/{}", group, object.kind),
+                                service_endpoint: service_endpoint.clone(),
+                                path: path.clone(),
+                                timeout_seconds: *timeout_seconds,
+                                api_version,
+                                positions: Vec::new(),
+                            })
+                            .positions
+                            .push(position);
+                    }
+                    Some(ResourceConversion::None) | None => object.api_version = api_version,
+                }
+            }
+        }
+
+        for batch in webhooks.into_values() {
+            self.call_webhook(&mut objects, batch).await?;
+        }
+
+        Ok(objects)
+    }
+
+    /// Replaces the objects at `batch.positions` with the webhook's
+    /// conversions, keeping their `kind` and `metadata`.
+    async fn call_webhook(
+        &self,
+        objects: &mut [SystemObject],
+        batch: WebhookBatch,
+    ) -> anyhow::Result<()> {
+        let unavailable = |message: String| {
+            KuiperError::ServiceUnavailable(format!("Conversion of {}: {}", batch.kind, message))
+        };
+
+        let endpoint = {
+            let registry = self.registry.read().await;
+            registry.get_service_endpoint(&batch.service_endpoint).await
+        }
+        .map_err(|e| {
+            unavailable(format!(
+                "ServiceEndpoint '{}' not found: {}",
+                batch.service_endpoint, e
+            ))
+        })?;
+
+        let url = format!("{}{}", endpoint.spec.url.trim_end_matches('/'), batch.path);
+        let mut headers = build_auth_headers(&endpoint.spec.auth)?;
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let timeout_secs = batch
+            .timeout_seconds
+            .unwrap_or(endpoint.spec.timeout_seconds);
+
+        let body = ConversionRequest {
+            desired_api_version: &batch.api_version,
+            objects: batch.positions.iter().map(|&i| &objects[i]).collect(),
+        };
+
+        tracing::debug!(
+            url = %url,
+            kind = %batch.kind,
+            objects = batch.positions.len(),
+            "Calling conversion webhook"
+        );
+
+        let response = self
+            .http_client
+            .post(&url)
+            .headers(headers)
+            .timeout(Duration::from_secs(timeout_secs as u64))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| unavailable(format!("webhook call failed: {}", e)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "<unreadable>".to_string());
+            return Err(unavailable(format!("webhook returned {}: {}", status, body_text)).into());
+        }
+
+        let ConversionResponse { objects: converted } = response
+            .json()
+            .await
+            .map_err(|e| unavailable(format!("unreadable webhook response: {}", e)))?;
+        if converted.len() != batch.positions.len() {
+            return Err(unavailable(format!(
+                "webhook returned {} objects for {}",
+                converted.len(),
+                batch.positions.len()
+            ))
+            .into());
+        }
+
+        for (&position, mut object) in batch.positions.iter().zip(converted) {
+            if !object.api_version.eq_ignore_ascii_case(&batch.api_version) {
+                return Err(unavailable(format!(
+                    "webhook returned apiVersion '{}', not '{}'",
+                    object.api_version, batch.api_version
+                ))
+                .into());
+            }
+            let original = &objects[position];
+            object.kind = original.kind.clone();
+            object.metadata = original.metadata.clone();
+            objects[position] = object;
+        }
+
+        Ok(())
+    }
+}
+
+/// The mappings leading from version `from` to `to`, each with whether it
+/// applies forwards. Empty when no chain of mappings connects them.
+fn route<'a>(
+    mappings: &'a [VersionMapping],
+    from: &str,
+    to: &str,
+) -> Vec<(&'a VersionMapping, bool)> {
+    let from = from.to_lowercase();
+    let to = to.to_lowercase();
+
+    // version → (the version it was reached from, mapping, forwards)
+    let mut reached: HashMap<String, (String, &VersionMapping, bool)> = HashMap::new();
+    let mut seen = HashSet::from([from.clone()]);
+    let mut queue = VecDeque::from([from]);
+    while let Some(version) = queue.pop_front() {
+        if version == to {
+            break;
+        }
+        for mapping in mappings {
+            let next = if mapping.from.eq_ignore_ascii_case(&version) {
+                (mapping.to.to_lowercase(), true)
+            } else if mapping.to.eq_ignore_ascii_case(&version) {
+                (mapping.from.to_lowercase(), false)
+            } else {
+                continue;
+            };
+            if seen.insert(next.0.clone()) {
+                reached.insert(next.0.clone(), (version.clone(), mapping, next.1));
+                queue.push_back(next.0);
+            }
+        }
+    }
+
+    let mut steps = Vec::new();
+    let mut version = to;
+    while let Some((previous, mapping, forwards)) = reached.get(&version) {
+        steps.push((*mapping, *forwards));
+        version = previous.clone();
+    }
+    steps.reverse();
+    steps
+}
+
+/// `object` with the field moves of `steps` applied, at `api_version`.
+fn map_fields(
+    object: &SystemObject,
+    steps: &[(&VersionMapping, bool)],
+    api_version: &str,
+) -> anyhow::Result<SystemObject> {
+    let mut value =
+        serde_json::to_value(object).context("Failed to convert SystemObject to JSON")?;
+
+    for (mapping, forwards) in steps {
+        // Take every value first, so mappings may swap fields.
+        let moved: Vec<(&str, Option<Value>)> = mapping
+            .fields
+            .iter()
+            .map(|field| match forwards {
+                true => (field.to.as_str(), take(&mut value, &field.from)),
+                false => (field.from.as_str(), take(&mut value, &field.to)),
+            })
+            .collect();
+        for (path, field) in moved {
+            if let Some(field) = field {
+                put(&mut value, path, field);
+            }
+        }
+    }
+
+    value["apiVersion"] = Value::String(api_version.to_string());
+    serde_json::from_value(value).context("Failed to parse converted object as SystemObject")
+}
+
+/// Removes and returns the value at the dotted `path`.
+fn take(value: &mut Value, path: &str) -> Option<Value> {
+    let (parents, field) = match path.rsplit_once('.') {
+        Some((parents, field)) => (Some(parents), field),
+        None => (None, path),
+    };
+    let mut parent = value;
+    for segment in parents.into_iter().flat_map(|p| p.split('.')) {
+        parent = parent.get_mut(segment)?;
+    }
+    parent.as_object_mut()?.remove(field)
+}
+
+/// Sets the value at the dotted `path`, creating missing parent objects.
+/// Does nothing when a parent is not an object.
+fn put(value: &mut Value, path: &str, field: Value) {
+    let mut target = value;
+    let mut segments = path.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        let Some(object) = target.as_object_mut() else {
+            return;
+        };
+        if segments.peek().is_none() {
+            object.insert(segment.to_string(), field);
+            return;
+        }
+        target = object
+            .entry(segment)
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+}
@@ -15,7 +15,7 @@ use tracing::{debug, warn};
 
 use crate::registry::ResourceRegistry;
 
-/// Observer command that intercepts `set` and `delete` operations and calls any
+/// Observer command that intercepts `set`, `set_status` and `delete` operations and calls any
 /// matching `AdmissionPolicy` webhooks before the store mutation is committed.
 pub struct AdmissionWebhookCommand {
     registry: Arc<RwLock<ResourceRegistry>>,
@@ -54,7 +54,7 @@ impl ValidationCommand for AdmissionWebhookCommand {
             return Ok(None);
         }
 
-        // Only intercept `set` and `delete`.
+        // Only intercept `set`, `set_status` and `delete`.
         let operation = match ctx.command_name.as_str() {
             "set" => {
                 let uid = ctx
@@ -71,6 +71,7 @@ impl ValidationCommand for AdmissionWebhookCommand {
                     AdmissionOperation::Update
                 }
             }
+            "set_status" => AdmissionOperation::Update,
             "delete" => AdmissionOperation::Delete,
             _ => return Ok(None),
         };
@@ -160,10 +161,13 @@ impl ValidationCommand for AdmissionWebhookCommand {
 
             headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
-            let body = json!({
+            let mut body = json!({
                 "operation": operation,
                 "object": value,
             });
+            if ctx.command_name == "set_status" {
+                body["subResource"] = json!("status");
+            }
 
             debug!(
                 policy = %policy.metadata.name,
@@ -245,7 +249,7 @@ impl ValidationCommand for AdmissionWebhookCommand {
 }
 
 /// Builds HTTP auth headers from a `ServiceAuth` configuration.
-fn build_auth_headers(
+pub(crate) fn build_auth_headers(
     auth: &crate::model::service_endpoint::ServiceAuth,
 ) -> anyhow::Result<HeaderMap> {
     use crate::model::service_endpoint::ServiceAuth;
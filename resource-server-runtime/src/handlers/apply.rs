@@ -0,0 +1,445 @@
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Weak},
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{
+        CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+        ExecutableCommand,
+    },
+    data::{TransactionalKeyValueStore, WriteBatch},
+};
+use kuiper_types::{
+    error::KuiperError,
+    model::resource::{ManagedFieldsEntry, SystemObject},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::sync::RwLock;
+
+use super::{patch::commit_set, CommandExecutor};
+use crate::{
+    constants::{resource_key, RESOURCE_CONTAINER},
+    conversion::Converter,
+    registry::ResourceRegistry,
+};
+
+/// How often an apply is merged again when the object changes between
+/// reading and writing it.
+const MAX_APPLY_ATTEMPTS: usize = 5;
+
+/// Inputs of the `apply` command.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ApplyRequest {
+    pub namespace: String,
+
+    /// Path of the resource within the namespace: `{group}/{kind}/{name}`.
+    pub resource: String,
+
+    /// Who is applying. Every field in `value` becomes owned by this manager.
+    #[serde(rename = "fieldManager")]
+    pub field_manager: String,
+
+    /// Take ownership of fields other managers set to different values,
+    /// instead of failing with a conflict.
+    #[serde(default)]
+    pub force: bool,
+
+    /// The manager's configuration: only the fields it cares about.
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    pub value: Value,
+}
+
+impl ApplyRequest {
+    pub const CONTENT_TYPE: &'static str = "application/apply-patch+json";
+}
+
+impl CommandRequest for ApplyRequest {
+    const COMMAND: &'static str = "apply";
+    type Response = SystemObject;
+}
+
+/// Server-side apply: merges a manager's configuration into the stored object
+/// and records the fields it set in `metadata.managedFields`.
+///
+/// Fields the manager applied before but left out now are removed, unless
+/// another manager owns them too. Setting a field another manager owns to a
+/// different value is a conflict, unless the apply is forced, which moves the
+/// field to the applier. Managed fields are everything below the top level
+/// except `apiVersion`, `kind`, `status` and `metadata`, plus labels and
+/// annotations.
+///
+/// The merged object is written through `set` in a [`WriteBatch`], like
+/// `patch`, and merged again if the object changed meanwhile.
+pub struct ApplyCommand {
+    executor: Weak<CommandExecutor>,
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    registry: Option<Arc<RwLock<ResourceRegistry>>>,
+    converter: Option<Converter>,
+}
+
+impl ApplyCommand {
+    pub fn new(
+        executor: Weak<CommandExecutor>,
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        registry: Option<Arc<RwLock<ResourceRegistry>>>,
+    ) -> Self {
+        let converter = registry.clone().map(Converter::new);
+        Self {
+            executor,
+            store,
+            registry,
+            converter,
+        }
+    }
+
+    /// Reads the object through a new [`WriteBatch`] and merges the applied
+    /// configuration. Returns the batch and the `set` that writes the result.
+    async fn prepare(
+        &self,
+        ctx: &CommandContext,
+        request: &ApplyRequest,
+    ) -> anyhow::Result<(WriteBatch, CommandContext)> {
+        let namespace = request.namespace.to_lowercase();
+        let resource = request.resource.to_lowercase();
+        let key = resource_key(&namespace, Some(&resource));
+
+        let batch = WriteBatch::new();
+        let stored = {
+            let store = self.store.read().await;
+            batch.get(&*store, RESOURCE_CONTAINER, &key).await
+        };
+        let stored: Option<Value> = stored
+            .map(|bytes| serde_json::from_slice(&bytes))
+            .transpose()
+            .context("Failed to parse stored value as JSON")?;
+
+        // The configuration is merged into the object at its own version.
+        let version = request.value["apiVersion"]
+            .as_str()
+            .and_then(|v| v.split_once('/'))
+            .map(|(_, version)| version);
+        let stored = match (&self.converter, stored) {
+            (Some(converter), Some(object)) => {
+                Some(converter.convert_stored(object, version).await?)
+            }
+            (_, stored) => stored,
+        };
+
+        let merged = merge_applied(
+            stored.as_ref(),
+            request,
+            chrono::Utc::now().timestamp_micros(),
+        )?;
+
+        let mut item = CommandContext {
+            command_name: "set".to_string(),
+            activity_id: ctx.activity_id,
+            caller_id: ctx.caller_id.clone(),
+            is_internal: ctx.is_internal,
+            write_batch: Some(batch.clone()),
+            cancellation_token: ctx.cancellation_token.clone(),
+            ..Default::default()
+        };
+        item.parameters
+            .insert("resource".to_string(), Value::String(resource));
+        item.parameters.insert("value".to_string(), merged);
+        item.metadata.insert("namespace".to_string(), namespace);
+
+        Ok((batch, item))
+    }
+}
+
+/// Merges `request.value` into `stored`, or into a new object when there is
+/// none, and updates `metadata.managedFields`.
+pub fn merge_applied(
+    stored: Option<&Value>,
+    request: &ApplyRequest,
+    now: i64,
+) -> anyhow::Result<Value> {
+    let manager = request.field_manager.as_str();
+    if manager.is_empty() {
+        return Err(
+            KuiperError::Invalid("command 'apply': fieldManager is required".to_string()).into(),
+        );
+    }
+    let config = request.value.as_object().ok_or_else(|| {
+        KuiperError::Invalid("command 'apply': value must be a JSON object".to_string())
+    })?;
+
+    let mut object = match stored {
+        Some(stored) => {
+            for (field, pointer) in [
+                ("apiVersion", "/apiVersion"),
+                ("kind", "/kind"),
+                ("metadata.name", "/metadata/name"),
+            ] {
+                if let Some(applied) = request.value.pointer(pointer) {
+                    if stored.pointer(pointer) != Some(applied) {
+                        return Err(KuiperError::Invalid(format!(
+                            "command 'apply': {} cannot be changed",
+                            field
+                        ))
+                        .into());
+                    }
+                }
+            }
+            stored.clone()
+        }
+        None => {
+            let mut object = Map::new();
+            for field in ["apiVersion", "kind"] {
+                let value = config.get(field).ok_or_else(|| {
+                    KuiperError::Invalid(format!(
+                        "command 'apply': {} is required to create an object",
+                        field
+                    ))
+                })?;
+                object.insert(field.to_string(), value.clone());
+            }
+            // Unmanaged metadata, such as the name or owner references, is
+            // only taken from the configuration that creates the object.
+            let mut metadata = config
+                .get("metadata")
+                .and_then(Value::as_object)
+                .cloned()
+                .unwrap_or_default();
+            for field in ["labels", "annotations", "resourceVersion", "managedFields"] {
+                metadata.remove(field);
+            }
+            object.insert("metadata".to_string(), Value::Object(metadata));
+            Value::Object(object)
+        }
+    };
+
+    let mut managed: Vec<ManagedFieldsEntry> = match object.pointer("/metadata/managedFields") {
+        Some(value) => serde_json::from_value(value.clone())
+            .context("Failed to parse metadata.managedFields")?,
+        None => Vec::new(),
+    };
+
+    let applied = managed_leaves(config);
+    let applied_fields: BTreeSet<&str> = applied.iter().map(|(f, _)| f.as_str()).collect();
+
+    // Fields other managers own with a value other than the applied one.
+    let mut conflicts = BTreeSet::new();
+    for entry in managed.iter().filter(|e| e.manager != manager) {
+        for owned in &entry.fields {
+            for (field, value) in &applied {
+                if overlaps(owned, field) && object.pointer(field) != Some(value) {
+                    conflicts.insert((owned.clone(), entry.manager.clone()));
+                }
+            }
+        }
+    }
+    if !conflicts.is_empty() {
+        if !request.force {
+            let details: Vec<String> = conflicts
+                .iter()
+                .map(|(field, owner)| format!("'{}' is managed by '{}'", field, owner))
+                .collect();
+            return Err(KuiperError::Conflict(format!(
+                "command 'apply': conflicts with other managers: {}",
+                details.join(", ")
+            ))
+            .into());
+        }
+        for entry in managed.iter_mut().filter(|e| e.manager != manager) {
+            entry
+                .fields
+                .retain(|f| !conflicts.iter().any(|(c, o)| c == f && *o == entry.manager));
+        }
+    }
+
+    // Drop what this manager applied before and no longer does, unless
+    // someone else still owns it.
+    let previous = managed
+        .iter()
+        .find(|e| e.manager == manager)
+        .map(|e| e.fields.clone())
+        .unwrap_or_default();
+    for field in previous
+        .iter()
+        .filter(|f| !applied_fields.contains(f.as_str()))
+    {
+        let shared = managed
+            .iter()
+            .filter(|e| e.manager != manager)
+            .any(|e| e.fields.iter().any(|owned| overlaps(owned, field)));
+        if !shared {
+            remove_pointer(&mut object, field);
+        }
+    }
+
+    for (field, value) in &applied {
+        set_pointer(&mut object, field, value.clone());
+    }
+
+    let fields: Vec<String> = applied_fields.iter().map(|f| f.to_string()).collect();
+    match managed.iter_mut().find(|e| e.manager == manager) {
+        Some(entry) => {
+            entry.fields = fields;
+            entry.time = now;
+        }
+        None => managed.push(ManagedFieldsEntry {
+            manager: manager.to_string(),
+            time: now,
+            fields,
+        }),
+    }
+    managed.retain(|e| !e.fields.is_empty());
+
+    let metadata = object["metadata"]
+        .as_object_mut()
+        .context("Object has no metadata")?;
+    metadata.insert(
+        "managedFields".to_string(),
+        serde_json::to_value(&managed).context("Failed to serialize managedFields")?,
+    );
+    // The write applies to the version the configuration was merged into.
+    match stored.and_then(|s| s.pointer("/metadata/resourceVersion")) {
+        Some(rv) => metadata.insert("resourceVersion".to_string(), rv.clone()),
+        None => metadata.remove("resourceVersion"),
+    };
+
+    Ok(object)
+}
+
+/// The managed fields `config` sets, as JSON pointers with their values.
+/// `null` values are left out.
+fn managed_leaves(config: &Map<String, Value>) -> Vec<(String, Value)> {
+    let mut leaves = Vec::new();
+    for (name, value) in config {
+        match name.as_str() {
+            "apiVersion" | "kind" | "status" => {}
+            "metadata" => {
+                for field in ["labels", "annotations"] {
+                    if let Some(value) = value.get(field) {
+                        collect_leaves(format!("/metadata/{}", field), value, &mut leaves);
+                    }
+                }
+            }
+            _ => collect_leaves(format!("/{}", escape(name)), value, &mut leaves),
+        }
+    }
+    leaves
+}
+
+fn collect_leaves(pointer: String, value: &Value, leaves: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Null => {}
+        Value::Object(map) if !map.is_empty() => {
+            for (name, value) in map {
+                collect_leaves(format!("{}/{}", pointer, escape(name)), value, leaves);
+            }
+        }
+        _ => leaves.push((pointer, value.clone())),
+    }
+}
+
+/// Whether one field is, or contains, the other.
+fn overlaps(a: &str, b: &str) -> bool {
+    let within = |inner: &str, outer: &str| {
+        inner
+            .strip_prefix(outer)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    };
+    within(a, b) || within(b, a)
+}
+
+fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn unescape(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+/// Sets the field at `pointer`, creating (or replacing non-object) parents.
+fn set_pointer(target: &mut Value, pointer: &str, value: Value) {
+    let tokens: Vec<String> = pointer.split('/').skip(1).map(unescape).collect();
+    let Some((last, parents)) = tokens.split_last() else {
+        return;
+    };
+    let mut current = target;
+    for token in parents {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        current = current
+            .as_object_mut()
+            .unwrap()
+            .entry(token.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    if !current.is_object() {
+        *current = Value::Object(Map::new());
+    }
+    current.as_object_mut().unwrap().insert(last.clone(), value);
+}
+
+fn remove_pointer(target: &mut Value, pointer: &str) {
+    let Some((parent, last)) = pointer.rsplit_once('/') else {
+        return;
+    };
+    if let Some(parent) = target.pointer_mut(parent).and_then(Value::as_object_mut) {
+        parent.remove(&unescape(last));
+    }
+}
+
+impl CommandHandler for ApplyCommand {
+    fn get_type(&self) -> CommandType {
+        CommandType::Internal
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        super::request_schema::<ApplyRequest>()
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for ApplyCommand {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let request: ApplyRequest = ctx.request()?;
+
+        let executor = self
+            .executor
+            .upgrade()
+            .context("Command executor is no longer available")?;
+
+        let mut attempt = 1;
+        loop {
+            let (batch, item) = self.prepare(ctx, &request).await?;
+            match commit_set(
+                &executor,
+                &*self.store,
+                self.registry.as_deref(),
+                batch,
+                item,
+            )
+            .await
+            {
+                Err(e)
+                    if attempt < MAX_APPLY_ATTEMPTS
+                        && matches!(e.downcast_ref(), Some(KuiperError::Conflict(_))) =>
+                {
+                    tracing::debug!(
+                        "Apply to {} conflicted (attempt {}); retrying",
+                        request.resource,
+                        attempt
+                    );
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::sync::{Arc, Weak};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{
+        respond, CommandContext, CommandDispatcher, CommandHandler, CommandRequest, CommandResult,
+        CommandType, ExecutableCommand,
+    },
+    data::{TransactionalKeyValueStore, WriteBatch},
+};
+use kuiper_types::error::KuiperError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use super::CommandExecutor;
+use crate::registry::ResourceRegistry;
+
+/// Upper bound on the number of operations in a single batch.
+pub const MAX_BATCH_OPERATIONS: usize = 100;
+
+/// Inputs of the `batch` command.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BatchRequest {
+    /// Applied in order, all or none.
+    #[schemars(length(min = 1, max = MAX_BATCH_OPERATIONS))]
+    pub operations: Vec<BatchOperation>,
+}
+
+impl CommandRequest for BatchRequest {
+    const COMMAND: &'static str = "batch";
+    type Response = BatchResponse;
+}
+
+/// One entry of the `operations` parameter.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BatchOperation {
+    /// `set` or `delete`.
+    #[schemars(extend("enum" = ["set", "delete"]))]
+    pub op: String,
+
+    pub namespace: String,
+
+    /// Resource path within the namespace, as passed to `set`/`delete`.
+    pub resource: String,
+
+    /// The object to write. Required for `set`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<serde_json::Map<String, Value>>")]
+    pub value: Option<Value>,
+}
+
+/// Result of a successful batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResponse {
+    pub results: Vec<BatchItemResult>,
+}
+
+/// Per-item outcome returned by a successful batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchItemResult {
+    pub index: usize,
+    pub op: String,
+    pub namespace: String,
+    pub resource: String,
+
+    /// The command's result; `null` for a hard delete.
+    pub value: Value,
+}
+
+/// Applies an ordered list of `set`/`delete` operations atomically.
+///
+/// Every operation is dispatched through the executor so its mutators,
+/// validators and pipeline stages run as usual, but store writes are staged in
+/// a shared [`WriteBatch`] and committed in one transaction after the last
+/// operation succeeds. Observers run only after that commit. The first failing
+/// operation aborts the batch and nothing is written.
+pub struct BatchCommand {
+    executor: Weak<CommandExecutor>,
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    registry: Option<Arc<RwLock<ResourceRegistry>>>,
+}
+
+impl BatchCommand {
+    pub fn new(
+        executor: Weak<CommandExecutor>,
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        registry: Option<Arc<RwLock<ResourceRegistry>>>,
+    ) -> Self {
+        Self {
+            executor,
+            store,
+            registry,
+        }
+    }
+}
+
+impl CommandHandler for BatchCommand {
+    fn get_type(&self) -> CommandType {
+        CommandType::Internal
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        super::request_schema::<BatchRequest>()
+    }
+}
+
+/// Prefixes `e` with the failing operation, keeping its `KuiperError` kind so
+/// callers map it to the same status code.
+fn item_error(index: usize, op: &BatchOperation, e: anyhow::Error) -> anyhow::Error {
+    let prefix = format!(
+        "operation {} ({} {}/{})",
+        index, op.op, op.namespace, op.resource
+    );
+
+    match e.downcast_ref::<KuiperError>() {
+        Some(KuiperError::NotFound(m)) => KuiperError::NotFound(format!("{}: {}", prefix, m)),
+        Some(KuiperError::Conflict(m)) => KuiperError::Conflict(format!("{}: {}", prefix, m)),
+        Some(KuiperError::Invalid(m)) => KuiperError::Invalid(format!("{}: {}", prefix, m)),
+        Some(KuiperError::Forbidden(m)) => KuiperError::Forbidden(format!("{}: {}", prefix, m)),
+        Some(KuiperError::ServiceUnavailable(m)) => {
+            KuiperError::ServiceUnavailable(format!("{}: {}", prefix, m))
+        }
+        None => return e.context(prefix),
+    }
+    .into()
+}
+
+#[async_trait]
+impl ExecutableCommand for BatchCommand {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let BatchRequest { operations } = ctx.request()?;
+
+        if operations.is_empty() {
+            return Err(KuiperError::Invalid("A batch needs at least one operation".into()).into());
+        }
+        if operations.len() > MAX_BATCH_OPERATIONS {
+            return Err(KuiperError::Invalid(format!(
+                "A batch may contain at most {} operations",
+                MAX_BATCH_OPERATIONS
+            ))
+            .into());
+        }
+
+        let executor = self
+            .executor
+            .upgrade()
+            .context("Command executor is no longer available")?;
+
+        let batch = WriteBatch::new();
+        let mut items = Vec::with_capacity(operations.len());
+        let mut results = Vec::with_capacity(operations.len());
+
+        for (index, op) in operations.iter().enumerate() {
+            let mut item = CommandContext {
+                command_name: op.op.clone(),
+                activity_id: ctx.activity_id,
+                caller_id: ctx.caller_id.clone(),
+                is_internal: ctx.is_internal,
+                write_batch: Some(batch.clone()),
+                cancellation_token: ctx.cancellation_token.clone(),
+                ..Default::default()
+            };
+
+            match op.op.as_str() {
+                "set" => {
+                    let value = op.value.clone().ok_or_else(|| {
+                        item_error(
+                            index,
+                            op,
+                            KuiperError::Invalid("'set' requires a value".into()).into(),
+                        )
+                    })?;
+                    item.parameters.insert("value".to_string(), value);
+                }
+                "delete" => {}
+                other => {
+                    return Err(item_error(
+                        index,
+                        op,
+                        KuiperError::Invalid(format!(
+                            "Unsupported batch operation '{}'; expected 'set' or 'delete'",
+                            other
+                        ))
+                        .into(),
+                    ));
+                }
+            }
+
+            item.parameters
+                .insert("resource".to_string(), Value::String(op.resource.clone()));
+            item.metadata
+                .insert("namespace".to_string(), op.namespace.clone());
+
+            let value = executor
+                .dispatch(&mut item)
+                .await
+                .map_err(|e| item_error(index, op, e))?;
+
+            results.push(BatchItemResult {
+                index,
+                op: op.op.clone(),
+                namespace: op.namespace.clone(),
+                resource: op.resource.clone(),
+                value: value.unwrap_or(Value::Null),
+            });
+            items.push(item);
+        }
+
+        {
+            let store = self.store.write().await;
+            batch.commit(&*store).await?;
+        }
+        batch.run_after_commit().await;
+
+        let touches_definitions = operations.iter().any(|op| {
+            op.resource.to_lowercase().contains("/resourcedefinition/")
+                || op
+                    .value
+                    .as_ref()
+                    .and_then(|v| v.get("kind"))
+                    .and_then(Value::as_str)
+                    .is_some_and(|k| k.eq_ignore_ascii_case("resourcedefinition"))
+        });
+        if touches_definitions {
+            if let Some(registry) = &self.registry {
+                registry
+                    .write()
+                    .await
+                    .reload()
+                    .await
+                    .context("Failed to reload ResourceRegistry after batch")?;
+            }
+        }
+
+        observe_committed(&executor, &mut items).await;
+
+        respond(&BatchResponse { results })
+    }
+}
+
+/// Runs the observers of commands whose writes were staged in a
+/// [`WriteBatch`] that has since committed. Observer failures no longer affect
+/// the committed writes and are only logged.
+pub(crate) async fn observe_committed(executor: &CommandExecutor, items: &mut [CommandContext]) {
+    for item in items.iter_mut() {
+        if item.defer_observers {
+            executor.notify_outbox();
+            continue;
+        }
+
+        // `value` already holds the write's result, as for inline observers.
+        item.write_batch = None;
+        if let Err(e) = executor.observe(item).await {
+            tracing::warn!(
+                activity_id = %item.activity_id,
+                "Observer failed after batch commit: {}",
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::Weak;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::command::{
+    respond, CommandContext, CommandHandler, CommandResult, CommandType, ExecutableCommand,
+};
+use kuiper_types::error::KuiperError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::CommandExecutor;
+
+/// One handler in a command's pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandlerDescriptor {
+    pub name: String,
+
+    /// `mutator`, `validator`, `internal` or `observer`.
+    #[serde(rename = "type")]
+    pub handler_type: String,
+}
+
+/// A registered command and the handlers it runs, in pipeline order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandDescriptor {
+    pub name: String,
+
+    pub handlers: Vec<HandlerDescriptor>,
+
+    /// JSON Schema of the command's parameters, when a handler publishes one.
+    #[serde(rename = "inputSchema", skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<Value>,
+}
+
+/// Lists the registered commands, or describes the one named by the optional
+/// `name` parameter.
+pub struct CommandsCommand {
+    executor: Weak<CommandExecutor>,
+}
+
+impl CommandsCommand {
+    pub fn new(executor: Weak<CommandExecutor>) -> Self {
+        Self { executor }
+    }
+}
+
+impl CommandHandler for CommandsCommand {
+    fn get_type(&self) -> CommandType {
+        CommandType::Internal
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+
+    fn input_schema(&self) -> Option<Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "description": "Describe only this command." }
+            }
+        }))
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for CommandsCommand {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let name = ctx.param::<Option<String>>("name")?;
+
+        let executor = self
+            .executor
+            .upgrade()
+            .context("Command executor is no longer available")?;
+
+        let commands = executor.describe();
+
+        match name {
+            None => respond(&commands),
+            Some(name) => {
+                let command = commands
+                    .into_iter()
+                    .find(|c| c.name == name)
+                    .ok_or_else(|| KuiperError::NotFound(format!("Unknown command '{}'", name)))?;
+                respond(&command)
+            }
+        }
+    }
+}
@@ -3,21 +3,79 @@ use std::sync::Arc;
 use anyhow::Context;
 use async_trait::async_trait;
 use kuiper_runtime::{
-    command::{CommandContext, CommandHandler, CommandResult, CommandType, ExecutableCommand},
-    data::TransactionalKeyValueStore,
+    command::{
+        CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+        ExecutableCommand,
+    },
+    data::{Transaction, TransactionalKeyValueStore},
+    outbox::{OutboxEntry, OUTBOX_CONTAINER},
 };
-use kuiper_types::{error::KuiperError, model::resource::SystemObject};
+use kuiper_types::{
+    error::KuiperError,
+    model::resource::{DeletionPropagation, SystemObject},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-use crate::constants::{resource_key, RESOURCE_CONTAINER};
+use crate::{
+    constants::{resource_key, RESOURCE_CONTAINER},
+    index, namespace,
+    registry::ResourceRegistry,
+};
+
+/// Inputs of the `delete` command.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DeleteRequest {
+    pub namespace: String,
+
+    /// Path of the resource within the namespace: `{group}/{kind}/{name}`.
+    pub resource: String,
+
+    /// What happens to the object's dependents. Defaults to
+    /// [`DeletionPropagation::Background`].
+    #[serde(
+        rename = "propagationPolicy",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub propagation_policy: Option<DeletionPropagation>,
+}
+
+impl DeleteRequest {
+    pub fn new(namespace: &str, resource: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            resource: resource.to_string(),
+            propagation_policy: None,
+        }
+    }
+
+    pub fn with_propagation(mut self, policy: DeletionPropagation) -> Self {
+        self.propagation_policy = Some(policy);
+        self
+    }
+}
+
+impl CommandRequest for DeleteRequest {
+    const COMMAND: &'static str = "delete";
+
+    /// `None` when the resource was removed immediately; otherwise the
+    /// object, now marked for deletion.
+    type Response = Option<SystemObject>;
+}
 
 pub struct DeleteCommand {
     store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    registry: Option<Arc<RwLock<ResourceRegistry>>>,
 }
 
 impl DeleteCommand {
-    pub fn new(store: Arc<RwLock<dyn TransactionalKeyValueStore>>) -> Self {
-        Self { store }
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        registry: Option<Arc<RwLock<ResourceRegistry>>>,
+    ) -> Self {
+        Self { store, registry }
     }
 }
 
@@ -29,45 +87,79 @@ impl CommandHandler for DeleteCommand {
     fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
         Some(self)
     }
+
+    fn writes_outbox(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        super::request_schema::<DeleteRequest>()
+    }
 }
 
 #[async_trait]
 impl ExecutableCommand for DeleteCommand {
     async fn execute(&self, ctx: &CommandContext) -> CommandResult {
-        let namespace = ctx
-            .metadata
-            .get("namespace")
-            .cloned()
-            .context("Missing required parameter: namespace")?
-            .to_lowercase();
-
-        let resource = ctx
-            .get_string_param("resource")
-            .context("Missing required parameter: resource")?
-            .to_lowercase();
+        let DeleteRequest {
+            namespace,
+            resource,
+            propagation_policy,
+        } = ctx.request()?;
+        let namespace = namespace.to_lowercase();
+        let resource = resource.to_lowercase();
+
+        // A namespace is held until everything in it has been deleted.
+        let cascade_finalizer = match namespace::namespace_name(&namespace, &resource) {
+            Some(name) if namespace::is_builtin(name) => {
+                return Err(KuiperError::Forbidden(format!(
+                    "Namespace '{}' cannot be deleted",
+                    name
+                ))
+                .into());
+            }
+            Some(_) => Some(namespace::NAMESPACE_FINALIZER),
+            None => propagation_policy.unwrap_or_default().finalizer(),
+        };
 
         let key = resource_key(&namespace, Some(&resource));
+        let fields = index::indexed_fields(self.registry.as_deref(), &resource).await;
 
         let store = self.store.write().await;
 
-        let bytes = store
-            .get(RESOURCE_CONTAINER, &key)
-            .await
-            .map_err(|_| KuiperError::NotFound(format!("Resource '{}' not found", resource)))?;
+        let existing = match &ctx.write_batch {
+            Some(batch) => batch.get(&*store, RESOURCE_CONTAINER, &key).await,
+            None => store.get(RESOURCE_CONTAINER, &key).await.ok(),
+        };
+
+        let bytes = existing
+            .ok_or_else(|| KuiperError::NotFound(format!("Resource '{}' not found", resource)))?;
 
         let mut obj: SystemObject = serde_json::from_slice(&bytes)
             .context("Failed to parse stored value as SystemObject")?;
 
+        if ctx.defer_observers {
+            store
+                .ensure_container(OUTBOX_CONTAINER)
+                .await
+                .context("Failed to create outbox container")?;
+        }
+
+        let mut tx = Transaction::new(&*store);
+
         // If there are no finalizers, we can delete immediately. Otherwise, we need to set the deletion timestamp.
-        if obj
-            .metadata
-            .finalizers
-            .as_ref()
-            .map_or(true, |f| f.is_empty())
+        // Foreground and orphan deletion hold the object with a finalizer the garbage collector removes.
+        if cascade_finalizer.is_none()
+            && obj
+                .metadata
+                .finalizers
+                .as_ref()
+                .map_or(true, |f| f.is_empty())
         {
             // No finalizers, safe to delete immediately
-            store
-                .delete(RESOURCE_CONTAINER, &key)
+            tx.delete(RESOURCE_CONTAINER.to_string(), key);
+            index::stage_update(&mut tx, &namespace, &resource, Some(&obj), None, &fields);
+            stage_outbox_entry(ctx, &mut tx, None)?;
+            tx.commit_or_stage(ctx.write_batch.as_ref())
                 .await
                 .context(format!("Failed to delete resource {}", resource))?;
             tracing::info!("Deleted resource {}", resource);
@@ -79,18 +171,58 @@ impl ExecutableCommand for DeleteCommand {
         if obj.metadata.deletion_timestamp.is_some() {
             let result =
                 serde_json::to_value(&obj).context("Failed to convert SystemObject to JSON")?;
+            stage_outbox_entry(ctx, &mut tx, Some(&result))?;
+            tx.commit_or_stage(ctx.write_batch.as_ref())
+                .await
+                .context("Failed to write outbox entry")?;
             return Ok(Some(result));
         }
 
         obj.metadata.deletion_timestamp = Some(chrono::Utc::now().timestamp_micros());
+        if let Some(finalizer) = cascade_finalizer {
+            obj.metadata
+                .finalizers
+                .get_or_insert_with(Vec::new)
+                .push(finalizer.to_string());
+        }
 
         let value_bytes =
             serde_json::to_vec_pretty(&obj).context("Failed to serialize SystemObject")?;
 
-        store.put(RESOURCE_CONTAINER, &key, value_bytes).await?;
-
         let result =
             serde_json::to_value(&obj).context("Failed to convert SystemObject to JSON")?;
+
+        tx.put(RESOURCE_CONTAINER.to_string(), key, value_bytes);
+        if !fields.is_empty() {
+            // The deletion timestamp may be an indexed field.
+            let previous: SystemObject = serde_json::from_slice(&bytes)
+                .context("Failed to parse stored value as SystemObject")?;
+            index::stage_update(
+                &mut tx,
+                &namespace,
+                &resource,
+                Some(&previous),
+                Some(&obj),
+                &fields,
+            );
+        }
+        stage_outbox_entry(ctx, &mut tx, Some(&result))?;
+        tx.commit_or_stage(ctx.write_batch.as_ref()).await?;
+
         Ok(Some(result))
     }
 }
+
+/// Stages the outbox entry for deferred observers, if the executor asked for one.
+fn stage_outbox_entry(
+    ctx: &CommandContext,
+    tx: &mut Transaction<'_>,
+    result: Option<&serde_json::Value>,
+) -> anyhow::Result<()> {
+    if ctx.defer_observers {
+        OutboxEntry::from_context(ctx, result)
+            .stage(tx)
+            .context("Failed to stage outbox entry")?;
+    }
+    Ok(())
+}
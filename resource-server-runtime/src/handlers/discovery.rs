@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use kuiper_runtime::command::{
+    respond, CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+    ExecutableCommand,
+};
+use kuiper_types::model::discovery::ApiDiscovery;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::registry::ResourceRegistry;
+
+/// Inputs of the `discovery` command.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscoveryRequest {}
+
+impl CommandRequest for DiscoveryRequest {
+    const COMMAND: &'static str = "discovery";
+    type Response = ApiDiscovery;
+}
+
+/// Describes the registered groups, their versions and kinds, with the verbs
+/// and subresources each kind serves.
+pub struct DiscoveryCommand {
+    registry: Arc<RwLock<ResourceRegistry>>,
+}
+
+impl DiscoveryCommand {
+    pub fn new(registry: Arc<RwLock<ResourceRegistry>>) -> Self {
+        Self { registry }
+    }
+}
+
+impl CommandHandler for DiscoveryCommand {
+    fn get_type(&self) -> CommandType {
+        CommandType::Internal
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({ "type": "object" }))
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for DiscoveryCommand {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let DiscoveryRequest {} = ctx.request()?;
+
+        let discovery = self.registry.read().await.discovery();
+        respond(&*discovery)
+    }
+}
@@ -14,14 +14,23 @@ impl CommandHandler for EchoCommand {
     fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
         Some(self)
     }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "message": { "type": "string", "default": "hello" }
+            }
+        }))
+    }
 }
 
 #[async_trait]
 impl ExecutableCommand for EchoCommand {
     async fn execute(&self, ctx: &CommandContext) -> CommandResult {
         let message = ctx
-            .get_string_param("message")
-            .unwrap_or_else(|_| "hello".to_string());
+            .param::<Option<String>>("message")?
+            .unwrap_or_else(|| "hello".to_string());
         Ok(Some(serde_json::json!({ "echo": message })))
     }
 }
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{
+        CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+        ExecutableCommand,
+    },
+    data::{Transaction, TransactionalKeyValueStore},
+    outbox::{OutboxEntry, OUTBOX_CONTAINER},
+};
+use kuiper_types::{error::KuiperError, model::resource::SystemObject};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    constants::{resource_key, RESOURCE_CONTAINER},
+    index,
+    registry::ResourceRegistry,
+};
+
+/// Inputs of the `finalize` command: finalizers to add to and remove from an
+/// object, applied atomically against the stored object.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct FinalizeRequest {
+    pub namespace: String,
+
+    /// Path of the resource within the namespace: `{group}/{kind}/{name}`.
+    pub resource: String,
+
+    /// Finalizers to add. Ones already present are left as they are.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub add: Vec<String>,
+
+    /// Finalizers to remove. Ones not present are ignored.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove: Vec<String>,
+}
+
+impl FinalizeRequest {
+    pub fn add(namespace: &str, resource: &str, finalizer: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            resource: resource.to_string(),
+            add: vec![finalizer.to_string()],
+            ..Default::default()
+        }
+    }
+
+    pub fn remove(namespace: &str, resource: &str, finalizer: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            resource: resource.to_string(),
+            remove: vec![finalizer.to_string()],
+            ..Default::default()
+        }
+    }
+}
+
+impl CommandRequest for FinalizeRequest {
+    const COMMAND: &'static str = "finalize";
+
+    /// The updated object, or `None` when removing the last finalizer of an
+    /// object marked for deletion removed the object itself.
+    type Response = Option<SystemObject>;
+}
+
+/// Adds and removes finalizers without a client-side read-modify-write, so
+/// controllers owning different finalizers never overwrite each other.
+pub struct FinalizeCommand {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    registry: Option<Arc<RwLock<ResourceRegistry>>>,
+}
+
+impl FinalizeCommand {
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        registry: Option<Arc<RwLock<ResourceRegistry>>>,
+    ) -> Self {
+        Self { store, registry }
+    }
+}
+
+impl CommandHandler for FinalizeCommand {
+    fn get_type(&self) -> CommandType {
+        CommandType::Internal
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+
+    fn writes_outbox(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        super::request_schema::<FinalizeRequest>()
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for FinalizeCommand {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let FinalizeRequest {
+            namespace,
+            resource,
+            add,
+            remove,
+        } = ctx.request()?;
+        let namespace = namespace.to_lowercase();
+        let resource = resource.to_lowercase();
+
+        if let Some(empty) = add.iter().chain(&remove).find(|f| f.trim().is_empty()) {
+            return Err(KuiperError::Invalid(format!(
+                "command 'finalize': invalid finalizer '{}'",
+                empty
+            ))
+            .into());
+        }
+
+        let key = resource_key(&namespace, Some(&resource));
+        let fields = index::indexed_fields(self.registry.as_deref(), &resource).await;
+
+        let store = self.store.write().await;
+
+        let existing = match &ctx.write_batch {
+            Some(batch) => batch.get(&*store, RESOURCE_CONTAINER, &key).await,
+            None => store.get(RESOURCE_CONTAINER, &key).await.ok(),
+        };
+
+        let bytes = existing
+            .ok_or_else(|| KuiperError::NotFound(format!("Resource '{}' not found", resource)))?;
+
+        let mut obj: SystemObject = serde_json::from_slice(&bytes)
+            .context("Failed to parse stored value as SystemObject")?;
+
+        let terminating = obj.metadata.deletion_timestamp.is_some();
+        let before = obj.metadata.finalizers.clone().unwrap_or_default();
+        let mut finalizers = before.clone();
+
+        finalizers.retain(|f| !remove.contains(f));
+        for finalizer in add {
+            if finalizers.contains(&finalizer) {
+                continue;
+            }
+            if terminating {
+                return Err(KuiperError::Invalid(format!(
+                    "command 'finalize': cannot add finalizer '{}' to '{}', which is being deleted",
+                    finalizer, resource
+                ))
+                .into());
+            }
+            finalizers.push(finalizer);
+        }
+
+        // Nothing to change: keep the resourceVersion so watchers see no event.
+        if finalizers == before {
+            let result =
+                serde_json::to_value(&obj).context("Failed to convert SystemObject to JSON")?;
+            return Ok(Some(result));
+        }
+
+        if ctx.defer_observers {
+            store
+                .ensure_container(OUTBOX_CONTAINER)
+                .await
+                .context("Failed to create outbox container")?;
+        }
+
+        let mut tx = Transaction::new(&*store);
+
+        // The last finalizer of a terminating object is gone: finish the deletion.
+        if terminating && finalizers.is_empty() {
+            tx.delete(RESOURCE_CONTAINER.to_string(), key);
+            index::stage_update(&mut tx, &namespace, &resource, Some(&obj), None, &fields);
+            stage_outbox_entry(ctx, &mut tx, None)?;
+            tx.commit_or_stage(ctx.write_batch.as_ref())
+                .await
+                .context(format!("Failed to delete resource {}", resource))?;
+            tracing::info!(
+                "Deleted resource {} after its last finalizer was removed",
+                resource
+            );
+            return Ok(None);
+        }
+
+        obj.metadata.finalizers = Some(finalizers);
+        obj.metadata.resource_version = Some(uuid::Uuid::new_v4().to_string());
+
+        let value_bytes =
+            serde_json::to_vec_pretty(&obj).context("Failed to serialize SystemObject")?;
+        let result =
+            serde_json::to_value(&obj).context("Failed to convert SystemObject to JSON")?;
+
+        tx.put(RESOURCE_CONTAINER.to_string(), key, value_bytes);
+        stage_outbox_entry(ctx, &mut tx, Some(&result))?;
+        tx.commit_or_stage(ctx.write_batch.as_ref())
+            .await
+            .context("Failed to write resource to store")?;
+
+        Ok(Some(result))
+    }
+}
+
+/// Stages the outbox entry for deferred observers, if the executor asked for one.
+fn stage_outbox_entry(
+    ctx: &CommandContext,
+    tx: &mut Transaction<'_>,
+    result: Option<&serde_json::Value>,
+) -> anyhow::Result<()> {
+    if ctx.defer_observers {
+        OutboxEntry::from_context(ctx, result)
+            .stage(tx)
+            .context("Failed to stage outbox entry")?;
+    }
+    Ok(())
+}
@@ -3,21 +3,56 @@ use std::sync::Arc;
 use anyhow::Context;
 use async_trait::async_trait;
 use kuiper_runtime::{
-    command::{CommandContext, CommandHandler, CommandResult, CommandType, ExecutableCommand},
+    command::{
+        respond, CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+        ExecutableCommand,
+    },
     data::TransactionalKeyValueStore,
 };
 use kuiper_types::{error::KuiperError, model::resource::SystemObject};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-use crate::constants::{resource_key, RESOURCE_CONTAINER};
+use crate::{
+    constants::{resource_key, RESOURCE_CONTAINER},
+    conversion::Converter,
+    registry::ResourceRegistry,
+};
+
+/// Inputs of the `get` command.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GetRequest {
+    pub namespace: String,
+
+    /// Path of the resource within the namespace: `{group}/{kind}/{name}`.
+    pub resource: String,
+
+    /// The version to return the object at. Defaults to the storage version
+    /// of its kind, if it has one, else the version it was written at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+impl CommandRequest for GetRequest {
+    const COMMAND: &'static str = "get";
+    type Response = SystemObject;
+}
 
 pub struct GetCommand {
     store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    converter: Option<Converter>,
 }
 
 impl GetCommand {
-    pub fn new(store: Arc<RwLock<dyn TransactionalKeyValueStore>>) -> Self {
-        Self { store }
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        registry: Option<Arc<RwLock<ResourceRegistry>>>,
+    ) -> Self {
+        Self {
+            store,
+            converter: registry.map(Converter::new),
+        }
     }
 }
 
@@ -30,22 +65,22 @@ impl CommandHandler for GetCommand {
     fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
         Some(self)
     }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        super::request_schema::<GetRequest>()
+    }
 }
 
 #[async_trait]
 impl ExecutableCommand for GetCommand {
     async fn execute(&self, ctx: &CommandContext) -> CommandResult {
-        let namespace = ctx
-            .metadata
-            .get("namespace")
-            .cloned()
-            .context("Missing required parameter: namespace")?
-            .to_lowercase();
-
-        let resource = ctx
-            .get_string_param("resource")
-            .context("Missing required parameter: resource")?
-            .to_lowercase();
+        let GetRequest {
+            namespace,
+            resource,
+            version,
+        } = ctx.request()?;
+        let namespace = namespace.to_lowercase();
+        let resource = resource.to_lowercase();
 
         let key = resource_key(&namespace, Some(&resource));
 
@@ -57,10 +92,12 @@ impl ExecutableCommand for GetCommand {
             .await
             .map_err(|_| KuiperError::NotFound(format!("Resource '{}' not found", resource)))?;
 
-        let obj: SystemObject = serde_json::from_slice(&bytes)
+        let mut obj: SystemObject = serde_json::from_slice(&bytes)
             .context("Failed to parse stored value as SystemObject")?;
+        if let Some(converter) = &self.converter {
+            obj = converter.convert(obj, version.as_deref()).await?;
+        }
 
-        let result = serde_json::to_value(&obj).context("Failed to serialize SystemObject")?;
-        Ok(Some(result))
+        respond(&obj)
     }
 }
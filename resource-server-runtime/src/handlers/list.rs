@@ -3,21 +3,87 @@ use std::sync::Arc;
 use anyhow::Context;
 use async_trait::async_trait;
 use kuiper_runtime::{
-    command::{CommandContext, CommandHandler, CommandResult, CommandType, ExecutableCommand},
+    command::{
+        respond, CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+        ExecutableCommand,
+    },
     data::TransactionalKeyValueStore,
 };
-use kuiper_types::model::resource::SystemObject;
+use kuiper_types::model::{
+    resource::SystemObject,
+    selector::{FieldSelector, LabelSelector},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-use crate::constants::{resource_key, RESOURCE_CONTAINER};
+use crate::{
+    constants::{resource_key, RESOURCE_CONTAINER},
+    conversion::Converter,
+    index,
+    registry::ResourceRegistry,
+};
+
+/// The `namespace` of a [`ListRequest`] across all namespaces.
+pub const ALL_NAMESPACES: &str = "*";
+
+/// Inputs of the `list` command.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ListRequest {
+    /// The namespace, or [`ALL_NAMESPACES`].
+    pub namespace: String,
+
+    /// Key prefix within the namespace, typically `{group}/{kind}`.
+    pub resource: String,
+
+    /// Only objects whose labels match, e.g. `tier=gold,env in (dev,test)`.
+    /// See [`LabelSelector`].
+    #[serde(
+        rename = "labelSelector",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub label_selector: Option<String>,
+
+    /// Only objects whose fields match, e.g. `status.phase=Running`. See
+    /// [`FieldSelector`].
+    #[serde(
+        rename = "fieldSelector",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub field_selector: Option<String>,
+
+    /// The version to return objects at. Defaults to the storage version of
+    /// their kind, if it has one. Selectors match objects as stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
 
+impl CommandRequest for ListRequest {
+    const COMMAND: &'static str = "list";
+    type Response = Vec<SystemObject>;
+}
+
+/// Lists objects, looking selected ones up in the secondary indexes when a
+/// selector allows it. See [`crate::index`].
 pub struct ListCommand {
     store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    registry: Option<Arc<RwLock<ResourceRegistry>>>,
+    converter: Option<Converter>,
 }
 
 impl ListCommand {
-    pub fn new(store: Arc<RwLock<dyn TransactionalKeyValueStore>>) -> Self {
-        Self { store }
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        registry: Option<Arc<RwLock<ResourceRegistry>>>,
+    ) -> Self {
+        let converter = registry.clone().map(Converter::new);
+        Self {
+            store,
+            registry,
+            converter,
+        }
     }
 }
 
@@ -29,33 +95,64 @@ impl CommandHandler for ListCommand {
     fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
         Some(self)
     }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        super::request_schema::<ListRequest>()
+    }
 }
 
 #[async_trait]
 impl ExecutableCommand for ListCommand {
     async fn execute(&self, ctx: &CommandContext) -> CommandResult {
-        let namespace = ctx
-            .metadata
-            .get("namespace")
-            .cloned()
-            .context("Missing required parameter: namespace")?
-            .to_lowercase();
-
-        let resource = ctx
-            .get_string_param("resource")
-            .context("Missing required parameter: resource")?
-            .to_lowercase();
-
+        let ListRequest {
+            namespace,
+            resource,
+            label_selector,
+            field_selector,
+            version,
+        } = ctx.request()?;
+        let namespace = namespace.to_lowercase();
+        let resource = resource.to_lowercase();
+
+        // Indexed fields of the kind are selectable too.
+        let fields = index::indexed_fields(self.registry.as_deref(), &resource).await;
+
+        let label_selector: LabelSelector = label_selector.as_deref().unwrap_or("").parse()?;
+        let field_selector =
+            FieldSelector::parse_with_fields(field_selector.as_deref().unwrap_or(""), &fields)?;
+
+        let all_namespaces = namespace == ALL_NAMESPACES;
         let key_prefix = resource_key(&namespace, Some(&resource));
 
         let store = self.store.read().await;
 
-        let keys = store
-            .list_keys(RESOURCE_CONTAINER, Some(&key_prefix))
-            .await
-            .context("Failed to list keys")?;
-
-        let mut items: Vec<serde_json::Value> = Vec::with_capacity(keys.len());
+        // Candidates from the indexes still go through both selectors below.
+        let terms = index::selector_terms(&label_selector, &field_selector, &fields);
+        let keys: Vec<String> = match terms {
+            Some(terms) if index::is_ready(&*store).await => {
+                let namespace = (!all_namespaces).then_some(namespace.as_str());
+                index::lookup(&*store, namespace, &terms, &resource)
+                    .await?
+                    .into_iter()
+                    .collect()
+            }
+            _ if all_namespaces => store
+                .list_keys(RESOURCE_CONTAINER, None)
+                .await
+                .context("Failed to list keys")?
+                .into_iter()
+                .filter(|key| {
+                    key.split_once('/')
+                        .is_some_and(|(_, path)| path.starts_with(&resource))
+                })
+                .collect(),
+            _ => store
+                .list_keys(RESOURCE_CONTAINER, Some(&key_prefix))
+                .await
+                .context("Failed to list keys")?,
+        };
+
+        let mut items: Vec<SystemObject> = Vec::with_capacity(keys.len());
 
         for key in &keys {
             let bytes = match store.get(RESOURCE_CONTAINER, key).await {
@@ -63,16 +160,20 @@ impl ExecutableCommand for ListCommand {
                 Err(_) => continue,
             };
 
-            let obj: SystemObject = match serde_json::from_slice(&bytes) {
-                Ok(o) => o,
+            match serde_json::from_slice::<SystemObject>(&bytes) {
+                Ok(obj) if label_selector.matches(&obj) && field_selector.matches(&obj) => {
+                    items.push(obj)
+                }
+                Ok(_) => continue,
                 Err(_) => continue,
             };
+        }
+        drop(store);
 
-            if let Ok(v) = serde_json::to_value(&obj) {
-                items.push(v);
-            }
+        if let Some(converter) = &self.converter {
+            items = converter.convert_all(items, version.as_deref()).await?;
         }
 
-        Ok(Some(serde_json::json!(items)))
+        respond(&items)
     }
 }
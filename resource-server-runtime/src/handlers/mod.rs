@@ -1,31 +1,81 @@
 pub mod admission;
+pub mod apply;
+pub mod batch;
+pub mod commands;
 pub mod delete;
+pub mod discovery;
 pub mod echo;
+pub mod finalize;
 pub mod get;
 pub mod list;
+pub mod patch;
+pub mod purge_audit_records;
+pub mod purge_idempotency_records;
+pub mod rebuild_indexes;
 pub mod reconcile;
 pub mod set;
+pub mod set_status;
 pub mod validate;
 pub mod version;
 
 use async_trait::async_trait;
 use kuiper_runtime::command::{
-    CommandContext, CommandDispatcher, CommandHandler, CommandResult, CommandType,
+    CommandContext, CommandDispatcher, CommandHandler, CommandResult, CommandType, Next,
+    PipelineStage,
 };
+use schemars::{generate::SchemaSettings, JsonSchema};
 use serde_json::Value;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Instant};
+use tokio::sync::Notify;
+use tracing::Instrument;
+
+use crate::telemetry::CommandMetrics;
+use commands::{CommandDescriptor, HandlerDescriptor};
+
+/// The JSON Schema of the request type `R`, for a handler's `input_schema`.
+/// Nested types are inlined, so the schema stands on its own.
+pub(crate) fn request_schema<R: JsonSchema>() -> Option<Value> {
+    let generator = SchemaSettings::default()
+        .with(|s| s.inline_subschemas = true)
+        .into_generator();
+    Some(generator.into_root_schema_for::<R>().to_value())
+}
 
 pub struct CommandExecutor {
     handlers: HashMap<String, Vec<Arc<dyn CommandHandler>>>,
+
+    /// Cross-cutting stages wrapped around every command, outermost first.
+    stages: Vec<Arc<dyn PipelineStage>>,
+
+    /// Wakes the outbox delivery service. `Some` when observers are deferred
+    /// to the outbox rather than run inline.
+    outbox_signal: Option<Arc<Notify>>,
+
+    metrics: CommandMetrics,
 }
 
 impl CommandExecutor {
     pub fn new() -> Self {
+        Self::with_metrics(CommandMetrics::new())
+    }
+
+    /// Creates an executor that records into `metrics` instead of the
+    /// instruments of the global meter provider.
+    pub fn with_metrics(metrics: CommandMetrics) -> Self {
         Self {
             handlers: HashMap::new(),
+            stages: Vec::new(),
+            outbox_signal: None,
+            metrics,
         }
     }
 
+    /// Defers observers of outbox-aware commands to the durable outbox.
+    /// `signal` is notified after every command that staged an entry.
+    pub fn enable_outbox(&mut self, signal: Arc<Notify>) {
+        self.outbox_signal = Some(signal);
+    }
+
     pub fn register_handler(&mut self, name: &str, handler: Arc<dyn CommandHandler>) {
         if let Some(existing_handlers) = self.handlers.get_mut(name) {
             existing_handlers.push(handler);
@@ -35,6 +85,45 @@ impl CommandExecutor {
         self.handlers.insert(name.to_string(), vec![handler]);
     }
 
+    /// Appends a stage to the pipeline. Stages run in registration order, the
+    /// first registered being the outermost.
+    pub fn register_stage(&mut self, stage: Arc<dyn PipelineStage>) {
+        self.stages.push(stage);
+    }
+
+    /// Runs one handler inside its own span, named after the handler and its
+    /// `CommandType`, and records its latency and failures.
+    async fn run_handler(
+        &self,
+        ctx: &mut CommandContext,
+        handler: &Arc<dyn CommandHandler>,
+    ) -> CommandResult {
+        let name = handler.name();
+        let handler_type = handler.get_type().as_str();
+        let span = tracing::info_span!(
+            "handler",
+            otel.name = %format!("{} {}", handler_type, name),
+            command = %ctx.command_name,
+            handler = name,
+            handler_type,
+            activity_id = %ctx.activity_id,
+        );
+
+        let command = ctx.command_name.clone();
+        let started = Instant::now();
+        let result = self.execute_handler(ctx, handler).instrument(span).await;
+
+        self.metrics.record_handler(
+            &command,
+            name,
+            handler_type,
+            started.elapsed(),
+            result.is_err(),
+        );
+
+        result
+    }
+
     async fn execute_handler(
         &self,
         ctx: &mut CommandContext,
@@ -64,12 +153,106 @@ impl CommandExecutor {
             ),
         )))
     }
+
+    /// Describes every registered command, sorted by name, with its handlers
+    /// in the order the pipeline runs them.
+    pub fn describe(&self) -> Vec<CommandDescriptor> {
+        let mut commands: Vec<CommandDescriptor> = self
+            .handlers
+            .iter()
+            .map(|(name, handlers)| {
+                let mut sorted = handlers.clone();
+                sorted.sort_by_key(|h| h.get_type().priority());
+
+                CommandDescriptor {
+                    name: name.clone(),
+                    handlers: sorted
+                        .iter()
+                        .map(|h| HandlerDescriptor {
+                            name: h.name().to_string(),
+                            handler_type: h.get_type().as_str().to_string(),
+                        })
+                        .collect(),
+                    input_schema: sorted.iter().find_map(|h| h.input_schema()),
+                }
+            })
+            .collect();
+
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+        commands
+    }
+
+    /// Wakes the outbox delivery service, if the outbox is enabled.
+    pub fn notify_outbox(&self) {
+        if let Some(signal) = &self.outbox_signal {
+            signal.notify_one();
+        }
+    }
+
+    /// Runs only the `Observer` handlers registered for `ctx.command_name`.
+    /// Used to deliver outbox entries after the originating write committed.
+    pub async fn observe(&self, ctx: &mut CommandContext) -> CommandResult {
+        let Some(handlers) = self.handlers.get(&ctx.command_name) else {
+            return Ok(None);
+        };
+
+        for handler in handlers
+            .iter()
+            .filter(|h| h.get_type() == CommandType::Observer)
+        {
+            self.run_handler(ctx, handler).await?;
+        }
+
+        Ok(None)
+    }
+
+    /// Observers are deferred when the outbox is enabled, the command has at
+    /// least one observer, and its write handler stages outbox entries.
+    fn should_defer_observers(&self, handlers: &[Arc<dyn CommandHandler>]) -> bool {
+        self.outbox_signal.is_some()
+            && handlers
+                .iter()
+                .any(|h| h.get_type() == CommandType::Observer)
+            && handlers
+                .iter()
+                .any(|h| h.get_type() == CommandType::Internal && h.writes_outbox())
+    }
 }
 
 #[async_trait]
 impl CommandDispatcher for CommandExecutor {
     async fn dispatch(&self, ctx: &mut CommandContext) -> CommandResult {
-        match self.handlers.get(&ctx.command_name) {
+        let span = tracing::info_span!(
+            "command",
+            otel.name = %ctx.command_name,
+            command = %ctx.command_name,
+            activity_id = %ctx.activity_id,
+        );
+
+        let command = ctx.command_name.clone();
+        let started = Instant::now();
+        let result = Next::new(&self.stages, &Handlers(self))
+            .run(ctx)
+            .instrument(span)
+            .await;
+
+        self.metrics
+            .record_command(&command, started.elapsed(), result.is_err());
+
+        result
+    }
+}
+
+/// The innermost step of the pipeline: runs the handlers registered for the
+/// command in priority order.
+struct Handlers<'a>(&'a CommandExecutor);
+
+#[async_trait]
+impl CommandDispatcher for Handlers<'_> {
+    async fn dispatch(&self, ctx: &mut CommandContext) -> CommandResult {
+        let executor = self.0;
+
+        match executor.handlers.get(&ctx.command_name) {
             Some(handlers) => {
                 let mut final_result: Option<Value> = None;
 
@@ -78,8 +261,17 @@ impl CommandDispatcher for CommandExecutor {
                 sorted_handlers
                     .sort_by(|a, b| a.get_type().priority().cmp(&b.get_type().priority()));
 
+                ctx.defer_observers = executor.should_defer_observers(&sorted_handlers);
+
+                // Batched commands are observed by the batch once it commits.
+                let skip_observers = ctx.defer_observers || ctx.write_batch.is_some();
+
                 for handler in sorted_handlers {
-                    let result = self.execute_handler(ctx, &handler).await?;
+                    if skip_observers && handler.get_type() == CommandType::Observer {
+                        continue;
+                    }
+
+                    let result = executor.run_handler(ctx, &handler).await?;
 
                     if final_result.is_none() && handler.get_type() == CommandType::Internal {
                         final_result = result.clone();
@@ -89,6 +281,10 @@ impl CommandDispatcher for CommandExecutor {
                     }
                 }
 
+                if ctx.defer_observers && ctx.write_batch.is_none() {
+                    executor.notify_outbox();
+                }
+
                 return Ok(final_result);
             }
             None => Err(anyhow::Error::new(std::io::Error::new(
@@ -98,3 +294,192 @@ impl CommandDispatcher for CommandExecutor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use kuiper_runtime::command::{ExecutableCommand, ValidationCommand};
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::{
+        data::{AggregatedMetrics, MetricData, ResourceMetrics},
+        InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id},
+        Subscriber,
+    };
+    use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+    use super::*;
+    use crate::telemetry::METER_NAME;
+
+    struct Succeeds;
+
+    #[async_trait]
+    impl CommandHandler for Succeeds {
+        fn get_type(&self) -> CommandType {
+            CommandType::Internal
+        }
+
+        fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+            Some(self)
+        }
+    }
+
+    #[async_trait]
+    impl ExecutableCommand for Succeeds {
+        async fn execute(&self, _ctx: &CommandContext) -> CommandResult {
+            Ok(None)
+        }
+    }
+
+    struct Rejects;
+
+    #[async_trait]
+    impl CommandHandler for Rejects {
+        fn get_type(&self) -> CommandType {
+            CommandType::Validator
+        }
+
+        fn as_validator(&self) -> Option<&dyn ValidationCommand> {
+            Some(self)
+        }
+    }
+
+    #[async_trait]
+    impl ValidationCommand for Rejects {
+        async fn validate(&self, _ctx: &CommandContext) -> CommandResult {
+            Err(anyhow::anyhow!("rejected"))
+        }
+    }
+
+    fn executor(metrics: CommandMetrics) -> CommandExecutor {
+        let mut executor = CommandExecutor::with_metrics(metrics);
+        executor.register_handler("ok", Arc::new(Succeeds));
+        executor.register_handler("fail", Arc::new(Rejects));
+        executor.register_handler("fail", Arc::new(Succeeds));
+        executor
+    }
+
+    fn context(command: &str) -> CommandContext {
+        CommandContext {
+            command_name: command.to_string(),
+            activity_id: uuid::Uuid::new_v4(),
+            ..Default::default()
+        }
+    }
+
+    /// Data points of the metric `name` as `(handler attribute, value)`, the
+    /// value being the sample count of histograms.
+    fn points(metrics: &[ResourceMetrics], name: &str) -> Vec<(String, u64)> {
+        let handler = |attributes: Vec<&opentelemetry::KeyValue>| {
+            attributes
+                .into_iter()
+                .find(|kv| kv.key.as_str() == "handler")
+                .map(|kv| kv.value.to_string())
+                .unwrap_or_default()
+        };
+
+        let mut points: Vec<(String, u64)> = metrics
+            .iter()
+            .flat_map(|rm| rm.scope_metrics())
+            .flat_map(|sm| sm.metrics())
+            .filter(|m| m.name() == name)
+            .flat_map(|m| match m.data() {
+                AggregatedMetrics::F64(MetricData::Histogram(h)) => h
+                    .data_points()
+                    .map(|p| (handler(p.attributes().collect()), p.count()))
+                    .collect::<Vec<_>>(),
+                AggregatedMetrics::U64(MetricData::Sum(s)) => s
+                    .data_points()
+                    .map(|p| (handler(p.attributes().collect()), p.value()))
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+        points.sort();
+        points
+    }
+
+    /// Every handler run records its latency; only failed runs count as
+    /// errors. A failing validator stops the command before later handlers.
+    #[tokio::test]
+    async fn records_handler_latency_and_errors() {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let executor = executor(CommandMetrics::from_meter(&provider.meter(METER_NAME)));
+
+        executor.dispatch(&mut context("ok")).await.unwrap();
+        executor.dispatch(&mut context("ok")).await.unwrap();
+        assert!(executor.dispatch(&mut context("fail")).await.is_err());
+
+        provider.force_flush().unwrap();
+        let metrics = exporter.get_finished_metrics().unwrap();
+
+        assert_eq!(
+            points(&metrics, "kuiper.command.handler.duration"),
+            vec![("Rejects".to_string(), 1), ("Succeeds".to_string(), 2)]
+        );
+        assert_eq!(
+            points(&metrics, "kuiper.command.handler.errors"),
+            vec![("Rejects".to_string(), 1)]
+        );
+        assert_eq!(points(&metrics, "kuiper.command.errors").len(), 1);
+    }
+
+    /// A span's name and its `activity_id` field.
+    type RecordedSpan = (&'static str, Option<String>);
+
+    /// Records every span created.
+    #[derive(Clone, Default)]
+    struct SpanRecorder(Arc<Mutex<Vec<RecordedSpan>>>);
+
+    struct ActivityId(Option<String>);
+
+    impl Visit for ActivityId {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "activity_id" {
+                self.0 = Some(format!("{:?}", value));
+            }
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for SpanRecorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            let mut visitor = ActivityId(None);
+            attrs.record(&mut visitor);
+            self.0
+                .lock()
+                .unwrap()
+                .push((attrs.metadata().name(), visitor.0));
+        }
+    }
+
+    /// The command span and the span of each handler carry the command's
+    /// `activity_id`, so traces correlate with the audit trail.
+    #[tokio::test]
+    async fn spans_carry_activity_id() {
+        let recorder = SpanRecorder::default();
+        let _guard = tracing_subscriber::registry()
+            .with(recorder.clone())
+            .set_default();
+
+        let executor = executor(CommandMetrics::new());
+        let mut ctx = context("fail");
+        let activity_id = ctx.activity_id.to_string();
+        assert!(executor.dispatch(&mut ctx).await.is_err());
+
+        let spans = recorder.0.lock().unwrap().clone();
+        assert_eq!(
+            spans,
+            vec![
+                ("command", Some(activity_id.clone())),
+                ("handler", Some(activity_id)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use std::sync::{Arc, Weak};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{
+        CommandContext, CommandDispatcher, CommandHandler, CommandRequest, CommandResult,
+        CommandType, ExecutableCommand,
+    },
+    data::{TransactionalKeyValueStore, WriteBatch},
+};
+use kuiper_types::{error::KuiperError, model::resource::SystemObject};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use super::{batch::observe_committed, CommandExecutor};
+use crate::{
+    constants::{resource_key, RESOURCE_CONTAINER},
+    conversion::Converter,
+    registry::ResourceRegistry,
+};
+
+/// How often a patch is re-applied when the object changes between reading
+/// and writing it.
+const MAX_PATCH_ATTEMPTS: usize = 5;
+
+/// Format of [`PatchRequest::patch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum PatchType {
+    /// RFC 7386 JSON Merge Patch: an object merged into the stored one, where
+    /// `null` removes a field.
+    #[serde(rename = "merge")]
+    Merge,
+
+    /// RFC 6902 JSON Patch: a list of `add`/`remove`/`replace`/`move`/`copy`/
+    /// `test` operations.
+    #[serde(rename = "json")]
+    Json,
+}
+
+impl PatchType {
+    pub const MERGE_PATCH_CONTENT_TYPE: &'static str = "application/merge-patch+json";
+    pub const JSON_PATCH_CONTENT_TYPE: &'static str = "application/json-patch+json";
+
+    /// The patch type sent with `content_type`, ignoring parameters such as
+    /// `charset`.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        if mime.eq_ignore_ascii_case(Self::MERGE_PATCH_CONTENT_TYPE) {
+            Some(Self::Merge)
+        } else if mime.eq_ignore_ascii_case(Self::JSON_PATCH_CONTENT_TYPE) {
+            Some(Self::Json)
+        } else {
+            None
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Merge => Self::MERGE_PATCH_CONTENT_TYPE,
+            Self::Json => Self::JSON_PATCH_CONTENT_TYPE,
+        }
+    }
+}
+
+/// Inputs of the `patch` command.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PatchRequest {
+    pub namespace: String,
+
+    /// Path of the resource within the namespace: `{group}/{kind}/{name}`.
+    pub resource: String,
+
+    #[serde(rename = "patchType")]
+    pub patch_type: PatchType,
+
+    /// A JSON Merge Patch object or a JSON Patch array, per `patchType`.
+    pub patch: Value,
+
+    /// When set, the patch only applies to this version of the object and
+    /// fails with a conflict otherwise.
+    #[serde(
+        rename = "resourceVersion",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub resource_version: Option<String>,
+
+    /// The version of the kind the patch is written against; the stored
+    /// object is converted to it first. Defaults to the `apiVersion` of a
+    /// merge patch, else the version the object is stored at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+impl PatchRequest {
+    /// The version the patch is written against, if it names one.
+    fn target_version(&self) -> Option<&str> {
+        self.version.as_deref().or_else(|| match self.patch_type {
+            PatchType::Merge => self.patch["apiVersion"]
+                .as_str()
+                .and_then(|v| v.split_once('/'))
+                .map(|(_, version)| version),
+            PatchType::Json => None,
+        })
+    }
+}
+
+impl CommandRequest for PatchRequest {
+    const COMMAND: &'static str = "patch";
+    type Response = SystemObject;
+}
+
+/// Applies a patch to the stored object and writes the result through `set`,
+/// so its mutators, validators and pipeline stages run on the patched object.
+///
+/// The object is read and written through one [`WriteBatch`], which commits
+/// only if the object is still the one the patch was applied to. A patch that
+/// lost that race is applied again to the new object, unless a
+/// `resourceVersion` precondition no longer holds.
+pub struct PatchCommand {
+    executor: Weak<CommandExecutor>,
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    registry: Option<Arc<RwLock<ResourceRegistry>>>,
+    converter: Option<Converter>,
+}
+
+impl PatchCommand {
+    pub fn new(
+        executor: Weak<CommandExecutor>,
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        registry: Option<Arc<RwLock<ResourceRegistry>>>,
+    ) -> Self {
+        let converter = registry.clone().map(Converter::new);
+        Self {
+            executor,
+            store,
+            registry,
+            converter,
+        }
+    }
+
+    /// Reads the object through a new [`WriteBatch`] and applies the patch.
+    /// Returns the batch and the `set` that writes the patched object.
+    async fn prepare(
+        &self,
+        ctx: &CommandContext,
+        request: &PatchRequest,
+    ) -> anyhow::Result<(WriteBatch, CommandContext)> {
+        let namespace = request.namespace.to_lowercase();
+        let resource = request.resource.to_lowercase();
+        let key = resource_key(&namespace, Some(&resource));
+
+        let batch = WriteBatch::new();
+        let stored = {
+            let store = self.store.read().await;
+            batch.get(&*store, RESOURCE_CONTAINER, &key).await
+        }
+        .ok_or_else(|| KuiperError::NotFound(format!("Resource '{}' not found", resource)))?;
+
+        let mut stored: Value =
+            serde_json::from_slice(&stored).context("Failed to parse stored value as JSON")?;
+        if let Some(converter) = &self.converter {
+            stored = converter
+                .convert_stored(stored, request.target_version())
+                .await?;
+        }
+        let stored_rv = stored["metadata"]["resourceVersion"].clone();
+
+        let mut patched = apply_patch(&stored, request.patch_type, &request.patch)?;
+
+        // A resourceVersion in the request or in the patch itself is a
+        // precondition on the stored object.
+        let provided_rv = request
+            .resource_version
+            .as_deref()
+            .or_else(|| patched["metadata"]["resourceVersion"].as_str());
+        if let Some(provided_rv) = provided_rv {
+            if stored_rv.as_str() != Some(provided_rv) {
+                return Err(KuiperError::Conflict(format!(
+                    "resourceVersion mismatch: provided '{}', stored '{}'",
+                    provided_rv,
+                    stored_rv.as_str().unwrap_or("")
+                ))
+                .into());
+            }
+        }
+
+        for (field, pointer) in [
+            ("apiVersion", "/apiVersion"),
+            ("kind", "/kind"),
+            ("metadata.name", "/metadata/name"),
+        ] {
+            if patched.pointer(pointer) != stored.pointer(pointer) {
+                return Err(KuiperError::Invalid(format!(
+                    "command 'patch': {} cannot be changed",
+                    field
+                ))
+                .into());
+            }
+        }
+
+        // The write applies to the version the patch was applied to.
+        patched["metadata"]["resourceVersion"] = stored_rv;
+
+        let mut item = CommandContext {
+            command_name: "set".to_string(),
+            activity_id: ctx.activity_id,
+            caller_id: ctx.caller_id.clone(),
+            is_internal: ctx.is_internal,
+            write_batch: Some(batch.clone()),
+            cancellation_token: ctx.cancellation_token.clone(),
+            ..Default::default()
+        };
+        item.parameters
+            .insert("resource".to_string(), Value::String(resource));
+        item.parameters.insert("value".to_string(), patched);
+        item.metadata.insert("namespace".to_string(), namespace);
+
+        Ok((batch, item))
+    }
+}
+
+/// Runs `item`, a `set` staged in `batch`, then commits the batch and runs
+/// the observers. Fails with a conflict when something the batch read
+/// changed meanwhile.
+pub(crate) async fn commit_set(
+    executor: &CommandExecutor,
+    store: &RwLock<dyn TransactionalKeyValueStore>,
+    registry: Option<&RwLock<ResourceRegistry>>,
+    batch: WriteBatch,
+    mut item: CommandContext,
+) -> CommandResult {
+    let result = executor.dispatch(&mut item).await?;
+
+    {
+        let store = store.write().await;
+        batch.commit(&*store).await?;
+    }
+    batch.run_after_commit().await;
+
+    let is_definition = result
+        .as_ref()
+        .and_then(|v| v.get("kind"))
+        .and_then(Value::as_str)
+        .is_some_and(|k| k.eq_ignore_ascii_case("resourcedefinition"));
+    if is_definition {
+        if let Some(registry) = registry {
+            registry
+                .write()
+                .await
+                .reload()
+                .await
+                .context("Failed to reload ResourceRegistry")?;
+        }
+    }
+
+    observe_committed(executor, std::slice::from_mut(&mut item)).await;
+
+    Ok(result)
+}
+
+/// Applies `patch` to a copy of `target`.
+pub fn apply_patch(target: &Value, patch_type: PatchType, patch: &Value) -> anyhow::Result<Value> {
+    let mut patched = target.clone();
+    match patch_type {
+        PatchType::Merge => {
+            if !patch.is_object() {
+                return Err(KuiperError::Invalid(
+                    "command 'patch': a merge patch must be a JSON object".to_string(),
+                )
+                .into());
+            }
+            json_patch::merge(&mut patched, patch);
+        }
+        PatchType::Json => {
+            let operations: json_patch::Patch = serde_json::from_value(patch.clone())
+                .map_err(|e| KuiperError::Invalid(format!("command 'patch': {}", e)))?;
+            json_patch::patch(&mut patched, &operations)
+                .map_err(|e| KuiperError::Invalid(format!("command 'patch': {}", e)))?;
+        }
+    }
+    Ok(patched)
+}
+
+impl CommandHandler for PatchCommand {
+    fn get_type(&self) -> CommandType {
+        CommandType::Internal
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        super::request_schema::<PatchRequest>()
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for PatchCommand {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let request: PatchRequest = ctx.request()?;
+
+        let executor = self
+            .executor
+            .upgrade()
+            .context("Command executor is no longer available")?;
+
+        let mut attempt = 1;
+        loop {
+            let (batch, item) = self.prepare(ctx, &request).await?;
+            match commit_set(
+                &executor,
+                &*self.store,
+                self.registry.as_deref(),
+                batch,
+                item,
+            )
+            .await
+            {
+                Err(e)
+                    if attempt < MAX_PATCH_ATTEMPTS
+                        && matches!(e.downcast_ref(), Some(KuiperError::Conflict(_))) =>
+                {
+                    tracing::debug!(
+                        "Patch of {} conflicted (attempt {}); retrying",
+                        request.resource,
+                        attempt
+                    );
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
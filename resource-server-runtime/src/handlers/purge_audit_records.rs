@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use kuiper_runtime::command::{
+    respond, CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+    ExecutableCommand,
+};
+use kuiper_types::error::KuiperError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::purge_idempotency_records::PurgedRecords;
+use crate::audit::AuditSink;
+
+/// Inputs of the `purge_audit_records` command.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct PurgeAuditRecordsRequest {}
+
+impl CommandRequest for PurgeAuditRecordsRequest {
+    const COMMAND: &'static str = "purge_audit_records";
+    type Response = PurgedRecords;
+}
+
+/// Deletes audit records that outlived the retention of their sink. Only runs
+/// for internal callers, such as the scheduler.
+pub struct PurgeAuditRecordsCommand {
+    sinks: Vec<Arc<dyn AuditSink>>,
+}
+
+impl PurgeAuditRecordsCommand {
+    pub fn new(sinks: Vec<Arc<dyn AuditSink>>) -> Self {
+        Self { sinks }
+    }
+}
+
+impl CommandHandler for PurgeAuditRecordsCommand {
+    fn get_type(&self) -> CommandType {
+        CommandType::Internal
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        super::request_schema::<PurgeAuditRecordsRequest>()
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for PurgeAuditRecordsCommand {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let PurgeAuditRecordsRequest {} = ctx.request()?;
+        if !ctx.is_internal {
+            return Err(KuiperError::Forbidden(format!(
+                "Command '{}' can only be run internally",
+                PurgeAuditRecordsRequest::COMMAND
+            ))
+            .into());
+        }
+
+        let mut purged = 0;
+        for sink in &self.sinks {
+            purged += sink.purge_expired().await?;
+        }
+        if purged > 0 {
+            tracing::info!("Purged {} expired audit records", purged);
+        }
+
+        respond(&PurgedRecords { purged })
+    }
+}
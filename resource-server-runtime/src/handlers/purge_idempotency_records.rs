@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{
+        respond, CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+        ExecutableCommand,
+    },
+    data::TransactionalKeyValueStore,
+};
+use kuiper_types::error::KuiperError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::idempotency;
+
+/// Inputs of the `purge_idempotency_records` command.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct PurgeIdempotencyRecordsRequest {}
+
+impl CommandRequest for PurgeIdempotencyRecordsRequest {
+    const COMMAND: &'static str = "purge_idempotency_records";
+    type Response = PurgedRecords;
+}
+
+/// Result of the `purge_idempotency_records` command.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PurgedRecords {
+    /// Expired records deleted.
+    pub purged: usize,
+}
+
+/// Deletes expired idempotency records. Only runs for internal callers, such
+/// as the scheduler, since it scans the whole `idempotency` container.
+pub struct PurgeIdempotencyRecordsCommand {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+}
+
+impl PurgeIdempotencyRecordsCommand {
+    pub fn new(store: Arc<RwLock<dyn TransactionalKeyValueStore>>) -> Self {
+        Self { store }
+    }
+}
+
+impl CommandHandler for PurgeIdempotencyRecordsCommand {
+    fn get_type(&self) -> CommandType {
+        CommandType::Internal
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        super::request_schema::<PurgeIdempotencyRecordsRequest>()
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for PurgeIdempotencyRecordsCommand {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let PurgeIdempotencyRecordsRequest {} = ctx.request()?;
+        if !ctx.is_internal {
+            return Err(KuiperError::Forbidden(format!(
+                "Command '{}' can only be run internally",
+                PurgeIdempotencyRecordsRequest::COMMAND
+            ))
+            .into());
+        }
+
+        let store = self.store.write().await;
+        let purged = idempotency::purge_expired(&*store).await?;
+        if purged > 0 {
+            tracing::info!("Purged {} expired idempotency records", purged);
+        }
+
+        respond(&PurgedRecords { purged })
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{
+        respond, CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+        ExecutableCommand,
+    },
+    data::TransactionalKeyValueStore,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    index::{self, RebuildStats},
+    registry::ResourceRegistry,
+};
+
+/// Inputs of the `rebuild_indexes` command.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RebuildIndexesRequest {}
+
+impl CommandRequest for RebuildIndexesRequest {
+    const COMMAND: &'static str = "rebuild_indexes";
+    type Response = RebuildStats;
+}
+
+/// Rewrites the secondary indexes of every stored object, e.g. for a store
+/// written before indexes existed, after which `list` starts using them.
+pub struct RebuildIndexesCommand {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    registry: Arc<RwLock<ResourceRegistry>>,
+}
+
+impl RebuildIndexesCommand {
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        registry: Arc<RwLock<ResourceRegistry>>,
+    ) -> Self {
+        Self { store, registry }
+    }
+}
+
+impl CommandHandler for RebuildIndexesCommand {
+    fn get_type(&self) -> CommandType {
+        CommandType::Internal
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({ "type": "object" }))
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for RebuildIndexesCommand {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let RebuildIndexesRequest {} = ctx.request()?;
+
+        let registry = self.registry.read().await;
+        let store = self.store.write().await;
+
+        let stats = index::rebuild(&*store, |g, k| registry.indexed_fields(g, k), None).await?;
+        tracing::info!(
+            "Rebuilt {} index entries of {} objects",
+            stats.entries,
+            stats.objects
+        );
+
+        respond(&stats)
+    }
+}
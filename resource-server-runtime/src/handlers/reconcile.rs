@@ -3,15 +3,29 @@ use std::sync::Arc;
 use anyhow::Context;
 use async_trait::async_trait;
 use kuiper_runtime::{
-    command::{CommandContext, CommandHandler, CommandResult, CommandType, ExecutableCommand},
+    command::{
+        CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+        ExecutableCommand,
+    },
     data::TransactionalKeyValueStore,
 };
 use kuiper_types::model::resource::SystemObject;
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 use tracing::{self, instrument};
 
 use crate::constants::RESOURCE_CONTAINER;
 
+/// Inputs of the `reconcile` command. It takes none; the sweep covers every
+/// stored resource.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReconcileRequest {}
+
+impl CommandRequest for ReconcileRequest {
+    const COMMAND: &'static str = "reconcile";
+    type Response = ();
+}
+
 pub struct ReconcileCommand {
     store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
 }
@@ -31,11 +45,17 @@ impl CommandHandler for ReconcileCommand {
     fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
         Some(self)
     }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({ "type": "object" }))
+    }
 }
 
 #[async_trait]
 impl ExecutableCommand for ReconcileCommand {
-    async fn execute(&self, _: &CommandContext) -> CommandResult {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let ReconcileRequest {} = ctx.request()?;
+
         let store = self.store.write().await;
 
         if !store
@@ -3,20 +3,54 @@ use std::sync::Arc;
 use anyhow::Context;
 use async_trait::async_trait;
 use kuiper_runtime::{
-    command::{CommandContext, CommandHandler, CommandResult, CommandType, ExecutableCommand},
-    data::TransactionalKeyValueStore,
+    command::{
+        respond, CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+        ExecutableCommand,
+    },
+    data::{Transaction, TransactionalKeyValueStore, WriteBatch},
+    outbox::{OutboxEntry, OUTBOX_CONTAINER},
 };
-use kuiper_types::{error::KuiperError, model::resource::SystemObject};
+use kuiper_types::{
+    error::KuiperError,
+    model::resource::{OwnerReference, SystemObject},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
 use crate::{
-    constants::{resource_key, RESOURCE_CONTAINER, SYSTEM_EXTENSION_GROUP},
+    constants::{resource_key, GLOBAL_NAMESPACE, RESOURCE_CONTAINER, SYSTEM_EXTENSION_GROUP},
+    conversion::Converter,
+    index,
+    model::resource_definition::ResourceDefinition,
+    namespace,
     registry::{ResourceRegistry, RESERVED_UID_PREFIX},
 };
 
+/// Inputs of the `set` command.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SetRequest {
+    pub namespace: String,
+
+    /// Path of the resource within the namespace: `{group}/{kind}/{name}`.
+    /// For registered kinds it has to be the path of `value`.
+    pub resource: String,
+
+    /// The object to create or replace. It is stored at its kind's storage
+    /// version and returned at the version it was written at.
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    pub value: SystemObject,
+}
+
+impl CommandRequest for SetRequest {
+    const COMMAND: &'static str = "set";
+    type Response = SystemObject;
+}
+
 pub struct SetCommand {
     store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
     registry: Option<Arc<RwLock<ResourceRegistry>>>,
+    converter: Option<Converter>,
 }
 
 impl SetCommand {
@@ -24,7 +58,12 @@ impl SetCommand {
         store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
         registry: Option<Arc<RwLock<ResourceRegistry>>>,
     ) -> Self {
-        Self { store, registry }
+        let converter = registry.clone().map(Converter::new);
+        Self {
+            store,
+            registry,
+            converter,
+        }
     }
 }
 
@@ -36,37 +75,68 @@ impl CommandHandler for SetCommand {
     fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
         Some(self)
     }
+
+    fn writes_outbox(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        super::request_schema::<SetRequest>()
+    }
 }
 
 #[async_trait]
 impl ExecutableCommand for SetCommand {
     async fn execute(&self, ctx: &CommandContext) -> CommandResult {
-        let namespace = ctx
-            .metadata
-            .get("namespace")
-            .cloned()
-            .context("Missing required parameter: namespace")?
-            .to_lowercase();
-
-        let resource = ctx
-            .get_string_param("resource")
-            .context("Missing required parameter: resource")?
-            .to_lowercase();
-
-        let raw_value = ctx
-            .get_param("value")
-            .context("Missing required parameter: value")?;
-
-        let mut obj: SystemObject =
-            serde_json::from_str(&raw_value).context("Failed to parse 'value' as SystemObject")?;
-
-        // Guard: the system extension group is reserved for internal operations.
+        let SetRequest {
+            namespace,
+            resource,
+            value: mut obj,
+        } = ctx.request()?;
+        let namespace = namespace.to_lowercase();
+        let resource = resource.to_lowercase();
+
+        // The object is stored at `resource` but checked as the kind it
+        // declares, so for registered kinds the two have to agree.
+        if let Some(registry) = &self.registry {
+            let path = object_path(&resource);
+            let registry = registry.read().await;
+            let group = obj.api_version.split('/').next().unwrap_or_default();
+            let registered = registry.get_definition(group, &obj.kind).is_some()
+                || path.is_some_and(|(group, _, kind, _)| {
+                    registry.get_definition(group, kind).is_some()
+                });
+            if registered {
+                check_resource_path(path, &resource, &obj)?;
+            }
+        }
+
+        // Guard: the system extension group is reserved for internal operations,
+        // except for the `Namespace` objects in `global` that clients create
+        // namespaces with.
         if !ctx.is_internal && obj.api_version.starts_with(SYSTEM_EXTENSION_GROUP) {
-            return Err(KuiperError::Forbidden(format!(
-                "apiVersion group '{}' is reserved for internal system operations.",
-                SYSTEM_EXTENSION_GROUP
-            ))
-            .into());
+            let namespace_object = obj
+                .kind
+                .eq_ignore_ascii_case(namespace::NAMESPACE_KIND)
+                .then(|| namespace::namespace_name(&namespace, &resource))
+                .flatten();
+            match namespace_object {
+                Some(name) if namespace::is_builtin(name) => {
+                    return Err(KuiperError::Forbidden(format!(
+                        "Namespace '{}' is built in",
+                        name
+                    ))
+                    .into());
+                }
+                Some(_) => {}
+                None => {
+                    return Err(KuiperError::Forbidden(format!(
+                        "apiVersion group '{}' is reserved for internal system operations.",
+                        SYSTEM_EXTENSION_GROUP
+                    ))
+                    .into());
+                }
+            }
         }
 
         // Guard: the reserved UID prefix is exclusive to internal system resources.
@@ -85,24 +155,49 @@ impl ExecutableCommand for SetCommand {
             .into());
         }
 
+        // Definitions are written by internal callers only, so they are
+        // checked here rather than by the schema validator.
+        if obj.kind.eq_ignore_ascii_case("ResourceDefinition")
+            && obj.api_version.starts_with(SYSTEM_EXTENSION_GROUP)
+        {
+            let definition =
+                serde_json::to_value(&obj).and_then(serde_json::from_value::<ResourceDefinition>);
+            if let Ok(definition) = definition {
+                definition.validate()?;
+            }
+        }
+
+        let written_version = obj.api_version.clone();
+        if let Some(converter) = &self.converter {
+            obj = converter.convert(obj, None).await?;
+        }
+
+        // With the status subresource enabled, only `set_status` writes status.
+        let status_subresource = match (&self.registry, obj.api_version.split_once('/')) {
+            (Some(registry), Some((group, version))) => registry
+                .read()
+                .await
+                .has_status_subresource(group, &obj.kind, version),
+            _ => false,
+        };
+
+        let fields = index::indexed_fields(self.registry.as_deref(), &resource).await;
+
         let key = resource_key(&namespace, Some(&resource));
 
         {
             let store = self.store.write().await;
 
-            if !store
-                .container_exists(RESOURCE_CONTAINER)
-                .await
-                .context("Failed to check resource container")?
-            {
-                store
-                    .new_container(RESOURCE_CONTAINER)
-                    .await
-                    .context("Failed to create resource container")?;
-            }
+            index::ensure_containers(&*store).await?;
+
+            let existing = match &ctx.write_batch {
+                Some(batch) => batch.get(&*store, RESOURCE_CONTAINER, &key).await,
+                None => store.get(RESOURCE_CONTAINER, &key).await.ok(),
+            };
 
-            match store.get(RESOURCE_CONTAINER, &key).await {
-                Ok(existing_bytes) => {
+            let mut previous = None;
+            match existing {
+                Some(existing_bytes) => {
                     let stored_obj: SystemObject = serde_json::from_slice(&existing_bytes)
                         .context("Failed to parse stored value as SystemObject")?;
 
@@ -123,11 +218,27 @@ impl ExecutableCommand for SetCommand {
 
                     obj.metadata.uid = stored_obj.metadata.uid;
                     obj.metadata.creation_timestamp = stored_obj.metadata.creation_timestamp;
+
+                    // Objects stored before generations were tracked start at 1.
+                    let generation = stored_obj.metadata.generation.unwrap_or(1);
+                    obj.metadata.generation = if obj.extension_data == stored_obj.extension_data {
+                        Some(generation)
+                    } else {
+                        Some(generation + 1)
+                    };
                     if stored_obj.metadata.deletion_timestamp.is_some() {
                         obj.metadata.deletion_timestamp = stored_obj.metadata.deletion_timestamp;
                     }
+                    if status_subresource {
+                        obj.status = stored_obj.status.clone();
+                    }
+                    // Managed fields are only what the write carries: a
+                    // replace that omits them is no longer what earlier applies
+                    // produced, so their ownership is dropped.
+                    previous = Some(stored_obj);
                 }
-                Err(_) => {
+                None => {
+                    namespace::ensure_active(&*store, ctx.write_batch.as_ref(), &namespace).await?;
                     if obj.metadata.uid.is_nil() {
                         obj.metadata.uid = uuid::Uuid::new_v4();
                     }
@@ -135,22 +246,65 @@ impl ExecutableCommand for SetCommand {
                         obj.metadata.creation_timestamp =
                             Some(chrono::Utc::now().timestamp_micros());
                     }
+                    obj.metadata.generation = Some(1);
+                    if status_subresource {
+                        obj.status = None;
+                    }
                 }
             }
 
-            obj.metadata.namespace = Some(namespace);
+            if let Some(owners) = obj.metadata.owner_references.as_mut() {
+                let known = previous
+                    .as_ref()
+                    .and_then(|p| p.metadata.owner_references.as_deref())
+                    .unwrap_or_default();
+                resolve_owner_references(
+                    &*store,
+                    ctx.write_batch.as_ref(),
+                    &namespace,
+                    owners,
+                    known,
+                )
+                .await?;
+            }
+
+            obj.metadata.namespace = Some(namespace.clone());
             obj.metadata.resource_version = Some(uuid::Uuid::new_v4().to_string());
 
             let value_bytes =
                 serde_json::to_vec_pretty(&obj).context("Failed to serialize SystemObject")?;
 
-            store
-                .put(RESOURCE_CONTAINER, &key, value_bytes)
+            let mut tx = Transaction::new(&*store);
+            tx.put(RESOURCE_CONTAINER.to_string(), key, value_bytes);
+            index::stage_update(
+                &mut tx,
+                &namespace,
+                &resource,
+                previous.as_ref(),
+                Some(&obj),
+                &fields,
+            );
+
+            if ctx.defer_observers {
+                store
+                    .ensure_container(OUTBOX_CONTAINER)
+                    .await
+                    .context("Failed to create outbox container")?;
+
+                let result =
+                    serde_json::to_value(&obj).context("Failed to convert SystemObject to JSON")?;
+                OutboxEntry::from_context(ctx, Some(&result))
+                    .stage(&mut tx)
+                    .context("Failed to stage outbox entry")?;
+            }
+
+            tx.commit_or_stage(ctx.write_batch.as_ref())
                 .await
                 .context("Failed to write resource to store")?;
         }
 
-        if obj.kind.to_lowercase() == "resourcedefinition" {
+        // Batched writes are not committed yet; the batch reloads the registry.
+        if ctx.write_batch.is_none() && obj.kind.to_lowercase() == "resourcedefinition" {
             if let Some(registry) = &self.registry {
                 registry
                     .write()
@@ -161,8 +315,137 @@ impl ExecutableCommand for SetCommand {
             }
         }
 
-        let result =
-            serde_json::to_value(&obj).context("Failed to convert SystemObject to JSON")?;
-        Ok(Some(result))
+        // The write is stored; if it cannot be converted back, answer with
+        // the stored object rather than an error.
+        let version = written_version.split_once('/').map(|(_, v)| v);
+        if let (Some(converter), Some(version)) = (&self.converter, version) {
+            if obj.api_version != written_version {
+                let copy = serde_json::from_value(serde_json::to_value(&obj)?)?;
+                match converter.convert(copy, Some(version)).await {
+                    Ok(converted) => obj = converted,
+                    Err(e) => tracing::warn!(
+                        "Returning {} at its storage version {}: {}",
+                        resource,
+                        obj.api_version,
+                        e
+                    ),
+                }
+            }
+        }
+
+        respond(&obj)
+    }
+}
+
+/// The group, version, kind and name of an object path: `{group}/{kind}/{name}`,
+/// or `{group}/{version}/{kind}/{name}` for built-in kinds.
+fn object_path(resource: &str) -> Option<(&str, Option<&str>, &str, &str)> {
+    match resource.split('/').collect::<Vec<_>>().as_slice() {
+        [g, v, k, n] if g.eq_ignore_ascii_case(SYSTEM_EXTENSION_GROUP) => Some((g, Some(v), k, n)),
+        [g, k, n] if !g.eq_ignore_ascii_case(SYSTEM_EXTENSION_GROUP) => Some((g, None, k, n)),
+        _ => None,
+    }
+}
+
+/// Fails unless `obj` declares the group, kind and name of `path`, the
+/// parsed `resource`, and the version when the path has one.
+fn check_resource_path(
+    path: Option<(&str, Option<&str>, &str, &str)>,
+    resource: &str,
+    obj: &SystemObject,
+) -> Result<(), KuiperError> {
+    let (group, version) = match obj.api_version.split_once('/') {
+        Some((group, version)) => (group, Some(version)),
+        None => (obj.api_version.as_str(), None),
+    };
+    let matches = path.is_some_and(|(g, v, k, n)| {
+        g.eq_ignore_ascii_case(group)
+            && v.is_none_or(|v| version.is_some_and(|version| v.eq_ignore_ascii_case(version)))
+            && k.eq_ignore_ascii_case(&obj.kind)
+            && n.eq_ignore_ascii_case(&obj.metadata.name)
+    });
+
+    if matches {
+        Ok(())
+    } else {
+        Err(KuiperError::Invalid(format!(
+            "command 'set': {} {} '{}' does not belong at '{}'",
+            obj.api_version, obj.kind, obj.metadata.name, resource
+        )))
+    }
+}
+
+/// Checks that every owner not already in `known`, the references of the
+/// stored object, exists in `namespace` or in `global`, and fills in owner
+/// UIDs that were left empty. Known owners may since have been deleted: the
+/// garbage collector still has to update their dependents.
+async fn resolve_owner_references(
+    store: &dyn TransactionalKeyValueStore,
+    batch: Option<&WriteBatch>,
+    namespace: &str,
+    owners: &mut [OwnerReference],
+    known: &[OwnerReference],
+) -> anyhow::Result<()> {
+    for owner in owners.iter_mut() {
+        let same_owner = |k: &&OwnerReference| {
+            k.api_version.eq_ignore_ascii_case(&owner.api_version)
+                && k.kind.eq_ignore_ascii_case(&owner.kind)
+                && k.name.eq_ignore_ascii_case(&owner.name)
+                && (owner.uid.is_nil() || owner.uid == k.uid)
+        };
+        if let Some(k) = known.iter().find(same_owner) {
+            owner.uid = k.uid;
+            continue;
+        }
+
+        let group = owner.api_version.split('/').next().unwrap_or_default();
+
+        // User kinds are stored as `{group}/{kind}/{name}`, built-in kinds
+        // under their full `{apiVersion}/{kind}/{name}`.
+        let paths = [
+            format!("{}/{}/{}", group, owner.kind, owner.name),
+            format!("{}/{}/{}", owner.api_version, owner.kind, owner.name),
+        ];
+
+        let mut found = None;
+        for ns in [namespace, GLOBAL_NAMESPACE] {
+            for path in &paths {
+                let key = resource_key(ns, Some(path));
+                let bytes = match batch {
+                    Some(batch) => batch.get(store, RESOURCE_CONTAINER, &key).await,
+                    None => store.get(RESOURCE_CONTAINER, &key).await.ok(),
+                };
+                if let Some(bytes) = bytes {
+                    found = Some(bytes);
+                    break;
+                }
+            }
+            if found.is_some() {
+                break;
+            }
+        }
+
+        let Some(bytes) = found else {
+            return Err(KuiperError::Invalid(format!(
+                "command 'set': owner {} '{}' not found",
+                owner.kind, owner.name
+            ))
+            .into());
+        };
+
+        let stored: SystemObject = serde_json::from_slice(&bytes)
+            .context("Failed to parse stored owner as SystemObject")?;
+
+        if owner.uid.is_nil() {
+            owner.uid = stored.metadata.uid;
+        } else if owner.uid != stored.metadata.uid {
+            return Err(KuiperError::Invalid(format!(
+                "command 'set': owner {} '{}' has uid '{}', not '{}'",
+                owner.kind, owner.name, stored.metadata.uid, owner.uid
+            ))
+            .into());
+        }
     }
+
+    Ok(())
 }
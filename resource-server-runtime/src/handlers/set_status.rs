@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{
+        respond, CommandContext, CommandHandler, CommandRequest, CommandResult, CommandType,
+        ExecutableCommand,
+    },
+    data::{Transaction, TransactionalKeyValueStore},
+    outbox::{OutboxEntry, OUTBOX_CONTAINER},
+};
+use kuiper_types::{error::KuiperError, model::resource::SystemObject};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    constants::{resource_key, RESOURCE_CONTAINER, SYSTEM_EXTENSION_GROUP},
+    index,
+    registry::ResourceRegistry,
+};
+
+/// Inputs of the `set_status` command.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SetStatusRequest {
+    pub namespace: String,
+
+    /// Path of the resource within the namespace: `{group}/{kind}/{name}`.
+    pub resource: String,
+
+    /// The object carrying the new `status`. Everything else is ignored,
+    /// except `metadata.resourceVersion`, which the write is conditional on
+    /// when set.
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    pub value: SystemObject,
+}
+
+impl CommandRequest for SetStatusRequest {
+    const COMMAND: &'static str = "set_status";
+    type Response = SystemObject;
+}
+
+/// Replaces the `status` of a stored object and nothing else, so controllers
+/// writing status and users writing the object never overwrite each other.
+///
+/// Callers outside the runtime may only use it for versions that enable the
+/// status subresource; internal callers, such as controllers, for any object.
+pub struct SetStatusCommand {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    registry: Arc<RwLock<ResourceRegistry>>,
+}
+
+impl SetStatusCommand {
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        registry: Arc<RwLock<ResourceRegistry>>,
+    ) -> Self {
+        Self { store, registry }
+    }
+}
+
+impl CommandHandler for SetStatusCommand {
+    fn get_type(&self) -> CommandType {
+        CommandType::Internal
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+
+    fn writes_outbox(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        super::request_schema::<SetStatusRequest>()
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for SetStatusCommand {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let SetStatusRequest {
+            namespace,
+            resource,
+            value,
+        } = ctx.request()?;
+        let namespace = namespace.to_lowercase();
+        let resource = resource.to_lowercase();
+
+        // Guard: the system extension group is reserved for internal operations.
+        if !ctx.is_internal && value.api_version.starts_with(SYSTEM_EXTENSION_GROUP) {
+            return Err(KuiperError::Forbidden(format!(
+                "apiVersion group '{}' is reserved for internal system operations.",
+                SYSTEM_EXTENSION_GROUP
+            ))
+            .into());
+        }
+
+        let key = resource_key(&namespace, Some(&resource));
+        let fields = index::indexed_fields(Some(&*self.registry), &resource).await;
+
+        // The subresource check needs the stored object, and the registry is
+        // locked before the store.
+        let registry = if ctx.is_internal {
+            None
+        } else {
+            Some(self.registry.read().await)
+        };
+        let store = self.store.write().await;
+
+        let existing = match &ctx.write_batch {
+            Some(batch) => batch.get(&*store, RESOURCE_CONTAINER, &key).await,
+            None => store.get(RESOURCE_CONTAINER, &key).await.ok(),
+        };
+
+        let bytes = existing
+            .ok_or_else(|| KuiperError::NotFound(format!("Resource '{}' not found", resource)))?;
+
+        let mut obj: SystemObject = serde_json::from_slice(&bytes)
+            .context("Failed to parse stored value as SystemObject")?;
+
+        if !value.api_version.eq_ignore_ascii_case(&obj.api_version)
+            || !value.kind.eq_ignore_ascii_case(&obj.kind)
+        {
+            return Err(KuiperError::Invalid(format!(
+                "command 'set_status': {} '{}' does not match the stored {} '{}'",
+                value.api_version, value.kind, obj.api_version, obj.kind
+            ))
+            .into());
+        }
+
+        if let Some(registry) = registry {
+            let enabled = obj
+                .api_version
+                .split_once('/')
+                .is_some_and(|(group, version)| {
+                    registry.has_status_subresource(group, &obj.kind, version)
+                });
+            if !enabled {
+                return Err(KuiperError::Invalid(format!(
+                    "command 'set_status': {} '{}' has no status subresource",
+                    obj.api_version, obj.kind
+                ))
+                .into());
+            }
+        }
+
+        if let Some(provided_rv) = &value.metadata.resource_version {
+            let stored_rv = obj.metadata.resource_version.as_deref().unwrap_or("");
+            if provided_rv.as_str() != stored_rv {
+                return Err(KuiperError::Conflict(format!(
+                    "resourceVersion mismatch: provided '{}', stored '{}'",
+                    provided_rv, stored_rv
+                ))
+                .into());
+            }
+        }
+
+        // Nothing to change: keep the resourceVersion so watchers see no event.
+        if obj.status == value.status {
+            return respond(&obj);
+        }
+
+        obj.status = value.status;
+        obj.metadata.resource_version = Some(uuid::Uuid::new_v4().to_string());
+
+        let value_bytes =
+            serde_json::to_vec_pretty(&obj).context("Failed to serialize SystemObject")?;
+
+        let mut tx = Transaction::new(&*store);
+        tx.put(RESOURCE_CONTAINER.to_string(), key, value_bytes);
+        if !fields.is_empty() {
+            // Labels are unchanged, but indexed fields may live in the status.
+            let previous: SystemObject = serde_json::from_slice(&bytes)
+                .context("Failed to parse stored value as SystemObject")?;
+            index::stage_update(
+                &mut tx,
+                &namespace,
+                &resource,
+                Some(&previous),
+                Some(&obj),
+                &fields,
+            );
+        }
+
+        if ctx.defer_observers {
+            store
+                .ensure_container(OUTBOX_CONTAINER)
+                .await
+                .context("Failed to create outbox container")?;
+
+            let result =
+                serde_json::to_value(&obj).context("Failed to convert SystemObject to JSON")?;
+            OutboxEntry::from_context(ctx, Some(&result))
+                .stage(&mut tx)
+                .context("Failed to stage outbox entry")?;
+        }
+
+        tx.commit_or_stage(ctx.write_batch.as_ref())
+            .await
+            .context("Failed to write status to store")?;
+
+        respond(&obj)
+    }
+}
@@ -1,4 +1,7 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
 use anyhow::Context;
 use async_trait::async_trait;
@@ -8,17 +11,72 @@ use kuiper_runtime::command::{
 use kuiper_types::error::KuiperError;
 use tokio::sync::RwLock;
 
-use crate::registry::ResourceRegistry;
+use crate::{
+    constants::GLOBAL_NAMESPACE, model::resource_definition::ResourceScope,
+    registry::ResourceRegistry,
+};
 
 /// Validates the `spec` of an incoming resource against the JSON Schema stored
 /// in the matching `ResourceDefinitionVersion`.
+///
+/// In strict mode it also rejects objects whose group, kind or version is not
+/// registered or not enabled, and objects outside their kind's scope:
+/// `System` kinds live in `global`, `Namespace` kinds anywhere else. Internal
+/// callers may write any kind anywhere.
 pub struct SchemaValidationCommand {
     registry: Arc<RwLock<ResourceRegistry>>,
+    strict: Arc<AtomicBool>,
 }
 
 impl SchemaValidationCommand {
-    pub fn new(registry: Arc<RwLock<ResourceRegistry>>) -> Self {
-        Self { registry }
+    pub fn new(registry: Arc<RwLock<ResourceRegistry>>, strict: Arc<AtomicBool>) -> Self {
+        Self { registry, strict }
+    }
+
+    /// The strict-mode checks of `{group}/{version}` `kind` written to
+    /// `namespace`.
+    fn check_registered(
+        registry: &ResourceRegistry,
+        group: &str,
+        version: &str,
+        kind: &str,
+        namespace: &str,
+    ) -> Result<(), KuiperError> {
+        let Some(definition) = registry.get_definition(group, kind) else {
+            return Err(KuiperError::NotFound(format!(
+                "No ResourceDefinition registers kind '{}' in group '{}'",
+                kind, group
+            )));
+        };
+
+        if !registry.version_exists(group, kind, version) {
+            let disabled = definition
+                .spec
+                .versions
+                .iter()
+                .any(|v| v.name.eq_ignore_ascii_case(version));
+            return Err(if disabled {
+                KuiperError::Invalid(format!(
+                    "Version '{}' of {}/{} is disabled",
+                    version, group, kind
+                ))
+            } else {
+                KuiperError::NotFound(format!("{}/{} has no version '{}'", group, kind, version))
+            });
+        }
+
+        let global = namespace.eq_ignore_ascii_case(GLOBAL_NAMESPACE);
+        match definition.spec.scope {
+            ResourceScope::System if !global => Err(KuiperError::Invalid(format!(
+                "{}/{} is System-scoped and can only be written to namespace '{}'",
+                group, kind, GLOBAL_NAMESPACE
+            ))),
+            ResourceScope::Namespace if global => Err(KuiperError::Invalid(format!(
+                "{}/{} is Namespace-scoped and cannot be written to namespace '{}'",
+                group, kind, GLOBAL_NAMESPACE
+            ))),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -39,27 +97,33 @@ impl ValidationCommand for SchemaValidationCommand {
             return Ok(None);
         }
 
+        let strict = self.strict.load(Ordering::Relaxed);
+
         let raw_value = match ctx.parameters.get("value") {
             Some(v) => v.clone(),
             None => return Ok(None),
         };
 
-        let api_version = match raw_value.get("apiVersion").and_then(|v| v.as_str()) {
-            Some(v) => v.to_string(),
-            None => return Ok(None),
-        };
-        let kind = match raw_value.get("kind").and_then(|v| v.as_str()) {
-            Some(v) => v.to_string(),
-            None => return Ok(None),
-        };
-
-        let (group, version) = match api_version.split_once('/') {
-            Some((g, v)) => (g.to_string(), v.to_string()),
-            None => return Ok(None),
+        let api_version = raw_value.get("apiVersion").and_then(|v| v.as_str());
+        let kind = raw_value.get("kind").and_then(|v| v.as_str());
+        let (group, version, kind) = match (api_version.and_then(|v| v.split_once('/')), kind) {
+            (Some((g, v)), Some(k)) => (g.to_string(), v.to_string(), k.to_string()),
+            _ if strict => {
+                return Err(KuiperError::Invalid(
+                    "Objects need an apiVersion of the form {group}/{version} and a kind"
+                        .to_string(),
+                )
+                .into())
+            }
+            _ => return Ok(None),
         };
 
         let schema = {
             let reg = self.registry.read().await;
+            if strict {
+                let namespace = ctx.metadata.get("namespace").map_or("", String::as_str);
+                Self::check_registered(&reg, &group, &version, &kind, namespace)?;
+            }
             reg.get_version(&group, &kind, &version)
                 .and_then(|v| v.schema.clone())
         };
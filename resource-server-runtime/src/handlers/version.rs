@@ -31,6 +31,10 @@ impl CommandHandler for VersionCommand {
     fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
         Some(self)
     }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({ "type": "object" }))
+    }
 }
 
 #[async_trait]
@@ -0,0 +1,386 @@
+//! Idempotency keys for mutating commands.
+//!
+//! [`IdempotencyStage`] is a [`PipelineStage`] that remembers the response of
+//! the first `set`/`delete`/`finalize`/`batch` carrying a given key (from the
+//! `Idempotency-Key` header or the RPC `idempotencyKey` field) and returns it on replay instead
+//! of running the command again. Records live in the `idempotency` container
+//! until their TTL expires; a key reused with a different request is rejected.
+//! A request still in progress holds its key with a lease of
+//! [`IdempotencyStage::IN_PROGRESS_LEASE`], which it renews while it runs; a
+//! retry after the lease ran out takes the key over, in case the request's
+//! server stopped before finishing it. Every change of a record is a
+//! compare-and-swap, so a request that lost its key does not overwrite the
+//! record of the one that took it over. Expired records are removed by
+//! [`purge_expired`], which the `purge_idempotency_records` command runs.
+
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{CommandContext, CommandResult, Next, PipelineStage},
+    data::{StoreValue, TransactionalKeyValueStore},
+};
+use kuiper_types::error::KuiperError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+/// The container holding idempotency records.
+pub const IDEMPOTENCY_CONTAINER: &str = "idempotency";
+
+/// `CommandContext::metadata` key carrying the client-supplied key.
+pub const IDEMPOTENCY_KEY_METADATA: &str = "idempotencyKey";
+
+/// `CommandContext::metadata` key set to `"true"` when a stored response was
+/// replayed instead of running the command.
+pub const IDEMPOTENT_REPLAY_METADATA: &str = "idempotentReplay";
+
+/// Commands that honour idempotency keys.
+const MUTATING_COMMANDS: &[&str] = &[
+    "set",
+    "set_status",
+    "delete",
+    "finalize",
+    "patch",
+    "apply",
+    "batch",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum RecordState {
+    InProgress,
+    Completed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IdempotencyRecord {
+    /// SHA-256 of the command name, namespace and parameters.
+    fingerprint: String,
+
+    state: RecordState,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<Value>,
+
+    /// Microseconds since the Unix epoch when the request in progress began
+    /// or last renewed its lease.
+    #[serde(rename = "renewedAt", default, skip_serializing_if = "Option::is_none")]
+    renewed_at: Option<i64>,
+
+    /// Microseconds since the Unix epoch after which the record is ignored.
+    #[serde(rename = "expiresAt")]
+    expires_at: i64,
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Identifies the request a key was first used with.
+fn fingerprint(ctx: &CommandContext) -> anyhow::Result<String> {
+    let parameters: BTreeMap<_, _> = ctx.parameters.iter().collect();
+    let request = serde_json::json!({
+        "command": ctx.command_name,
+        "namespace": ctx.metadata.get("namespace"),
+        "parameters": parameters,
+    });
+    Ok(sha256_hex(&serde_json::to_vec(&request)?))
+}
+
+/// Pipeline stage that deduplicates retried mutating commands.
+pub struct IdempotencyStage {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    ttl: Duration,
+    lease: Duration,
+}
+
+impl IdempotencyStage {
+    /// Records are kept for a day by default.
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// How long a request in progress holds its key against retries without
+    /// renewing it. Leases are renewed every third of this.
+    pub const IN_PROGRESS_LEASE: Duration = Duration::from_secs(60);
+
+    pub fn new(store: Arc<RwLock<dyn TransactionalKeyValueStore>>, ttl: Duration) -> Self {
+        Self {
+            store,
+            ttl,
+            lease: Self::IN_PROGRESS_LEASE,
+        }
+    }
+
+    /// Replaces [`IN_PROGRESS_LEASE`](Self::IN_PROGRESS_LEASE).
+    pub fn with_lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// Store key for a client key, scoped to the caller. Hashed so arbitrary
+    /// client input never becomes a path segment.
+    fn record_key(ctx: &CommandContext, key: &str) -> String {
+        let caller = ctx
+            .caller_id
+            .as_ref()
+            .map(|c| serde_json::to_string(c).unwrap_or_default())
+            .unwrap_or_default();
+        sha256_hex(format!("{}:{}", caller, key).as_bytes())
+    }
+
+    /// The stored record, expired or not, with the bytes it was read from.
+    async fn load(
+        &self,
+        record_key: &str,
+    ) -> anyhow::Result<Option<(StoreValue, IdempotencyRecord)>> {
+        let store = self.store.read().await;
+        if !store
+            .container_exists(IDEMPOTENCY_CONTAINER)
+            .await
+            .context("Failed to check idempotency container")?
+        {
+            return Ok(None);
+        }
+
+        let Ok(bytes) = store.get(IDEMPOTENCY_CONTAINER, record_key).await else {
+            return Ok(None);
+        };
+
+        let record: IdempotencyRecord =
+            serde_json::from_slice(&bytes).context("Failed to parse idempotency record")?;
+
+        Ok(Some((bytes, record)))
+    }
+
+    /// Writes `record` if the stored record is still `expected`, so only one
+    /// of several servers retrying a key runs the request. Returns the bytes
+    /// written, or `None` when the record changed in the meantime.
+    async fn acquire(
+        &self,
+        record_key: &str,
+        expected: Option<&[u8]>,
+        record: &IdempotencyRecord,
+    ) -> anyhow::Result<Option<StoreValue>> {
+        let bytes = serde_json::to_vec(record)?;
+        let store = self.store.write().await;
+        store
+            .ensure_container(IDEMPOTENCY_CONTAINER)
+            .await
+            .context("Failed to create idempotency container")?;
+        let written = store
+            .compare_and_swap(IDEMPOTENCY_CONTAINER, record_key, expected, bytes.clone())
+            .await
+            .context("Failed to write idempotency record")?;
+        Ok(written.then_some(bytes))
+    }
+
+    /// Replaces the record this request holds, stored as `held`, with
+    /// `record`. Returns the bytes written, or `None` when another request
+    /// took the key over. A held record that a purge removed in passing is
+    /// written again.
+    async fn replace(
+        &self,
+        record_key: &str,
+        held: &[u8],
+        record: &IdempotencyRecord,
+    ) -> anyhow::Result<Option<StoreValue>> {
+        match self.acquire(record_key, Some(held), record).await? {
+            Some(bytes) => Ok(Some(bytes)),
+            None => self.acquire(record_key, None, record).await,
+        }
+    }
+
+    /// Runs `next` while renewing the lease of `record`, stored as `held`.
+    /// Returns the command's result and the bytes of the record still held,
+    /// if the request did not lose its key.
+    async fn run_leased(
+        &self,
+        ctx: &mut CommandContext,
+        next: Next<'_>,
+        record_key: &str,
+        mut record: IdempotencyRecord,
+        held: StoreValue,
+    ) -> (CommandResult, Option<StoreValue>) {
+        let activity_id = ctx.activity_id;
+        let mut held = Some(held);
+        let run = next.run(ctx);
+        tokio::pin!(run);
+
+        loop {
+            tokio::select! {
+                result = &mut run => return (result, held),
+                _ = tokio::time::sleep(self.lease / 3), if held.is_some() => {
+                    record.renewed_at = Some(chrono::Utc::now().timestamp_micros());
+                    let bytes = held.take().unwrap_or_default();
+                    held = match self.replace(record_key, &bytes, &record).await {
+                        Ok(Some(renewed)) => Some(renewed),
+                        Ok(None) => {
+                            tracing::warn!(
+                                activity_id = %activity_id,
+                                "Lost idempotency key to another request while running"
+                            );
+                            None
+                        }
+                        // Retried on the next tick.
+                        Err(e) => {
+                            tracing::warn!(
+                                activity_id = %activity_id,
+                                "Failed to renew idempotency lease: {}",
+                                e
+                            );
+                            Some(bytes)
+                        }
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// Deletes the idempotency records that expired, returning how many.
+pub(crate) async fn purge_expired(store: &dyn TransactionalKeyValueStore) -> anyhow::Result<usize> {
+    if !store
+        .container_exists(IDEMPOTENCY_CONTAINER)
+        .await
+        .context("Failed to check idempotency container")?
+    {
+        return Ok(0);
+    }
+
+    let now = chrono::Utc::now().timestamp_micros();
+    let mut purged = 0;
+    for key in store.list_keys(IDEMPOTENCY_CONTAINER, None).await? {
+        let Ok(bytes) = store.get(IDEMPOTENCY_CONTAINER, &key).await else {
+            continue;
+        };
+        let expired = serde_json::from_slice::<IdempotencyRecord>(&bytes)
+            .map_or(true, |record| record.expires_at <= now);
+        if expired {
+            store
+                .delete(IDEMPOTENCY_CONTAINER, &key)
+                .await
+                .context("Failed to delete idempotency record")?;
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+#[async_trait]
+impl PipelineStage for IdempotencyStage {
+    async fn handle(&self, ctx: &mut CommandContext, next: Next<'_>) -> CommandResult {
+        let Some(key) = ctx.metadata.get(IDEMPOTENCY_KEY_METADATA).cloned() else {
+            return next.run(ctx).await;
+        };
+        if !MUTATING_COMMANDS.contains(&ctx.command_name.as_str()) {
+            return next.run(ctx).await;
+        }
+
+        let record_key = Self::record_key(ctx, &key);
+        let fingerprint = fingerprint(ctx)?;
+
+        let stored = self.load(&record_key).await?;
+        let now = chrono::Utc::now().timestamp_micros();
+        let in_progress = || {
+            KuiperError::Conflict(format!(
+                "A request with idempotency key '{}' is still in progress",
+                key
+            ))
+        };
+
+        if let Some((_, existing)) = stored.as_ref().filter(|(_, r)| r.expires_at > now) {
+            if existing.fingerprint != fingerprint {
+                return Err(KuiperError::Invalid(format!(
+                    "Idempotency key '{}' was already used with a different request",
+                    key
+                ))
+                .into());
+            }
+
+            if existing.state == RecordState::Completed {
+                ctx.metadata
+                    .insert(IDEMPOTENT_REPLAY_METADATA.to_string(), "true".to_string());
+                return Ok(existing.response.clone());
+            }
+
+            let lease = self.lease.as_micros() as i64;
+            if existing.renewed_at.is_some_and(|t| t + lease > now) {
+                return Err(in_progress().into());
+            }
+            tracing::warn!(
+                activity_id = %ctx.activity_id,
+                "Taking over idempotency key '{}' from a request that did not finish",
+                key
+            );
+        }
+
+        let record = IdempotencyRecord {
+            fingerprint,
+            state: RecordState::InProgress,
+            response: None,
+            renewed_at: Some(now),
+            expires_at: now + self.ttl.as_micros() as i64,
+        };
+        let Some(held) = self
+            .acquire(
+                &record_key,
+                stored.as_ref().map(|(bytes, _)| bytes.as_slice()),
+                &record,
+            )
+            .await?
+        else {
+            return Err(in_progress().into());
+        };
+
+        let fingerprint = record.fingerprint.clone();
+        let (result, held) = self.run_leased(ctx, next, &record_key, record, held).await;
+        let Some(held) = held else {
+            tracing::warn!(
+                activity_id = %ctx.activity_id,
+                "Not recording the outcome of idempotency key '{}', which another request took over",
+                key
+            );
+            return result;
+        };
+
+        let now = chrono::Utc::now().timestamp_micros();
+        let record = match &result {
+            Ok(response) => IdempotencyRecord {
+                fingerprint,
+                state: RecordState::Completed,
+                response: response.clone(),
+                renewed_at: None,
+                expires_at: now + self.ttl.as_micros() as i64,
+            },
+            // Failed attempts are not remembered so the client can retry: the
+            // record expires at once and the next request takes it over.
+            Err(_) => IdempotencyRecord {
+                fingerprint,
+                state: RecordState::InProgress,
+                response: None,
+                renewed_at: None,
+                expires_at: now,
+            },
+        };
+        match self.replace(&record_key, &held, &record).await {
+            Ok(Some(_)) => {}
+            Ok(None) => tracing::warn!(
+                activity_id = %ctx.activity_id,
+                "Not recording the outcome of idempotency key '{}', which another request took over",
+                key
+            ),
+            Err(e) => tracing::warn!(
+                activity_id = %ctx.activity_id,
+                "Failed to store idempotent response: {}",
+                e
+            ),
+        }
+
+        result
+    }
+}
@@ -0,0 +1,335 @@
+//! Secondary indexes of stored objects.
+//!
+//! The labels of every object, and the fields its kind declares in
+//! `indexedFields`, are kept as keys of the `index` container:
+//!
+//! * `{namespace}/labels/{key}={value}/{resource}`
+//! * `{namespace}/fields/{field}={value}/{resource}`
+//!
+//! where `{resource}` is the object's path within the namespace, typically
+//! `{group}/{kind}/{name}`. Listing the keys under
+//! `{namespace}/labels/tier=gold/{group}/{kind}/` finds the objects of a kind
+//! with that label without reading the others.
+//!
+//! Every command that writes an object stages its index changes in the same
+//! transaction. `list` only trusts the indexes once they are known to be
+//! complete: in a store they were kept from the start, or after
+//! `rebuild_indexes` ran.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Context;
+use kuiper_runtime::data::{Transaction, TransactionalKeyValueStore};
+use kuiper_types::model::{
+    resource::SystemObject,
+    selector::{field_value, FieldSelector, LabelOperator, LabelSelector},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    constants::{RESOURCE_CONTAINER, SYSTEM_EXTENSION_GROUP},
+    registry::ResourceRegistry,
+};
+
+/// The key-value container holding the secondary indexes.
+pub const INDEX_CONTAINER: &str = "index";
+
+/// Present once the indexes hold an entry for every stored object.
+const READY_KEY: &str = ".ready";
+
+/// Index term of an object with label `key` set to `value`.
+pub(crate) fn label_term(key: &str, value: &str) -> String {
+    format!("labels/{}={}", escape(key), escape(value))
+}
+
+/// Index term of an object whose indexed `field` has `value`.
+pub(crate) fn field_term(field: &str, value: &str) -> String {
+    format!("fields/{}={}", escape(field), escape(value))
+}
+
+/// Terms `object` is indexed under, given the indexed fields of its kind.
+fn terms(object: &SystemObject, fields: &[String]) -> BTreeSet<String> {
+    let labels = object.metadata.labels.iter().flatten();
+    let mut terms: BTreeSet<String> = labels.map(|(k, v)| label_term(k, v)).collect();
+    for field in fields {
+        if let Some(value) = field_value(object, field) {
+            terms.insert(field_term(field, &value));
+        }
+    }
+    terms
+}
+
+fn entry_key(namespace: &str, term: &str, resource: &str) -> String {
+    format!("{}/{}/{}", namespace, term, resource)
+}
+
+/// Keys in terms may not contain the `/` that separates key segments, nor
+/// the `=` between name and value.
+fn escape(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('/', "%2F")
+        .replace('=', "%3D")
+}
+
+/// Stages the index changes of replacing `old` with `new` at
+/// `{namespace}/{resource}`; `None` for an object that did not or will no
+/// longer exist.
+pub(crate) fn stage_update(
+    tx: &mut Transaction<'_>,
+    namespace: &str,
+    resource: &str,
+    old: Option<&SystemObject>,
+    new: Option<&SystemObject>,
+    fields: &[String],
+) {
+    let old = old.map(|o| terms(o, fields)).unwrap_or_default();
+    let new = new.map(|o| terms(o, fields)).unwrap_or_default();
+    for term in old.difference(&new) {
+        tx.delete(
+            INDEX_CONTAINER.to_string(),
+            entry_key(namespace, term, resource),
+        );
+    }
+    for term in new.difference(&old) {
+        tx.put(
+            INDEX_CONTAINER.to_string(),
+            entry_key(namespace, term, resource),
+            resource.as_bytes().to_vec(),
+        );
+    }
+}
+
+/// Terms to look up for a list with these selectors: the values of the
+/// first `=` or `in` label requirement, else the value of the first `=`
+/// requirement on an indexed field. `None` when neither narrows the list.
+pub(crate) fn selector_terms(
+    labels: &LabelSelector,
+    field_selector: &FieldSelector,
+    fields: &[String],
+) -> Option<Vec<String>> {
+    labels
+        .requirements
+        .iter()
+        .find(|r| matches!(r.operator, LabelOperator::Equals | LabelOperator::In))
+        .map(|r| r.values.iter().map(|v| label_term(&r.key, v)).collect())
+        .or_else(|| {
+            field_selector
+                .requirements
+                .iter()
+                .find(|r| !r.negated && !r.value.is_empty() && fields.contains(&r.field))
+                .map(|r| vec![field_term(&r.field, &r.value)])
+        })
+}
+
+/// The indexed fields of the kind at `resource`, an object path or list
+/// prefix: `{group}/{kind}[/{name}]`, or `{group}/{version}/{kind}[/{name}]`
+/// for built-in kinds. Read before locking the store, which registry reloads
+/// lock after the registry.
+pub(crate) async fn indexed_fields(
+    registry: Option<&RwLock<ResourceRegistry>>,
+    resource: &str,
+) -> Vec<String> {
+    let Some(registry) = registry else {
+        return Vec::new();
+    };
+    let mut segments = resource.split('/');
+    let group = segments.next().unwrap_or_default();
+    if group.eq_ignore_ascii_case(SYSTEM_EXTENSION_GROUP) {
+        segments.next();
+    }
+    match segments.next() {
+        Some(kind) if !kind.is_empty() => registry.read().await.indexed_fields(group, kind),
+        _ => Vec::new(),
+    }
+}
+
+/// Creates the resource and index containers. Indexes created along with an
+/// empty resource container are complete from the start.
+pub(crate) async fn ensure_containers(
+    store: &dyn TransactionalKeyValueStore,
+) -> anyhow::Result<()> {
+    if !store
+        .container_exists(RESOURCE_CONTAINER)
+        .await
+        .context("Failed to check resource container")?
+    {
+        store
+            .new_container(RESOURCE_CONTAINER)
+            .await
+            .context("Failed to create resource container")?;
+        store
+            .ensure_container(INDEX_CONTAINER)
+            .await
+            .context("Failed to create index container")?;
+        store
+            .put(INDEX_CONTAINER, READY_KEY, Vec::new())
+            .await
+            .context("Failed to mark indexes ready")?;
+    }
+    store
+        .ensure_container(INDEX_CONTAINER)
+        .await
+        .context("Failed to create index container")
+}
+
+/// Whether the indexes hold every stored object.
+pub(crate) async fn is_ready(store: &dyn TransactionalKeyValueStore) -> bool {
+    store.get(INDEX_CONTAINER, READY_KEY).await.is_ok()
+}
+
+/// Storage keys of the objects in `namespace`, or in any namespace when
+/// `None`, under `resource_prefix` that are indexed under any of `terms`.
+pub(crate) async fn lookup(
+    store: &dyn TransactionalKeyValueStore,
+    namespace: Option<&str>,
+    terms: &[String],
+    resource_prefix: &str,
+) -> anyhow::Result<BTreeSet<String>> {
+    let Some(namespace) = namespace else {
+        return lookup_all(store, terms, resource_prefix).await;
+    };
+
+    let mut keys = BTreeSet::new();
+    for term in terms {
+        let prefix = entry_key(namespace, term, resource_prefix);
+        let entries = store
+            .list_keys(INDEX_CONTAINER, Some(&prefix))
+            .await
+            .context("Failed to list index entries")?;
+        let term_prefix = entry_key(namespace, term, "");
+        for entry in entries {
+            if let Some(resource) = entry.strip_prefix(&term_prefix) {
+                keys.insert(format!("{}/{}", namespace, resource));
+            }
+        }
+    }
+    Ok(keys)
+}
+
+/// [`lookup`] across namespaces, which reads every index entry.
+async fn lookup_all(
+    store: &dyn TransactionalKeyValueStore,
+    terms: &[String],
+    resource_prefix: &str,
+) -> anyhow::Result<BTreeSet<String>> {
+    let entries = store
+        .list_keys(INDEX_CONTAINER, None)
+        .await
+        .context("Failed to list index entries")?;
+
+    let mut keys = BTreeSet::new();
+    for entry in entries {
+        let mut parts = entry.splitn(4, '/');
+        let (Some(namespace), Some(section), Some(value), Some(resource)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        let term = format!("{}/{}", section, value);
+        if resource.starts_with(resource_prefix) && terms.contains(&term) {
+            keys.insert(format!("{}/{}", namespace, resource));
+        }
+    }
+    Ok(keys)
+}
+
+/// Counts of a rebuild.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RebuildStats {
+    /// Objects indexed.
+    pub objects: usize,
+
+    /// Index entries those objects have.
+    pub entries: usize,
+}
+
+/// Rewrites the index entries of every stored object, or only of objects of
+/// `{group}/{kind}` when `kind` is set, in one transaction. `fields_of`
+/// returns the indexed fields of an object's kind. Rebuilding everything
+/// marks the indexes ready.
+///
+/// The caller holds the store's write lock.
+pub(crate) async fn rebuild(
+    store: &dyn TransactionalKeyValueStore,
+    fields_of: impl Fn(&str, &str) -> Vec<String>,
+    kind: Option<(&str, &str)>,
+) -> anyhow::Result<RebuildStats> {
+    ensure_containers(store).await?;
+
+    let mut stats = RebuildStats::default();
+    let mut rebuilt = BTreeSet::new();
+    let mut wanted = BTreeMap::new();
+
+    let keys = store
+        .list_keys(RESOURCE_CONTAINER, None)
+        .await
+        .context("Failed to list resources")?;
+    for key in keys {
+        let Some((namespace, resource)) = key.split_once('/') else {
+            continue;
+        };
+        let Ok(bytes) = store.get(RESOURCE_CONTAINER, &key).await else {
+            continue;
+        };
+        let object = match serde_json::from_slice::<SystemObject>(&bytes) {
+            Ok(object) => object,
+            Err(e) => {
+                tracing::warn!("Not indexing unreadable {}: {}", key, e);
+                continue;
+            }
+        };
+        let group = object.api_version.split('/').next().unwrap_or_default();
+        if let Some((g, k)) = kind {
+            if !g.eq_ignore_ascii_case(group) || !k.eq_ignore_ascii_case(&object.kind) {
+                continue;
+            }
+        }
+
+        for term in terms(&object, &fields_of(group, &object.kind)) {
+            wanted.insert(entry_key(namespace, &term, resource), resource.to_string());
+        }
+        stats.objects += 1;
+        rebuilt.insert(key);
+    }
+    stats.entries = wanted.len();
+
+    let mut tx = Transaction::new(store);
+
+    // Drop the entries of rebuilt objects, and with a full rebuild those of
+    // objects that no longer exist, unless they are still wanted.
+    let existing: BTreeSet<String> = store
+        .list_keys(INDEX_CONTAINER, None)
+        .await
+        .context("Failed to list index entries")?
+        .into_iter()
+        .collect();
+    for entry in &existing {
+        let mut parts = entry.splitn(4, '/');
+        let (Some(namespace), Some(_), Some(_), Some(resource)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        let in_scope = kind.is_none() || rebuilt.contains(&format!("{}/{}", namespace, resource));
+        if in_scope && !wanted.contains_key(entry) {
+            tx.delete(INDEX_CONTAINER.to_string(), entry.clone());
+        }
+    }
+    for (key, resource) in wanted {
+        if !existing.contains(&key) {
+            tx.put(INDEX_CONTAINER.to_string(), key, resource.into_bytes());
+        }
+    }
+
+    if kind.is_none() {
+        tx.put(
+            INDEX_CONTAINER.to_string(),
+            READY_KEY.to_string(),
+            Vec::new(),
+        );
+    }
+
+    tx.commit().await.context("Failed to write indexes")?;
+    Ok(stats)
+}
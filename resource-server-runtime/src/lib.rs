@@ -1,39 +1,99 @@
+pub mod audit;
 pub mod constants;
+pub mod controller;
+pub mod conversion;
 pub mod handlers;
+pub mod idempotency;
+pub mod index;
 pub mod model;
+pub mod namespace;
 pub mod registry;
+pub mod services;
+pub mod telemetry;
 
 pub use registry::ResourceRegistry;
 
-use std::sync::Arc;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
+use audit::{AuditPolicy, AuditSink, AuditStage};
+use constants::{SYSTEM_API_VERSION, SYSTEM_EXTENSION_GROUP};
+use controller::{Controller, ControllerOptions, ControllerWatch, Reconciler, WorkQueue};
 use handlers::{
-    admission::AdmissionWebhookCommand, delete::DeleteCommand, echo::EchoCommand, get::GetCommand,
-    list::ListCommand, reconcile::ReconcileCommand, set::SetCommand,
-    validate::SchemaValidationCommand, version::VersionCommand, CommandExecutor,
+    admission::AdmissionWebhookCommand,
+    apply::ApplyCommand,
+    batch::BatchCommand,
+    commands::{CommandDescriptor, CommandsCommand},
+    delete::DeleteCommand,
+    discovery::DiscoveryCommand,
+    echo::EchoCommand,
+    finalize::FinalizeCommand,
+    get::GetCommand,
+    list::ListCommand,
+    patch::PatchCommand,
+    purge_audit_records::PurgeAuditRecordsCommand,
+    purge_idempotency_records::PurgeIdempotencyRecordsCommand,
+    rebuild_indexes::RebuildIndexesCommand,
+    reconcile::ReconcileCommand,
+    set::SetCommand,
+    set_status::SetStatusCommand,
+    validate::SchemaValidationCommand,
+    version::VersionCommand,
+    CommandExecutor,
 };
+use idempotency::IdempotencyStage;
 use kuiper_runtime::{
-    command::{CommandContext, CommandDispatcher, CommandHandler, CommandResult},
+    command::{
+        parse_response, CommandContext, CommandDispatcher, CommandHandler, CommandRequest,
+        CommandResult, PipelineStage,
+    },
     data::TransactionalKeyValueStore,
+    scheduler::{Schedule, ScheduledJob, Scheduler, SchedulerOptions},
     KuiperConfig,
 };
-use tokio::sync::RwLock;
+use services::{
+    GarbageCollector, GarbageCollectorOptions, GarbageCollectorTrigger, OutboxDeliveryService,
+    OutboxOptions, ScheduledCommandSource, StorageMigrationOptions, StorageMigrationTrigger,
+    StorageMigrator,
+};
+use tokio::sync::{Notify, RwLock};
 
 pub struct KuiperRuntimeBuilder {
     config: KuiperConfig,
     executor: CommandExecutor,
     registry: Arc<RwLock<ResourceRegistry>>,
     store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    /// Shared with the `SchemaValidationCommand` registered on `set`.
+    strict: Arc<AtomicBool>,
+    outbox: Option<(Arc<Notify>, OutboxOptions)>,
+    scheduler: Option<SchedulerOptions>,
+    /// Housekeeping jobs added to the scheduler, if there is one.
+    jobs: Vec<ScheduledJob>,
+    controllers: Vec<(ControllerOptions, Arc<dyn Reconciler>, Arc<WorkQueue>)>,
+    garbage_collector: Option<(Arc<Notify>, GarbageCollectorOptions)>,
+    storage_migration: Option<(Arc<Notify>, StorageMigrationOptions)>,
 }
 
 impl KuiperRuntimeBuilder {
     pub fn new(shared_store: Arc<RwLock<dyn TransactionalKeyValueStore>>) -> Self {
         let registry = Arc::new(RwLock::new(ResourceRegistry::new(shared_store.clone())));
+        let strict = Arc::new(AtomicBool::new(true));
 
         let mut executor = CommandExecutor::new();
         executor.register_handler("echo", Arc::new(EchoCommand));
         executor.register_handler("version", Arc::new(VersionCommand));
-        executor.register_handler("get", Arc::new(GetCommand::new(shared_store.clone())));
+        executor.register_handler(
+            "get",
+            Arc::new(GetCommand::new(
+                shared_store.clone(),
+                Some(registry.clone()),
+            )),
+        );
         executor.register_handler(
             "set",
             Arc::new(SetCommand::new(
@@ -43,16 +103,56 @@ impl KuiperRuntimeBuilder {
         );
         executor.register_handler(
             "set",
-            Arc::new(SchemaValidationCommand::new(registry.clone())),
+            Arc::new(SchemaValidationCommand::new(
+                registry.clone(),
+                strict.clone(),
+            )),
+        );
+        executor.register_handler(
+            "set_status",
+            Arc::new(SetStatusCommand::new(
+                shared_store.clone(),
+                registry.clone(),
+            )),
+        );
+        executor.register_handler(
+            "delete",
+            Arc::new(DeleteCommand::new(
+                shared_store.clone(),
+                Some(registry.clone()),
+            )),
+        );
+        executor.register_handler(
+            "list",
+            Arc::new(ListCommand::new(
+                shared_store.clone(),
+                Some(registry.clone()),
+            )),
+        );
+        executor.register_handler(
+            "finalize",
+            Arc::new(FinalizeCommand::new(
+                shared_store.clone(),
+                Some(registry.clone()),
+            )),
+        );
+        executor.register_handler(
+            "discovery",
+            Arc::new(DiscoveryCommand::new(registry.clone())),
         );
-        executor.register_handler("delete", Arc::new(DeleteCommand::new(shared_store.clone())));
-        executor.register_handler("list", Arc::new(ListCommand::new(shared_store.clone())));
 
         Self {
             config: KuiperConfig::default(),
             executor,
             registry,
             store: shared_store,
+            strict,
+            outbox: None,
+            scheduler: None,
+            jobs: Vec::new(),
+            controllers: Vec::new(),
+            garbage_collector: None,
+            storage_migration: None,
         }
     }
 
@@ -61,6 +161,66 @@ impl KuiperRuntimeBuilder {
         self
     }
 
+    /// Wraps every command in `stage`. Stages run in registration order.
+    pub fn register_stage(&mut self, stage: Arc<dyn PipelineStage>) -> &mut Self {
+        self.executor.register_stage(stage);
+        self
+    }
+
+    /// Turns strict mode, on by default, on or off. In strict mode external
+    /// writes of kinds, or versions, that are not registered and enabled are
+    /// rejected, as are writes outside the kind's scope.
+    pub fn with_strict_mode(&mut self, strict: bool) -> &mut Self {
+        self.strict.store(strict, Ordering::Relaxed);
+        self
+    }
+
+    /// Records mutating commands (`set`, `set_status`, `delete`, `finalize`) to `sinks`, at the level
+    /// `policy` selects for each group/kind.
+    ///
+    /// Records that outlived their sink's retention are deleted by the
+    /// `purge_audit_records` command, which the scheduler runs hourly when the
+    /// runtime has one.
+    pub fn with_audit(&mut self, policy: AuditPolicy, sinks: Vec<Arc<dyn AuditSink>>) -> &mut Self {
+        let stage = AuditStage::new(self.store.clone(), policy, sinks.clone());
+        self.executor.register_stage(Arc::new(stage));
+        self.executor.register_handler(
+            "purge_audit_records",
+            Arc::new(PurgeAuditRecordsCommand::new(sinks)),
+        );
+        self.jobs.push(ScheduledJob::new(
+            "purge-audit-records",
+            "purge_audit_records",
+            Schedule::every(Duration::from_secs(60 * 60)),
+        ));
+        self
+    }
+
+    /// Honours idempotency keys on mutating commands, keeping responses for
+    /// [`IdempotencyStage::DEFAULT_TTL`].
+    pub fn with_idempotency(&mut self) -> &mut Self {
+        self.with_idempotency_ttl(IdempotencyStage::DEFAULT_TTL)
+    }
+
+    /// Same as [`with_idempotency`](Self::with_idempotency) with a custom TTL.
+    ///
+    /// Expired records are deleted by the `purge_idempotency_records`
+    /// command, which the scheduler runs hourly when the runtime has one.
+    pub fn with_idempotency_ttl(&mut self, ttl: Duration) -> &mut Self {
+        let stage = IdempotencyStage::new(self.store.clone(), ttl);
+        self.executor.register_stage(Arc::new(stage));
+        self.executor.register_handler(
+            "purge_idempotency_records",
+            Arc::new(PurgeIdempotencyRecordsCommand::new(self.store.clone())),
+        );
+        self.jobs.push(ScheduledJob::new(
+            "purge-idempotency-records",
+            "purge_idempotency_records",
+            Schedule::every(Duration::from_secs(60 * 60)),
+        ));
+        self
+    }
+
     /// Registers the `reconcile` command. Call this for consumers that are
     /// permitted to run reconciliation (coordinator). Do **not** call this for
     /// the resource-server so that reconcile is not reachable via the HTTP/WebSocket API.
@@ -72,21 +232,194 @@ impl KuiperRuntimeBuilder {
         self
     }
 
-    /// Registers the admission webhook validator on both `set` and `delete`.
+    /// Registers the `rebuild_indexes` command, which rescans the whole store
+    /// with it locked. Call this only for runtimes whose callers are trusted
+    /// to run it, and keep it off the public HTTP/WebSocket API otherwise.
+    pub fn with_index_admin(&mut self) -> &mut Self {
+        self.executor.register_handler(
+            "rebuild_indexes",
+            Arc::new(RebuildIndexesCommand::new(
+                self.store.clone(),
+                self.registry.clone(),
+            )),
+        );
+        self
+    }
+
+    /// Registers the admission webhook validator on `set`, `set_status` and `delete`.
     /// Call this for any runtime that should enforce `AdmissionPolicy` rules
     /// (typically the resource-server).
     pub fn with_admission_webhooks(&mut self) -> &mut Self {
         let handler = Arc::new(AdmissionWebhookCommand::new(self.registry.clone()));
         self.executor.register_handler("set", handler.clone());
+        self.executor
+            .register_handler("set_status", handler.clone());
         self.executor.register_handler("delete", handler);
         self
     }
 
+    /// Delivers `Observer` handlers of `set` and `delete` from a durable
+    /// outbox written in the same store transaction as the change, instead of
+    /// running them inline. Start [`KuiperRuntime::outbox_service`] to deliver.
+    pub fn with_outbox(&mut self) -> &mut Self {
+        self.with_outbox_options(OutboxOptions::default())
+    }
+
+    /// Same as [`with_outbox`](Self::with_outbox) with custom retry settings.
+    pub fn with_outbox_options(&mut self, options: OutboxOptions) -> &mut Self {
+        let signal = Arc::new(Notify::new());
+        self.executor.enable_outbox(signal.clone());
+        self.outbox = Some((signal, options));
+        self
+    }
+
+    /// Creates a [`Scheduler`] that runs `ScheduledCommand` resources and any
+    /// jobs added in code. Start [`KuiperRuntime::scheduler`] to run them.
+    pub fn with_scheduler(&mut self) -> &mut Self {
+        self.with_scheduler_options(SchedulerOptions::default())
+    }
+
+    /// Same as [`with_scheduler`](Self::with_scheduler) with custom timing.
+    pub fn with_scheduler_options(&mut self, options: SchedulerOptions) -> &mut Self {
+        self.scheduler = Some(options);
+        self
+    }
+
+    /// Adds a [`Controller`] that runs `reconciler` for the group/kind in
+    /// `options`, and watches `set`, `set_status`, `delete` and `finalize` for it. Start each of
+    /// [`KuiperRuntime::controllers`] to reconcile.
+    pub fn with_controller(
+        &mut self,
+        options: ControllerOptions,
+        reconciler: Arc<dyn Reconciler>,
+    ) -> &mut Self {
+        let queue = Arc::new(WorkQueue::new(options.rate_limit.clone()));
+        let watch = Arc::new(ControllerWatch::new(options.clone(), queue.clone()));
+        self.executor.register_handler("set", watch.clone());
+        self.executor.register_handler("set_status", watch.clone());
+        self.executor.register_handler("delete", watch.clone());
+        self.executor.register_handler("finalize", watch);
+        self.controllers.push((options, reconciler, queue));
+        self
+    }
+
+    /// Creates a [`GarbageCollector`] that deletes or orphans the dependents
+    /// of deleted owners, and wakes it on every `delete` and `finalize`. Start
+    /// [`KuiperRuntime::garbage_collector`] to collect.
+    pub fn with_garbage_collector(&mut self) -> &mut Self {
+        self.with_garbage_collector_options(GarbageCollectorOptions::default())
+    }
+
+    /// Same as [`with_garbage_collector`](Self::with_garbage_collector) with a
+    /// custom interval.
+    pub fn with_garbage_collector_options(
+        &mut self,
+        options: GarbageCollectorOptions,
+    ) -> &mut Self {
+        let signal = Arc::new(Notify::new());
+        let trigger = Arc::new(GarbageCollectorTrigger::new(signal.clone()));
+        self.executor.register_handler("delete", trigger.clone());
+        self.executor.register_handler("finalize", trigger);
+        self.garbage_collector = Some((signal, options));
+        self
+    }
+
+    /// Creates a [`StorageMigrator`] that rewrites objects stored at other
+    /// than their kind's storage version, and wakes it whenever a
+    /// `ResourceDefinition` is set. Start [`KuiperRuntime::storage_migrator`]
+    /// to migrate.
+    pub fn with_storage_migration(&mut self) -> &mut Self {
+        self.with_storage_migration_options(StorageMigrationOptions::default())
+    }
+
+    /// Same as [`with_storage_migration`](Self::with_storage_migration) with
+    /// a custom interval.
+    pub fn with_storage_migration_options(
+        &mut self,
+        options: StorageMigrationOptions,
+    ) -> &mut Self {
+        let signal = Arc::new(Notify::new());
+        let trigger = Arc::new(StorageMigrationTrigger::new(signal.clone()));
+        self.executor.register_handler("set", trigger);
+        self.storage_migration = Some((signal, options));
+        self
+    }
+
     pub fn build(self) -> KuiperRuntime {
+        let store = self.store.clone();
+        let registry = self.registry.clone();
+        let mut executor = self.executor;
+
+        // `batch`, `patch`, `apply` and `commands` need the executor they are registered on.
+        let executor = Arc::new_cyclic(|weak| {
+            executor.register_handler(
+                "batch",
+                Arc::new(BatchCommand::new(
+                    weak.clone(),
+                    store.clone(),
+                    Some(registry.clone()),
+                )),
+            );
+            executor.register_handler(
+                "patch",
+                Arc::new(PatchCommand::new(
+                    weak.clone(),
+                    store.clone(),
+                    Some(registry.clone()),
+                )),
+            );
+            executor.register_handler(
+                "apply",
+                Arc::new(ApplyCommand::new(weak.clone(), store, Some(registry))),
+            );
+            executor.register_handler("commands", Arc::new(CommandsCommand::new(weak.clone())));
+            executor
+        });
+
+        let outbox = self.outbox.map(|(signal, options)| {
+            OutboxDeliveryService::new(self.store.clone(), executor.clone(), signal, options)
+        });
+
+        let scheduler = self.scheduler.map(|options| {
+            let source = ScheduledCommandSource::new(self.registry.clone());
+            Arc::new(
+                Scheduler::new(self.store.clone(), executor.clone(), options)
+                    .with_source(Arc::new(source)),
+            )
+        });
+
+        let controllers = self
+            .controllers
+            .into_iter()
+            .map(|(options, reconciler, queue)| {
+                Controller::new(
+                    self.store.clone(),
+                    executor.clone(),
+                    options,
+                    reconciler,
+                    queue,
+                )
+            })
+            .collect();
+
+        let garbage_collector = self.garbage_collector.map(|(signal, options)| {
+            GarbageCollector::new(self.store.clone(), executor.clone(), signal, options)
+        });
+
+        let storage_migrator = self.storage_migration.map(|(signal, options)| {
+            StorageMigrator::new(self.store.clone(), self.registry.clone(), signal, options)
+        });
+
         KuiperRuntime {
             config: self.config,
-            executor: Arc::new(self.executor),
+            executor,
             registry: self.registry,
+            outbox,
+            scheduler,
+            jobs: self.jobs,
+            controllers,
+            garbage_collector,
+            storage_migrator,
         }
     }
 }
@@ -95,13 +428,26 @@ pub struct KuiperRuntime {
     config: KuiperConfig,
     executor: Arc<CommandExecutor>,
     registry: Arc<RwLock<ResourceRegistry>>,
+    outbox: Option<Arc<OutboxDeliveryService>>,
+    scheduler: Option<Arc<Scheduler>>,
+    jobs: Vec<ScheduledJob>,
+    controllers: Vec<Arc<Controller>>,
+    garbage_collector: Option<Arc<GarbageCollector>>,
+    storage_migrator: Option<Arc<StorageMigrator>>,
 }
 
 impl KuiperRuntime {
-    /// Seeds the built-in core `ResourceDefinition` objects and loads all
-    /// persisted definitions into the in-memory registry.
+    /// Seeds the built-in core `ResourceDefinition` objects, loads all
+    /// persisted definitions into the in-memory registry and schedules the
+    /// housekeeping jobs of the enabled features.
     pub async fn initialize(&self) -> anyhow::Result<()> {
-        self.registry.write().await.initialize().await
+        self.registry.write().await.initialize().await?;
+        if let Some(scheduler) = &self.scheduler {
+            for job in &self.jobs {
+                scheduler.add_job(job.clone()).await?;
+            }
+        }
+        Ok(())
     }
 
     /// Returns a clone of the registry handle for external inspection.
@@ -109,7 +455,77 @@ impl KuiperRuntime {
         self.registry.clone()
     }
 
+    /// The resource path of `{group}/{kind}[/{name}]` as a REST route
+    /// addresses it, where `kind` may also be the plural or singular name or
+    /// a short name. Registered kinds are canonicalized to their kind, and
+    /// built-in kinds get the version their objects are stored under.
+    pub async fn resource_path(
+        &self,
+        group: &str,
+        kind: &str,
+        name: Option<&str>,
+    ) -> anyhow::Result<String> {
+        let registry = self.registry.read().await;
+        let kind = registry.resolve_kind(group, kind)?.unwrap_or(kind);
+        let mut path = if group.eq_ignore_ascii_case(SYSTEM_EXTENSION_GROUP) {
+            format!("{}/{}/{}", group, SYSTEM_API_VERSION, kind)
+        } else {
+            format!("{}/{}", group, kind)
+        };
+        if let Some(name) = name {
+            path = format!("{}/{}", path, name);
+        }
+        Ok(path)
+    }
+
+    /// Returns the outbox delivery service when the runtime was built with
+    /// [`KuiperRuntimeBuilder::with_outbox`]. The caller owns its lifecycle.
+    pub fn outbox_service(&self) -> Option<Arc<OutboxDeliveryService>> {
+        self.outbox.clone()
+    }
+
+    /// Returns the scheduler when the runtime was built with
+    /// [`KuiperRuntimeBuilder::with_scheduler`]. The caller owns its lifecycle.
+    pub fn scheduler(&self) -> Option<Arc<Scheduler>> {
+        self.scheduler.clone()
+    }
+
+    /// Returns the controllers added with
+    /// [`KuiperRuntimeBuilder::with_controller`]. The caller owns their
+    /// lifecycle.
+    pub fn controllers(&self) -> Vec<Arc<Controller>> {
+        self.controllers.clone()
+    }
+
+    /// Returns the garbage collector when the runtime was built with
+    /// [`KuiperRuntimeBuilder::with_garbage_collector`]. The caller owns its
+    /// lifecycle.
+    pub fn garbage_collector(&self) -> Option<Arc<GarbageCollector>> {
+        self.garbage_collector.clone()
+    }
+
+    /// Returns the storage migrator when the runtime was built with
+    /// [`KuiperRuntimeBuilder::with_storage_migration`]. The caller owns its
+    /// lifecycle.
+    pub fn storage_migrator(&self) -> Option<Arc<StorageMigrator>> {
+        self.storage_migrator.clone()
+    }
+
     pub async fn execute(&self, context: &mut CommandContext) -> CommandResult {
         self.executor.clone().dispatch(context).await
     }
+
+    /// Lists every registered command with its handler pipeline and input
+    /// schema. Served as the `commands` command and `GET /commands`.
+    pub fn commands(&self) -> Vec<CommandDescriptor> {
+        self.executor.describe()
+    }
+
+    /// Runs a typed command request, e.g. [`handlers::get::GetRequest`], and
+    /// returns its typed response.
+    pub async fn send<R: CommandRequest>(&self, request: &R) -> anyhow::Result<R::Response> {
+        let mut ctx = CommandContext::from_request(request)?;
+        let result = self.execute(&mut ctx).await?;
+        parse_response(result)
+    }
 }
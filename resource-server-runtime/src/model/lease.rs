@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use kuiper_types::model::resource::SystemObjectMetadata;
+
+// ── LeaseSpec ─────────────────────────────────────────────────────────────────
+
+/// Times are microseconds since the Unix epoch, like `metadata` timestamps.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LeaseSpec {
+    /// Identity of the current holder; `None` when the lease was released.
+    #[serde(rename = "holderIdentity", skip_serializing_if = "Option::is_none")]
+    pub holder_identity: Option<String>,
+
+    /// How long the lease stays valid after `renewTime`.
+    #[serde(rename = "leaseDurationSeconds")]
+    pub lease_duration_seconds: u64,
+
+    #[serde(rename = "acquireTime", skip_serializing_if = "Option::is_none")]
+    pub acquire_time: Option<i64>,
+
+    #[serde(rename = "renewTime", skip_serializing_if = "Option::is_none")]
+    pub renew_time: Option<i64>,
+
+    /// Incremented every time the lease changes holder.
+    #[serde(rename = "leaseTransitions", default)]
+    pub lease_transitions: u64,
+}
+
+impl LeaseSpec {
+    /// Whether the lease is held by someone and has not expired at `now`.
+    pub fn is_held(&self, now: i64) -> bool {
+        let expires = self
+            .renew_time
+            .map(|t| t + self.lease_duration_seconds as i64 * 1_000_000);
+        self.holder_identity.is_some() && expires.is_some_and(|e| e > now)
+    }
+}
+
+// ── Lease ─────────────────────────────────────────────────────────────────────
+
+/// A named lock held by one identity at a time, e.g. for leader election.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lease {
+    #[serde(rename = "apiVersion")]
+    pub api_version: String,
+
+    #[serde(rename = "kind")]
+    pub kind: String,
+
+    pub metadata: SystemObjectMetadata,
+
+    pub spec: LeaseSpec,
+}
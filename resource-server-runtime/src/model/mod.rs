@@ -1,3 +1,5 @@
 pub mod admission_policy;
+pub mod lease;
 pub mod resource_definition;
+pub mod scheduled_command;
 pub mod service_endpoint;
@@ -1,8 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
 
-use kuiper_types::model::resource::SystemObjectMetadata;
+use kuiper_types::{error::KuiperError, model::resource::SystemObjectMetadata};
 
 // ── ResourceScope ─────────────────────────────────────────────────────────────
 
@@ -36,11 +36,52 @@ pub struct ResourceDefinitionVersion {
     #[serde(default = "default_true")]
     pub enabled: bool,
 
+    /// Whether objects of the kind are stored at this version. At most one
+    /// version is the storage version; without one, objects are stored at
+    /// the version they were written at.
+    #[serde(default)]
+    pub storage: bool,
+
     /// Optional JSON Schema (OpenAPI v3 flavour) stored as a raw JSON value.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub schema: Option<serde_json::Value>,
+
+    /// Subresources served for objects of this version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subresources: Option<ResourceDefinitionSubresources>,
+
+    /// Dotted paths, such as `spec.host`, of fields kept in a secondary index
+    /// and usable in field selectors. Labels are always indexed.
+    #[serde(
+        rename = "indexedFields",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub indexed_fields: Option<Vec<String>>,
+}
+
+impl ResourceDefinitionVersion {
+    /// Whether `status` is written only through the `/status` subresource.
+    pub fn has_status_subresource(&self) -> bool {
+        self.subresources
+            .as_ref()
+            .is_some_and(|s| s.status.is_some())
+    }
 }
 
+/// Subresources of a `ResourceDefinitionVersion`, each enabled by being present.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceDefinitionSubresources {
+    /// `PUT .../{name}/status` updates only `status`, and a PUT of the object
+    /// itself leaves `status` as stored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<StatusSubresource>,
+}
+
+/// Enables the `/status` subresource. Has no settings yet: `"status": {}`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatusSubresource {}
+
 fn default_true() -> bool {
     true
 }
@@ -53,6 +94,66 @@ pub struct ResourceDefinitionSpec {
     pub names: ResourceDefinitionNames,
     pub scope: ResourceScope,
     pub versions: Vec<ResourceDefinitionVersion>,
+
+    /// How objects convert between versions. Without it, fields carry over
+    /// unchanged and only `apiVersion` is rewritten.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversion: Option<ResourceConversion>,
+}
+
+// ── ResourceConversion ────────────────────────────────────────────────────────
+
+/// How objects of a kind convert from one version to another. Writes are
+/// converted to the storage version, and reads to the version asked for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "strategy")]
+pub enum ResourceConversion {
+    /// Fields carry over unchanged; only `apiVersion` is rewritten.
+    None,
+
+    /// Fields move between versions as `mappings` declare. Versions that no
+    /// chain of mappings connects convert as with `None`.
+    FieldMapping { mappings: Vec<VersionMapping> },
+
+    /// The named `ServiceEndpoint` converts objects. It is sent
+    /// `{"desiredAPIVersion": "...", "objects": [...]}` and answers
+    /// `{"objects": [...]}`, in the same order; it cannot change `metadata`.
+    Webhook {
+        #[serde(rename = "serviceEndpoint")]
+        service_endpoint: String,
+
+        /// Appended to the endpoint's URL.
+        #[serde(default = "default_conversion_path")]
+        path: String,
+
+        /// Overrides the endpoint's timeout.
+        #[serde(
+            rename = "timeoutSeconds",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        timeout_seconds: Option<u32>,
+    },
+}
+
+fn default_conversion_path() -> String {
+    "/convert".to_string()
+}
+
+/// Field moves between two versions, applied as listed from `from` to `to`
+/// and reversed from `to` to `from`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionMapping {
+    pub from: String,
+    pub to: String,
+    pub fields: Vec<FieldMapping>,
+}
+
+/// Moves the value at the dotted path `from`, such as `spec.size`, to `to`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldMapping {
+    pub from: String,
+    pub to: String,
 }
 
 // ── ResourceDefinition ────────────────────────────────────────────────────────
@@ -77,6 +178,106 @@ impl ResourceDefinition {
         format!("{}/{}", self.spec.group, self.spec.names.kind).to_lowercase()
     }
 
+    /// The version objects are stored at, if one is designated.
+    pub fn storage_version(&self) -> Option<&str> {
+        self.spec
+            .versions
+            .iter()
+            .find(|v| v.storage)
+            .map(|v| v.name.as_str())
+    }
+
+    /// Checks the storage version and the conversion settings.
+    pub fn validate(&self) -> Result<(), KuiperError> {
+        let spec = &self.spec;
+        let name = format!("{}/{}", spec.group, spec.names.kind);
+
+        let storage: Vec<&ResourceDefinitionVersion> =
+            spec.versions.iter().filter(|v| v.storage).collect();
+        match storage.as_slice() {
+            [] => {}
+            [version] if !version.enabled => {
+                return Err(KuiperError::Invalid(format!(
+                    "Storage version '{}' of {} is disabled",
+                    version.name, name
+                )))
+            }
+            [_] => {}
+            _ => {
+                let names: Vec<&str> = storage.iter().map(|v| v.name.as_str()).collect();
+                return Err(KuiperError::Invalid(format!(
+                    "{} has more than one storage version: {}",
+                    name,
+                    names.join(", ")
+                )));
+            }
+        }
+
+        match &spec.conversion {
+            Some(ResourceConversion::FieldMapping { mappings }) => {
+                let is_version =
+                    |v: &str| spec.versions.iter().any(|d| d.name.eq_ignore_ascii_case(v));
+                for mapping in mappings {
+                    for version in [&mapping.from, &mapping.to] {
+                        if !is_version(version) {
+                            return Err(KuiperError::Invalid(format!(
+                                "Conversion mapping of {} names unknown version '{}'",
+                                name, version
+                            )));
+                        }
+                    }
+                    let mut paths = mapping.fields.iter().flat_map(|f| [&f.from, &f.to]);
+                    if let Some(path) = paths.find(|p| p.split('.').any(str::is_empty)) {
+                        return Err(KuiperError::Invalid(format!(
+                            "Conversion mapping of {} has invalid field path '{}'",
+                            name, path
+                        )));
+                    }
+                }
+
+                // Objects of a version no mapping leads to could only be
+                // relabelled, so every enabled version must be reachable from
+                // the storage version (without one, from the others).
+                let mut enabled = spec.versions.iter().filter(|v| v.enabled);
+                let start = storage.first().copied().or_else(|| enabled.clone().next());
+                if let Some(start) = start {
+                    let mut reached = HashSet::from([start.name.to_lowercase()]);
+                    let mut grew = true;
+                    while grew {
+                        grew = false;
+                        for mapping in mappings {
+                            let (from, to) =
+                                (mapping.from.to_lowercase(), mapping.to.to_lowercase());
+                            if reached.contains(&from) != reached.contains(&to) {
+                                reached.extend([from, to]);
+                                grew = true;
+                            }
+                        }
+                    }
+                    if let Some(version) =
+                        enabled.find(|v| !reached.contains(&v.name.to_lowercase()))
+                    {
+                        return Err(KuiperError::Invalid(format!(
+                            "Conversion mappings of {} do not lead from version '{}' to '{}'",
+                            name, start.name, version.name
+                        )));
+                    }
+                }
+            }
+            Some(ResourceConversion::Webhook {
+                service_endpoint, ..
+            }) if service_endpoint.is_empty() => {
+                return Err(KuiperError::Invalid(format!(
+                    "Conversion webhook of {} names no serviceEndpoint",
+                    name
+                )));
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
     /// Returns the enabled versions keyed by `{group}/{kind}/{version}` (lower-cased).
     pub fn enabled_versions(&self) -> HashMap<String, ResourceDefinitionVersion> {
         self.spec
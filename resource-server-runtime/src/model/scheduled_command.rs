@@ -0,0 +1,74 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use kuiper_runtime::scheduler::{Schedule, ScheduledJob};
+use kuiper_types::model::resource::SystemObjectMetadata;
+
+// ── ScheduledCommandSpec ──────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledCommandSpec {
+    /// Name of the command to dispatch (e.g. `reconcile`).
+    pub command: String,
+
+    /// A five-field cron expression (UTC), a macro such as `@hourly`, or a
+    /// fixed interval such as `@every 30s`.
+    pub schedule: String,
+
+    /// Namespace the command runs in, if it takes one.
+    #[serde(rename = "targetNamespace", skip_serializing_if = "Option::is_none")]
+    pub target_namespace: Option<String>,
+
+    /// Command parameters.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub parameters: HashMap<String, Value>,
+
+    /// Upper bound of a random delay added to every run.
+    #[serde(rename = "jitterSeconds", default)]
+    pub jitter_seconds: u64,
+
+    /// Start a run even when the previous one has not finished.
+    #[serde(rename = "allowOverlap", default)]
+    pub allow_overlap: bool,
+
+    /// Stop scheduling without deleting the resource.
+    #[serde(default)]
+    pub suspend: bool,
+}
+
+// ── ScheduledCommand ──────────────────────────────────────────────────────────
+
+/// Dispatches a command on a schedule, through the runtime's `Scheduler`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledCommand {
+    #[serde(rename = "apiVersion")]
+    pub api_version: String,
+
+    #[serde(rename = "kind")]
+    pub kind: String,
+
+    pub metadata: SystemObjectMetadata,
+
+    pub spec: ScheduledCommandSpec,
+}
+
+impl ScheduledCommand {
+    /// Name of the scheduler job backing this resource.
+    pub fn job_name(&self) -> String {
+        format!("scheduledcommand/{}", self.metadata.name)
+    }
+
+    pub fn to_job(&self) -> anyhow::Result<ScheduledJob> {
+        let schedule = Schedule::parse(&self.spec.schedule)?;
+
+        let mut job = ScheduledJob::new(&self.job_name(), &self.spec.command, schedule)
+            .with_jitter(Duration::from_secs(self.spec.jitter_seconds))
+            .with_overlap(self.spec.allow_overlap);
+        job.namespace = self.spec.target_namespace.clone();
+        job.parameters = self.spec.parameters.clone();
+
+        Ok(job)
+    }
+}
@@ -0,0 +1,80 @@
+//! Namespace lifecycle.
+//!
+//! A namespace other than `global` and `default` exists while a `Namespace`
+//! object of that name is stored in `global`. Objects can only be created in
+//! an existing namespace that is not being deleted. Deleting a `Namespace`
+//! holds it with the [`NAMESPACE_FINALIZER`] until the garbage collector has
+//! deleted everything in it.
+
+use kuiper_runtime::data::{TransactionalKeyValueStore, WriteBatch};
+use kuiper_types::{error::KuiperError, model::resource::SystemObject};
+
+use crate::constants::{
+    resource_key, DEFAULT_NAMESPACE, GLOBAL_NAMESPACE, RESOURCE_CONTAINER, SYSTEM_API_VERSION,
+    SYSTEM_EXTENSION_GROUP,
+};
+
+pub use kuiper_types::model::resource::NAMESPACE_FINALIZER;
+
+/// Kind of the objects that back namespaces: the only kind in the system
+/// group that external callers may write.
+pub const NAMESPACE_KIND: &str = "Namespace";
+
+/// Namespaces that exist without a `Namespace` object and cannot be deleted.
+pub fn is_builtin(namespace: &str) -> bool {
+    namespace.eq_ignore_ascii_case(GLOBAL_NAMESPACE)
+        || namespace.eq_ignore_ascii_case(DEFAULT_NAMESPACE)
+}
+
+/// Path of the `Namespace` object `name` within `global`.
+pub fn namespace_resource(name: &str) -> String {
+    format!(
+        "{}/{}/{}/{}",
+        SYSTEM_EXTENSION_GROUP, SYSTEM_API_VERSION, NAMESPACE_KIND, name
+    )
+}
+
+/// The name of the namespace whose `Namespace` object is stored at
+/// `{namespace}/{resource}`, if it is one.
+pub(crate) fn namespace_name<'a>(namespace: &str, resource: &'a str) -> Option<&'a str> {
+    if !namespace.eq_ignore_ascii_case(GLOBAL_NAMESPACE) {
+        return None;
+    }
+    let prefix = namespace_resource("");
+    let name = resource
+        .get(..prefix.len())
+        .filter(|p| p.eq_ignore_ascii_case(&prefix))
+        .map(|_| &resource[prefix.len()..])?;
+    (!name.is_empty() && !name.contains('/')).then_some(name)
+}
+
+/// Fails unless objects can be created in `namespace`: it is built in, or
+/// its `Namespace` object exists and is not being deleted.
+pub(crate) async fn ensure_active(
+    store: &dyn TransactionalKeyValueStore,
+    batch: Option<&WriteBatch>,
+    namespace: &str,
+) -> anyhow::Result<()> {
+    if is_builtin(namespace) {
+        return Ok(());
+    }
+
+    let key = resource_key(GLOBAL_NAMESPACE, Some(&namespace_resource(namespace)));
+    let stored = match batch {
+        Some(batch) => batch.get(store, RESOURCE_CONTAINER, &key).await,
+        None => store.get(RESOURCE_CONTAINER, &key).await.ok(),
+    };
+    let Some(bytes) = stored else {
+        return Err(KuiperError::NotFound(format!("Namespace '{}' not found", namespace)).into());
+    };
+
+    let object: SystemObject = serde_json::from_slice(&bytes)?;
+    if object.metadata.deletion_timestamp.is_some() {
+        return Err(KuiperError::Forbidden(format!(
+            "Namespace '{}' is being deleted; no new objects can be created in it",
+            namespace
+        ))
+        .into());
+    }
+    Ok(())
+}
@@ -16,6 +16,8 @@ pub(super) fn core_resource_definitions() -> Vec<ResourceDefinition> {
         namespace_definition(),
         service_endpoint_definition(),
         admission_policy_definition(),
+        scheduled_command_definition(),
+        lease_definition(),
     ]
 }
 
@@ -45,8 +47,12 @@ fn resource_definition_definition() -> ResourceDefinition {
             versions: vec![ResourceDefinitionVersion {
                 name: SYSTEM_API_VERSION.to_string(),
                 enabled: true,
+                storage: true,
                 schema: None,
+                subresources: None,
+                indexed_fields: None,
             }],
+            conversion: None,
         },
     }
 }
@@ -73,8 +79,12 @@ fn namespace_definition() -> ResourceDefinition {
             versions: vec![ResourceDefinitionVersion {
                 name: SYSTEM_API_VERSION.to_string(),
                 enabled: true,
+                storage: true,
                 schema: None,
+                subresources: None,
+                indexed_fields: None,
             }],
+            conversion: None,
         },
     }
 }
@@ -101,8 +111,12 @@ fn service_endpoint_definition() -> ResourceDefinition {
             versions: vec![ResourceDefinitionVersion {
                 name: SYSTEM_API_VERSION.to_string(),
                 enabled: true,
+                storage: true,
                 schema: None,
+                subresources: None,
+                indexed_fields: None,
             }],
+            conversion: None,
         },
     }
 }
@@ -129,8 +143,76 @@ fn admission_policy_definition() -> ResourceDefinition {
             versions: vec![ResourceDefinitionVersion {
                 name: SYSTEM_API_VERSION.to_string(),
                 enabled: true,
+                storage: true,
                 schema: None,
+                subresources: None,
+                indexed_fields: None,
             }],
+            conversion: None,
+        },
+    }
+}
+
+fn scheduled_command_definition() -> ResourceDefinition {
+    ResourceDefinition {
+        api_version: format!("{}/{}", SYSTEM_EXTENSION_GROUP, SYSTEM_API_VERSION),
+        kind: "ResourceDefinition".to_string(),
+        metadata: SystemObjectMetadata {
+            name: "scheduledcommands".to_string(),
+            namespace: Some(GLOBAL_NAMESPACE.to_string()),
+            uid: Uuid::parse_str("00000000-0000-0000-0000-000000000005").unwrap(),
+            ..Default::default()
+        },
+        spec: ResourceDefinitionSpec {
+            group: SYSTEM_EXTENSION_GROUP.to_string(),
+            scope: ResourceScope::System,
+            names: ResourceDefinitionNames {
+                kind: "ScheduledCommand".to_string(),
+                singular: "scheduledcommand".to_string(),
+                plural: "scheduledcommands".to_string(),
+                short_names: Some(vec!["sc".to_string()]),
+            },
+            versions: vec![ResourceDefinitionVersion {
+                name: SYSTEM_API_VERSION.to_string(),
+                enabled: true,
+                storage: true,
+                schema: None,
+                subresources: None,
+                indexed_fields: None,
+            }],
+            conversion: None,
+        },
+    }
+}
+
+fn lease_definition() -> ResourceDefinition {
+    ResourceDefinition {
+        api_version: format!("{}/{}", SYSTEM_EXTENSION_GROUP, SYSTEM_API_VERSION),
+        kind: "ResourceDefinition".to_string(),
+        metadata: SystemObjectMetadata {
+            name: "leases".to_string(),
+            namespace: Some(GLOBAL_NAMESPACE.to_string()),
+            uid: Uuid::parse_str("00000000-0000-0000-0000-000000000006").unwrap(),
+            ..Default::default()
+        },
+        spec: ResourceDefinitionSpec {
+            group: SYSTEM_EXTENSION_GROUP.to_string(),
+            scope: ResourceScope::System,
+            names: ResourceDefinitionNames {
+                kind: "Lease".to_string(),
+                singular: "lease".to_string(),
+                plural: "leases".to_string(),
+                short_names: None,
+            },
+            versions: vec![ResourceDefinitionVersion {
+                name: SYSTEM_API_VERSION.to_string(),
+                enabled: true,
+                storage: true,
+                schema: None,
+                subresources: None,
+                indexed_fields: None,
+            }],
+            conversion: None,
         },
     }
 }
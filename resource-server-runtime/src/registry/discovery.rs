@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+
+use kuiper_types::model::discovery::{ApiDiscovery, ApiGroup, ApiGroupVersion, ApiResource};
+
+use super::is_client_writable;
+use crate::model::resource_definition::{ResourceDefinition, ResourceScope};
+
+/// Verbs of kinds clients write.
+const VERBS: &[&str] = &["get", "list", "watch", "set", "patch", "apply", "delete"];
+
+/// Verbs of kinds in the system extension group, which only the server
+/// itself writes.
+const SYSTEM_VERBS: &[&str] = &["get", "list", "watch", "delete"];
+
+/// The discovery document of `definitions`, listing each enabled version.
+pub(super) fn document<'a>(
+    definitions: impl IntoIterator<Item = &'a ResourceDefinition>,
+) -> ApiDiscovery {
+    // group → version → kind → resource
+    let mut groups: BTreeMap<String, BTreeMap<String, BTreeMap<String, ApiResource>>> =
+        BTreeMap::new();
+
+    for definition in definitions {
+        let spec = &definition.spec;
+        let scope = match spec.scope {
+            ResourceScope::Namespace => "Namespace",
+            ResourceScope::System => "System",
+        };
+        let verbs = if is_client_writable(&spec.group, &spec.names.kind) {
+            VERBS
+        } else {
+            SYSTEM_VERBS
+        };
+
+        for version in spec.versions.iter().filter(|v| v.enabled) {
+            let mut subresources = Vec::new();
+            if version.has_status_subresource() {
+                subresources.push("status".to_string());
+            }
+            subresources.push("finalize".to_string());
+
+            let resource = ApiResource {
+                kind: spec.names.kind.clone(),
+                plural: spec.names.plural.clone(),
+                singular: spec.names.singular.clone(),
+                short_names: spec.names.short_names.clone().unwrap_or_default(),
+                scope: scope.to_string(),
+                verbs: verbs.iter().map(|v| v.to_string()).collect(),
+                subresources,
+            };
+            groups
+                .entry(spec.group.clone())
+                .or_default()
+                .entry(version.name.clone())
+                .or_default()
+                .insert(spec.names.kind.clone(), resource);
+        }
+    }
+
+    ApiDiscovery {
+        groups: groups
+            .into_iter()
+            .map(|(name, versions)| ApiGroup {
+                name,
+                versions: versions
+                    .into_iter()
+                    .map(|(version, resources)| ApiGroupVersion {
+                        version,
+                        resources: resources.into_values().collect(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
@@ -1,22 +1,27 @@
 mod core;
+mod discovery;
+mod openapi;
 
 pub use core::RESERVED_UID_PREFIX;
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 
 use crate::model::{
     admission_policy::AdmissionPolicy,
     resource_definition::{ResourceDefinition, ResourceDefinitionVersion},
+    scheduled_command::ScheduledCommand,
     service_endpoint::ServiceEndpoint,
 };
 use anyhow::Context;
 use kuiper_runtime::data::TransactionalKeyValueStore;
+use kuiper_types::{error::KuiperError, model::discovery::ApiDiscovery};
 use tokio::sync::RwLock;
 
 use crate::constants::{
     resource_key, GLOBAL_NAMESPACE, RESOURCE_CONTAINER, SYSTEM_API_VERSION, SYSTEM_EXTENSION_GROUP,
 };
+use crate::{index, namespace};
 
 /// The resource-path prefix used to list / store all `ResourceDefinition` objects.
 fn definition_resource_path(name: &str) -> String {
@@ -26,6 +31,14 @@ fn definition_resource_path(name: &str) -> String {
     )
 }
 
+/// Whether callers outside the server may write objects of `kind`: the system
+/// extension group is reserved, except for the `Namespace` objects clients
+/// create namespaces with.
+pub(crate) fn is_client_writable(group: &str, kind: &str) -> bool {
+    !group.eq_ignore_ascii_case(SYSTEM_EXTENSION_GROUP)
+        || kind.eq_ignore_ascii_case(namespace::NAMESPACE_KIND)
+}
+
 fn definition_list_prefix() -> String {
     resource_key(
         GLOBAL_NAMESPACE,
@@ -46,6 +59,12 @@ pub struct ResourceRegistry {
 
     /// `{group}/{kind}/{version}` → `ResourceDefinitionVersion`
     resource_versions: HashMap<String, ResourceDefinitionVersion>,
+
+    /// Generated from `resources` whenever definitions are loaded.
+    discovery: Arc<ApiDiscovery>,
+
+    /// The OpenAPI 3 document, generated along with `discovery`.
+    openapi: Arc<serde_json::Value>,
 }
 
 impl ResourceRegistry {
@@ -54,6 +73,8 @@ impl ResourceRegistry {
             store,
             resources: HashMap::new(),
             resource_versions: HashMap::new(),
+            discovery: Arc::default(),
+            openapi: Arc::default(),
         }
     }
 
@@ -64,6 +85,45 @@ impl ResourceRegistry {
             .get(&format!("{}/{}", group, kind).to_lowercase())
     }
 
+    /// The kind of `group` that `name` refers to: a kind or its plural or
+    /// singular name, else one of its short names, in any case. `None` when
+    /// no definition in the group declares `name`; `Invalid` when several do.
+    pub fn resolve_kind(&self, group: &str, name: &str) -> Result<Option<&str>, KuiperError> {
+        let definitions: Vec<&ResourceDefinition> = self
+            .resources
+            .values()
+            .filter(|d| d.spec.group.eq_ignore_ascii_case(group))
+            .collect();
+        let is_name = |s: &String| s.eq_ignore_ascii_case(name);
+
+        let mut kinds: Vec<&str> = definitions
+            .iter()
+            .map(|d| &d.spec.names)
+            .filter(|n| is_name(&n.kind) || is_name(&n.plural) || is_name(&n.singular))
+            .map(|n| n.kind.as_str())
+            .collect();
+        if kinds.is_empty() {
+            kinds = definitions
+                .iter()
+                .map(|d| &d.spec.names)
+                .filter(|n| n.short_names.iter().flatten().any(is_name))
+                .map(|n| n.kind.as_str())
+                .collect();
+        }
+
+        kinds.sort_unstable();
+        match kinds.as_slice() {
+            [] => Ok(None),
+            [kind] => Ok(Some(kind)),
+            _ => Err(KuiperError::Invalid(format!(
+                "'{}' is ambiguous in group '{}': it names {}",
+                name,
+                group,
+                kinds.join(", ")
+            ))),
+        }
+    }
+
     pub fn version_exists(&self, group: &str, kind: &str, version: &str) -> bool {
         self.resource_versions
             .contains_key(&format!("{}/{}/{}", group, kind, version).to_lowercase())
@@ -79,6 +139,43 @@ impl ResourceRegistry {
             .get(&format!("{}/{}/{}", group, kind, version).to_lowercase())
     }
 
+    /// Whether the version of `{group}/{kind}` serves the `/status`
+    /// subresource, so `status` is written only through `set_status`.
+    pub fn has_status_subresource(&self, group: &str, kind: &str, version: &str) -> bool {
+        self.get_version(group, kind, version)
+            .is_some_and(|v| v.has_status_subresource())
+    }
+
+    /// The discovery document of the registered kinds, current as of the
+    /// last `initialize` or `reload`.
+    pub fn discovery(&self) -> Arc<ApiDiscovery> {
+        self.discovery.clone()
+    }
+
+    /// The OpenAPI 3 document of the REST API for the registered kinds,
+    /// current as of the last `initialize` or `reload`.
+    pub fn openapi(&self) -> Arc<serde_json::Value> {
+        self.openapi.clone()
+    }
+
+    /// Fields indexed for `{group}/{kind}`: those any enabled version
+    /// declares, so every object of the kind is indexed alike.
+    pub fn indexed_fields(&self, group: &str, kind: &str) -> Vec<String> {
+        let Some(definition) = self.get_definition(group, kind) else {
+            return Vec::new();
+        };
+        let mut fields: Vec<String> = definition
+            .spec
+            .versions
+            .iter()
+            .filter(|v| v.enabled)
+            .flat_map(|v| v.indexed_fields.iter().flatten().cloned())
+            .collect();
+        fields.sort();
+        fields.dedup();
+        fields
+    }
+
     // ── Extension-type store lookups ──────────────────────────────────────────
 
     /// Retrieves a `ServiceEndpoint` by name from the store.
@@ -130,6 +227,33 @@ impl ResourceRegistry {
         Ok(matching)
     }
 
+    /// Lists all `ScheduledCommand` objects.
+    pub async fn get_scheduled_commands(&self) -> anyhow::Result<Vec<ScheduledCommand>> {
+        let prefix = resource_key(
+            GLOBAL_NAMESPACE,
+            Some(&format!(
+                "{}/{}/ScheduledCommand/",
+                SYSTEM_EXTENSION_GROUP, SYSTEM_API_VERSION
+            )),
+        );
+        let store = self.store.read().await;
+        let keys = store
+            .list_keys(RESOURCE_CONTAINER, Some(&prefix))
+            .await
+            .context("Failed to list ScheduledCommand keys")?;
+
+        let mut commands = Vec::new();
+        for key in &keys {
+            if let Ok(bytes) = store.get(RESOURCE_CONTAINER, key).await {
+                match serde_json::from_slice::<ScheduledCommand>(&bytes) {
+                    Ok(command) => commands.push(command),
+                    Err(e) => tracing::warn!("Skipping unreadable ScheduledCommand {}: {}", key, e),
+                }
+            }
+        }
+        Ok(commands)
+    }
+
     // ── Lifecycle ─────────────────────────────────────────────────────────────
 
     /// Seeds core definitions and loads all persisted definitions.
@@ -145,12 +269,45 @@ impl ResourceRegistry {
         self.load_from_store().await
     }
 
-    /// Re-reads all persisted definitions from the store.
+    /// Re-reads all persisted definitions from the store, re-indexing the
+    /// objects of kinds whose `indexedFields` changed.
     /// Called after every successful `set` of a `ResourceDefinition`.
     pub async fn reload(&mut self) -> anyhow::Result<()> {
+        let before = self.indexed_fields_by_kind();
         self.resources.clear();
         self.resource_versions.clear();
-        self.load_from_store().await
+        self.load_from_store().await?;
+        let after = self.indexed_fields_by_kind();
+
+        let changed: BTreeSet<&String> = before
+            .keys()
+            .chain(after.keys())
+            .filter(|k| {
+                before.get(*k).unwrap_or(&Vec::new()) != after.get(*k).unwrap_or(&Vec::new())
+            })
+            .collect();
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        let store = self.store.write().await;
+        for key in changed {
+            let Some((group, kind)) = key.split_once('/') else {
+                continue;
+            };
+            let stats = index::rebuild(
+                &*store,
+                |g, k| self.indexed_fields(g, k),
+                Some((group, kind)),
+            )
+            .await?;
+            tracing::info!(
+                "Re-indexed {} objects of {} after its indexed fields changed",
+                stats.objects,
+                key
+            );
+        }
+        Ok(())
     }
 
     // ── Private helpers ───────────────────────────────────────────────────────
@@ -158,17 +315,7 @@ impl ResourceRegistry {
     /// Writes `def` to the store only if the key does not already exist.
     async fn persist_if_absent(&self, def: &ResourceDefinition) -> anyhow::Result<()> {
         let store = self.store.write().await;
-
-        if !store
-            .container_exists(RESOURCE_CONTAINER)
-            .await
-            .context("Failed to check resource container")?
-        {
-            store
-                .new_container(RESOURCE_CONTAINER)
-                .await
-                .context("Failed to create resource container")?;
-        }
+        index::ensure_containers(&*store).await?;
 
         let key = resource_key(
             GLOBAL_NAMESPACE,
@@ -202,8 +349,9 @@ impl ResourceRegistry {
         Ok(())
     }
 
-    /// Scans the store for all keys under the `ResourceDefinition` prefix and
-    /// indexes every parsed definition.
+    /// Scans the store for all keys under the `ResourceDefinition` prefix,
+    /// indexes every parsed definition and regenerates the discovery and
+    /// OpenAPI documents.
     async fn load_from_store(&mut self) -> anyhow::Result<()> {
         let prefix = definition_list_prefix();
 
@@ -230,9 +378,22 @@ impl ResourceRegistry {
             }
         }
 
+        self.discovery = Arc::new(discovery::document(self.resources.values()));
+        self.openapi = Arc::new(openapi::document(self.resources.values()));
         Ok(())
     }
 
+    /// `{group}/{kind}` → indexed fields, for every registered kind.
+    fn indexed_fields_by_kind(&self) -> HashMap<String, Vec<String>> {
+        self.resources
+            .iter()
+            .map(|(key, def)| {
+                let fields = self.indexed_fields(&def.spec.group, &def.spec.names.kind);
+                (key.clone(), fields)
+            })
+            .collect()
+    }
+
     fn index_definition(&mut self, def: ResourceDefinition) {
         for (k, v) in def.enabled_versions() {
             self.resource_versions.insert(k, v);
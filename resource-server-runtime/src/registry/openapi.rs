@@ -0,0 +1,530 @@
+use std::collections::BTreeMap;
+
+use kuiper_types::model::openapi::{component_schemas, schema_ref};
+use serde_json::{json, Map, Value};
+
+use super::is_client_writable;
+use crate::{
+    constants::GLOBAL_NAMESPACE,
+    model::resource_definition::{ResourceDefinition, ResourceScope},
+};
+
+/// The OpenAPI 3 document of the REST API serving `definitions`: the fixed
+/// routes, and the collection, object and subresource routes of every kind,
+/// addressed by its plural name. Objects are described by their version's
+/// `schema` for `spec` and the shared metadata model for the rest.
+pub(super) fn document<'a>(definitions: impl IntoIterator<Item = &'a ResourceDefinition>) -> Value {
+    let mut schemas = component_schemas();
+    let mut paths: BTreeMap<String, Value> = fixed_paths();
+
+    for definition in definitions {
+        let spec = &definition.spec;
+        let versions: Vec<_> = spec.versions.iter().filter(|v| v.enabled).collect();
+        if versions.is_empty() {
+            continue;
+        }
+
+        let mut refs = Vec::new();
+        for version in &versions {
+            let name = format!("{}.{}.{}", spec.group, version.name, spec.names.kind);
+            let spec_schema = version
+                .schema
+                .clone()
+                .unwrap_or_else(|| json!({ "type": "object" }));
+            schemas.insert(
+                name.clone(),
+                json!({
+                    "type": "object",
+                    "required": ["apiVersion", "kind", "metadata"],
+                    "properties": {
+                        "apiVersion": {
+                            "type": "string",
+                            "enum": [format!("{}/{}", spec.group, version.name)]
+                        },
+                        "kind": { "type": "string", "enum": [spec.names.kind] },
+                        "metadata": schema_ref("ObjectMeta"),
+                        "spec": spec_schema,
+                        "status": schema_ref("ObjectStatus")
+                    },
+                    "additionalProperties": true
+                }),
+            );
+            refs.push(schema_ref(&name));
+        }
+        let object = match refs.as_slice() {
+            [one] => one.clone(),
+            _ => json!({ "oneOf": refs }),
+        };
+
+        let kind = Kind {
+            group: &spec.group,
+            kind: &spec.names.kind,
+            object,
+            writable: is_client_writable(&spec.group, &spec.names.kind),
+        };
+        let collection = match spec.scope {
+            ResourceScope::Namespace => {
+                format!("/api/{}/{{namespace}}/{}", spec.group, spec.names.plural)
+            }
+            ResourceScope::System => {
+                format!(
+                    "/api/{}/{}/{}",
+                    spec.group, GLOBAL_NAMESPACE, spec.names.plural
+                )
+            }
+        };
+        let item = format!("{}/{{name}}", collection);
+
+        paths.insert(collection, kind.collection_path());
+        paths.insert(item.clone(), kind.item_path());
+        if kind.writable && versions.iter().any(|v| v.has_status_subresource()) {
+            paths.insert(format!("{}/status", item), kind.status_path());
+        }
+        paths.insert(format!("{}/finalize", item), kind.finalize_path());
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Kuiper resource server",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "parameters": parameters(),
+            "responses": {
+                "Error": {
+                    "description": "The request failed.",
+                    "content": { "text/plain": { "schema": { "type": "string" } } }
+                }
+            }
+        }
+    })
+}
+
+/// One kind's part of the document.
+struct Kind<'a> {
+    group: &'a str,
+    kind: &'a str,
+    /// Schema of an object of any served version.
+    object: Value,
+    /// Whether clients may write objects; other kinds of the system
+    /// extension group are only read and deleted.
+    writable: bool,
+}
+
+impl Kind<'_> {
+    fn operation_id(&self, verb: &str) -> String {
+        let group: String = self
+            .group
+            .split(['.', '-'])
+            .map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+                    .unwrap_or_default()
+            })
+            .collect();
+        format!("{}{}{}", verb, group, self.kind)
+    }
+
+    /// Parameters of the routes below the collection.
+    fn parameters(&self, name: bool) -> Value {
+        let mut parameters = Vec::new();
+        if self.writable {
+            parameters.push(parameter_ref("namespace"));
+        }
+        if name {
+            parameters.push(parameter_ref("name"));
+        }
+        Value::Array(parameters)
+    }
+
+    fn object_response(&self, description: &str) -> Value {
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": self.object } }
+        })
+    }
+
+    fn collection_path(&self) -> Value {
+        json!({
+            "parameters": self.parameters(false),
+            "get": {
+                "operationId": self.operation_id("list"),
+                "tags": [self.group],
+                "summary": format!("Lists {} objects.", self.kind),
+                "parameters": [
+                    parameter_ref("labelSelector"),
+                    parameter_ref("fieldSelector"),
+                    parameter_ref("version")
+                ],
+                "responses": {
+                    "200": {
+                        "description": "The matching objects.",
+                        "content": {
+                            "application/json": {
+                                "schema": { "type": "array", "items": self.object }
+                            }
+                        }
+                    },
+                    "default": response_ref("Error")
+                }
+            }
+        })
+    }
+
+    fn item_path(&self) -> Value {
+        let mut path = json!({
+            "parameters": self.parameters(true),
+            "get": {
+                "operationId": self.operation_id("get"),
+                "tags": [self.group],
+                "summary": format!("Reads a {}.", self.kind),
+                "parameters": [parameter_ref("version")],
+                "responses": {
+                    "200": self.object_response("The object."),
+                    "default": response_ref("Error")
+                }
+            },
+            "delete": {
+                "operationId": self.operation_id("delete"),
+                "tags": [self.group],
+                "summary": format!("Deletes a {}.", self.kind),
+                "parameters": [
+                    parameter_ref("propagationPolicy"),
+                    parameter_ref("Idempotency-Key")
+                ],
+                "responses": {
+                    "202": self.object_response("The object, held by its finalizers."),
+                    "204": { "description": "The object was deleted." },
+                    "default": response_ref("Error")
+                }
+            }
+        });
+        if !self.writable {
+            return path;
+        }
+
+        path["put"] = json!({
+            "operationId": self.operation_id("set"),
+            "tags": [self.group],
+            "summary": format!("Creates or replaces a {}.", self.kind),
+            "parameters": [parameter_ref("Idempotency-Key")],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": self.object } }
+            },
+            "responses": {
+                "200": self.object_response("The stored object."),
+                "default": response_ref("Error")
+            }
+        });
+        path["patch"] = json!({
+            "operationId": self.operation_id("patch"),
+            "tags": [self.group],
+            "summary": format!(
+                "Patches a {}, or applies a configuration of it server-side.",
+                self.kind
+            ),
+            "parameters": [
+                parameter_ref("resourceVersion"),
+                parameter_ref("version"),
+                parameter_ref("fieldManager"),
+                parameter_ref("force")
+            ],
+            "requestBody": {
+                "required": true,
+                "content": {
+                    "application/merge-patch+json": { "schema": { "type": "object" } },
+                    "application/json-patch+json": {
+                        "schema": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "required": ["op", "path"],
+                                "properties": {
+                                    "op": {
+                                        "type": "string",
+                                        "enum": ["add", "remove", "replace", "move", "copy", "test"]
+                                    },
+                                    "path": { "type": "string" },
+                                    "from": { "type": "string" },
+                                    "value": {}
+                                }
+                            }
+                        }
+                    },
+                    "application/apply-patch+json": { "schema": self.object }
+                }
+            },
+            "responses": {
+                "200": self.object_response("The stored object."),
+                "default": response_ref("Error")
+            }
+        });
+        path
+    }
+
+    fn status_path(&self) -> Value {
+        json!({
+            "parameters": self.parameters(true),
+            "put": {
+                "operationId": self.operation_id("setStatus"),
+                "tags": [self.group],
+                "summary": format!("Replaces the status of a {}.", self.kind),
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": self.object } }
+                },
+                "responses": {
+                    "200": self.object_response("The stored object."),
+                    "default": response_ref("Error")
+                }
+            }
+        })
+    }
+
+    fn finalize_path(&self) -> Value {
+        json!({
+            "parameters": self.parameters(true),
+            "post": {
+                "operationId": self.operation_id("finalize"),
+                "tags": [self.group],
+                "summary": format!("Adds and removes finalizers of a {}.", self.kind),
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/json": {
+                            "schema": {
+                                "type": "object",
+                                "properties": {
+                                    "add": { "type": "array", "items": { "type": "string" } },
+                                    "remove": { "type": "array", "items": { "type": "string" } }
+                                }
+                            }
+                        }
+                    }
+                },
+                "responses": {
+                    "200": self.object_response("The stored object."),
+                    "204": { "description": "The object was deleted with its last finalizer." },
+                    "default": response_ref("Error")
+                }
+            }
+        })
+    }
+}
+
+fn parameter_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/parameters/{}", name) })
+}
+
+fn response_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/responses/{}", name) })
+}
+
+fn parameters() -> Map<String, Value> {
+    let query = |name: &str, schema: Value, description: &str| {
+        json!({
+            "name": name,
+            "in": "query",
+            "required": false,
+            "schema": schema,
+            "description": description
+        })
+    };
+    let string = json!({ "type": "string" });
+
+    let mut parameters = Map::new();
+    parameters.insert(
+        "namespace".to_string(),
+        json!({
+            "name": "namespace",
+            "in": "path",
+            "required": true,
+            "schema": string,
+            "description": "The object's namespace; `*` lists across namespaces."
+        }),
+    );
+    parameters.insert(
+        "name".to_string(),
+        json!({ "name": "name", "in": "path", "required": true, "schema": string }),
+    );
+    parameters.insert(
+        "labelSelector".to_string(),
+        query(
+            "labelSelector",
+            string.clone(),
+            "E.g. `tier=gold,env in (dev,test)`.",
+        ),
+    );
+    parameters.insert(
+        "fieldSelector".to_string(),
+        query("fieldSelector", string.clone(), "E.g. `metadata.name=web`."),
+    );
+    parameters.insert(
+        "version".to_string(),
+        query(
+            "version",
+            string.clone(),
+            "The version of the kind to read at, or that a patch is written against; \
+             defaults to the storage version.",
+        ),
+    );
+    parameters.insert(
+        "propagationPolicy".to_string(),
+        query(
+            "propagationPolicy",
+            json!({ "type": "string", "enum": ["Background", "Foreground", "Orphan"] }),
+            "What happens to the object's dependents.",
+        ),
+    );
+    parameters.insert(
+        "resourceVersion".to_string(),
+        query(
+            "resourceVersion",
+            string.clone(),
+            "Only patch the object at this version.",
+        ),
+    );
+    parameters.insert(
+        "fieldManager".to_string(),
+        query(
+            "fieldManager",
+            string.clone(),
+            "The applier; required with `application/apply-patch+json`.",
+        ),
+    );
+    parameters.insert(
+        "force".to_string(),
+        query(
+            "force",
+            json!({ "type": "boolean" }),
+            "Take over fields other managers own.",
+        ),
+    );
+    parameters.insert(
+        "Idempotency-Key".to_string(),
+        json!({
+            "name": "Idempotency-Key",
+            "in": "header",
+            "required": false,
+            "schema": string,
+            "description": "Replays the first response when a request is retried."
+        }),
+    );
+    parameters
+}
+
+/// Routes that do not depend on the registered kinds.
+fn fixed_paths() -> BTreeMap<String, Value> {
+    let get = |id: &str, summary: &str, schema: Value| {
+        json!({
+            "get": {
+                "operationId": id,
+                "summary": summary,
+                "responses": {
+                    "200": {
+                        "description": "OK",
+                        "content": { "application/json": { "schema": schema } }
+                    },
+                    "default": response_ref("Error")
+                }
+            }
+        })
+    };
+    let object = json!({ "type": "object" });
+
+    let mut paths = BTreeMap::new();
+    paths.insert(
+        "/version".to_string(),
+        get(
+            "getVersion",
+            "Reads the server version.",
+            json!({ "type": "object", "properties": { "version": { "type": "string" } } }),
+        ),
+    );
+    paths.insert(
+        "/health".to_string(),
+        get(
+            "getHealth",
+            "Reports the state of each hosted service.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "ready": { "type": "boolean" },
+                    "services": { "type": "array", "items": object }
+                }
+            }),
+        ),
+    );
+    paths.insert(
+        "/commands".to_string(),
+        get(
+            "listCommands",
+            "Lists the registered commands.",
+            json!({ "type": "array", "items": object }),
+        ),
+    );
+    let mut command = get("getCommand", "Describes a command.", object.clone());
+    command["parameters"] = json!([parameter_ref("name")]);
+    paths.insert("/commands/{name}".to_string(), command);
+    paths.insert(
+        "/apis".to_string(),
+        get(
+            "getDiscovery",
+            "Describes the registered groups, versions and kinds.",
+            schema_ref("ApiDiscovery"),
+        ),
+    );
+    paths.insert(
+        "/openapi/v3".to_string(),
+        get("getOpenApi", "Reads this document.", object.clone()),
+    );
+    paths.insert(
+        "/api/batch".to_string(),
+        json!({
+            "post": {
+                "operationId": "batch",
+                "summary": "Applies set and delete operations in one transaction.",
+                "parameters": [parameter_ref("Idempotency-Key")],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/json": {
+                            "schema": {
+                                "type": "object",
+                                "required": ["operations"],
+                                "properties": {
+                                    "operations": {
+                                        "type": "array",
+                                        "items": {
+                                            "type": "object",
+                                            "required": ["op", "path"],
+                                            "properties": {
+                                                "op": { "enum": ["set", "delete"] },
+                                                "path": { "type": "string" },
+                                                "value": object
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                "responses": {
+                    "200": {
+                        "description": "The result of each operation.",
+                        "content": { "application/json": { "schema": object } }
+                    },
+                    "default": response_ref("Error")
+                }
+            }
+        }),
+    );
+    paths
+}
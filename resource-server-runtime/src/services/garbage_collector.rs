@@ -0,0 +1,435 @@
+//! Garbage collection of dependent objects.
+//!
+//! [`GarbageCollector`] implements [`HostedService`]. On every pass it reads
+//! all stored objects, follows their `ownerReferences`, and carries out the
+//! propagation policy each deleted owner was given:
+//!
+//! * **Background** — the owner is already gone; dependents whose owners are
+//!   all missing are deleted.
+//! * **Foreground** — the owner holds the `foregroundDeletion` finalizer. Its
+//!   dependents are deleted (in the foreground too) and the finalizer is
+//!   removed once none are left, which deletes the owner.
+//! * **Orphan** — the owner holds the `orphan` finalizer. The reference to the
+//!   owner is removed from every dependent, then the finalizer.
+//!
+//! A deleted `Namespace` holds the `namespace` finalizer: everything in the
+//! namespace is deleted, and the finalizer is removed once it is empty.
+//!
+//! All changes go through the `set`, `delete` and `finalize` commands as
+//! internal calls. A pass runs on a fixed interval and whenever a `delete` or
+//! `finalize` wakes the collector.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{
+        parse_response, CommandContext, CommandDispatcher, CommandHandler, CommandRequest,
+        CommandResult, CommandType, ExecutableCommand,
+    },
+    data::TransactionalKeyValueStore,
+    service::{HostedService, ServiceTask},
+};
+use kuiper_types::model::resource::{
+    DeletionPropagation, SystemObject, FOREGROUND_DELETION_FINALIZER, NAMESPACE_FINALIZER,
+    ORPHAN_FINALIZER,
+};
+use tokio::sync::{Notify, RwLock};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::{
+    constants::RESOURCE_CONTAINER,
+    handlers::{delete::DeleteRequest, finalize::FinalizeRequest, set::SetRequest},
+    namespace,
+};
+
+/// Tuning knobs for [`GarbageCollector`].
+#[derive(Debug, Clone)]
+pub struct GarbageCollectorOptions {
+    /// How often a pass runs when no command wakes the collector.
+    pub interval: Duration,
+}
+
+impl Default for GarbageCollectorOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Deletes and orphans dependents of deleted owners.
+pub struct GarbageCollector {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    dispatcher: Arc<dyn CommandDispatcher>,
+    signal: Arc<Notify>,
+    options: GarbageCollectorOptions,
+    /// Replaced on every start, so the collector can be started again after
+    /// it was stopped (e.g. when it only runs while leader).
+    stop: Mutex<CancellationToken>,
+    task: ServiceTask,
+}
+
+struct StoredObject {
+    namespace: String,
+    resource: String,
+    object: SystemObject,
+}
+
+impl StoredObject {
+    fn has_finalizer(&self, finalizer: &str) -> bool {
+        self.object
+            .metadata
+            .finalizers
+            .as_ref()
+            .is_some_and(|f| f.iter().any(|f| f == finalizer))
+    }
+
+    fn owner_uids(&self) -> impl Iterator<Item = Uuid> + '_ {
+        self.object
+            .metadata
+            .owner_references
+            .iter()
+            .flatten()
+            .map(|owner| owner.uid)
+    }
+}
+
+impl GarbageCollector {
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        dispatcher: Arc<dyn CommandDispatcher>,
+        signal: Arc<Notify>,
+        options: GarbageCollectorOptions,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            store,
+            dispatcher,
+            signal,
+            options,
+            stop: Mutex::new(CancellationToken::new()),
+            task: ServiceTask::default(),
+        })
+    }
+
+    /// Runs one collection pass. Returns the number of changes made; more
+    /// passes may be needed to finish deep ownership chains.
+    pub async fn run_pass(&self) -> anyhow::Result<usize> {
+        let (objects, complete) = self.load().await?;
+
+        let by_uid: HashMap<Uuid, usize> = objects
+            .iter()
+            .enumerate()
+            .map(|(i, o)| (o.object.metadata.uid, i))
+            .collect();
+
+        let mut dependents: HashMap<Uuid, Vec<usize>> = HashMap::new();
+        for (i, o) in objects.iter().enumerate() {
+            for uid in o.owner_uids() {
+                dependents.entry(uid).or_default().push(i);
+            }
+        }
+
+        let mut changes = 0;
+        for owner in &objects {
+            if owner.object.metadata.deletion_timestamp.is_none() {
+                continue;
+            }
+            let uid = owner.object.metadata.uid;
+            let owned = dependents.get(&uid).map(Vec::as_slice).unwrap_or_default();
+
+            if owner.has_finalizer(ORPHAN_FINALIZER) {
+                let mut orphaned = true;
+                for &i in owned {
+                    let done = succeeded(self.orphan(&objects[i], uid).await, &objects[i]);
+                    changes += usize::from(done);
+                    orphaned &= done;
+                }
+                // A dependent still referencing the owner would be collected
+                // as dangling once the owner is gone.
+                if orphaned
+                    && succeeded(self.remove_finalizer(owner, ORPHAN_FINALIZER).await, owner)
+                {
+                    changes += 1;
+                }
+            } else if owner.has_finalizer(FOREGROUND_DELETION_FINALIZER) {
+                if owned.is_empty() {
+                    let result = self
+                        .remove_finalizer(owner, FOREGROUND_DELETION_FINALIZER)
+                        .await;
+                    changes += usize::from(succeeded(result, owner));
+                    continue;
+                }
+                for &i in owned {
+                    let dependent = &objects[i];
+                    if dependent.object.metadata.deletion_timestamp.is_none() {
+                        let result = self
+                            .delete(dependent, DeletionPropagation::Foreground)
+                            .await;
+                        changes += usize::from(succeeded(result, dependent));
+                    }
+                }
+            }
+        }
+
+        for ns in &objects {
+            if ns.object.metadata.deletion_timestamp.is_none()
+                || !ns.has_finalizer(NAMESPACE_FINALIZER)
+                || namespace::namespace_name(&ns.namespace, &ns.resource).is_none()
+            {
+                continue;
+            }
+            let name = ns.object.metadata.name.to_lowercase();
+            let contents: Vec<&StoredObject> =
+                objects.iter().filter(|o| o.namespace == name).collect();
+
+            // An unreadable object might still be in the namespace.
+            if contents.is_empty() && complete {
+                let result = self.remove_finalizer(ns, NAMESPACE_FINALIZER).await;
+                changes += usize::from(succeeded(result, ns));
+                continue;
+            }
+            for object in contents {
+                if object.object.metadata.deletion_timestamp.is_none() {
+                    let result = self.delete(object, DeletionPropagation::Background).await;
+                    changes += usize::from(succeeded(result, object));
+                }
+            }
+        }
+
+        // An unreadable object might be someone's owner; don't treat its
+        // dependents as dangling.
+        if !complete {
+            return Ok(changes);
+        }
+
+        for dependent in &objects {
+            let metadata = &dependent.object.metadata;
+            if metadata.deletion_timestamp.is_some()
+                || metadata.owner_references.as_ref().is_none_or(Vec::is_empty)
+            {
+                continue;
+            }
+            if dependent.owner_uids().all(|uid| !by_uid.contains_key(&uid)) {
+                let result = self
+                    .delete(dependent, DeletionPropagation::Background)
+                    .await;
+                changes += usize::from(succeeded(result, dependent));
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Every stored object, and whether all of them could be read.
+    async fn load(&self) -> anyhow::Result<(Vec<StoredObject>, bool)> {
+        let store = self.store.read().await;
+        if !store
+            .container_exists(RESOURCE_CONTAINER)
+            .await
+            .context("Failed to check resource container")?
+        {
+            return Ok((Vec::new(), true));
+        }
+
+        let keys = store
+            .list_keys(RESOURCE_CONTAINER, None)
+            .await
+            .context("Failed to list resources")?;
+
+        let mut objects = Vec::with_capacity(keys.len());
+        let mut complete = true;
+        for key in keys {
+            let bytes = match store.get(RESOURCE_CONTAINER, &key).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    tracing::warn!("Garbage collector failed to read {}: {}", key, e);
+                    complete = false;
+                    continue;
+                }
+            };
+            let Some((namespace, resource)) = key.split_once('/') else {
+                continue;
+            };
+            match serde_json::from_slice::<SystemObject>(&bytes) {
+                Ok(object) => objects.push(StoredObject {
+                    namespace: namespace.to_string(),
+                    resource: resource.to_string(),
+                    object,
+                }),
+                Err(e) => {
+                    tracing::warn!("Garbage collector skipping unreadable {}: {}", key, e);
+                    complete = false;
+                }
+            }
+        }
+
+        Ok((objects, complete))
+    }
+
+    /// Removes the reference to `owner` from `dependent`.
+    async fn orphan(&self, dependent: &StoredObject, owner: Uuid) -> anyhow::Result<()> {
+        let value = serde_json::to_value(&dependent.object)?;
+        let mut value: SystemObject = serde_json::from_value(value)?;
+
+        if let Some(owners) = value.metadata.owner_references.as_mut() {
+            owners.retain(|o| o.uid != owner);
+            if owners.is_empty() {
+                value.metadata.owner_references = None;
+            }
+        }
+
+        tracing::debug!(
+            "Orphaning {}/{} from owner {}",
+            dependent.namespace,
+            dependent.resource,
+            owner
+        );
+        self.send(&SetRequest {
+            namespace: dependent.namespace.clone(),
+            resource: dependent.resource.clone(),
+            value,
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn delete(
+        &self,
+        object: &StoredObject,
+        policy: DeletionPropagation,
+    ) -> anyhow::Result<()> {
+        tracing::info!(
+            "Garbage collecting {}/{} ({:?})",
+            object.namespace,
+            object.resource,
+            policy
+        );
+        self.send(
+            &DeleteRequest::new(&object.namespace, &object.resource).with_propagation(policy),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_finalizer(&self, object: &StoredObject, finalizer: &str) -> anyhow::Result<()> {
+        self.send(&FinalizeRequest::remove(
+            &object.namespace,
+            &object.resource,
+            finalizer,
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn send<R: CommandRequest>(&self, request: &R) -> anyhow::Result<R::Response> {
+        let mut ctx = CommandContext::from_request(request)?;
+        ctx.is_internal = true;
+        ctx.cancellation_token = self.stop.lock().unwrap().child_token();
+        let result = self.dispatcher.dispatch(&mut ctx).await?;
+        parse_response(result)
+    }
+}
+
+#[async_trait]
+impl HostedService for GarbageCollector {
+    async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        let stop = CancellationToken::new();
+        *self.stop.lock().unwrap() = stop.clone();
+
+        let collector = self.clone();
+        self.task.spawn(async move {
+            tracing::info!(
+                "GarbageCollector started (interval={}s)",
+                collector.options.interval.as_secs()
+            );
+
+            loop {
+                let changes = match collector.run_pass().await {
+                    Ok(changes) => changes,
+                    Err(e) => {
+                        tracing::warn!("Garbage collection pass failed: {}", e);
+                        0
+                    }
+                };
+
+                // Keep going while passes make progress through ownership chains.
+                if changes > 0 && !stop.is_cancelled() {
+                    continue;
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(collector.options.interval) => {}
+                    _ = collector.signal.notified() => {}
+                    _ = stop.cancelled() => break,
+                }
+            }
+
+            tracing::info!("GarbageCollector stopped");
+            Ok(())
+        });
+
+        Ok(())
+    }
+
+    async fn stop(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.stop.lock().unwrap().cancel();
+        self.task.join().await
+    }
+
+    async fn join(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.task.join().await
+    }
+}
+
+/// Whether a change to `object` was made. A failure is logged and skipped, so
+/// one object the collector cannot change does not hold up the others.
+fn succeeded(result: anyhow::Result<()>, object: &StoredObject) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!(
+                "Garbage collector skipping {}/{}: {:#}",
+                object.namespace,
+                object.resource,
+                e
+            );
+            false
+        }
+    }
+}
+
+/// `Observer` of `delete` and `finalize` that wakes the [`GarbageCollector`].
+pub struct GarbageCollectorTrigger {
+    signal: Arc<Notify>,
+}
+
+impl GarbageCollectorTrigger {
+    pub fn new(signal: Arc<Notify>) -> Self {
+        Self { signal }
+    }
+}
+
+impl CommandHandler for GarbageCollectorTrigger {
+    fn get_type(&self) -> CommandType {
+        CommandType::Observer
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for GarbageCollectorTrigger {
+    async fn execute(&self, _ctx: &CommandContext) -> CommandResult {
+        self.signal.notify_one();
+        Ok(None)
+    }
+}
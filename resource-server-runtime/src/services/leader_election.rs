@@ -0,0 +1,418 @@
+//! Leader election on a `Lease` resource.
+//!
+//! [`LeaderElector`] implements [`HostedService`] and [`Leadership`]. It
+//! acquires the named `Lease` in the `global` namespace when it is free or
+//! expired, renews it while it holds it, and releases it on stop. Every write
+//! is a compare-and-swap against the lease as it was read, so two electors
+//! never both believe they won. While the lease is held the elector exposes a
+//! [`LeadershipToken`]; wrap services in
+//! [`Singleton`](kuiper_runtime::service::Singleton) to run them only then.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::{
+    data::TransactionalKeyValueStore,
+    service::{HostedService, Leadership, LeadershipToken, ServiceTask},
+};
+use kuiper_types::model::resource::SystemObjectMetadata;
+use tokio::sync::{watch, RwLock};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::{
+    constants::{
+        resource_key, GLOBAL_NAMESPACE, RESOURCE_CONTAINER, SYSTEM_API_VERSION,
+        SYSTEM_EXTENSION_GROUP,
+    },
+    index,
+    model::lease::Lease,
+};
+
+/// Tuning knobs for [`LeaderElector`].
+#[derive(Debug, Clone)]
+pub struct LeaderElectionOptions {
+    /// Name of the `Lease` contended for.
+    pub lease_name: String,
+
+    /// Identity written to the lease; unique per process by default.
+    pub identity: String,
+
+    /// How long the lease stays valid without being renewed.
+    pub lease_duration: Duration,
+
+    /// The leader steps down when it could not renew for this long. Shorter
+    /// than `lease_duration`, so it stops before anyone else can take over.
+    pub renew_deadline: Duration,
+
+    /// How often the leader renews.
+    pub renew_interval: Duration,
+
+    /// How often a follower tries to acquire.
+    pub retry_interval: Duration,
+}
+
+impl LeaderElectionOptions {
+    pub fn new(lease_name: &str) -> Self {
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "kuiper".to_string());
+        Self {
+            lease_name: lease_name.to_string(),
+            identity: format!("{}-{}", host, Uuid::new_v4().simple()),
+            lease_duration: Duration::from_secs(15),
+            renew_deadline: Duration::from_secs(10),
+            renew_interval: Duration::from_secs(5),
+            retry_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+fn lease_key(name: &str) -> String {
+    resource_key(
+        GLOBAL_NAMESPACE,
+        Some(&format!(
+            "{}/{}/Lease/{}",
+            SYSTEM_EXTENSION_GROUP, SYSTEM_API_VERSION, name
+        )),
+    )
+}
+
+/// Contends for a `Lease` and tracks whether this process is the leader.
+pub struct LeaderElector {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    options: LeaderElectionOptions,
+    leader: watch::Sender<Option<LeadershipToken>>,
+    stop: CancellationToken,
+    task: ServiceTask,
+}
+
+impl LeaderElector {
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        options: LeaderElectionOptions,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            store,
+            options,
+            leader: watch::channel(None).0,
+            stop: CancellationToken::new(),
+            task: ServiceTask::default(),
+        })
+    }
+
+    pub fn identity(&self) -> &str {
+        &self.options.identity
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.current().is_some()
+    }
+
+    /// Reads the lease as currently stored.
+    pub async fn lease(&self) -> Option<Lease> {
+        let store = self.store.read().await;
+        let bytes = store
+            .get(RESOURCE_CONTAINER, &lease_key(&self.options.lease_name))
+            .await
+            .ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    /// Takes the lease if it is free, expired or already ours, and renews it.
+    /// Returns the lease's transition count (the leadership term) on success,
+    /// or `None` when another identity holds it or won a concurrent update.
+    pub async fn try_acquire_or_renew(&self) -> anyhow::Result<Option<u64>> {
+        let key = lease_key(&self.options.lease_name);
+        let identity = &self.options.identity;
+
+        let store = self.store.write().await;
+        index::ensure_containers(&*store).await?;
+
+        let current = store.get(RESOURCE_CONTAINER, &key).await.ok();
+        let now = chrono::Utc::now().timestamp_micros();
+
+        let mut lease = match &current {
+            Some(bytes) => {
+                serde_json::from_slice::<Lease>(bytes).context("Failed to parse Lease")?
+            }
+            None => Lease {
+                api_version: format!("{}/{}", SYSTEM_EXTENSION_GROUP, SYSTEM_API_VERSION),
+                kind: "Lease".to_string(),
+                metadata: SystemObjectMetadata {
+                    name: self.options.lease_name.clone(),
+                    namespace: Some(GLOBAL_NAMESPACE.to_string()),
+                    uid: Uuid::new_v4(),
+                    creation_timestamp: Some(now),
+                    ..Default::default()
+                },
+                spec: Default::default(),
+            },
+        };
+
+        if lease.spec.holder_identity.as_ref() != Some(identity) {
+            if lease.spec.is_held(now) {
+                return Ok(None);
+            }
+            lease.spec.holder_identity = Some(identity.clone());
+            lease.spec.acquire_time = Some(now);
+            lease.spec.lease_transitions += 1;
+        }
+
+        lease.spec.renew_time = Some(now);
+        lease.spec.lease_duration_seconds = self.options.lease_duration.as_secs().max(1);
+        lease.metadata.resource_version = Some(Uuid::new_v4().to_string());
+
+        let bytes = serde_json::to_vec_pretty(&lease).context("Failed to serialize Lease")?;
+        let swapped = store
+            .compare_and_swap(RESOURCE_CONTAINER, &key, current.as_deref(), bytes)
+            .await
+            .context("Failed to write Lease")?;
+
+        Ok(swapped.then_some(lease.spec.lease_transitions))
+    }
+
+    /// Gives up the lease if this identity holds it, so a follower can take
+    /// over without waiting for it to expire.
+    async fn release(&self) -> anyhow::Result<()> {
+        let key = lease_key(&self.options.lease_name);
+        let store = self.store.write().await;
+
+        let Ok(current) = store.get(RESOURCE_CONTAINER, &key).await else {
+            return Ok(());
+        };
+        let mut lease: Lease = serde_json::from_slice(&current).context("Failed to parse Lease")?;
+        if lease.spec.holder_identity.as_ref() != Some(&self.options.identity) {
+            return Ok(());
+        }
+
+        lease.spec.holder_identity = None;
+        lease.metadata.resource_version = Some(Uuid::new_v4().to_string());
+        let bytes = serde_json::to_vec_pretty(&lease).context("Failed to serialize Lease")?;
+        store
+            .compare_and_swap(RESOURCE_CONTAINER, &key, Some(&current), bytes)
+            .await
+            .context("Failed to release Lease")?;
+        Ok(())
+    }
+
+    fn become_leader(&self, term: u64) {
+        if self.current().is_some_and(|t| t.term() == term) {
+            return;
+        }
+        tracing::info!(
+            lease = %self.options.lease_name,
+            identity = %self.options.identity,
+            term,
+            "Acquired leadership"
+        );
+        if let Some(previous) = self.leader.send_replace(Some(LeadershipToken::new(term))) {
+            previous.revoke();
+        }
+    }
+
+    fn step_down(&self, reason: &str) {
+        if let Some(token) = self.leader.send_replace(None) {
+            tracing::warn!(
+                lease = %self.options.lease_name,
+                identity = %self.options.identity,
+                term = token.term(),
+                "Lost leadership: {}",
+                reason
+            );
+            token.revoke();
+        }
+    }
+
+    async fn run(&self) {
+        let mut renewed_at: Option<Instant> = None;
+
+        loop {
+            match self.try_acquire_or_renew().await {
+                Ok(Some(term)) => {
+                    renewed_at = Some(Instant::now());
+                    self.become_leader(term);
+                }
+                Ok(None) => self.step_down("the lease is held by another identity"),
+                Err(e) => {
+                    tracing::warn!(lease = %self.options.lease_name, "Lease update failed: {}", e);
+                    if renewed_at.is_some_and(|t| t.elapsed() >= self.options.renew_deadline) {
+                        self.step_down("the lease could not be renewed");
+                    }
+                }
+            }
+
+            let wait = match self.is_leader() {
+                true => self.options.renew_interval,
+                false => self.options.retry_interval,
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.stop.cancelled() => return,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Leadership for LeaderElector {
+    async fn acquire(&self) -> LeadershipToken {
+        let mut leader = self.leader.subscribe();
+        loop {
+            if let Some(token) = leader.borrow_and_update().clone() {
+                if token.is_held() {
+                    return token;
+                }
+            }
+            // The sender lives as long as `self`.
+            let _ = leader.changed().await;
+        }
+    }
+
+    fn current(&self) -> Option<LeadershipToken> {
+        self.leader.borrow().clone().filter(|t| t.is_held())
+    }
+}
+
+#[async_trait]
+impl HostedService for LeaderElector {
+    async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        let elector = self.clone();
+
+        self.task.spawn(async move {
+            tracing::info!(
+                lease = %elector.options.lease_name,
+                identity = %elector.options.identity,
+                "LeaderElector started"
+            );
+            elector.run().await;
+            Ok(())
+        });
+
+        Ok(())
+    }
+
+    async fn stop(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.stop.cancel();
+        self.task.join().await?;
+
+        self.step_down("the elector stopped");
+        self.release().await?;
+
+        tracing::info!(lease = %self.options.lease_name, "LeaderElector stopped");
+        Ok(())
+    }
+
+    async fn join(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.task.join().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kuiper_runtime::data::{file_system_store::FileSystemStore, InMemoryStore};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{handlers::get::GetRequest, KuiperRuntimeBuilder};
+
+    fn options(identity: &str) -> LeaderElectionOptions {
+        LeaderElectionOptions {
+            identity: identity.to_string(),
+            lease_duration: Duration::from_secs(1),
+            renew_deadline: Duration::from_millis(500),
+            renew_interval: Duration::from_millis(50),
+            retry_interval: Duration::from_millis(20),
+            ..LeaderElectionOptions::new("test")
+        }
+    }
+
+    /// Only one elector holds the lease; it passes to the other, with a new
+    /// term, once the leader stops and releases it.
+    #[tokio::test]
+    async fn hands_over_lease() {
+        let store = Arc::new(RwLock::new(InMemoryStore::new()));
+        let a = LeaderElector::new(store.clone(), options("a"));
+        let b = LeaderElector::new(store.clone(), options("b"));
+
+        assert_eq!(a.try_acquire_or_renew().await.unwrap(), Some(1));
+        assert_eq!(b.try_acquire_or_renew().await.unwrap(), None);
+
+        a.start().await.unwrap();
+        b.start().await.unwrap();
+        let token = tokio::time::timeout(Duration::from_secs(1), a.acquire())
+            .await
+            .unwrap();
+        assert_eq!(token.term(), 1);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(a.is_leader());
+        assert!(!b.is_leader());
+
+        a.stop().await.unwrap();
+        assert!(!token.is_held());
+        assert_eq!(a.lease().await.unwrap().spec.holder_identity, None);
+
+        let token = tokio::time::timeout(Duration::from_secs(1), b.acquire())
+            .await
+            .unwrap();
+        assert_eq!(token.term(), 2);
+
+        // The lease is an ordinary system resource.
+        let rt = KuiperRuntimeBuilder::new(store.clone()).build();
+        rt.initialize().await.unwrap();
+        let lease = rt
+            .send(&GetRequest {
+                namespace: "global".to_string(),
+                resource: "ext.api.cloud-api.dev/v1alpha1/Lease/test".to_string(),
+                version: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(lease.extension_data["spec"]["holderIdentity"], json!("b"));
+        assert_eq!(lease.extension_data["spec"]["leaseTransitions"], json!(2));
+
+        b.stop().await.unwrap();
+    }
+
+    /// Electors on separate stores over one directory, like replicas sharing
+    /// a store, never both win a lease: the swap is atomic across stores, not
+    /// only under one store's lock.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn contention_on_shared_store() {
+        let root = std::env::temp_dir().join(format!("kuiper-election-{}", Uuid::new_v4()));
+        let store = || -> Arc<RwLock<dyn TransactionalKeyValueStore>> {
+            Arc::new(RwLock::new(FileSystemStore::new(&root).unwrap()))
+        };
+        let options = |identity: String, lease: &str| LeaderElectionOptions {
+            lease_name: lease.to_string(),
+            ..options(&identity)
+        };
+
+        // Create the containers before contending.
+        let first = LeaderElector::new(store(), options("setup".to_string(), "setup"));
+        first.try_acquire_or_renew().await.unwrap();
+
+        for round in 0..20 {
+            let lease = format!("contended-{round}");
+            let attempts: Vec<_> = (0..4)
+                .map(|i| {
+                    let elector = LeaderElector::new(store(), options(format!("e{i}"), &lease));
+                    tokio::spawn(async move { elector.try_acquire_or_renew().await.unwrap() })
+                })
+                .collect();
+
+            let mut winners = 0;
+            for attempt in attempts {
+                if attempt.await.unwrap().is_some() {
+                    winners += 1;
+                }
+            }
+            assert_eq!(winners, 1, "round {round}");
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,11 @@
+pub mod garbage_collector;
+pub mod leader_election;
+pub mod outbox;
+pub mod scheduled_commands;
+pub mod storage_migration;
+
+pub use garbage_collector::{GarbageCollector, GarbageCollectorOptions, GarbageCollectorTrigger};
+pub use leader_election::{LeaderElectionOptions, LeaderElector};
+pub use outbox::{OutboxDeliveryService, OutboxOptions};
+pub use scheduled_commands::ScheduledCommandSource;
+pub use storage_migration::{StorageMigrationOptions, StorageMigrationTrigger, StorageMigrator};
@@ -0,0 +1,333 @@
+//! Background delivery of the durable observer outbox.
+//!
+//! [`OutboxDeliveryService`] implements [`HostedService`]. It wakes whenever a
+//! command stages an outbox entry (and on a fixed poll interval as a fallback),
+//! replays each due entry through the command's `Observer` handlers, and removes
+//! it once they succeed. Failed deliveries are retried with exponential backoff;
+//! entries that exhaust `max_attempts` are moved to the dead-letter container.
+//!
+//! Every replica sharing the store runs one. Each delivers the entries its own
+//! process staged, and takes over those of other replicas only once they are
+//! overdue by [`OutboxOptions::takeover_after`], e.g. because that replica is
+//! gone. An entry is claimed with a compare-and-swap before its observers run.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::{
+    data::{Transaction, TransactionalKeyValueStore},
+    outbox::{replica_id, OutboxEntry, OUTBOX_CONTAINER, OUTBOX_DEAD_LETTER_CONTAINER},
+    service::{HostedService, ServiceTask},
+};
+use tokio::sync::{Notify, RwLock};
+use tokio_util::sync::CancellationToken;
+
+use crate::handlers::CommandExecutor;
+
+/// Tuning knobs for [`OutboxDeliveryService`].
+#[derive(Debug, Clone)]
+pub struct OutboxOptions {
+    /// How often the outbox is scanned when no command wakes the service.
+    pub poll_interval: Duration,
+
+    /// Delivery attempts before an entry is moved to the dead-letter container.
+    pub max_attempts: u32,
+
+    /// Delay before the first retry; doubled on every further failure.
+    pub initial_backoff: Duration,
+
+    /// Upper bound for the retry delay.
+    pub max_backoff: Duration,
+
+    /// The replica whose entries this service delivers first. Defaults to
+    /// this process.
+    pub replica: String,
+
+    /// How long a claim on an entry holds while its observers run. Entries
+    /// whose deliverer died mid-delivery are retried after this.
+    pub claim_timeout: Duration,
+
+    /// How long an entry of another replica has to be due before this one
+    /// delivers it instead.
+    pub takeover_after: Duration,
+}
+
+impl Default for OutboxOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            max_attempts: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+            replica: replica_id().to_string(),
+            claim_timeout: Duration::from_secs(60),
+            takeover_after: Duration::from_secs(30),
+        }
+    }
+}
+
+impl OutboxOptions {
+    /// Retry delay after `attempts` failed deliveries.
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Delivers outbox entries to observers with at-least-once semantics.
+pub struct OutboxDeliveryService {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    executor: Arc<CommandExecutor>,
+    signal: Arc<Notify>,
+    options: OutboxOptions,
+    stop: CancellationToken,
+    task: ServiceTask,
+}
+
+impl OutboxDeliveryService {
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        executor: Arc<CommandExecutor>,
+        signal: Arc<Notify>,
+        options: OutboxOptions,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            store,
+            executor,
+            signal,
+            options,
+            stop: CancellationToken::new(),
+            task: ServiceTask::default(),
+        })
+    }
+
+    /// Delivers every entry that is currently due. Returns the number of
+    /// entries that were delivered successfully.
+    pub async fn run_pass(&self) -> anyhow::Result<usize> {
+        let mut keys = {
+            let store = self.store.read().await;
+            if !store
+                .container_exists(OUTBOX_CONTAINER)
+                .await
+                .context("Failed to check outbox container")?
+            {
+                return Ok(0);
+            }
+            store
+                .list_keys(OUTBOX_CONTAINER, None)
+                .await
+                .context("Failed to list outbox entries")?
+        };
+
+        // Keys are prefixed with the zero-padded enqueue time.
+        keys.sort();
+
+        let mut delivered = 0;
+        for key in keys {
+            if self.stop.is_cancelled() {
+                break;
+            }
+
+            let (bytes, entry) = {
+                let store = self.store.read().await;
+                let Ok(bytes) = store.get(OUTBOX_CONTAINER, &key).await else {
+                    continue;
+                };
+                match serde_json::from_slice::<OutboxEntry>(&bytes) {
+                    Ok(entry) => (bytes, entry),
+                    Err(e) => {
+                        tracing::warn!("Skipping unreadable outbox entry {}: {}", key, e);
+                        continue;
+                    }
+                }
+            };
+
+            if !self.is_due_here(&entry, chrono::Utc::now().timestamp_micros()) {
+                continue;
+            }
+
+            let Some((claimed, entry)) = self.claim(&key, &bytes, entry).await? else {
+                continue;
+            };
+
+            if self.deliver(&key, claimed, entry).await? {
+                delivered += 1;
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    /// Whether this service delivers `entry` at `now`: it is due, nobody
+    /// holds a claim on it, and it was staged by this replica or has been due
+    /// for `takeover_after` without its own replica delivering it.
+    fn is_due_here(&self, entry: &OutboxEntry, now: i64) -> bool {
+        if entry.next_attempt_at > now || entry.claimed_until.is_some_and(|until| until > now) {
+            return false;
+        }
+        let own = entry
+            .replica
+            .as_deref()
+            .is_none_or(|replica| replica == self.options.replica);
+        own || entry.next_attempt_at + self.options.takeover_after.as_micros() as i64 <= now
+    }
+
+    /// Claims `entry`, stored as `bytes`, for this replica. Returns the
+    /// claimed entry and how it is stored, or `None` when another replica
+    /// changed it first.
+    async fn claim(
+        &self,
+        key: &str,
+        bytes: &[u8],
+        mut entry: OutboxEntry,
+    ) -> anyhow::Result<Option<(Vec<u8>, OutboxEntry)>> {
+        entry.claimed_by = Some(self.options.replica.clone());
+        entry.claimed_until = Some(
+            chrono::Utc::now().timestamp_micros() + self.options.claim_timeout.as_micros() as i64,
+        );
+        let claimed = serde_json::to_vec(&entry)?;
+
+        let store = self.store.write().await;
+        let won = store
+            .compare_and_swap(OUTBOX_CONTAINER, key, Some(bytes), claimed.clone())
+            .await
+            .context("Failed to claim outbox entry")?;
+        Ok(won.then_some((claimed, entry)))
+    }
+
+    /// Runs the observers for a single entry, claimed as `claimed`, and
+    /// records the outcome.
+    async fn deliver(
+        &self,
+        key: &str,
+        claimed: Vec<u8>,
+        mut entry: OutboxEntry,
+    ) -> anyhow::Result<bool> {
+        let mut ctx = entry.to_context();
+
+        let outcome = self.executor.observe(&mut ctx).await;
+
+        let store = self.store.write().await;
+
+        match outcome {
+            Ok(_) => {
+                store
+                    .delete(OUTBOX_CONTAINER, key)
+                    .await
+                    .context("Failed to remove delivered outbox entry")?;
+                Ok(true)
+            }
+            Err(e) => {
+                entry.attempts += 1;
+                entry.last_error = Some(e.to_string());
+                entry.claimed_by = None;
+                entry.claimed_until = None;
+
+                if entry.attempts >= self.options.max_attempts {
+                    tracing::error!(
+                        activity_id = %entry.activity_id,
+                        command = %entry.command_name,
+                        attempts = entry.attempts,
+                        "Outbox delivery failed permanently; moving to dead-letter: {}",
+                        e
+                    );
+
+                    store
+                        .ensure_container(OUTBOX_DEAD_LETTER_CONTAINER)
+                        .await
+                        .context("Failed to create outbox dead-letter container")?;
+
+                    let mut tx = Transaction::new(&*store);
+                    tx.put(
+                        OUTBOX_DEAD_LETTER_CONTAINER.to_string(),
+                        key.to_string(),
+                        serde_json::to_vec(&entry)?,
+                    );
+                    tx.delete(OUTBOX_CONTAINER.to_string(), key.to_string());
+                    tx.commit()
+                        .await
+                        .context("Failed to dead-letter outbox entry")?;
+                } else {
+                    let delay = self.options.backoff(entry.attempts);
+                    entry.next_attempt_at =
+                        chrono::Utc::now().timestamp_micros() + delay.as_micros() as i64;
+
+                    tracing::warn!(
+                        activity_id = %entry.activity_id,
+                        command = %entry.command_name,
+                        attempts = entry.attempts,
+                        "Outbox delivery failed; retrying in {}ms: {}",
+                        delay.as_millis(),
+                        e
+                    );
+
+                    // Another replica may have taken over after the claim
+                    // expired; its outcome stands.
+                    let rescheduled = store
+                        .compare_and_swap(
+                            OUTBOX_CONTAINER,
+                            key,
+                            Some(&claimed),
+                            serde_json::to_vec(&entry)?,
+                        )
+                        .await
+                        .context("Failed to reschedule outbox entry")?;
+                    if !rescheduled {
+                        tracing::warn!(
+                            activity_id = %entry.activity_id,
+                            "Outbox entry {} was taken over while it was delivered",
+                            key
+                        );
+                    }
+                }
+
+                Ok(false)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl HostedService for OutboxDeliveryService {
+    async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        let service = self.clone();
+
+        self.task.spawn(async move {
+            tracing::info!(
+                "OutboxDeliveryService started (poll interval={}s)",
+                service.options.poll_interval.as_secs()
+            );
+
+            loop {
+                if let Err(e) = service.run_pass().await {
+                    tracing::warn!("Outbox delivery pass failed: {}", e);
+                }
+
+                tokio::select! {
+                    _ = service.signal.notified() => {}
+                    _ = tokio::time::sleep(service.options.poll_interval) => {}
+                    _ = service.stop.cancelled() => {
+                        tracing::info!("OutboxDeliveryService stopping");
+                        return Ok(());
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    async fn stop(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.stop.cancel();
+        self.task.join().await?;
+        tracing::info!("OutboxDeliveryService stopped");
+        Ok(())
+    }
+
+    async fn join(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.task.join().await
+    }
+}
@@ -0,0 +1,42 @@
+//! `ScheduledCommand` resources as a [`JobSource`] for the [`Scheduler`].
+//!
+//! [`Scheduler`]: kuiper_runtime::scheduler::Scheduler
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use kuiper_runtime::scheduler::{JobSource, ScheduledJob};
+use tokio::sync::RwLock;
+
+use crate::ResourceRegistry;
+
+/// Reads every `ScheduledCommand` that is not suspended. Resources with an
+/// invalid schedule are skipped with a warning.
+pub struct ScheduledCommandSource {
+    registry: Arc<RwLock<ResourceRegistry>>,
+}
+
+impl ScheduledCommandSource {
+    pub fn new(registry: Arc<RwLock<ResourceRegistry>>) -> Self {
+        Self { registry }
+    }
+}
+
+#[async_trait]
+impl JobSource for ScheduledCommandSource {
+    async fn jobs(&self) -> anyhow::Result<Vec<ScheduledJob>> {
+        let commands = self.registry.read().await.get_scheduled_commands().await?;
+
+        Ok(commands
+            .iter()
+            .filter(|c| !c.spec.suspend)
+            .filter_map(|c| match c.to_job() {
+                Ok(job) => Some(job),
+                Err(e) => {
+                    tracing::warn!("Ignoring ScheduledCommand '{}': {}", c.metadata.name, e);
+                    None
+                }
+            })
+            .collect())
+    }
+}
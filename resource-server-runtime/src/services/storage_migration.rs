@@ -0,0 +1,283 @@
+//! Migration of stored objects to their kind's storage version.
+//!
+//! [`StorageMigrator`] implements [`HostedService`]. On every pass it reads
+//! all stored objects and rewrites those of kinds with a storage version that
+//! are stored at another version, converted as a read would convert them.
+//!
+//! A rewrite changes only how an object is stored, not the object, so it goes
+//! straight to the store instead of through `set`: `resourceVersion` and
+//! `generation` are kept, and no observers run. An object written since it
+//! was read is left for a later pass. A pass runs on a fixed interval and
+//! whenever a `ResourceDefinition` is set, which may change a storage version.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use kuiper_runtime::{
+    command::{CommandContext, CommandHandler, CommandResult, CommandType, ExecutableCommand},
+    data::{Transaction, TransactionalKeyValueStore},
+    service::{HostedService, ServiceTask},
+};
+use kuiper_types::model::resource::SystemObject;
+use tokio::sync::{Notify, RwLock};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    constants::{resource_key, RESOURCE_CONTAINER},
+    conversion::Converter,
+    index,
+    registry::ResourceRegistry,
+};
+
+/// Tuning knobs for [`StorageMigrator`].
+#[derive(Debug, Clone)]
+pub struct StorageMigrationOptions {
+    /// How often a pass runs when no `ResourceDefinition` write wakes the
+    /// migrator.
+    pub interval: Duration,
+}
+
+impl Default for StorageMigrationOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Rewrites objects stored at other than their kind's storage version.
+pub struct StorageMigrator {
+    store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+    registry: Arc<RwLock<ResourceRegistry>>,
+    converter: Converter,
+    signal: Arc<Notify>,
+    options: StorageMigrationOptions,
+    /// Replaced on every start, so the migrator can be started again after
+    /// it was stopped (e.g. when it only runs while leader).
+    stop: Mutex<CancellationToken>,
+    task: ServiceTask,
+}
+
+struct StoredObject {
+    namespace: String,
+    resource: String,
+    object: SystemObject,
+}
+
+impl StorageMigrator {
+    pub fn new(
+        store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
+        registry: Arc<RwLock<ResourceRegistry>>,
+        signal: Arc<Notify>,
+        options: StorageMigrationOptions,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            store,
+            converter: Converter::new(registry.clone()),
+            registry,
+            signal,
+            options,
+            stop: Mutex::new(CancellationToken::new()),
+            task: ServiceTask::default(),
+        })
+    }
+
+    /// Runs one migration pass. Returns the number of objects rewritten.
+    pub async fn run_pass(&self) -> anyhow::Result<usize> {
+        let mut rewritten = 0;
+        for stale in self.load().await? {
+            let key = resource_key(&stale.namespace, Some(&stale.resource));
+            let resource_version = stale.object.metadata.resource_version.clone();
+
+            let object = match self.converter.convert(stale.object, None).await {
+                Ok(object) => object,
+                Err(e) => {
+                    tracing::warn!("Storage migration skipping {}: {}", key, e);
+                    continue;
+                }
+            };
+            if self
+                .rewrite(&stale.namespace, &stale.resource, resource_version, &object)
+                .await?
+            {
+                tracing::debug!("Migrated {} to {}", key, object.api_version);
+                rewritten += 1;
+            }
+        }
+
+        if rewritten > 0 {
+            tracing::info!("Migrated {} objects to their storage version", rewritten);
+        }
+        Ok(rewritten)
+    }
+
+    /// Every stored object of a kind with a storage version that it is not
+    /// stored at.
+    async fn load(&self) -> anyhow::Result<Vec<StoredObject>> {
+        let registry = self.registry.read().await;
+        let store = self.store.read().await;
+        if !store
+            .container_exists(RESOURCE_CONTAINER)
+            .await
+            .context("Failed to check resource container")?
+        {
+            return Ok(Vec::new());
+        }
+
+        let keys = store
+            .list_keys(RESOURCE_CONTAINER, None)
+            .await
+            .context("Failed to list resources")?;
+
+        let mut objects = Vec::new();
+        for key in keys {
+            let Ok(bytes) = store.get(RESOURCE_CONTAINER, &key).await else {
+                continue;
+            };
+            let Some((namespace, resource)) = key.split_once('/') else {
+                continue;
+            };
+            let Ok(object) = serde_json::from_slice::<SystemObject>(&bytes) else {
+                continue;
+            };
+            let Some((group, version)) = object.api_version.split_once('/') else {
+                continue;
+            };
+            let storage_version = registry
+                .get_definition(group, &object.kind)
+                .and_then(|d| d.storage_version());
+            if storage_version.is_some_and(|s| !s.eq_ignore_ascii_case(version)) {
+                objects.push(StoredObject {
+                    namespace: namespace.to_string(),
+                    resource: resource.to_string(),
+                    object,
+                });
+            }
+        }
+
+        Ok(objects)
+    }
+
+    /// Stores `object` unless the stored one is no longer at
+    /// `resource_version`. Returns whether it was stored.
+    async fn rewrite(
+        &self,
+        namespace: &str,
+        resource: &str,
+        resource_version: Option<String>,
+        object: &SystemObject,
+    ) -> anyhow::Result<bool> {
+        let fields = index::indexed_fields(Some(&*self.registry), resource).await;
+        let key = resource_key(namespace, Some(resource));
+
+        let store = self.store.write().await;
+        let Ok(bytes) = store.get(RESOURCE_CONTAINER, &key).await else {
+            return Ok(false);
+        };
+        let stored: SystemObject = serde_json::from_slice(&bytes)
+            .context("Failed to parse stored value as SystemObject")?;
+        if stored.metadata.resource_version != resource_version {
+            return Ok(false);
+        }
+
+        let value_bytes =
+            serde_json::to_vec_pretty(object).context("Failed to serialize SystemObject")?;
+        let mut tx = Transaction::new(&*store);
+        tx.put(RESOURCE_CONTAINER.to_string(), key, value_bytes);
+        index::stage_update(
+            &mut tx,
+            namespace,
+            resource,
+            Some(&stored),
+            Some(object),
+            &fields,
+        );
+        tx.commit()
+            .await
+            .context("Failed to store object at its storage version")?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl HostedService for StorageMigrator {
+    async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        let stop = CancellationToken::new();
+        *self.stop.lock().unwrap() = stop.clone();
+
+        let migrator = self.clone();
+        self.task.spawn(async move {
+            tracing::info!(
+                "StorageMigrator started (interval={}s)",
+                migrator.options.interval.as_secs()
+            );
+
+            loop {
+                if let Err(e) = migrator.run_pass().await {
+                    tracing::warn!("Storage migration pass failed: {}", e);
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(migrator.options.interval) => {}
+                    _ = migrator.signal.notified() => {}
+                    _ = stop.cancelled() => break,
+                }
+            }
+
+            tracing::info!("StorageMigrator stopped");
+            Ok(())
+        });
+
+        Ok(())
+    }
+
+    async fn stop(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.stop.lock().unwrap().cancel();
+        self.task.join().await
+    }
+
+    async fn join(self: &Arc<Self>) -> anyhow::Result<()> {
+        self.task.join().await
+    }
+}
+
+/// `Observer` of `set` that wakes the [`StorageMigrator`] when a
+/// `ResourceDefinition` is written.
+pub struct StorageMigrationTrigger {
+    signal: Arc<Notify>,
+}
+
+impl StorageMigrationTrigger {
+    pub fn new(signal: Arc<Notify>) -> Self {
+        Self { signal }
+    }
+}
+
+impl CommandHandler for StorageMigrationTrigger {
+    fn get_type(&self) -> CommandType {
+        CommandType::Observer
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for StorageMigrationTrigger {
+    async fn execute(&self, ctx: &CommandContext) -> CommandResult {
+        let kind = ctx
+            .parameters
+            .get("value")
+            .and_then(|v| v.get("kind"))
+            .and_then(|k| k.as_str());
+        if kind.is_some_and(|k| k.eq_ignore_ascii_case("ResourceDefinition")) {
+            self.signal.notify_one();
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,90 @@
+//! OpenTelemetry metrics recorded by the [`CommandExecutor`](crate::handlers::CommandExecutor).
+//!
+//! Instruments are created from the global meter provider, so the host must
+//! install one (see `resource-server`'s `logging::init`) before building the
+//! runtime. Without a provider every instrument is a no-op.
+
+use std::time::Duration;
+
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram, Meter},
+    KeyValue,
+};
+
+/// Instrumentation scope of all command metrics.
+pub const METER_NAME: &str = "kuiper-runtime";
+
+pub struct CommandMetrics {
+    command_duration: Histogram<f64>,
+    command_errors: Counter<u64>,
+    handler_duration: Histogram<f64>,
+    handler_errors: Counter<u64>,
+}
+
+impl Default for CommandMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandMetrics {
+    pub fn new() -> Self {
+        Self::from_meter(&global::meter(METER_NAME))
+    }
+
+    /// Creates the instruments from `meter` rather than the global provider.
+    pub fn from_meter(meter: &Meter) -> Self {
+        Self {
+            command_duration: meter
+                .f64_histogram("kuiper.command.duration")
+                .with_unit("s")
+                .with_description("Duration of a dispatched command, including all handlers.")
+                .build(),
+            command_errors: meter
+                .u64_counter("kuiper.command.errors")
+                .with_description("Dispatched commands that returned an error.")
+                .build(),
+            handler_duration: meter
+                .f64_histogram("kuiper.command.handler.duration")
+                .with_unit("s")
+                .with_description("Duration of a single command handler.")
+                .build(),
+            handler_errors: meter
+                .u64_counter("kuiper.command.handler.errors")
+                .with_description("Command handlers that returned an error.")
+                .build(),
+        }
+    }
+
+    pub fn record_command(&self, command: &str, elapsed: Duration, failed: bool) {
+        let attributes = [KeyValue::new("command", command.to_string())];
+
+        self.command_duration
+            .record(elapsed.as_secs_f64(), &attributes);
+        if failed {
+            self.command_errors.add(1, &attributes);
+        }
+    }
+
+    pub fn record_handler(
+        &self,
+        command: &str,
+        handler: &str,
+        handler_type: &'static str,
+        elapsed: Duration,
+        failed: bool,
+    ) {
+        let attributes = [
+            KeyValue::new("command", command.to_string()),
+            KeyValue::new("handler", handler.to_string()),
+            KeyValue::new("handler_type", handler_type),
+        ];
+
+        self.handler_duration
+            .record(elapsed.as_secs_f64(), &attributes);
+        if failed {
+            self.handler_errors.add(1, &attributes);
+        }
+    }
+}
@@ -1,9 +1,16 @@
 use anyhow::Context;
-use kuiper_types::model::resource::SystemObject;
+use kuiper_types::model::{
+    discovery::ApiDiscovery,
+    resource::{DeletionPropagation, SystemObject},
+};
+
+/// The namespace segment of a list route across all namespaces.
+pub const ALL_NAMESPACES: &str = "*";
 
 /// Async HTTP client for the resource-server REST API.
 ///
-/// Routes follow the pattern: `/api/{group}/{namespace}/{kind}[/{name}]`
+/// Routes follow the pattern: `/api/{group}/{namespace}/{kind}[/{name}[/{subresource}]]`
+/// where `{namespace}` may be [`ALL_NAMESPACES`] when listing.
 pub struct ResourceServerClient {
     base_url: String,
     client: reqwest::Client,
@@ -37,8 +44,26 @@ impl ResourceServerClient {
         name: &str,
     ) -> anyhow::Result<SystemObject> {
         let url = self.resource_url(group, namespace, kind, name);
-        self.client
-            .get(&url)
+        self.send_get(self.client.get(&url)).await
+    }
+
+    /// Same as [`get`](Self::get), converting the resource to `version` of
+    /// its kind rather than the version it is stored at.
+    pub async fn get_at_version(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        name: &str,
+        version: &str,
+    ) -> anyhow::Result<SystemObject> {
+        let url = self.resource_url(group, namespace, kind, name);
+        let request = self.client.get(&url).query(&[("version", version)]);
+        self.send_get(request).await
+    }
+
+    async fn send_get(&self, request: reqwest::RequestBuilder) -> anyhow::Result<SystemObject> {
+        request
             .send()
             .await
             .context("GET request failed")?
@@ -57,8 +82,59 @@ impl ResourceServerClient {
         kind: &str,
     ) -> anyhow::Result<Vec<SystemObject>> {
         let url = self.list_url(group, namespace, kind);
-        self.client
-            .get(&url)
+        self.send_list(self.client.get(&url)).await
+    }
+
+    /// Lists all resources of a given kind across every namespace.
+    pub async fn list_all_namespaces(
+        &self,
+        group: &str,
+        kind: &str,
+    ) -> anyhow::Result<Vec<SystemObject>> {
+        self.list(group, ALL_NAMESPACES, kind).await
+    }
+
+    /// Same as [`list`](Self::list), converting the resources to `version`
+    /// of their kind rather than the version they are stored at.
+    pub async fn list_at_version(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        version: &str,
+    ) -> anyhow::Result<Vec<SystemObject>> {
+        let url = self.list_url(group, namespace, kind);
+        let request = self.client.get(&url).query(&[("version", version)]);
+        self.send_list(request).await
+    }
+
+    /// Same as [`list`](Self::list), returning only the resources matching a
+    /// label selector such as `tier=gold,env in (dev,test)` and a field
+    /// selector such as `status.phase=Running`. Empty selectors match all.
+    pub async fn list_with_selectors(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        label_selector: &str,
+        field_selector: &str,
+    ) -> anyhow::Result<Vec<SystemObject>> {
+        let url = self.list_url(group, namespace, kind);
+        let mut query = Vec::new();
+        if !label_selector.is_empty() {
+            query.push(("labelSelector", label_selector));
+        }
+        if !field_selector.is_empty() {
+            query.push(("fieldSelector", field_selector));
+        }
+        self.send_list(self.client.get(&url).query(&query)).await
+    }
+
+    async fn send_list(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> anyhow::Result<Vec<SystemObject>> {
+        request
             .send()
             .await
             .context("LIST request failed")?
@@ -92,6 +168,118 @@ impl ResourceServerClient {
             .context("Failed to parse PUT response")
     }
 
+    /// Replaces only the status of a resource, through the `/status`
+    /// subresource. Fails with a conflict when `body` carries a stale
+    /// `metadata.resourceVersion`.
+    pub async fn set_status(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        name: &str,
+        body: &SystemObject,
+    ) -> anyhow::Result<SystemObject> {
+        let url = format!("{}/status", self.resource_url(group, namespace, kind, name));
+        self.client
+            .put(&url)
+            .json(body)
+            .send()
+            .await
+            .context("PUT status request failed")?
+            .error_for_status()
+            .context("PUT status returned non-2xx")?
+            .json::<SystemObject>()
+            .await
+            .context("Failed to parse PUT status response")
+    }
+
+    /// Applies a JSON Merge Patch (RFC 7386) to a resource, e.g.
+    /// `{"metadata": {"labels": {"tier": "gold"}}}`. A `null` removes a field.
+    pub async fn merge_patch(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        name: &str,
+        patch: &serde_json::Value,
+    ) -> anyhow::Result<SystemObject> {
+        let url = self.resource_url(group, namespace, kind, name);
+        self.patch(&url, "application/merge-patch+json", &[], patch)
+            .await
+    }
+
+    /// Applies a JSON Patch (RFC 6902), a list of operations such as
+    /// `{"op": "replace", "path": "/spec/replicas", "value": 3}`.
+    pub async fn json_patch(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        name: &str,
+        patch: &serde_json::Value,
+    ) -> anyhow::Result<SystemObject> {
+        let url = self.resource_url(group, namespace, kind, name);
+        self.patch(&url, "application/json-patch+json", &[], patch)
+            .await
+    }
+
+    /// Applies `config` server-side on behalf of `field_manager`, which then
+    /// owns every field in it. Fails with a 409 when another manager owns a
+    /// field set to a different value; see [`Self::force_apply`].
+    pub async fn apply(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        name: &str,
+        field_manager: &str,
+        config: &serde_json::Value,
+    ) -> anyhow::Result<SystemObject> {
+        let url = self.resource_url(group, namespace, kind, name);
+        let query = [("fieldManager", field_manager)];
+        self.patch(&url, "application/apply-patch+json", &query, config)
+            .await
+    }
+
+    /// Like [`Self::apply`], but takes over fields other managers own instead
+    /// of failing.
+    pub async fn force_apply(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        name: &str,
+        field_manager: &str,
+        config: &serde_json::Value,
+    ) -> anyhow::Result<SystemObject> {
+        let url = self.resource_url(group, namespace, kind, name);
+        let query = [("fieldManager", field_manager), ("force", "true")];
+        self.patch(&url, "application/apply-patch+json", &query, config)
+            .await
+    }
+
+    async fn patch(
+        &self,
+        url: &str,
+        content_type: &str,
+        query: &[(&str, &str)],
+        body: &serde_json::Value,
+    ) -> anyhow::Result<SystemObject> {
+        self.client
+            .patch(url)
+            .query(query)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(serde_json::to_vec(body)?)
+            .send()
+            .await
+            .context("PATCH request failed")?
+            .error_for_status()
+            .context("PATCH returned non-2xx")?
+            .json::<SystemObject>()
+            .await
+            .context("Failed to parse PATCH response")
+    }
+
     /// Soft-deletes a resource. Returns the updated object if the server
     /// echoes it back (when finalizers are present), or `None` on a 204.
     pub async fn delete(
@@ -102,9 +290,33 @@ impl ResourceServerClient {
         name: &str,
     ) -> anyhow::Result<Option<SystemObject>> {
         let url = self.resource_url(group, namespace, kind, name);
-        let resp = self
+        self.send_delete(self.client.delete(&url)).await
+    }
+
+    /// Same as [`delete`](Self::delete), choosing what happens to the
+    /// object's dependents: deleted after it (`Background`), deleted before it
+    /// (`Foreground`), or kept without their reference to it (`Orphan`).
+    pub async fn delete_with_propagation(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        name: &str,
+        policy: DeletionPropagation,
+    ) -> anyhow::Result<Option<SystemObject>> {
+        let url = self.resource_url(group, namespace, kind, name);
+        let request = self
             .client
             .delete(&url)
+            .query(&[("propagationPolicy", format!("{:?}", policy))]);
+        self.send_delete(request).await
+    }
+
+    async fn send_delete(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> anyhow::Result<Option<SystemObject>> {
+        let resp = request
             .send()
             .await
             .context("DELETE request failed")?
@@ -121,4 +333,101 @@ impl ResourceServerClient {
             .context("Failed to parse DELETE response")?;
         Ok(Some(obj))
     }
+
+    /// Adds and removes finalizers in one server-side update, without
+    /// reading and writing back the whole object. Returns `None` when the
+    /// object was marked for deletion and lost its last finalizer, which
+    /// removes it.
+    pub async fn finalize(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        name: &str,
+        add: &[&str],
+        remove: &[&str],
+    ) -> anyhow::Result<Option<SystemObject>> {
+        let url = format!(
+            "{}/finalize",
+            self.resource_url(group, namespace, kind, name)
+        );
+        let resp = self
+            .client
+            .post(&url)
+            .json(&serde_json::json!({ "add": add, "remove": remove }))
+            .send()
+            .await
+            .context("POST finalize request failed")?
+            .error_for_status()
+            .context("POST finalize returned non-2xx")?;
+
+        if resp.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        let obj = resp
+            .json::<SystemObject>()
+            .await
+            .context("Failed to parse finalize response")?;
+        Ok(Some(obj))
+    }
+
+    /// Adds `finalizer` to a resource unless it is already present.
+    pub async fn add_finalizer(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        name: &str,
+        finalizer: &str,
+    ) -> anyhow::Result<Option<SystemObject>> {
+        self.finalize(group, namespace, kind, name, &[finalizer], &[])
+            .await
+    }
+
+    /// Removes `finalizer` from a resource; see [`finalize`](Self::finalize).
+    pub async fn remove_finalizer(
+        &self,
+        group: &str,
+        namespace: &str,
+        kind: &str,
+        name: &str,
+        finalizer: &str,
+    ) -> anyhow::Result<Option<SystemObject>> {
+        self.finalize(group, namespace, kind, name, &[], &[finalizer])
+            .await
+    }
+
+    /// Rewrites the server's secondary indexes. Returns the counts of
+    /// indexed objects and entries: `{ "objects": .., "entries": .. }`.
+    /// Servers only serve this with `KUIPER_INDEX_ADMIN` set.
+    pub async fn rebuild_indexes(&self) -> anyhow::Result<serde_json::Value> {
+        let url = format!("{}/admin/rebuild-indexes", self.base_url);
+        self.client
+            .post(&url)
+            .send()
+            .await
+            .context("POST rebuild-indexes request failed")?
+            .error_for_status()
+            .context("POST rebuild-indexes returned non-2xx")?
+            .json::<serde_json::Value>()
+            .await
+            .context("Failed to parse rebuild-indexes response")
+    }
+
+    /// Fetches the discovery document: every group with its versions and
+    /// kinds, and the verbs and subresources each kind serves.
+    pub async fn discovery(&self) -> anyhow::Result<ApiDiscovery> {
+        let url = format!("{}/apis", self.base_url);
+        self.client
+            .get(&url)
+            .send()
+            .await
+            .context("GET apis request failed")?
+            .error_for_status()
+            .context("GET apis returned non-2xx")?
+            .json::<ApiDiscovery>()
+            .await
+            .context("Failed to parse discovery response")
+    }
 }
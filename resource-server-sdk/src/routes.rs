@@ -2,6 +2,9 @@ use serde::Serialize;
 
 /// Parsed representation of a resource URL path:
 /// `/api/{group}/{namespace}/{kind}[/{name}[/{subresource}]]`
+///
+/// `kind` is the segment as written; the server also accepts the plural,
+/// singular or short name of a kind there.
 #[derive(Debug, Clone, Serialize)]
 pub struct ResourceDescriptor {
     pub group: String,
@@ -4,8 +4,9 @@ use actix_web::{web, HttpRequest, HttpResponse};
 use actix_ws::Message;
 use futures_util::TryStreamExt;
 use kuiper_runtime::command::CommandContext;
-use models::{ClientMessage, ServerMessage};
-use resource_server_runtime::KuiperRuntime;
+use kuiper_types::error::KuiperError;
+use models::{ClientMessage, ServerMessage, Subscription};
+use resource_server_runtime::{idempotency::IDEMPOTENCY_KEY_METADATA, KuiperRuntime};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::mpsc;
@@ -41,6 +42,18 @@ fn validate_token(token: &str) -> Result<String, actix_web::Error> {
     }
 }
 
+fn parse_subscription(
+    resource: String,
+    label_selector: Option<String>,
+    field_selector: Option<String>,
+) -> Result<Subscription, KuiperError> {
+    Ok(Subscription {
+        resource,
+        label_selector: label_selector.as_deref().unwrap_or("").parse()?,
+        field_selector: field_selector.as_deref().unwrap_or("").parse()?,
+    })
+}
+
 pub async fn ws_handler(
     req: HttpRequest,
     body: web::Payload,
@@ -87,15 +100,25 @@ pub async fn ws_handler(
                         match msg {
                             Message::Text(txt) => {
                                 match serde_json::from_str::<ClientMessage>(&txt) {
-                                    Ok(ClientMessage::Subscribe { resource }) => {
-                                        if let Some(mut subs) = subscription_map.get_mut(&client_id) {
-                                            if !subs.contains(&resource) {
-                                                subs.push(resource.clone());
+                                    Ok(ClientMessage::Subscribe { resource, label_selector, field_selector }) => {
+                                        match parse_subscription(resource, label_selector, field_selector) {
+                                            Ok(subscription) => {
+                                                let resource = subscription.resource.clone();
+                                                // Subscribing again to a type replaces its selectors.
+                                                if let Some(mut subs) = subscription_map.get_mut(&client_id) {
+                                                    subs.retain(|s| s.resource != resource);
+                                                    subs.push(subscription);
+                                                }
+                                                let _ = tx.send(ServerMessage::Subscribed { resource });
+                                            }
+                                            Err(e) => {
+                                                let _ = tx.send(ServerMessage::Error {
+                                                    message: e.to_string(),
+                                                });
                                             }
                                         }
-                                        let _ = tx.send(ServerMessage::Subscribed { resource });
                                     }
-                                    Ok(ClientMessage::Rpc { method, payload }) => {
+                                    Ok(ClientMessage::Rpc { method, payload, idempotency_key }) => {
                                         let mut ctx = CommandContext {
                                             command_name: method,
                                             parameters: HashMap::new(),
@@ -104,7 +127,12 @@ pub async fn ws_handler(
                                             caller_id: None,
                                             cancellation_token: CancellationToken::new(),
                                             is_internal: false,
+                                            defer_observers: false,
+                                            write_batch: None,
                                         };
+                                        if let Some(key) = idempotency_key {
+                                            ctx.metadata.insert(IDEMPOTENCY_KEY_METADATA.to_string(), key);
+                                        }
                                         // Flatten JSON object payload into individual parameters.
                                         if let Some(obj) = payload.as_object() {
                                             for (k, v) in obj {
@@ -1,13 +1,46 @@
+use kuiper_types::model::{
+    resource::SystemObject,
+    selector::{FieldSelector, LabelSelector},
+};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+/// Subscribes to events for every resource type.
+pub const WILDCARD_RESOURCE: &str = "*";
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum ClientMessage {
     #[serde(rename = "subscribe")]
-    Subscribe { resource: String },
+    Subscribe {
+        resource: String,
+        /// Only events for objects whose labels match.
+        #[serde(
+            rename = "labelSelector",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        label_selector: Option<String>,
+        /// Only events for objects whose fields match.
+        #[serde(
+            rename = "fieldSelector",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        field_selector: Option<String>,
+    },
     #[serde(rename = "rpc")]
-    Rpc { method: String, payload: Value },
+    Rpc {
+        method: String,
+        payload: Value,
+        /// Replays the stored response when a mutating call is retried.
+        #[serde(
+            rename = "idempotencyKey",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        idempotency_key: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -30,4 +63,34 @@ pub enum ServerMessage {
     RpcResult { value: Value },
     #[serde(rename = "error")]
     Error { message: String },
+    /// A `ResourceDefinition` was written; `/apis` describes the change.
+    #[serde(rename = "discovery_changed")]
+    DiscoveryChanged,
+}
+
+/// A client's interest in events for one resource type.
+#[derive(Debug, Clone)]
+pub struct Subscription {
+    /// `"{apiVersion}/{kind}"`, or [`WILDCARD_RESOURCE`].
+    pub resource: String,
+    pub label_selector: LabelSelector,
+    pub field_selector: FieldSelector,
+}
+
+impl Subscription {
+    /// Every event for `resource`.
+    pub fn new(resource: impl Into<String>) -> Self {
+        Self {
+            resource: resource.into(),
+            label_selector: LabelSelector::default(),
+            field_selector: FieldSelector::default(),
+        }
+    }
+
+    /// Whether an event about `object`, of type `resource`, is wanted.
+    pub fn matches(&self, resource: &str, object: &SystemObject) -> bool {
+        (self.resource == resource || self.resource == WILDCARD_RESOURCE)
+            && self.label_selector.matches(object)
+            && self.field_selector.matches(object)
+    }
 }
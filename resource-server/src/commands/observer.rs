@@ -11,8 +11,6 @@ use tokio::sync::RwLock;
 
 use crate::{SubscriberMap, SubscriptionMap};
 
-const WILDCARD_RESOURCE: &str = "*";
-
 pub struct SetObserverCommand {
     store: Arc<RwLock<dyn TransactionalKeyValueStore>>,
     subscribers: SubscriberMap,
@@ -51,29 +49,40 @@ impl CommandHandler for SetObserverCommand {
 #[async_trait]
 impl ExecutableCommand for SetObserverCommand {
     async fn execute(&self, ctx: &CommandContext) -> CommandResult {
-        let value = match ctx.get_param("value") {
-            Ok(v) => v,
-            Err(_) => return Ok(None), // no result to observe
+        let Some(system_object) = ctx.param::<Option<SystemObject>>("value")? else {
+            return Ok(None); // no result to observe
         };
 
-        let system_object = serde_json::from_str::<SystemObject>(&value)
-            .context("Failed to deserialize 'value' parameter")?;
-
         let resource = format!("{}/{}", system_object.api_version, system_object.kind);
 
         let ctx_value =
             serde_json::to_value(&system_object).context("Failed to serialize system object")?;
 
-        let wildcard = WILDCARD_RESOURCE.to_string();
+        // The registry has reloaded by now, so every client, subscribed or
+        // not, can fetch the new discovery document.
+        if system_object
+            .kind
+            .eq_ignore_ascii_case("ResourceDefinition")
+        {
+            for entry in self.subscribers.iter() {
+                if let Err(e) = entry
+                    .value()
+                    .send(crate::actors::models::ServerMessage::DiscoveryChanged)
+                {
+                    tracing::warn!("Failed to notify subscriber {}: {}", entry.key(), e);
+                }
+            }
+        }
 
         for entry in self.subscribers.iter() {
             let client_id = entry.key();
 
-            // Notify clients subscribed to this specific resource type or to the wildcard "*".
+            // Notify clients subscribed to this specific resource type or to the
+            // wildcard "*", with selectors matching the object.
             let is_subscribed = self
                 .subscription_map
                 .get(client_id)
-                .map(|subs| subs.contains(&resource) || subs.contains(&wildcard))
+                .map(|subs| subs.iter().any(|s| s.matches(&resource, &system_object)))
                 .unwrap_or(false);
 
             if !is_subscribed {
@@ -135,29 +144,24 @@ impl CommandHandler for DeleteObserverCommand {
 #[async_trait]
 impl ExecutableCommand for DeleteObserverCommand {
     async fn execute(&self, ctx: &CommandContext) -> CommandResult {
-        let value = match ctx.get_param("value") {
-            Ok(v) => v,
-            Err(_) => return Ok(None), // no result to observe
+        let Some(system_object) = ctx.param::<Option<SystemObject>>("value")? else {
+            return Ok(None); // no result to observe
         };
 
-        let system_object = serde_json::from_str::<SystemObject>(&value)
-            .context("Failed to deserialize 'value' parameter")?;
-
         let resource = format!("{}/{}", system_object.api_version, system_object.kind);
 
         let ctx_value =
             serde_json::to_value(&system_object).context("Failed to serialize system object")?;
 
-        let wildcard = WILDCARD_RESOURCE.to_string();
-
         for entry in self.subscribers.iter() {
             let client_id = entry.key();
 
-            // Notify clients subscribed to this specific resource type or to the wildcard "*".
+            // Notify clients subscribed to this specific resource type or to the
+            // wildcard "*", with selectors matching the object.
             let is_subscribed = self
                 .subscription_map
                 .get(client_id)
-                .map(|subs| subs.contains(&resource) || subs.contains(&wildcard))
+                .map(|subs| subs.iter().any(|s| s.matches(&resource, &system_object)))
                 .unwrap_or(false);
 
             if !is_subscribed {
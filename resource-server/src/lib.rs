@@ -8,16 +8,25 @@ pub mod middleware;
 pub mod routing;
 pub mod services;
 
-use actix_web::{get, put, web, HttpRequest, HttpResponse, Responder};
-use actors::models::ServerMessage;
+use actix_web::{get, patch, post, put, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use actors::models::{ServerMessage, Subscription};
 use actors::ws_handler;
 use dashmap::DashMap;
 use kuiper_runtime::command::CommandContext;
+use kuiper_runtime::service::ServiceHost;
 use kuiper_types::error::KuiperError;
+use kuiper_types::model::resource::DeletionPropagation;
+use kuiper_types::model::security::UserId;
+use resource_server_runtime::handlers::{
+    apply::ApplyRequest, list::ALL_NAMESPACES, patch::PatchType,
+};
+use resource_server_runtime::idempotency::{IDEMPOTENCY_KEY_METADATA, IDEMPOTENT_REPLAY_METADATA};
 use resource_server_runtime::KuiperRuntime;
 use routing::ResourceDescriptor;
+use serde::Deserialize;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio_util::sync::CancellationToken;
@@ -25,7 +34,7 @@ use tokio_util::sync::CancellationToken;
 pub type ClientId = String;
 pub type SubscriberMap = Arc<DashMap<ClientId, UnboundedSender<ServerMessage>>>;
 /// Maps each connected client to the resource types it has subscribed to (e.g. `"apiVersion/Kind"`).
-pub type SubscriptionMap = Arc<DashMap<ClientId, Vec<String>>>;
+pub type SubscriptionMap = Arc<DashMap<ClientId, Vec<Subscription>>>;
 
 pub fn kuiper_error_response(e: anyhow::Error) -> HttpResponse {
     if let Some(kuiper_err) = e.downcast_ref::<KuiperError>() {
@@ -42,6 +51,79 @@ pub fn kuiper_error_response(e: anyhow::Error) -> HttpResponse {
     HttpResponse::InternalServerError().body(e.to_string())
 }
 
+/// Request header carrying a client-chosen idempotency key for PUT/DELETE.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Response header set to `true` when the response replays a stored result.
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Longest accepted `Idempotency-Key` value.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Copies the `Idempotency-Key` header, if any, into the command metadata.
+fn apply_idempotency_key(req: &HttpRequest, ctx: &mut CommandContext) -> Result<(), HttpResponse> {
+    let Some(header) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(());
+    };
+
+    match header.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN => {
+            ctx.metadata
+                .insert(IDEMPOTENCY_KEY_METADATA.to_string(), key.to_string());
+            Ok(())
+        }
+        _ => Err(HttpResponse::BadRequest().body(format!(
+            "{} must be 1-{} visible ASCII characters",
+            IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LEN
+        ))),
+    }
+}
+
+/// The authenticated caller. Authentication middleware installed by the host
+/// stores the caller's [`UserId`] in the request extensions; without one the
+/// command runs anonymously.
+fn caller_id(req: &HttpRequest) -> Option<UserId> {
+    req.extensions().get::<UserId>().cloned()
+}
+
+/// Flags responses that were replayed from an earlier request with the same key.
+fn mark_idempotent_replay(ctx: &CommandContext, mut resp: HttpResponse) -> HttpResponse {
+    if ctx.metadata.contains_key(IDEMPOTENT_REPLAY_METADATA) {
+        resp.headers_mut().insert(
+            actix_web::http::header::HeaderName::from_static(IDEMPOTENT_REPLAYED_HEADER),
+            actix_web::http::header::HeaderValue::from_static("true"),
+        );
+    }
+    resp
+}
+
+/// `*` lists a kind across namespaces; reads and writes of one object need
+/// its namespace.
+fn require_single_namespace(descriptor: &ResourceDescriptor) -> Result<(), HttpResponse> {
+    if descriptor.namespace == ALL_NAMESPACES {
+        return Err(HttpResponse::BadRequest().body(format!(
+            "Namespace '{}' is only valid when listing",
+            ALL_NAMESPACES
+        )));
+    }
+    Ok(())
+}
+
+/// The resource path `descriptor` addresses, its kind segment resolved
+/// through the registry.
+async fn resource_path(
+    rt: &KuiperRuntime,
+    descriptor: &ResourceDescriptor,
+) -> Result<String, HttpResponse> {
+    rt.resource_path(
+        &descriptor.group,
+        &descriptor.kind,
+        descriptor.name.as_deref(),
+    )
+    .await
+    .map_err(kuiper_error_response)
+}
+
 pub fn truncate(s: &str, max_chars: usize) -> &str {
     match s.char_indices().nth(max_chars) {
         None => s,
@@ -62,6 +144,8 @@ pub async fn version_handler(
         caller_id: None,
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     match runtime.execute(&mut ctx).await {
@@ -71,6 +155,94 @@ pub async fn version_handler(
     }
 }
 
+/// Lists the registered commands with their handler pipelines and input schemas.
+#[get("/commands")]
+pub async fn commands_handler(rt: web::Data<Arc<KuiperRuntime>>) -> impl Responder {
+    HttpResponse::Ok().json(rt.commands())
+}
+
+/// Describes a single command.
+#[get("/commands/{name}")]
+pub async fn command_handler(
+    rt: web::Data<Arc<KuiperRuntime>>,
+    name: web::Path<String>,
+) -> impl Responder {
+    match rt.commands().into_iter().find(|c| c.name == *name) {
+        Some(command) => HttpResponse::Ok().json(command),
+        None => HttpResponse::NotFound().body(format!("Unknown command '{}'", name)),
+    }
+}
+
+/// Reports the state of each hosted service. Responds `503` until every
+/// service is running and ready. Hosts without a `ServiceHost` are always ready.
+#[get("/health")]
+pub async fn health_handler(host: Option<web::Data<Arc<ServiceHost>>>) -> impl Responder {
+    let services = host.map(|h| h.status()).unwrap_or_default();
+    let ready = services.iter().all(|s| s.ready);
+    let body = serde_json::json!({ "ready": ready, "services": services });
+
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+/// Describes the registered groups, their versions and kinds, with the verbs
+/// and subresources each kind serves.
+#[get("/apis")]
+pub async fn discovery_handler(rt: web::Data<Arc<KuiperRuntime>>) -> impl Responder {
+    let mut ctx = CommandContext {
+        command_name: "discovery".to_string(),
+        parameters: HashMap::new(),
+        metadata: HashMap::new(),
+        activity_id: uuid::Uuid::new_v4(),
+        caller_id: None,
+        cancellation_token: CancellationToken::new(),
+        is_internal: false,
+        defer_observers: false,
+        write_batch: None,
+    };
+
+    match rt.execute(&mut ctx).await {
+        Ok(Some(result)) => HttpResponse::Ok().json(result),
+        Ok(None) => HttpResponse::NoContent().finish(),
+        Err(e) => kuiper_error_response(e),
+    }
+}
+
+/// Serves the OpenAPI 3 document of the REST API for the registered kinds.
+#[get("/openapi/v3")]
+pub async fn openapi_handler(rt: web::Data<Arc<KuiperRuntime>>) -> impl Responder {
+    let openapi = rt.registry().read().await.openapi();
+    HttpResponse::Ok().json(&*openapi)
+}
+
+/// Rewrites the secondary indexes of every stored object. Responds with the
+/// number of objects and index entries. Mounted by [`configure_admin`] only.
+#[post("/admin/rebuild-indexes")]
+pub async fn rebuild_indexes_handler(rt: web::Data<Arc<KuiperRuntime>>) -> impl Responder {
+    let mut ctx = CommandContext {
+        command_name: "rebuild_indexes".to_string(),
+        parameters: HashMap::new(),
+        metadata: HashMap::new(),
+        activity_id: uuid::Uuid::new_v4(),
+        caller_id: None,
+        cancellation_token: CancellationToken::new(),
+        is_internal: false,
+        defer_observers: false,
+        write_batch: None,
+    };
+
+    match rt.execute(&mut ctx).await {
+        Ok(Some(result)) => HttpResponse::Ok().json(result),
+        Ok(None) => HttpResponse::NoContent().finish(),
+        Err(e) => kuiper_error_response(e),
+    }
+}
+
+/// Creates or replaces an object, or replaces its status through the
+/// `/status` subresource.
 #[put("/api/{tail:.*}")]
 pub async fn api_put_handler(
     rt: web::Data<Arc<KuiperRuntime>>,
@@ -89,36 +261,363 @@ pub async fn api_put_handler(
         Err(e) => return HttpResponse::BadRequest().body(format!("Invalid path: {}, {}", path, e)),
     };
 
-    let name = match &descriptor.name {
-        Some(n) => n.clone(),
-        None => return HttpResponse::BadRequest().body("PUT requires a resource name"),
+    if let Err(resp) = require_single_namespace(&descriptor) {
+        return resp;
+    }
+
+    if descriptor.name.is_none() {
+        return HttpResponse::BadRequest().body("PUT requires a resource name");
+    }
+
+    // `PUT .../{name}/status` replaces only the status.
+    let command_name = match descriptor.subresource.as_deref() {
+        None => "set",
+        Some("status") => "set_status",
+        Some(_) => return HttpResponse::MethodNotAllowed().body("Method PUT not allowed"),
+    };
+
+    let resource = match resource_path(&rt, &descriptor).await {
+        Ok(r) => r,
+        Err(resp) => return resp,
     };
 
     let mut ctx = CommandContext {
-        command_name: "set".to_string(),
+        command_name: command_name.to_string(),
         parameters: HashMap::new(),
         metadata: HashMap::new(),
         activity_id: uuid::Uuid::new_v4(),
-        caller_id: None,
+        caller_id: caller_id(&req),
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     ctx.parameters.insert("value".to_string(), body.clone());
-    ctx.parameters.insert(
-        "resource".to_string(),
-        serde_json::json!(format!("{}/{}/{}", descriptor.group, descriptor.kind, name)),
-    );
+    ctx.parameters
+        .insert("resource".to_string(), serde_json::json!(resource));
     ctx.metadata
         .insert("namespace".to_string(), descriptor.namespace.clone());
 
-    match rt.execute(&mut ctx).await {
+    if let Err(resp) = apply_idempotency_key(&req, &mut ctx) {
+        return resp;
+    }
+
+    let resp = match rt.execute(&mut ctx).await {
+        Ok(Some(value)) => HttpResponse::Ok().json(value),
+        Ok(None) => HttpResponse::Ok().finish(),
+        Err(e) => kuiper_error_response(e),
+    };
+    mark_idempotent_replay(&ctx, resp)
+}
+
+/// Patches an object with a JSON Merge Patch (`application/merge-patch+json`)
+/// or a JSON Patch (`application/json-patch+json`). The optional
+/// `resourceVersion` query parameter makes the patch conditional, and
+/// `version` names the version of the kind the patch is written against.
+///
+/// With `application/apply-patch+json` the body is a configuration applied
+/// server-side on behalf of the `fieldManager` query parameter; `force=true`
+/// takes over fields other managers own.
+#[patch("/api/{tail:.*}")]
+pub async fn api_patch_handler(
+    rt: web::Data<Arc<KuiperRuntime>>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> impl Responder {
+    let full_path = req.path();
+
+    let path = full_path
+        .strip_prefix("/api/")
+        .or_else(|| full_path.strip_prefix("/api"))
+        .unwrap_or(full_path);
+
+    let descriptor = match ResourceDescriptor::parse(path) {
+        Ok(d) => d,
+        Err(e) => return HttpResponse::BadRequest().body(format!("Invalid path: {}, {}", path, e)),
+    };
+
+    if let Err(resp) = require_single_namespace(&descriptor) {
+        return resp;
+    }
+
+    let (Some(_), None) = (&descriptor.name, &descriptor.subresource) else {
+        return HttpResponse::MethodNotAllowed().body("Method PATCH not allowed");
+    };
+
+    let content_type = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    let is_apply = mime.eq_ignore_ascii_case(ApplyRequest::CONTENT_TYPE);
+    let patch_type = PatchType::from_content_type(content_type);
+    if patch_type.is_none() && !is_apply {
+        return HttpResponse::UnsupportedMediaType().body(format!(
+            "PATCH requires Content-Type {}, {} or {}",
+            PatchType::MERGE_PATCH_CONTENT_TYPE,
+            PatchType::JSON_PATCH_CONTENT_TYPE,
+            ApplyRequest::CONTENT_TYPE
+        ));
+    }
+
+    let patch: Value = match serde_json::from_slice(&body) {
+        Ok(p) => p,
+        Err(e) => return HttpResponse::BadRequest().body(format!("Invalid patch body: {}", e)),
+    };
+
+    let query = match web::Query::<HashMap<String, String>>::from_query(req.query_string()) {
+        Ok(q) => q,
+        Err(e) => return HttpResponse::BadRequest().body(format!("Invalid query: {}", e)),
+    };
+
+    let resource = match resource_path(&rt, &descriptor).await {
+        Ok(r) => r,
+        Err(resp) => return resp,
+    };
+
+    let mut ctx = CommandContext {
+        command_name: if is_apply { "apply" } else { "patch" }.to_string(),
+        parameters: HashMap::new(),
+        metadata: HashMap::new(),
+        activity_id: uuid::Uuid::new_v4(),
+        caller_id: caller_id(&req),
+        cancellation_token: CancellationToken::new(),
+        is_internal: false,
+        defer_observers: false,
+        write_batch: None,
+    };
+
+    ctx.parameters
+        .insert("resource".to_string(), serde_json::json!(resource));
+    match patch_type {
+        Some(patch_type) => {
+            ctx.parameters
+                .insert("patchType".to_string(), serde_json::json!(patch_type));
+            ctx.parameters.insert("patch".to_string(), patch);
+            for parameter in ["resourceVersion", "version"] {
+                if let Some(value) = query.get(parameter) {
+                    ctx.parameters
+                        .insert(parameter.to_string(), serde_json::json!(value));
+                }
+            }
+        }
+        None => {
+            let Some(manager) = query.get("fieldManager").filter(|m| !m.is_empty()) else {
+                return HttpResponse::BadRequest()
+                    .body("Apply requires the fieldManager query parameter");
+            };
+            let force = match query.get("force").map(String::as_str) {
+                None | Some("false") => false,
+                Some("true") => true,
+                Some(other) => {
+                    return HttpResponse::BadRequest().body(format!("Invalid force: '{}'", other))
+                }
+            };
+            ctx.parameters
+                .insert("fieldManager".to_string(), serde_json::json!(manager));
+            ctx.parameters
+                .insert("force".to_string(), serde_json::json!(force));
+            ctx.parameters.insert("value".to_string(), patch);
+        }
+    }
+    ctx.metadata
+        .insert("namespace".to_string(), descriptor.namespace.clone());
+
+    if let Err(resp) = apply_idempotency_key(&req, &mut ctx) {
+        return resp;
+    }
+
+    let resp = match rt.execute(&mut ctx).await {
         Ok(Some(value)) => HttpResponse::Ok().json(value),
         Ok(None) => HttpResponse::Ok().finish(),
         Err(e) => kuiper_error_response(e),
+    };
+    mark_idempotent_replay(&ctx, resp)
+}
+
+/// Body of `POST /api/{group}/{namespace}/{kind}/{name}/finalize`.
+#[derive(Debug, Deserialize)]
+pub struct FinalizeBody {
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// Adds and removes finalizers through the `/finalize` subresource. Responds
+/// with the updated object, or `204 No Content` when removing the last
+/// finalizer of an object marked for deletion removed it.
+#[post("/api/{tail:.*}")]
+pub async fn api_post_handler(
+    rt: web::Data<Arc<KuiperRuntime>>,
+    req: HttpRequest,
+    body: web::Json<Value>,
+) -> impl Responder {
+    let full_path = req.path();
+
+    let path = full_path
+        .strip_prefix("/api/")
+        .or_else(|| full_path.strip_prefix("/api"))
+        .unwrap_or(full_path);
+
+    let descriptor = match ResourceDescriptor::parse(path) {
+        Ok(d) => d,
+        Err(e) => return HttpResponse::BadRequest().body(format!("Invalid path: {}, {}", path, e)),
+    };
+
+    if let Err(resp) = require_single_namespace(&descriptor) {
+        return resp;
     }
+
+    let (Some(_), Some("finalize")) = (&descriptor.name, descriptor.subresource.as_deref()) else {
+        return HttpResponse::MethodNotAllowed().body("Method POST not allowed");
+    };
+
+    let FinalizeBody { add, remove } = match serde_json::from_value(body.into_inner()) {
+        Ok(b) => b,
+        Err(e) => return HttpResponse::BadRequest().body(format!("Invalid finalize body: {}", e)),
+    };
+
+    let resource = match resource_path(&rt, &descriptor).await {
+        Ok(r) => r,
+        Err(resp) => return resp,
+    };
+
+    let mut ctx = CommandContext {
+        command_name: "finalize".to_string(),
+        parameters: HashMap::new(),
+        metadata: HashMap::new(),
+        activity_id: uuid::Uuid::new_v4(),
+        caller_id: caller_id(&req),
+        cancellation_token: CancellationToken::new(),
+        is_internal: false,
+        defer_observers: false,
+        write_batch: None,
+    };
+
+    ctx.parameters
+        .insert("resource".to_string(), serde_json::json!(resource));
+    ctx.parameters
+        .insert("add".to_string(), serde_json::json!(add));
+    ctx.parameters
+        .insert("remove".to_string(), serde_json::json!(remove));
+    ctx.metadata
+        .insert("namespace".to_string(), descriptor.namespace.clone());
+
+    if let Err(resp) = apply_idempotency_key(&req, &mut ctx) {
+        return resp;
+    }
+
+    let resp = match rt.execute(&mut ctx).await {
+        Ok(Some(value)) => HttpResponse::Ok().json(value),
+        Ok(None) => HttpResponse::NoContent().finish(),
+        Err(e) => kuiper_error_response(e),
+    };
+    mark_idempotent_replay(&ctx, resp)
 }
 
+/// One entry of a `POST /api/batch` body. `path` uses the same
+/// `{group}/{namespace}/{kind}/{name}` shape as the resource routes.
+#[derive(Debug, Deserialize)]
+pub struct BatchRequestItem {
+    pub op: String,
+    pub path: String,
+    #[serde(default)]
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchRequestItem>,
+}
+
+/// Applies an ordered list of set/delete operations in one transaction.
+/// Responds with per-item results, or with the first failing item's error
+/// when nothing was applied.
+#[post("/api/batch")]
+pub async fn api_batch_handler(
+    rt: web::Data<Arc<KuiperRuntime>>,
+    req: HttpRequest,
+    body: web::Json<BatchRequest>,
+) -> impl Responder {
+    let mut operations = Vec::with_capacity(body.operations.len());
+
+    for (index, item) in body.operations.iter().enumerate() {
+        let descriptor = match ResourceDescriptor::parse(&item.path) {
+            Ok(d) => d,
+            Err(e) => {
+                return HttpResponse::BadRequest().body(format!(
+                    "operation {}: invalid path {}: {}",
+                    index, item.path, e
+                ))
+            }
+        };
+
+        if descriptor.name.is_none() {
+            return HttpResponse::BadRequest().body(format!(
+                "operation {}: path requires a resource name",
+                index
+            ));
+        }
+        if descriptor.namespace == ALL_NAMESPACES {
+            return HttpResponse::BadRequest().body(format!(
+                "operation {}: path requires a single namespace",
+                index
+            ));
+        }
+        let resource = match rt
+            .resource_path(
+                &descriptor.group,
+                &descriptor.kind,
+                descriptor.name.as_deref(),
+            )
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                return HttpResponse::BadRequest().body(format!("operation {}: {}", index, e))
+            }
+        };
+
+        operations.push(serde_json::json!({
+            "op": item.op,
+            "namespace": descriptor.namespace,
+            "resource": resource,
+            "value": item.value,
+        }));
+    }
+
+    let mut ctx = CommandContext {
+        command_name: "batch".to_string(),
+        parameters: HashMap::new(),
+        metadata: HashMap::new(),
+        activity_id: uuid::Uuid::new_v4(),
+        caller_id: caller_id(&req),
+        cancellation_token: CancellationToken::new(),
+        is_internal: false,
+        defer_observers: false,
+        write_batch: None,
+    };
+
+    ctx.parameters
+        .insert("operations".to_string(), Value::Array(operations));
+
+    if let Err(resp) = apply_idempotency_key(&req, &mut ctx) {
+        return resp;
+    }
+
+    let resp = match rt.execute(&mut ctx).await {
+        Ok(Some(value)) => HttpResponse::Ok().json(value),
+        Ok(None) => HttpResponse::Ok().finish(),
+        Err(e) => kuiper_error_response(e),
+    };
+    mark_idempotent_replay(&ctx, resp)
+}
+
+/// Gets, lists and deletes objects. Lists under the `*` namespace span all
+/// namespaces.
 pub async fn api_handler(rt: web::Data<Arc<KuiperRuntime>>, req: HttpRequest) -> impl Responder {
     let full_path = req.path();
 
@@ -134,48 +633,98 @@ pub async fn api_handler(rt: web::Data<Arc<KuiperRuntime>>, req: HttpRequest) ->
 
     let method = req.method().as_str();
 
-    let (command_name, resource_path) = match method {
-        "GET" => match &descriptor.name {
-            Some(name) => (
-                "get",
-                format!("{}/{}/{}", descriptor.group, descriptor.kind, name),
-            ),
-            None => ("list", format!("{}/{}", descriptor.group, descriptor.kind)),
-        },
-        "DELETE" => match &descriptor.name {
-            Some(name) => (
-                "delete",
-                format!("{}/{}/{}", descriptor.group, descriptor.kind, name),
-            ),
-            None => return HttpResponse::BadRequest().body("DELETE requires a resource name"),
-        },
+    if descriptor.name.is_some() {
+        if let Err(resp) = require_single_namespace(&descriptor) {
+            return resp;
+        }
+    }
+
+    let command_name = match (method, &descriptor.name) {
+        ("GET", Some(_)) => "get",
+        ("GET", None) => "list",
+        ("DELETE", Some(_)) => "delete",
+        ("DELETE", None) => {
+            return HttpResponse::BadRequest().body("DELETE requires a resource name")
+        }
         _ => {
             return HttpResponse::MethodNotAllowed().body(format!("Method {} not allowed", method))
         }
     };
 
+    let resource = match resource_path(&rt, &descriptor).await {
+        Ok(r) => r,
+        Err(resp) => return resp,
+    };
+
     let mut ctx = CommandContext {
         command_name: command_name.to_string(),
         parameters: HashMap::new(),
         metadata: HashMap::new(),
         activity_id: uuid::Uuid::new_v4(),
-        caller_id: None,
+        caller_id: caller_id(&req),
         cancellation_token: CancellationToken::new(),
         is_internal: false,
+        defer_observers: false,
+        write_batch: None,
     };
 
     ctx.parameters
-        .insert("resource".to_string(), serde_json::json!(resource_path));
+        .insert("resource".to_string(), serde_json::json!(resource));
     ctx.metadata
         .insert("namespace".to_string(), descriptor.namespace.clone());
 
-    match rt.execute(&mut ctx).await {
+    if method == "GET" {
+        let query = match web::Query::<HashMap<String, String>>::from_query(req.query_string()) {
+            Ok(q) => q,
+            Err(e) => return HttpResponse::BadRequest().body(format!("Invalid query: {}", e)),
+        };
+        let parameters: &[&str] = match command_name {
+            "list" => &["labelSelector", "fieldSelector", "version"],
+            _ => &["version"],
+        };
+        for parameter in parameters {
+            if let Some(value) = query.get(*parameter) {
+                ctx.parameters
+                    .insert(parameter.to_string(), serde_json::json!(value));
+            }
+        }
+    }
+
+    if method == "DELETE" {
+        match deletion_propagation(&req) {
+            Ok(Some(policy)) => {
+                ctx.parameters
+                    .insert("propagationPolicy".to_string(), serde_json::json!(policy));
+            }
+            Ok(None) => {}
+            Err(resp) => return resp,
+        }
+    }
+
+    if let Err(resp) = apply_idempotency_key(&req, &mut ctx) {
+        return resp;
+    }
+
+    let resp = match rt.execute(&mut ctx).await {
         Ok(Some(value)) if method == "DELETE" => HttpResponse::Accepted().json(value),
         Ok(Some(value)) => HttpResponse::Ok().json(value),
         // Hard-delete: no finalizers, resource removed immediately.
         Ok(None) => HttpResponse::NoContent().finish(),
         Err(e) => kuiper_error_response(e),
-    }
+    };
+    mark_idempotent_replay(&ctx, resp)
+}
+
+/// Reads the `propagationPolicy` query parameter of a DELETE
+/// (`Background`, `Foreground` or `Orphan`).
+fn deletion_propagation(req: &HttpRequest) -> Result<Option<DeletionPropagation>, HttpResponse> {
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .map_err(|e| HttpResponse::BadRequest().body(format!("Invalid query: {}", e)))?;
+    query
+        .get("propagationPolicy")
+        .map(|p| DeletionPropagation::from_str(p))
+        .transpose()
+        .map_err(|e| HttpResponse::BadRequest().body(e))
 }
 
 /// Registers all route handlers and shared app data onto the given `ServiceConfig`.
@@ -191,7 +740,22 @@ pub fn configure_app(
         .app_data(web::Data::new(subscribers))
         .app_data(web::Data::new(subscription_map))
         .service(version_handler)
+        .service(commands_handler)
+        .service(command_handler)
+        .service(health_handler)
+        .service(discovery_handler)
+        .service(openapi_handler)
         .service(api_put_handler)
+        .service(api_batch_handler)
+        .service(api_post_handler)
+        .service(api_patch_handler)
         .route("/ws", web::get().to(ws_handler))
         .route("/api/{tail:.*}", web::route().to(api_handler));
 }
+
+/// Registers the administrative routes, which are not part of
+/// [`configure_app`]. Mount them only where operators alone can reach them,
+/// for a runtime built with `KuiperRuntimeBuilder::with_index_admin`.
+pub fn configure_admin(cfg: &mut web::ServiceConfig) {
+    cfg.service(rebuild_indexes_handler);
+}
@@ -2,6 +2,7 @@ use std::sync::OnceLock;
 
 use opentelemetry::global::{self, BoxedTracer};
 use opentelemetry::trace::{TraceContextExt as _, TracerProvider as _};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
 use opentelemetry_sdk::trace::SdkTracerProvider;
 use tracing::Span;
 use tracing_opentelemetry::OpenTelemetrySpanExt as _;
@@ -32,6 +33,15 @@ pub fn get_current_activity_id() -> Option<String> {
     }
 }
 
+fn init_meter() {
+    // Periodically export metrics (command/handler latency and error counts) to stdout
+    let meter_provider = SdkMeterProvider::builder()
+        .with_periodic_exporter(opentelemetry_stdout::MetricExporter::default())
+        .build();
+
+    global::set_meter_provider(meter_provider);
+}
+
 fn init_tracer(level: &str) {
     // Create a new OpenTelemetry trace pipeline that prints to stdout
     let sdk_provider = SdkTracerProvider::builder()
@@ -62,6 +72,7 @@ pub fn init(level: &str) {
 
     if *INITIALIZED.get_or_init(|| {
         init_tracer(level);
+        init_meter();
         true
     }) {
         return;
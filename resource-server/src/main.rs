@@ -3,18 +3,24 @@
 //--------------------------------------------------------------------------
 
 use actix_web::middleware::Logger;
-use actix_web::{App, HttpServer};
+use actix_web::{web, App, HttpServer};
 use dashmap::DashMap;
 use kuiper_runtime::data::file_system_store::FileSystemStore;
 use kuiper_runtime::data::TransactionalKeyValueStore;
+use kuiper_runtime::service::{shutdown_signal, ServiceHost, Singleton};
 use kuiper_runtime::KuiperConfig;
 use resource_server::{
     commands::observer::{DeleteObserverCommand, SetObserverCommand},
-    configure_app, SubscriberMap, SubscriptionMap,
+    configure_admin, configure_app, SubscriberMap, SubscriptionMap,
 };
+use resource_server_runtime::audit::{
+    AuditPolicy, AuditSink, JsonLinesFileSink, StoreAuditSink, TracingAuditSink,
+};
+use resource_server_runtime::services::{LeaderElectionOptions, LeaderElector};
 use resource_server_runtime::KuiperRuntimeBuilder;
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -34,6 +40,19 @@ async fn main() -> std::io::Result<()> {
 
     let mut builder = KuiperRuntimeBuilder::new(shared_store.clone());
     builder.with_admission_webhooks();
+    builder.with_outbox();
+    builder.with_scheduler();
+    builder.with_garbage_collector();
+    builder.with_storage_migration();
+    builder.with_idempotency();
+    builder.with_strict_mode(config.strict_mode);
+    if config.index_admin {
+        builder.with_index_admin();
+    }
+    builder.with_audit(
+        AuditPolicy::default(),
+        build_audit_sinks(&config, &shared_store)?,
+    );
     builder.register_handler(
         "set",
         Arc::new(SetObserverCommand::new(
@@ -50,6 +69,22 @@ async fn main() -> std::io::Result<()> {
             subscription_map.clone(),
         )),
     );
+    builder.register_handler(
+        "set_status",
+        Arc::new(SetObserverCommand::new(
+            shared_store.clone(),
+            subscribers.clone(),
+            subscription_map.clone(),
+        )),
+    );
+    builder.register_handler(
+        "finalize",
+        Arc::new(SetObserverCommand::new(
+            shared_store.clone(),
+            subscribers.clone(),
+            subscription_map.clone(),
+        )),
+    );
     let runtime = Arc::new(builder.build());
 
     runtime
@@ -57,16 +92,78 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Failed to initialize runtime — could not seed/load ResourceDefinitions");
 
+    // Observers run from the durable outbox, delivered in the background.
+    // Every replica delivers the entries it staged, so WebSocket events reach
+    // the subscribers of the replica that served the write.
+    let mut host = ServiceHost::new();
+    host.register(
+        "outbox",
+        runtime
+            .outbox_service()
+            .expect("outbox is enabled on the runtime builder"),
+    );
+    // Only the replica holding the leader lease runs `ScheduledCommand`s,
+    // collects garbage and migrates objects to their storage version.
+    let elector = LeaderElector::new(
+        shared_store.clone(),
+        LeaderElectionOptions::new("resource-server"),
+    );
+    host.register("leader-election", elector.clone());
+    host.register(
+        "scheduler",
+        Singleton::new(
+            elector.clone(),
+            runtime
+                .scheduler()
+                .expect("scheduler is enabled on the runtime builder"),
+        ),
+    )
+    .depends_on("outbox")
+    .depends_on("leader-election");
+    host.register(
+        "garbage-collector",
+        Singleton::new(
+            elector.clone(),
+            runtime
+                .garbage_collector()
+                .expect("garbage collector is enabled on the runtime builder"),
+        ),
+    )
+    .depends_on("outbox")
+    .depends_on("leader-election");
+    host.register(
+        "storage-migration",
+        Singleton::new(
+            elector,
+            runtime
+                .storage_migrator()
+                .expect("storage migration is enabled on the runtime builder"),
+        ),
+    )
+    .depends_on("leader-election");
+    let host = Arc::new(host);
+    host.start()
+        .await
+        .map_err(|e| std::io::Error::other(format!("Failed to start hosted services: {}", e)))?;
+
     let port = 8080;
     let ip = "0.0.0.0";
 
+    let index_admin = config.index_admin;
+    let host_data = host.clone();
     let server = HttpServer::new(move || {
         let rt = runtime.clone();
         let subs = subscribers.clone();
         let sub_map = subscription_map.clone();
 
         App::new()
+            .app_data(web::Data::new(host_data.clone()))
             .configure(move |cfg| configure_app(cfg, rt.clone(), subs.clone(), sub_map.clone()))
+            .configure(move |cfg| {
+                if index_admin {
+                    configure_admin(cfg);
+                }
+            })
             .wrap(
                 actix_web::middleware::DefaultHeaders::new()
                     .add(("X-Content-Type-Options", "nosniff"))
@@ -79,6 +176,7 @@ async fn main() -> std::io::Result<()> {
             .wrap(resource_server::middleware::catch_panic::CatchPanic::default())
     })
     .workers(count)
+    .disable_signals()
     .bind((ip, port))?;
 
     tracing::warn!(">> Number of Workers: {}", count);
@@ -91,7 +189,45 @@ async fn main() -> std::io::Result<()> {
     tracing::info!(">> Build Time: {}", env!("VERGEN_BUILD_TIMESTAMP"));
     tracing::info!(">> Starting Server On {}:{}", ip, port);
     tracing::info!(">> Press Ctrl-C to stop the server.");
-    server.run().await
+    let server = server.run();
+    let server_handle = server.handle();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::warn!(">> Shutting down...");
+        server_handle.stop(true).await;
+    });
+    server.await?;
+
+    host.stop()
+        .await
+        .map_err(|e| std::io::Error::other(format!("Hosted service shutdown error: {}", e)))?;
+
+    Ok(())
+}
+
+// ── Audit sinks ───────────────────────────────────────────────────────────────
+
+fn build_audit_sinks(
+    config: &KuiperConfig,
+    store: &Arc<tokio::sync::RwLock<dyn TransactionalKeyValueStore>>,
+) -> std::io::Result<Vec<Arc<dyn AuditSink>>> {
+    let mut sinks: Vec<Arc<dyn AuditSink>> = vec![
+        Arc::new(TracingAuditSink),
+        Arc::new(
+            StoreAuditSink::new(store.clone()).with_retention(Duration::from_secs(
+                config.audit_retention_days.saturating_mul(24 * 60 * 60),
+            )),
+        ),
+    ];
+
+    if let Some(path) = &config.audit_log_path {
+        tracing::info!("Writing audit log to {}", path);
+        let sink =
+            JsonLinesFileSink::new(path).map_err(|e| std::io::Error::other(e.to_string()))?;
+        sinks.push(Arc::new(sink));
+    }
+
+    Ok(sinks)
 }
 
 // ── Store factory ─────────────────────────────────────────────────────────────
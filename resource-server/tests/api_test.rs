@@ -5,39 +5,157 @@
 //! an in-memory store.
 
 use actix_web::http::StatusCode;
-use actix_web::{test, App};
+use actix_web::{test, App, HttpMessage};
+use async_trait::async_trait;
 use dashmap::DashMap;
-use kuiper_runtime::data::InMemoryStore;
+use kuiper_runtime::command::{
+    CommandContext, CommandHandler, CommandResult, CommandType, ExecutableCommand, Typed,
+    TypedCommand, ValidationCommand,
+};
+use kuiper_runtime::data::{InMemoryStore, TransactionalKeyValueStore};
+use kuiper_runtime::outbox::{OutboxEntry, OUTBOX_CONTAINER};
+use kuiper_runtime::service::{HostedService, ServiceHost};
+use kuiper_types::error::KuiperError;
+use kuiper_types::model::discovery::ApiDiscovery;
+use kuiper_types::model::security::UserId;
+use kuiper_types::model::status::{Condition, ConditionStatus};
 use resource_server::{
-    commands::observer::SetObserverCommand, configure_app, SubscriberMap, SubscriptionMap,
+    actors::models::{ServerMessage, Subscription},
+    commands::observer::SetObserverCommand,
+    configure_admin, configure_app, SubscriberMap, SubscriptionMap,
+};
+use resource_server_runtime::audit::{
+    AuditLevel, AuditPolicy, AuditRecord, AuditRule, StoreAuditSink, AUDIT_CONTAINER,
+};
+use resource_server_runtime::controller::{
+    Action, ControllerOptions, ReconcileContext, Reconciler,
+};
+use resource_server_runtime::handlers::{
+    batch::MAX_BATCH_OPERATIONS, delete::DeleteRequest, discovery::DiscoveryRequest,
+    get::GetRequest, purge_audit_records::PurgeAuditRecordsRequest,
+    purge_idempotency_records::PurgeIdempotencyRecordsRequest,
+    rebuild_indexes::RebuildIndexesRequest, set::SetRequest,
+};
+use resource_server_runtime::idempotency::{
+    IdempotencyStage, IDEMPOTENCY_CONTAINER, IDEMPOTENCY_KEY_METADATA,
+};
+use resource_server_runtime::index::INDEX_CONTAINER;
+use resource_server_runtime::namespace::namespace_resource;
+use resource_server_runtime::services::{
+    GarbageCollectorOptions, OutboxOptions, StorageMigrationOptions,
 };
 use resource_server_runtime::{KuiperRuntime, KuiperRuntimeBuilder};
 use serde_json::{json, Value};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
 
 // ─── helpers ────────────────────────────────────────────────────────────────
 
-fn build_runtime() -> (Arc<KuiperRuntime>, SubscriberMap, SubscriptionMap) {
-    let store = InMemoryStore::new();
-    let shared_store = Arc::new(RwLock::new(store));
+async fn build_runtime() -> (Arc<KuiperRuntime>, SubscriberMap, SubscriptionMap) {
+    build_runtime_with(&Arc::new(RwLock::new(InMemoryStore::new())), |_| {}).await
+}
+
+/// Builds and initializes a runtime over `store` after `configure` enabled
+/// the features a test needs. The `mygroup/Widget` kind is seeded first, and
+/// `set` notifies the returned WebSocket subscribers.
+async fn build_runtime_with(
+    store: &Arc<RwLock<InMemoryStore>>,
+    configure: impl FnOnce(&mut KuiperRuntimeBuilder),
+) -> (Arc<KuiperRuntime>, SubscriberMap, SubscriptionMap) {
     let subscribers: SubscriberMap = Arc::new(DashMap::new());
     let subscription_map: SubscriptionMap = Arc::new(DashMap::new());
+    seed_widget_definition(store).await;
 
-    let mut builder = KuiperRuntimeBuilder::new(shared_store.clone());
+    let mut builder = KuiperRuntimeBuilder::new(store.clone());
+    configure(&mut builder);
     builder.register_handler(
         "set",
         Arc::new(SetObserverCommand::new(
-            shared_store.clone(),
+            store.clone(),
             subscribers.clone(),
             subscription_map.clone(),
         )),
     );
 
     let runtime = Arc::new(builder.build());
+    runtime.initialize().await.unwrap();
     (runtime, subscribers, subscription_map)
 }
 
+/// Stores the definition of the `mygroup/Widget` kind most tests write,
+/// which strict mode requires, without going through the runtime under test.
+/// This keeps it out of that runtime's outbox and audit log; the runtime
+/// picks it up on `initialize`.
+async fn seed_widget_definition(store: &Arc<RwLock<InMemoryStore>>) {
+    define_kind(
+        &KuiperRuntimeBuilder::new(store.clone()).build(),
+        "mygroup",
+        "Widget",
+        json!({ "versions": [{ "name": "v1", "enabled": true }] }),
+    )
+    .await;
+}
+
+/// Applies `patch` to `target` as a JSON merge patch: objects are merged and
+/// `null` removes a field.
+fn merge(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                if value.is_null() {
+                    target.remove(&key);
+                } else {
+                    merge(target.entry(key).or_insert(Value::Null), value);
+                }
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+/// Defines `kind` in `group` through `rt`. `spec` is merged over a
+/// Namespace-scoped definition with names derived from `kind`, and gives at
+/// least its `versions`.
+async fn define_kind(rt: &KuiperRuntime, group: &str, kind: &str, spec: Value) {
+    let plural = format!("{}s", kind.to_lowercase());
+    let mut definition = json!({
+        "apiVersion": "ext.api.cloud-api.dev/v1alpha1",
+        "kind": "ResourceDefinition",
+        "metadata": { "name": plural, "namespace": "global" },
+        "spec": {
+            "group": group,
+            "scope": "Namespace",
+            "names": {
+                "kind": kind,
+                "singular": kind.to_lowercase(),
+                "plural": plural
+            }
+        }
+    });
+    merge(&mut definition["spec"], spec);
+
+    let mut ctx = CommandContext::from_request(&SetRequest {
+        namespace: "global".to_string(),
+        resource: format!("ext.api.cloud-api.dev/v1alpha1/ResourceDefinition/{plural}"),
+        value: serde_json::from_value(definition).unwrap(),
+    })
+    .unwrap();
+    ctx.is_internal = true;
+    rt.execute(&mut ctx).await.unwrap();
+}
+
+/// An object of `kind` at `api_version` named `name`, with `fields` merged
+/// over it, e.g. its `spec`.
+fn object(api_version: &str, kind: &str, name: &str, fields: Value) -> Value {
+    let mut object = json!({
+        "apiVersion": api_version,
+        "kind": kind,
+        "metadata": { "name": name }
+    });
+    merge(&mut object, fields);
+    object
+}
+
 /// Convenience: initialise the Actix test service.
 macro_rules! init_app {
     ($rt:expr, $subs:expr, $sub_map:expr) => {{
@@ -58,7 +176,7 @@ macro_rules! init_app {
 /// `GET /version` should return 200 with a `version` field.
 #[actix_web::test]
 async fn test_get_version_ok() {
-    let (rt, subs, sub_map) = build_runtime();
+    let (rt, subs, sub_map) = build_runtime().await;
     let app = init_app!(rt, subs, sub_map);
 
     let req = test::TestRequest::get().uri("/version").to_request();
@@ -77,7 +195,7 @@ async fn test_get_version_ok() {
 /// `PUT /api/{group}/{ns}/{kind}/{name}` with a valid body → 200.
 #[actix_web::test]
 async fn test_put_resource_created() {
-    let (rt, subs, sub_map) = build_runtime();
+    let (rt, subs, sub_map) = build_runtime().await;
     let app = init_app!(rt, subs, sub_map);
 
     let body = json!({
@@ -99,7 +217,7 @@ async fn test_put_resource_created() {
 /// `PUT /api/{group}/{ns}/{kind}` (no name) → 400.
 #[actix_web::test]
 async fn test_put_without_name_is_400() {
-    let (rt, subs, sub_map) = build_runtime();
+    let (rt, subs, sub_map) = build_runtime().await;
     let app = init_app!(rt, subs, sub_map);
 
     let req = test::TestRequest::put()
@@ -116,7 +234,7 @@ async fn test_put_without_name_is_400() {
 /// After a PUT, `GET /api/{group}/{ns}/{kind}/{name}` → 200 with the stored resource.
 #[actix_web::test]
 async fn test_get_resource_after_put() {
-    let (rt, subs, sub_map) = build_runtime();
+    let (rt, subs, sub_map) = build_runtime().await;
     let app = init_app!(rt, subs, sub_map);
 
     let body = json!({
@@ -151,7 +269,7 @@ async fn test_get_resource_after_put() {
 /// `GET` for a resource that was never created → 404.
 #[actix_web::test]
 async fn test_get_nonexistent_resource_is_404() {
-    let (rt, subs, sub_map) = build_runtime();
+    let (rt, subs, sub_map) = build_runtime().await;
     let app = init_app!(rt, subs, sub_map);
 
     let req = test::TestRequest::get()
@@ -167,7 +285,7 @@ async fn test_get_nonexistent_resource_is_404() {
 /// `GET /api/{group}/{ns}/{kind}` (no name) → 200 with a JSON array.
 #[actix_web::test]
 async fn test_list_returns_array() {
-    let (rt, subs, sub_map) = build_runtime();
+    let (rt, subs, sub_map) = build_runtime().await;
     let app = init_app!(rt, subs, sub_map);
 
     // Seed two widgets
@@ -202,7 +320,7 @@ async fn test_list_returns_array() {
 /// Listing an empty namespace/kind → 200 with an empty array.
 #[actix_web::test]
 async fn test_list_empty_returns_empty_array() {
-    let (rt, subs, sub_map) = build_runtime();
+    let (rt, subs, sub_map) = build_runtime().await;
     let app = init_app!(rt, subs, sub_map);
 
     let req = test::TestRequest::get()
@@ -220,7 +338,7 @@ async fn test_list_empty_returns_empty_array() {
 /// After DELETE the resource is immediately hard-deleted (no finalizers) and a subsequent GET returns 404.
 #[actix_web::test]
 async fn test_delete_then_get_is_404() {
-    let (rt, subs, sub_map) = build_runtime();
+    let (rt, subs, sub_map) = build_runtime().await;
     let app = init_app!(rt, subs, sub_map);
 
     // Create
@@ -254,7 +372,7 @@ async fn test_delete_then_get_is_404() {
 /// `DELETE /api/{group}/{ns}/{kind}` (no name) → 400.
 #[actix_web::test]
 async fn test_delete_without_name_is_400() {
-    let (rt, subs, sub_map) = build_runtime();
+    let (rt, subs, sub_map) = build_runtime().await;
     let app = init_app!(rt, subs, sub_map);
 
     let req = test::TestRequest::delete()
@@ -270,7 +388,7 @@ async fn test_delete_without_name_is_400() {
 /// Paths with fewer than 3 segments → 400.
 #[actix_web::test]
 async fn test_short_path_is_400() {
-    let (rt, subs, sub_map) = build_runtime();
+    let (rt, subs, sub_map) = build_runtime().await;
     let app = init_app!(rt, subs, sub_map);
 
     let req = test::TestRequest::get().uri("/api/onlyone").to_request();
@@ -278,3 +396,2947 @@ async fn test_short_path_is_400() {
 
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
+
+// ─── outbox ──────────────────────────────────────────────────────────────────
+
+/// Observer that always fails, used to exercise outbox retries.
+struct FailingObserver;
+
+impl CommandHandler for FailingObserver {
+    fn get_type(&self) -> CommandType {
+        CommandType::Observer
+    }
+
+    fn as_executable(&self) -> Option<&dyn ExecutableCommand> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl ExecutableCommand for FailingObserver {
+    async fn execute(&self, _: &CommandContext) -> CommandResult {
+        Err(anyhow::anyhow!("observer is down"))
+    }
+}
+
+/// Handler spans and metrics are labelled with the short type name by default.
+#[actix_web::test]
+async fn test_handler_name_defaults_to_type_name() {
+    assert_eq!(FailingObserver.name(), "FailingObserver");
+}
+
+/// With the outbox enabled, observers run from the background delivery pass
+/// rather than inside the PUT request.
+#[actix_web::test]
+async fn test_outbox_delivers_set_event_after_commit() {
+    let shared_store = Arc::new(RwLock::new(InMemoryStore::new()));
+    let (rt, subs, sub_map) = build_runtime_with(&shared_store, |builder| {
+        builder.with_outbox();
+    })
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    subs.insert("client".to_string(), tx);
+    sub_map.insert("client".to_string(), vec![Subscription::new("*")]);
+
+    let put = test::TestRequest::put()
+        .uri("/api/mygroup/default/Widget/queued")
+        .set_json(json!({
+            "apiVersion": "mygroup/v1",
+            "kind": "Widget",
+            "metadata": { "name": "queued", "namespace": "default" },
+            "spec": {}
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, put).await.status(), StatusCode::OK);
+    assert!(rx.try_recv().is_err(), "observer must not run inline");
+
+    let delivered = rt.outbox_service().unwrap().run_pass().await.unwrap();
+    assert_eq!(delivered, 1);
+
+    match rx.try_recv() {
+        Ok(ServerMessage::Event { action, object, .. }) => {
+            assert_eq!(action, "set");
+            assert_eq!(object["metadata"]["name"], "queued");
+            assert!(object["metadata"]["resourceVersion"].is_string());
+        }
+        other => panic!("expected an event, got {:?}", other),
+    }
+
+    let remaining = shared_store
+        .read()
+        .await
+        .list_keys(OUTBOX_CONTAINER, None)
+        .await
+        .unwrap();
+    assert!(remaining.is_empty(), "delivered entries must be removed");
+}
+
+/// A failing observer no longer fails the write; the entry stays queued for retry.
+#[actix_web::test]
+async fn test_outbox_failing_observer_is_retried_not_surfaced() {
+    let shared_store = Arc::new(RwLock::new(InMemoryStore::new()));
+    let (rt, subs, sub_map) = build_runtime_with(&shared_store, |builder| {
+        builder.with_outbox();
+        builder.register_handler("set", Arc::new(FailingObserver));
+    })
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let put = test::TestRequest::put()
+        .uri("/api/mygroup/default/Widget/flaky")
+        .set_json(json!({
+            "apiVersion": "mygroup/v1",
+            "kind": "Widget",
+            "metadata": { "name": "flaky", "namespace": "default" },
+            "spec": {}
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, put).await.status(), StatusCode::OK);
+
+    let delivered = rt.outbox_service().unwrap().run_pass().await.unwrap();
+    assert_eq!(delivered, 0);
+
+    let store = shared_store.read().await;
+    let keys = store.list_keys(OUTBOX_CONTAINER, None).await.unwrap();
+    assert_eq!(keys.len(), 1);
+
+    let entry: OutboxEntry =
+        serde_json::from_slice(&store.get(OUTBOX_CONTAINER, &keys[0]).await.unwrap()).unwrap();
+    assert_eq!(entry.attempts, 1);
+    assert_eq!(entry.last_error.as_deref(), Some("observer is down"));
+}
+
+/// A replica of the server on `store` whose outbox service runs with
+/// `options`, with a WebSocket client subscribed to every event.
+async fn outbox_replica(
+    store: &Arc<RwLock<InMemoryStore>>,
+    options: OutboxOptions,
+) -> (Arc<KuiperRuntime>, mpsc::UnboundedReceiver<ServerMessage>) {
+    let subs: SubscriberMap = Arc::new(DashMap::new());
+    let sub_map: SubscriptionMap = Arc::new(DashMap::new());
+    let mut builder = KuiperRuntimeBuilder::new(store.clone());
+    builder.with_outbox_options(options);
+    builder.register_handler(
+        "set",
+        Arc::new(SetObserverCommand::new(
+            store.clone(),
+            subs.clone(),
+            sub_map.clone(),
+        )),
+    );
+    let rt = Arc::new(builder.build());
+    rt.initialize().await.unwrap();
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    subs.insert("client".to_string(), tx);
+    sub_map.insert("client".to_string(), vec![Subscription::new("*")]);
+    (rt, rx)
+}
+
+/// Replicas sharing a store deliver the entries they staged themselves, so
+/// events reach the subscribers of the replica that served the write. They
+/// take over the overdue entries of other replicas, each entry once.
+#[actix_web::test]
+async fn test_outbox_delivery_across_replicas() {
+    let store = Arc::new(RwLock::new(InMemoryStore::new()));
+    seed_widget_definition(&store).await;
+
+    let (writer, mut writer_events) = outbox_replica(&store, OutboxOptions::default()).await;
+    let (other, mut other_events) = outbox_replica(
+        &store,
+        OutboxOptions {
+            replica: "other".to_string(),
+            ..OutboxOptions::default()
+        },
+    )
+    .await;
+    let (takeover, mut takeover_events) = outbox_replica(
+        &store,
+        OutboxOptions {
+            replica: "takeover".to_string(),
+            takeover_after: std::time::Duration::ZERO,
+            ..OutboxOptions::default()
+        },
+    )
+    .await;
+
+    let set = |name: String| {
+        let writer = writer.clone();
+        async move {
+            let request = SetRequest {
+                namespace: "default".to_string(),
+                resource: format!("mygroup/Widget/{name}"),
+                value: serde_json::from_value(object("mygroup/v1", "Widget", &name, json!({})))
+                    .unwrap(),
+            };
+            writer.send(&request).await.unwrap();
+        }
+    };
+    let outbox_len = || async {
+        let store = store.read().await;
+        store.list_keys(OUTBOX_CONTAINER, None).await.unwrap().len()
+    };
+
+    set("mine".to_string()).await;
+    assert_eq!(other.outbox_service().unwrap().run_pass().await.unwrap(), 0);
+    assert_eq!(outbox_len().await, 1);
+    assert_eq!(
+        writer.outbox_service().unwrap().run_pass().await.unwrap(),
+        1
+    );
+    assert!(matches!(
+        writer_events.try_recv(),
+        Ok(ServerMessage::Event { .. })
+    ));
+    assert!(other_events.try_recv().is_err());
+
+    // Delivering at the same time, the writer and a replica taking over its
+    // entries never deliver one twice.
+    for i in 0..5 {
+        set(format!("shared-{i}")).await;
+    }
+    let writer_outbox = writer.outbox_service().unwrap();
+    let takeover_outbox = takeover.outbox_service().unwrap();
+    let (a, b) = tokio::join!(writer_outbox.run_pass(), takeover_outbox.run_pass());
+    assert_eq!(a.unwrap() + b.unwrap(), 5);
+    assert_eq!(outbox_len().await, 0);
+
+    let mut events = 0;
+    while writer_events.try_recv().is_ok() {
+        events += 1;
+    }
+    while takeover_events.try_recv().is_ok() {
+        events += 1;
+    }
+    assert_eq!(events, 5);
+}
+
+// ─── audit ───────────────────────────────────────────────────────────────────
+
+async fn read_audit(store: &Arc<RwLock<InMemoryStore>>) -> Vec<AuditRecord> {
+    let store = store.read().await;
+    let mut keys = store.list_keys(AUDIT_CONTAINER, None).await.unwrap();
+    keys.sort();
+    let mut records = Vec::new();
+    for key in keys {
+        let bytes = store.get(AUDIT_CONTAINER, &key).await.unwrap();
+        records.push(serde_json::from_slice(&bytes).unwrap());
+    }
+    records
+}
+
+/// Updates are audited with redacted before/after images at the `Full` level,
+/// attributed to the caller that authentication stored in the request extensions.
+#[actix_web::test]
+async fn test_audit_records_update_with_redaction() {
+    let shared_store = Arc::new(RwLock::new(InMemoryStore::new()));
+    let policy = AuditPolicy::new(AuditLevel::Metadata).with_rule(AuditRule {
+        group: Some("mygroup".to_string()),
+        kind: Some("widget".to_string()),
+        level: AuditLevel::Full,
+        redact: vec!["/spec/password".to_string()],
+    });
+
+    let (rt, subs, sub_map) = build_runtime_with(&shared_store, |builder| {
+        builder.with_audit(
+            policy,
+            vec![Arc::new(StoreAuditSink::new(shared_store.clone()))],
+        );
+    })
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+    let caller = UserId::from(uuid::Uuid::new_v4());
+
+    for size in [1, 2] {
+        let put = test::TestRequest::put()
+            .uri("/api/mygroup/default/Widget/audited")
+            .set_json(json!({
+                "apiVersion": "mygroup/v1",
+                "kind": "Widget",
+                "metadata": { "name": "audited", "namespace": "default" },
+                "spec": { "size": size, "password": "hunter2" }
+            }))
+            .to_request();
+        put.extensions_mut().insert(caller.clone());
+        assert_eq!(test::call_service(&app, put).await.status(), StatusCode::OK);
+    }
+
+    let records = read_audit(&shared_store).await;
+    assert_eq!(records.len(), 2);
+
+    let update = &records[1];
+    assert_eq!(update.operation, "set");
+    assert_eq!(update.caller_id.as_ref(), Some(&caller));
+    assert_eq!(update.key, "default/mygroup/widget/audited");
+    assert_eq!(update.level, AuditLevel::Full);
+    assert!(update.error.is_none());
+
+    let before = update.before.as_ref().expect("before image");
+    let after = update.after.as_ref().expect("after image");
+    assert_eq!(before["spec"]["size"], 1);
+    assert_eq!(after["spec"]["size"], 2);
+    assert_eq!(before["spec"]["password"], "***");
+    assert_eq!(after["spec"]["password"], "***");
+}
+
+/// Failed commands are audited with the error; reads are not audited.
+#[actix_web::test]
+async fn test_audit_records_failures_and_skips_reads() {
+    let shared_store = Arc::new(RwLock::new(InMemoryStore::new()));
+    let (rt, subs, sub_map) = build_runtime_with(&shared_store, |builder| {
+        builder.with_audit(
+            AuditPolicy::default(),
+            vec![Arc::new(StoreAuditSink::new(shared_store.clone()))],
+        );
+    })
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let get = test::TestRequest::get()
+        .uri("/api/mygroup/default/Widget/missing")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, get).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let delete = test::TestRequest::delete()
+        .uri("/api/mygroup/default/Widget/missing")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, delete).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let records = read_audit(&shared_store).await;
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].operation, "delete");
+    assert_eq!(records[0].group.as_deref(), Some("mygroup"));
+    assert_eq!(records[0].kind.as_deref(), Some("Widget"));
+    assert!(records[0].error.is_some());
+    assert!(records[0].before.is_none() && records[0].after.is_none());
+    assert!(records[0].caller_id.is_none());
+}
+
+/// Records older than the store sink's retention are purged by an internal
+/// command the scheduler runs.
+#[actix_web::test]
+async fn test_audit_records_are_purged() {
+    let shared_store = Arc::new(RwLock::new(InMemoryStore::new()));
+    let (rt, subs, sub_map) = build_runtime_with(&shared_store, |builder| {
+        builder.with_scheduler();
+        builder.with_audit(
+            AuditPolicy::default(),
+            vec![Arc::new(
+                StoreAuditSink::new(shared_store.clone())
+                    .with_retention(std::time::Duration::from_millis(100)),
+            )],
+        );
+    })
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let jobs = rt.scheduler().unwrap().jobs();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].command, "purge_audit_records");
+
+    for name in ["old", "new"] {
+        let put = test::TestRequest::put()
+            .uri(&format!("/api/mygroup/default/Widget/{name}"))
+            .set_json(object("mygroup/v1", "Widget", name, json!({})))
+            .to_request();
+        assert_eq!(test::call_service(&app, put).await.status(), StatusCode::OK);
+        if name == "old" {
+            tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        }
+    }
+    assert_eq!(read_audit(&shared_store).await.len(), 2);
+
+    let err = rt.send(&PurgeAuditRecordsRequest {}).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<KuiperError>(),
+        Some(KuiperError::Forbidden(_))
+    ));
+
+    let mut purge = CommandContext::from_request(&PurgeAuditRecordsRequest {}).unwrap();
+    purge.is_internal = true;
+    let purged = rt.execute(&mut purge).await.unwrap().unwrap();
+    assert_eq!(purged, json!({ "purged": 1 }));
+
+    let records = read_audit(&shared_store).await;
+    assert_eq!(records.len(), 1);
+    assert!(records[0].key.ends_with("/new"));
+}
+
+// ─── idempotency ─────────────────────────────────────────────────────────────
+
+/// A retried PUT with the same `Idempotency-Key` replays the first response,
+/// and reusing the key for a different body is rejected.
+#[actix_web::test]
+async fn test_idempotency_key_replays_and_rejects_mismatch() {
+    let shared_store = Arc::new(RwLock::new(InMemoryStore::new()));
+    let (rt, subs, sub_map) = build_runtime_with(&shared_store, |builder| {
+        builder.with_idempotency();
+    })
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let put = |size: u32| {
+        test::TestRequest::put()
+            .uri("/api/mygroup/default/Widget/retry")
+            .insert_header(("Idempotency-Key", "retry-1"))
+            .set_json(json!({
+                "apiVersion": "mygroup/v1",
+                "kind": "Widget",
+                "metadata": { "name": "retry", "namespace": "default" },
+                "spec": { "size": size }
+            }))
+            .to_request()
+    };
+
+    let first = test::call_service(&app, put(1)).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert!(first.headers().get("Idempotent-Replayed").is_none());
+    let first: Value = test::read_body_json(first).await;
+
+    let replay = test::call_service(&app, put(1)).await;
+    assert_eq!(replay.status(), StatusCode::OK);
+    assert_eq!(replay.headers().get("Idempotent-Replayed").unwrap(), "true");
+    let replay: Value = test::read_body_json(replay).await;
+    assert_eq!(
+        replay["metadata"]["resourceVersion"], first["metadata"]["resourceVersion"],
+        "a replay must not write the resource again"
+    );
+
+    let mismatch = test::call_service(&app, put(2)).await;
+    assert_eq!(mismatch.status(), StatusCode::BAD_REQUEST);
+
+    // A request that never finished holds its key for a while only, after
+    // which a retry runs it again.
+    let mark_in_progress = |renewed_at: i64| {
+        let store = shared_store.clone();
+        async move {
+            let store = store.read().await;
+            let key = store
+                .list_keys(IDEMPOTENCY_CONTAINER, None)
+                .await
+                .unwrap()
+                .pop()
+                .unwrap();
+            let mut record: Value =
+                serde_json::from_slice(&store.get(IDEMPOTENCY_CONTAINER, &key).await.unwrap())
+                    .unwrap();
+            record["state"] = json!("inProgress");
+            record["renewedAt"] = json!(renewed_at);
+            record.as_object_mut().unwrap().remove("response");
+            store
+                .put(
+                    IDEMPOTENCY_CONTAINER,
+                    &key,
+                    serde_json::to_vec(&record).unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+    };
+
+    mark_in_progress(chrono::Utc::now().timestamp_micros()).await;
+    let busy = test::call_service(&app, put(1)).await;
+    assert_eq!(busy.status(), StatusCode::CONFLICT);
+
+    mark_in_progress(chrono::Utc::now().timestamp_micros() - 10 * 60 * 1_000_000).await;
+    let retried = test::call_service(&app, put(1)).await;
+    assert_eq!(retried.status(), StatusCode::OK);
+    assert!(retried.headers().get("Idempotent-Replayed").is_none());
+    let retried: Value = test::read_body_json(retried).await;
+    assert_ne!(
+        retried["metadata"]["resourceVersion"],
+        first["metadata"]["resourceVersion"]
+    );
+    let replay = test::call_service(&app, put(1)).await;
+    assert_eq!(replay.headers().get("Idempotent-Replayed").unwrap(), "true");
+}
+
+/// Holds `set` up, so a request stays in progress for a while.
+struct SlowValidator;
+
+impl CommandHandler for SlowValidator {
+    fn get_type(&self) -> CommandType {
+        CommandType::Validator
+    }
+
+    fn as_validator(&self) -> Option<&dyn ValidationCommand> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl ValidationCommand for SlowValidator {
+    async fn validate(&self, _ctx: &CommandContext) -> CommandResult {
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        Ok(None)
+    }
+}
+
+/// A request that runs for longer than its lease renews it, so a retry
+/// meanwhile does not run the request a second time.
+#[actix_web::test]
+async fn test_idempotency_lease_is_renewed_while_running() {
+    let shared_store = Arc::new(RwLock::new(InMemoryStore::new()));
+    let (rt, subs, sub_map) = build_runtime_with(&shared_store, |builder| {
+        builder.register_handler("set", Arc::new(SlowValidator));
+        builder.register_stage(Arc::new(
+            IdempotencyStage::new(shared_store.clone(), IdempotencyStage::DEFAULT_TTL)
+                .with_lease(std::time::Duration::from_millis(60)),
+        ));
+    })
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let put = || {
+        test::TestRequest::put()
+            .uri("/api/mygroup/default/Widget/slow")
+            .insert_header(("Idempotency-Key", "slow-1"))
+            .set_json(json!({
+                "apiVersion": "mygroup/v1",
+                "kind": "Widget",
+                "metadata": { "name": "slow", "namespace": "default" },
+                "spec": { "size": 1 }
+            }))
+            .to_request()
+    };
+
+    let (first, retry) = tokio::join!(test::call_service(&app, put()), async {
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        test::call_service(&app, put()).await
+    });
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(
+        retry.status(),
+        StatusCode::CONFLICT,
+        "the lease must still be held after it would have run out"
+    );
+
+    let replay = test::call_service(&app, put()).await;
+    assert_eq!(replay.status(), StatusCode::OK);
+    assert_eq!(replay.headers().get("Idempotent-Replayed").unwrap(), "true");
+}
+
+/// Expired records are purged by an internal command the scheduler runs.
+#[actix_web::test]
+async fn test_idempotency_records_are_purged() {
+    let shared_store = Arc::new(RwLock::new(InMemoryStore::new()));
+    let (rt, _, _) = build_runtime_with(&shared_store, |builder| {
+        builder.with_scheduler();
+        builder.with_idempotency_ttl(std::time::Duration::from_millis(100));
+    })
+    .await;
+
+    let jobs = rt.scheduler().unwrap().jobs();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].command, "purge_idempotency_records");
+
+    let set = |name: &str| {
+        let mut ctx = CommandContext::from_request(&SetRequest {
+            namespace: "default".to_string(),
+            resource: format!("mygroup/Widget/{name}"),
+            value: serde_json::from_value(json!({
+                "apiVersion": "mygroup/v1",
+                "kind": "Widget",
+                "metadata": { "name": name, "namespace": "default" }
+            }))
+            .unwrap(),
+        })
+        .unwrap();
+        ctx.metadata
+            .insert(IDEMPOTENCY_KEY_METADATA.to_string(), name.to_string());
+        ctx
+    };
+    let records = || async {
+        shared_store
+            .read()
+            .await
+            .list_keys(IDEMPOTENCY_CONTAINER, None)
+            .await
+            .unwrap()
+            .len()
+    };
+
+    rt.execute(&mut set("old")).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+    rt.execute(&mut set("new")).await.unwrap();
+    assert_eq!(records().await, 2);
+
+    let err = rt
+        .send(&PurgeIdempotencyRecordsRequest {})
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<KuiperError>(),
+        Some(KuiperError::Forbidden(_))
+    ));
+
+    let mut purge = CommandContext::from_request(&PurgeIdempotencyRecordsRequest {}).unwrap();
+    purge.is_internal = true;
+    let purged = rt.execute(&mut purge).await.unwrap().unwrap();
+    assert_eq!(purged, json!({ "purged": 1 }));
+    assert_eq!(records().await, 1);
+}
+
+// ─── batch ───────────────────────────────────────────────────────────────────
+
+/// All operations of a batch are applied, and observers see each write.
+#[actix_web::test]
+async fn test_batch_applies_all_operations() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    subs.insert("client".to_string(), tx);
+    sub_map.insert("client".to_string(), vec![Subscription::new("*")]);
+
+    let req = test::TestRequest::post()
+        .uri("/api/batch")
+        .set_json(json!({
+            "operations": [
+                {
+                    "op": "set",
+                    "path": "mygroup/default/Widget/first",
+                    "value": {
+                        "apiVersion": "mygroup/v1",
+                        "kind": "Widget",
+                        "metadata": { "name": "first", "namespace": "default" }
+                    }
+                },
+                {
+                    "op": "set",
+                    "path": "mygroup/default/Widget/second",
+                    "value": {
+                        "apiVersion": "mygroup/v1",
+                        "kind": "Widget",
+                        "metadata": { "name": "second", "namespace": "default" }
+                    }
+                }
+            ]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: Value = test::read_body_json(resp).await;
+    let results = body["results"].as_array().expect("results array");
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[1]["value"]["metadata"]["name"], "second");
+
+    for name in ["first", "second"] {
+        let get = test::TestRequest::get()
+            .uri(&format!("/api/mygroup/default/Widget/{}", name))
+            .to_request();
+        assert_eq!(test::call_service(&app, get).await.status(), StatusCode::OK);
+    }
+
+    assert!(matches!(rx.try_recv(), Ok(ServerMessage::Event { .. })));
+    assert!(matches!(rx.try_recv(), Ok(ServerMessage::Event { .. })));
+}
+
+/// A failing operation aborts the batch: earlier operations are not written.
+#[actix_web::test]
+async fn test_batch_failure_applies_nothing() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let req = test::TestRequest::post()
+        .uri("/api/batch")
+        .set_json(json!({
+            "operations": [
+                {
+                    "op": "set",
+                    "path": "mygroup/default/Widget/orphan",
+                    "value": {
+                        "apiVersion": "mygroup/v1",
+                        "kind": "Widget",
+                        "metadata": { "name": "orphan", "namespace": "default" }
+                    }
+                },
+                { "op": "delete", "path": "mygroup/default/Widget/missing" }
+            ]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body = test::read_body(resp).await;
+    assert!(String::from_utf8_lossy(&body).starts_with("operation 1"));
+
+    let get = test::TestRequest::get()
+        .uri("/api/mygroup/default/Widget/orphan")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, get).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+// ─── typed commands ──────────────────────────────────────────────────────────
+
+#[derive(serde::Deserialize)]
+struct GreetRequest {
+    name: String,
+    #[serde(default)]
+    excited: bool,
+}
+
+struct GreetCommand;
+
+#[async_trait]
+impl TypedCommand for GreetCommand {
+    type Request = GreetRequest;
+    type Response = String;
+
+    async fn run(&self, _: &CommandContext, request: GreetRequest) -> anyhow::Result<String> {
+        let mark = if request.excited { "!" } else { "." };
+        Ok(format!("Hello, {}{}", request.name, mark))
+    }
+}
+
+/// Built-in commands round-trip through their typed requests and responses.
+#[actix_web::test]
+async fn test_typed_set_and_get() {
+    let (rt, _, _) = build_runtime().await;
+
+    let value = serde_json::from_value(json!({
+        "apiVersion": "mygroup/v1",
+        "kind": "Widget",
+        "metadata": { "name": "typed" }
+    }))
+    .unwrap();
+    let created = rt
+        .send(&SetRequest {
+            namespace: "default".to_string(),
+            resource: "mygroup/Widget/typed".to_string(),
+            value,
+        })
+        .await
+        .unwrap();
+    assert_eq!(created.metadata.namespace.as_deref(), Some("default"));
+
+    let fetched = rt
+        .send(&GetRequest {
+            namespace: "default".to_string(),
+            resource: "mygroup/Widget/typed".to_string(),
+            version: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(fetched.metadata.uid, created.metadata.uid);
+}
+
+/// Custom handlers declare typed inputs; missing or mistyped inputs are
+/// reported as `400 Bad Request` with a consistent message.
+#[actix_web::test]
+async fn test_typed_custom_handler_inputs() {
+    let store = Arc::new(RwLock::new(InMemoryStore::new()));
+    let mut builder = KuiperRuntimeBuilder::new(store);
+    builder.register_handler("greet", Typed::handler(GreetCommand));
+    let rt = builder.build();
+
+    let mut ctx = CommandContext {
+        command_name: "greet".to_string(),
+        ..Default::default()
+    };
+    ctx.parameters.insert("name".to_string(), json!("Ada"));
+    ctx.parameters.insert("excited".to_string(), json!(true));
+    assert_eq!(
+        rt.execute(&mut ctx).await.unwrap(),
+        Some(json!("Hello, Ada!"))
+    );
+
+    for parameters in [json!({}), json!({ "name": 42 })] {
+        let mut ctx = CommandContext {
+            command_name: "greet".to_string(),
+            parameters: serde_json::from_value(parameters).unwrap(),
+            ..Default::default()
+        };
+        let err = rt.execute(&mut ctx).await.unwrap_err();
+        let resp = resource_server::kuiper_error_response(err);
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let mut ctx = CommandContext {
+        command_name: "get".to_string(),
+        ..Default::default()
+    };
+    let err = rt.execute(&mut ctx).await.unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid request: command 'get': missing field `namespace`"
+    );
+}
+
+// ─── command introspection ───────────────────────────────────────────────────
+
+/// `GET /commands` lists each command with its pipeline and input schema.
+#[actix_web::test]
+async fn test_commands_lists_pipeline_and_schema() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let req = test::TestRequest::get().uri("/commands").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: Value = test::read_body_json(resp).await;
+    let set = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["name"] == "set")
+        .expect("set is listed");
+
+    let pipeline: Vec<(&str, &str)> = set["handlers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|h| (h["name"].as_str().unwrap(), h["type"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        pipeline,
+        vec![
+            ("SchemaValidationCommand", "validator"),
+            ("SetCommand", "internal"),
+            ("SetObserverCommand", "observer"),
+        ]
+    );
+    assert_eq!(
+        set["inputSchema"]["required"],
+        json!(["namespace", "resource", "value"])
+    );
+
+    // Input schemas follow the request types, optional fields included.
+    let patch = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["name"] == "patch")
+        .expect("patch is listed");
+    assert_eq!(
+        patch["inputSchema"]["required"],
+        json!(["namespace", "resource", "patchType", "patch"])
+    );
+    let properties = &patch["inputSchema"]["properties"];
+    assert!(properties["resourceVersion"].is_object());
+    assert!(properties["version"].is_object());
+
+    let batch = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["name"] == "batch")
+        .expect("batch is listed");
+    let operations = &batch["inputSchema"]["properties"]["operations"];
+    assert_eq!(operations["minItems"], json!(1));
+    assert_eq!(operations["maxItems"], json!(MAX_BATCH_OPERATIONS));
+    assert_eq!(
+        operations["items"]["required"],
+        json!(["op", "namespace", "resource"])
+    );
+    assert_eq!(
+        operations["items"]["properties"]["op"]["enum"],
+        json!(["set", "delete"])
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/commands/no-such-command")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+/// The `commands` command describes a single command over RPC.
+#[actix_web::test]
+async fn test_commands_rpc_describes_one_command() {
+    let (rt, _, _) = build_runtime().await;
+
+    let mut ctx = CommandContext {
+        command_name: "commands".to_string(),
+        ..Default::default()
+    };
+    ctx.parameters.insert("name".to_string(), json!("echo"));
+
+    let echo = rt.execute(&mut ctx).await.unwrap().unwrap();
+    assert_eq!(echo["name"], "echo");
+    assert_eq!(echo["handlers"][0]["name"], "EchoCommand");
+    assert!(echo["inputSchema"]["properties"]["message"].is_object());
+}
+
+// ─── hosted services ────────────────────────────────────────────────────────
+
+/// A hosted service that does nothing, reporting `ready`.
+struct IdleService {
+    ready: bool,
+}
+
+#[async_trait]
+impl HostedService for IdleService {
+    async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn stop(self: &Arc<Self>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        self.ready
+    }
+}
+
+/// `/health` reports every hosted service, and is unavailable while one of
+/// them is not ready.
+#[actix_web::test]
+async fn test_health_reports_hosted_services() {
+    let mut host = ServiceHost::new();
+    host.register("api", Arc::new(IdleService { ready: true }));
+    host.register("cache", Arc::new(IdleService { ready: false }))
+        .depends_on("api");
+    let host = Arc::new(host);
+    host.start().await.unwrap();
+
+    let (rt, subs, sub_map) = build_runtime().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(host.clone()))
+            .configure(move |cfg| configure_app(cfg, rt.clone(), subs.clone(), sub_map.clone())),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/health").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["services"][0]["name"], "api");
+    assert_eq!(body["services"][0]["state"], "running");
+    assert_eq!(body["services"][0]["ready"], true);
+    assert_eq!(body["services"][1]["ready"], false);
+
+    host.stop().await.unwrap();
+}
+
+// ─── scheduler ──────────────────────────────────────────────────────────────
+
+/// `ScheduledCommand` resources become jobs on resync and leave when deleted.
+#[actix_web::test]
+async fn test_scheduled_command_resource_is_scheduled() {
+    let (rt, _, _) = build_runtime_with(&Arc::new(RwLock::new(InMemoryStore::new())), |builder| {
+        builder.with_scheduler();
+    })
+    .await;
+    let scheduler = rt.scheduler().unwrap();
+
+    let resource = "ext.api.cloud-api.dev/v1alpha1/ScheduledCommand/nightly";
+    let mut set = CommandContext {
+        command_name: "set".to_string(),
+        is_internal: true,
+        ..Default::default()
+    };
+    set.metadata
+        .insert("namespace".to_string(), "global".to_string());
+    set.parameters
+        .insert("resource".to_string(), json!(resource));
+    set.parameters.insert(
+        "value".to_string(),
+        json!({
+            "apiVersion": "ext.api.cloud-api.dev/v1alpha1",
+            "kind": "ScheduledCommand",
+            "metadata": { "name": "nightly", "namespace": "global" },
+            "spec": { "command": "echo", "schedule": "0 2 * * *", "jitterSeconds": 60 }
+        }),
+    );
+    rt.execute(&mut set).await.unwrap();
+
+    scheduler.resync().await.unwrap();
+    let jobs = scheduler.jobs();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].name, "scheduledcommand/nightly");
+    assert_eq!(jobs[0].schedule, "0 2 * * *");
+    assert!(jobs[0].state.next_run_at.is_some());
+
+    let mut delete = CommandContext {
+        command_name: "delete".to_string(),
+        is_internal: true,
+        ..Default::default()
+    };
+    delete
+        .metadata
+        .insert("namespace".to_string(), "global".to_string());
+    delete
+        .parameters
+        .insert("resource".to_string(), json!(resource));
+    rt.execute(&mut delete).await.unwrap();
+
+    scheduler.resync().await.unwrap();
+    assert!(scheduler.jobs().is_empty());
+}
+
+// ─── controllers ────────────────────────────────────────────────────────────
+
+/// Sets the `Ready` condition on every widget, fails each widget's first attempt, and
+/// removes terminating widgets once their finalizers are gone.
+#[derive(Default)]
+struct WidgetReconciler {
+    attempts: std::sync::Mutex<std::collections::HashMap<String, u32>>,
+}
+
+#[async_trait]
+impl Reconciler for WidgetReconciler {
+    async fn reconcile(
+        &self,
+        object: kuiper_types::model::resource::SystemObject,
+        ctx: &ReconcileContext,
+    ) -> anyhow::Result<Action> {
+        let attempt = {
+            let mut attempts = self.attempts.lock().unwrap();
+            let attempt = attempts.entry(object.metadata.name.clone()).or_default();
+            *attempt += 1;
+            *attempt
+        };
+        if attempt == 1 {
+            anyhow::bail!("not yet");
+        }
+
+        if object.metadata.deletion_timestamp.is_some() {
+            if object
+                .metadata
+                .finalizers
+                .as_ref()
+                .is_none_or(|f| f.is_empty())
+            {
+                ctx.send(&DeleteRequest::new(ctx.namespace(), ctx.resource()))
+                    .await?;
+            }
+            return Ok(Action::await_change());
+        }
+
+        let mut status = object.status.clone().unwrap_or_default();
+        status.set_condition(Condition::new(
+            "Ready",
+            ConditionStatus::True,
+            "Reconciled",
+            "",
+        ));
+        status.observed_generation = object.metadata.generation;
+        ctx.update_status(object, status).await?;
+        Ok(Action::await_change())
+    }
+}
+
+async fn get_widget(rt: &KuiperRuntime, name: &str) -> Option<Value> {
+    rt.send(&GetRequest {
+        namespace: "default".to_string(),
+        resource: format!("mygroup/widget/{}", name),
+        version: None,
+    })
+    .await
+    .ok()
+    .map(|o| serde_json::to_value(o).unwrap())
+}
+
+async fn set_widget(rt: &KuiperRuntime, value: Value) {
+    let name = value["metadata"]["name"].as_str().unwrap().to_string();
+    rt.send(&SetRequest {
+        namespace: "default".to_string(),
+        resource: format!("mygroup/widget/{}", name),
+        value: serde_json::from_value(value).unwrap(),
+    })
+    .await
+    .unwrap();
+}
+
+/// A controller reconciles existing and newly written objects of its kind,
+/// retries failures with backoff, writes status, and finishes deletion once
+/// the last finalizer is removed.
+#[actix_web::test]
+async fn test_controller_reconciles_watched_objects() {
+    let reconciler = Arc::new(WidgetReconciler::default());
+    let mut options = ControllerOptions::new("mygroup", "Widget");
+    options.rate_limit.initial_backoff = std::time::Duration::from_millis(10);
+    let (rt, _, _) = build_runtime_with(&Arc::new(RwLock::new(InMemoryStore::new())), |builder| {
+        builder.with_controller(options, reconciler.clone());
+    })
+    .await;
+
+    let widget = |name: &str| {
+        object(
+            "mygroup/v1",
+            "Widget",
+            name,
+            json!({ "metadata": { "finalizers": ["example.dev/protect"] } }),
+        )
+    };
+
+    // Listed on start.
+    set_widget(&rt, widget("existing")).await;
+    let controller = rt.controllers().remove(0);
+    assert_eq!(controller.name(), "widget-controller");
+    controller.start().await.unwrap();
+
+    // Watched afterwards.
+    set_widget(&rt, widget("created")).await;
+
+    let wait_for = |name: &'static str, check: fn(Option<Value>) -> bool| {
+        let rt = rt.clone();
+        async move {
+            for _ in 0..200 {
+                if check(get_widget(&rt, name).await) {
+                    return;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            panic!("{} did not reach the expected state", name);
+        }
+    };
+    let ready = |w: Option<Value>| {
+        w.is_some_and(|w| {
+            w["status"]["conditions"][0]["type"] == json!("Ready")
+                && w["status"]["observedGeneration"] == w["metadata"]["generation"]
+        })
+    };
+    wait_for("existing", ready).await;
+    wait_for("created", ready).await;
+    assert!(reconciler.attempts.lock().unwrap()["created"] >= 2);
+
+    // Deleting with a finalizer only marks the object; removing the
+    // finalizer lets the controller finish the deletion.
+    rt.send(&DeleteRequest::new("default", "mygroup/widget/created"))
+        .await
+        .unwrap();
+    let mut terminating = get_widget(&rt, "created").await.unwrap();
+    assert!(terminating["metadata"]["deletionTimestamp"].is_i64());
+    terminating["metadata"]["finalizers"] = json!([]);
+    set_widget(&rt, terminating).await;
+    wait_for("created", |w| w.is_none()).await;
+
+    controller.stop().await.unwrap();
+    assert!(get_widget(&rt, "existing").await.is_some());
+}
+
+// ─── finalizers ─────────────────────────────────────────────────────────────
+
+/// `POST .../finalize` adds and removes finalizers in place; removing the
+/// last one from an object marked for deletion removes the object.
+#[actix_web::test]
+async fn test_finalize_subresource() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    let app = init_app!(rt, subs, sub_map);
+    let uri = "/api/mygroup/default/Widget/guarded";
+
+    let put = test::TestRequest::put()
+        .uri(uri)
+        .set_json(json!({
+            "apiVersion": "mygroup/v1",
+            "kind": "Widget",
+            "metadata": { "name": "guarded", "finalizers": ["a.example.dev/one"] }
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, put).await.status(), StatusCode::OK);
+
+    let finalize = |body: Value| {
+        test::TestRequest::post()
+            .uri(&format!("{uri}/finalize"))
+            .set_json(body)
+            .to_request()
+    };
+
+    let added: Value =
+        test::call_and_read_body_json(&app, finalize(json!({ "add": ["b.example.dev/two"] })))
+            .await;
+    assert_eq!(
+        added["metadata"]["finalizers"],
+        json!(["a.example.dev/one", "b.example.dev/two"])
+    );
+
+    // Adding a finalizer that is already present changes nothing.
+    let again: Value =
+        test::call_and_read_body_json(&app, finalize(json!({ "add": ["b.example.dev/two"] })))
+            .await;
+    assert_eq!(
+        again["metadata"]["resourceVersion"],
+        added["metadata"]["resourceVersion"]
+    );
+
+    let delete = test::TestRequest::delete().uri(uri).to_request();
+    assert_eq!(
+        test::call_service(&app, delete).await.status(),
+        StatusCode::ACCEPTED
+    );
+
+    // No new finalizers once the object is being deleted.
+    let resp = test::call_service(&app, finalize(json!({ "add": ["c.example.dev/three"] }))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let removed: Value =
+        test::call_and_read_body_json(&app, finalize(json!({ "remove": ["a.example.dev/one"] })))
+            .await;
+    assert_eq!(
+        removed["metadata"]["finalizers"],
+        json!(["b.example.dev/two"])
+    );
+    assert!(removed["metadata"]["deletionTimestamp"].is_i64());
+
+    let resp = test::call_service(&app, finalize(json!({ "remove": ["b.example.dev/two"] }))).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let get = test::TestRequest::get().uri(uri).to_request();
+    assert_eq!(
+        test::call_service(&app, get).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let resp = test::call_service(&app, finalize(json!({ "remove": ["b.example.dev/two"] }))).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // Other subresources are not writable through POST.
+    let other = test::TestRequest::post()
+        .uri("/api/mygroup/default/Widget/guarded/scale")
+        .set_json(json!({}))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, other).await.status(),
+        StatusCode::METHOD_NOT_ALLOWED
+    );
+}
+
+#[actix_web::test]
+async fn test_garbage_collector_propagation_policies() {
+    let (rt, subs, sub_map) =
+        build_runtime_with(&Arc::new(RwLock::new(InMemoryStore::new())), |builder| {
+            builder.with_garbage_collector_options(GarbageCollectorOptions {
+                interval: std::time::Duration::from_secs(3600),
+            });
+        })
+        .await;
+    let app = init_app!(rt, subs, sub_map);
+    let gc = rt.garbage_collector().expect("garbage collector enabled");
+    let uri = |name: &str| format!("/api/mygroup/default/Widget/{name}");
+
+    let put = |name: &str, owner: Option<&str>| {
+        test::TestRequest::put()
+            .uri(&uri(name))
+            .set_json(object(
+                "mygroup/v1",
+                "Widget",
+                name,
+                json!({ "metadata": { "ownerReferences": owner.map(|owner| {
+                    json!([{ "apiVersion": "mygroup/v1", "kind": "Widget", "name": owner }])
+                }) } }),
+            ))
+            .to_request()
+    };
+    let status = |name: &str| {
+        let req = test::TestRequest::get().uri(&uri(name)).to_request();
+        async { test::call_service(&app, req).await.status() }
+    };
+
+    // Owners must exist; their uid is filled in.
+    let resp = test::call_service(&app, put("child", Some("missing"))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let parent: Value = test::call_and_read_body_json(&app, put("parent", None)).await;
+    let child: Value = test::call_and_read_body_json(&app, put("child", Some("parent"))).await;
+    assert_eq!(
+        child["metadata"]["ownerReferences"][0]["uid"],
+        parent["metadata"]["uid"]
+    );
+
+    // Background: the owner goes at once, the dependent on the next pass.
+    let delete = test::TestRequest::delete().uri(&uri("parent")).to_request();
+    assert_eq!(
+        test::call_service(&app, delete).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(status("child").await, StatusCode::OK);
+    assert_eq!(gc.run_pass().await.unwrap(), 1);
+    assert_eq!(status("child").await, StatusCode::NOT_FOUND);
+
+    // Foreground: the owner stays until its dependents are gone.
+    test::call_service(&app, put("parent", None)).await;
+    test::call_service(&app, put("child", Some("parent"))).await;
+    let delete = test::TestRequest::delete()
+        .uri(&format!("{}?propagationPolicy=Foreground", uri("parent")))
+        .to_request();
+    let terminating: Value = test::call_and_read_body_json(&app, delete).await;
+    assert_eq!(
+        terminating["metadata"]["finalizers"],
+        json!(["foregroundDeletion"])
+    );
+    gc.run_pass().await.unwrap();
+    assert_eq!(status("parent").await, StatusCode::OK);
+    while gc.run_pass().await.unwrap() > 0 {}
+    assert_eq!(status("child").await, StatusCode::NOT_FOUND);
+    assert_eq!(status("parent").await, StatusCode::NOT_FOUND);
+
+    // Orphan: the dependent stays without its reference to the owner.
+    test::call_service(&app, put("parent", None)).await;
+    test::call_service(&app, put("child", Some("parent"))).await;
+    let delete = test::TestRequest::delete()
+        .uri(&format!("{}?propagationPolicy=Orphan", uri("parent")))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, delete).await.status(),
+        StatusCode::ACCEPTED
+    );
+    while gc.run_pass().await.unwrap() > 0 {}
+    assert_eq!(status("parent").await, StatusCode::NOT_FOUND);
+    let get = test::TestRequest::get().uri(&uri("child")).to_request();
+    let orphan: Value = test::call_and_read_body_json(&app, get).await;
+    assert!(orphan["metadata"].get("ownerReferences").is_none());
+
+    // A reference to an owner that is already gone doesn't block updates,
+    // including the collector's own.
+    test::call_service(&app, put("left", None)).await;
+    test::call_service(&app, put("right", None)).await;
+    let twin = object(
+        "mygroup/v1",
+        "Widget",
+        "twin",
+        json!({ "metadata": { "ownerReferences": [
+            { "apiVersion": "mygroup/v1", "kind": "Widget", "name": "left" },
+            { "apiVersion": "mygroup/v1", "kind": "Widget", "name": "right" }
+        ] } }),
+    );
+    let req = test::TestRequest::put()
+        .uri(&uri("twin"))
+        .set_json(twin)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let delete = test::TestRequest::delete().uri(&uri("right")).to_request();
+    test::call_service(&app, delete).await;
+    let get = test::TestRequest::get().uri(&uri("twin")).to_request();
+    let twin: Value = test::call_and_read_body_json(&app, get).await;
+    let req = test::TestRequest::put()
+        .uri(&uri("twin"))
+        .set_json(twin)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let delete = test::TestRequest::delete()
+        .uri(&format!("{}?propagationPolicy=Orphan", uri("left")))
+        .to_request();
+    test::call_service(&app, delete).await;
+    while gc.run_pass().await.unwrap() > 0 {}
+    assert_eq!(status("left").await, StatusCode::NOT_FOUND);
+    assert_eq!(status("twin").await, StatusCode::NOT_FOUND);
+
+    let delete = test::TestRequest::delete()
+        .uri(&format!("{}?propagationPolicy=Sideways", uri("child")))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, delete).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+/// `metadata.generation` starts at 1 and only moves when the spec changes.
+#[actix_web::test]
+async fn test_generation_tracks_spec_changes() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    let app = init_app!(rt, subs, sub_map);
+    let uri = "/api/mygroup/default/Widget/generational";
+
+    let put = |labels: Value, spec: Value| {
+        test::TestRequest::put()
+            .uri(uri)
+            .set_json(json!({
+                "apiVersion": "mygroup/v1",
+                "kind": "Widget",
+                "metadata": { "name": "generational", "labels": labels, "generation": 42 },
+                "spec": spec
+            }))
+            .to_request()
+    };
+
+    let created: Value =
+        test::call_and_read_body_json(&app, put(json!({}), json!({ "size": 1 }))).await;
+    assert_eq!(created["metadata"]["generation"], json!(1));
+
+    let relabeled: Value =
+        test::call_and_read_body_json(&app, put(json!({ "tier": "gold" }), json!({ "size": 1 })))
+            .await;
+    assert_eq!(relabeled["metadata"]["generation"], json!(1));
+
+    let resized: Value =
+        test::call_and_read_body_json(&app, put(json!({ "tier": "gold" }), json!({ "size": 2 })))
+            .await;
+    assert_eq!(resized["metadata"]["generation"], json!(2));
+}
+
+/// Registers a `ResourceDefinition` through an internal `set`.
+/// With the status subresource enabled, the object PUT keeps the stored
+/// status and `PUT .../status` changes nothing but the status.
+#[actix_web::test]
+async fn test_status_subresource() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    define_kind(
+        &rt,
+        "statusgroup",
+        "Gadget",
+        json!({ "versions": [{ "name": "v1", "enabled": true, "subresources": { "status": {} } }] }),
+    )
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+    let uri = "/api/statusgroup/default/Gadget/g1";
+
+    let gadget = |size: u32, phase: &str| {
+        json!({
+            "apiVersion": "statusgroup/v1",
+            "kind": "Gadget",
+            "metadata": { "name": "g1" },
+            "spec": { "size": size },
+            "status": { "phase": phase }
+        })
+    };
+
+    let put = test::TestRequest::put()
+        .uri(uri)
+        .set_json(gadget(1, "Bogus"))
+        .to_request();
+    let created: Value = test::call_and_read_body_json(&app, put).await;
+    assert!(created.get("status").is_none());
+
+    let mut body = gadget(99, "Ready");
+    body["status"]["conditions"] = json!([{ "type": "Ready", "status": "True" }]);
+    body["metadata"]["resourceVersion"] = created["metadata"]["resourceVersion"].clone();
+    let put = test::TestRequest::put()
+        .uri(&format!("{uri}/status"))
+        .set_json(body.clone())
+        .to_request();
+    let updated: Value = test::call_and_read_body_json(&app, put).await;
+    assert_eq!(updated["status"]["phase"], json!("Ready"));
+    assert_eq!(updated["status"]["conditions"][0]["type"], json!("Ready"));
+    assert_eq!(updated["spec"], json!({ "size": 1 }));
+    assert_eq!(updated["metadata"]["generation"], json!(1));
+
+    // The status write was conditional on the resourceVersion, now stale.
+    let put = test::TestRequest::put()
+        .uri(&format!("{uri}/status"))
+        .set_json(body)
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, put).await.status(),
+        StatusCode::CONFLICT
+    );
+
+    let put = test::TestRequest::put()
+        .uri(uri)
+        .set_json(gadget(2, "Overwritten"))
+        .to_request();
+    let resized: Value = test::call_and_read_body_json(&app, put).await;
+    assert_eq!(resized["status"]["phase"], json!("Ready"));
+    assert_eq!(resized["metadata"]["generation"], json!(2));
+
+    // A body claiming a kind without the subresource cannot write the
+    // status through the object's path.
+    let mut disguised = gadget(2, "Disguised");
+    disguised["apiVersion"] = json!("mygroup/v1");
+    disguised["kind"] = json!("Widget");
+    let put = test::TestRequest::put()
+        .uri(uri)
+        .set_json(disguised)
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, put).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    // Kinds without the subresource do not serve it, whatever the body
+    // claims the object is.
+    let put = test::TestRequest::put()
+        .uri("/api/mygroup/default/Widget/w1")
+        .set_json(object("mygroup/v1", "Widget", "w1", json!({})))
+        .to_request();
+    assert_eq!(test::call_service(&app, put).await.status(), StatusCode::OK);
+    let status_of = |body: Value| {
+        test::TestRequest::put()
+            .uri("/api/mygroup/default/Widget/w1/status")
+            .set_json(body)
+            .to_request()
+    };
+    let put = status_of(json!({
+        "apiVersion": "mygroup/v1",
+        "kind": "Widget",
+        "metadata": { "name": "w1" },
+        "status": { "phase": "Ready" }
+    }));
+    assert_eq!(
+        test::call_service(&app, put).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+    let put = status_of(gadget(1, "Ready"));
+    assert_eq!(
+        test::call_service(&app, put).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+    let put = status_of(json!({
+        "apiVersion": "ext.api.cloud-api.dev/v1alpha1",
+        "kind": "Namespace",
+        "metadata": { "name": "w1" },
+        "status": { "phase": "Ready" }
+    }));
+    assert_eq!(
+        test::call_service(&app, put).await.status(),
+        StatusCode::FORBIDDEN
+    );
+    let get = test::TestRequest::get()
+        .uri("/api/mygroup/default/Widget/w1")
+        .to_request();
+    let w1: Value = test::call_and_read_body_json(&app, get).await;
+    assert!(w1.get("status").is_none());
+
+    let put = test::TestRequest::put()
+        .uri(&format!("{uri}/scale"))
+        .set_json(json!({}))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, put).await.status(),
+        StatusCode::METHOD_NOT_ALLOWED
+    );
+}
+
+// ─── PATCH ──────────────────────────────────────────────────────────────────
+
+/// Merge and JSON patches apply to the stored object, go through the
+/// validators of `set`, and honour a resourceVersion precondition.
+#[actix_web::test]
+async fn test_patch_merge_and_json() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    define_kind(
+        &rt,
+        "patchgroup",
+        "Gizmo",
+        json!({ "versions": [{
+            "name": "v1",
+            "enabled": true,
+            "schema": {
+                "type": "object",
+                "properties": { "size": { "type": "integer", "maximum": 10 } }
+            }
+        }] }),
+    )
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+    let uri = "/api/patchgroup/default/Gizmo/gz";
+
+    let put = test::TestRequest::put()
+        .uri(uri)
+        .set_json(json!({
+            "apiVersion": "patchgroup/v1",
+            "kind": "Gizmo",
+            "metadata": { "name": "gz", "labels": { "tier": "bronze", "team": "a" } },
+            "spec": { "size": 1 }
+        }))
+        .to_request();
+    let created: Value = test::call_and_read_body_json(&app, put).await;
+
+    let patch = |content_type: &str, body: Value| {
+        test::TestRequest::patch()
+            .uri(uri)
+            .insert_header(("Content-Type", content_type))
+            .set_payload(body.to_string())
+            .to_request()
+    };
+    let merge = "application/merge-patch+json";
+
+    let merged: Value = test::call_and_read_body_json(
+        &app,
+        patch(
+            merge,
+            json!({ "metadata": { "labels": { "tier": "gold", "team": null } } }),
+        ),
+    )
+    .await;
+    assert_eq!(merged["metadata"]["labels"], json!({ "tier": "gold" }));
+    assert_eq!(merged["spec"], json!({ "size": 1 }));
+    assert_eq!(merged["metadata"]["uid"], created["metadata"]["uid"]);
+    assert_ne!(
+        merged["metadata"]["resourceVersion"],
+        created["metadata"]["resourceVersion"]
+    );
+
+    let patched: Value = test::call_and_read_body_json(
+        &app,
+        patch(
+            "application/json-patch+json",
+            json!([
+                { "op": "test", "path": "/spec/size", "value": 1 },
+                { "op": "replace", "path": "/spec/size", "value": 3 }
+            ]),
+        ),
+    )
+    .await;
+    assert_eq!(patched["spec"], json!({ "size": 3 }));
+    assert_eq!(patched["metadata"]["generation"], json!(2));
+
+    // A failing `test` operation rejects the whole patch.
+    let resp = test::call_service(
+        &app,
+        patch(
+            "application/json-patch+json",
+            json!([{ "op": "test", "path": "/spec/size", "value": 1 }]),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // The schema validator runs on the patched object.
+    let resp = test::call_service(&app, patch(merge, json!({ "spec": { "size": 11 } }))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = test::call_service(
+        &app,
+        patch(merge, json!({ "metadata": { "name": "other" } })),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // A stale resourceVersion, in the query or in the patch, is a conflict.
+    let stale = created["metadata"]["resourceVersion"].as_str().unwrap();
+    let req = test::TestRequest::patch()
+        .uri(&format!("{uri}?resourceVersion={stale}"))
+        .insert_header(("Content-Type", merge))
+        .set_payload(json!({ "spec": { "size": 4 } }).to_string())
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::CONFLICT
+    );
+    let resp = test::call_service(
+        &app,
+        patch(
+            merge,
+            json!({ "metadata": { "resourceVersion": stale }, "spec": { "size": 4 } }),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let current = patched["metadata"]["resourceVersion"].as_str().unwrap();
+    let req = test::TestRequest::patch()
+        .uri(&format!("{uri}?resourceVersion={current}"))
+        .insert_header(("Content-Type", merge))
+        .set_payload(json!({ "spec": { "size": 4 } }).to_string())
+        .to_request();
+    let resized: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resized["spec"], json!({ "size": 4 }));
+
+    let resp = test::call_service(&app, patch("application/json", json!({}))).await;
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let req = test::TestRequest::patch()
+        .uri("/api/patchgroup/default/Gizmo/missing")
+        .insert_header(("Content-Type", merge))
+        .set_payload("{}")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+// ─── Server-side apply ──────────────────────────────────────────────────────
+
+/// Apply records field ownership per manager, rejects conflicting values
+/// unless forced, and removes fields a manager stops applying.
+#[actix_web::test]
+async fn test_server_side_apply() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    define_kind(
+        &rt,
+        "applygroup",
+        "Gizmo",
+        json!({ "versions": [{ "name": "v1", "enabled": true }] }),
+    )
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+    let uri = "/api/applygroup/default/Gizmo/gz";
+
+    let apply = |query: &str, body: Value| {
+        test::TestRequest::patch()
+            .uri(&format!("{uri}?{query}"))
+            .insert_header(("Content-Type", "application/apply-patch+json"))
+            .set_payload(body.to_string())
+            .to_request()
+    };
+
+    let created: Value = test::call_and_read_body_json(
+        &app,
+        apply(
+            "fieldManager=cli",
+            json!({
+                "apiVersion": "applygroup/v1",
+                "kind": "Gizmo",
+                "metadata": { "name": "gz", "labels": { "team": "a" } },
+                "spec": { "size": 1, "color": "red" }
+            }),
+        ),
+    )
+    .await;
+    assert_eq!(created["spec"], json!({ "size": 1, "color": "red" }));
+    assert_eq!(created["metadata"]["generation"], json!(1));
+    assert_eq!(
+        created["metadata"]["managedFields"][0]["fields"],
+        json!(["/metadata/labels/team", "/spec/color", "/spec/size"])
+    );
+
+    // Another manager sharing a field with the same value is no conflict.
+    let shared: Value = test::call_and_read_body_json(
+        &app,
+        apply(
+            "fieldManager=scaler",
+            json!({ "spec": { "size": 1, "replicas": 2 } }),
+        ),
+    )
+    .await;
+    assert_eq!(shared["spec"]["replicas"], json!(2));
+    assert_eq!(shared["metadata"]["managedFields"][1]["manager"], "scaler");
+
+    // Changing a field someone else owns conflicts until forced.
+    let resp = test::call_service(
+        &app,
+        apply(
+            "fieldManager=scaler",
+            json!({ "spec": { "color": "blue" } }),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(
+        body.contains("/spec/color") && body.contains("cli"),
+        "{body}"
+    );
+
+    let forced: Value = test::call_and_read_body_json(
+        &app,
+        apply(
+            "fieldManager=scaler&force=true",
+            json!({ "spec": { "size": 1, "replicas": 2, "color": "blue" } }),
+        ),
+    )
+    .await;
+    assert_eq!(forced["spec"]["color"], "blue");
+    assert_eq!(
+        forced["metadata"]["managedFields"][0]["fields"],
+        json!(["/metadata/labels/team", "/spec/size"])
+    );
+
+    // Fields a manager stops applying are removed, unless still shared.
+    let pruned: Value = test::call_and_read_body_json(
+        &app,
+        apply(
+            "fieldManager=cli",
+            json!({ "metadata": { "labels": { "team": "b" } } }),
+        ),
+    )
+    .await;
+    assert_eq!(pruned["metadata"]["labels"], json!({ "team": "b" }));
+    assert_eq!(
+        pruned["spec"],
+        json!({ "size": 1, "replicas": 2, "color": "blue" })
+    );
+
+    let pruned: Value = test::call_and_read_body_json(
+        &app,
+        apply("fieldManager=scaler", json!({ "spec": { "replicas": 3 } })),
+    )
+    .await;
+    assert_eq!(pruned["spec"], json!({ "replicas": 3 }));
+    assert_eq!(pruned["metadata"]["uid"], created["metadata"]["uid"]);
+
+    // A plain PUT replaces the object and with it the recorded ownership, so
+    // another manager can then apply the fields without a conflict.
+    let mut replaced = pruned.clone();
+    replaced["metadata"]
+        .as_object_mut()
+        .unwrap()
+        .remove("managedFields");
+    replaced["spec"]["replicas"] = json!(4);
+    let put = test::TestRequest::put()
+        .uri(uri)
+        .set_json(replaced)
+        .to_request();
+    let put: Value = test::call_and_read_body_json(&app, put).await;
+    assert!(put["metadata"].get("managedFields").is_none());
+
+    let taken: Value = test::call_and_read_body_json(
+        &app,
+        apply("fieldManager=other", json!({ "spec": { "replicas": 5 } })),
+    )
+    .await;
+    assert_eq!(taken["spec"]["replicas"], 5);
+    let managed = taken["metadata"]["managedFields"].as_array().unwrap();
+    assert_eq!(managed.len(), 1);
+    assert_eq!(managed[0]["manager"], "other");
+    assert_eq!(managed[0]["fields"], json!(["/spec/replicas"]));
+
+    let resp = test::call_service(&app, apply("", json!({ "spec": {} }))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+// ─── Selectors ──────────────────────────────────────────────────────────────
+
+/// Lists and subscriptions only return objects matching their label and
+/// field selectors.
+#[actix_web::test]
+async fn test_list_and_subscribe_with_selectors() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    subs.insert("client".to_string(), tx);
+    sub_map.insert(
+        "client".to_string(),
+        vec![Subscription {
+            label_selector: "tier=gold".parse().unwrap(),
+            ..Subscription::new("mygroup/v1/Widget")
+        }],
+    );
+
+    for (name, labels) in [
+        ("a", json!({ "tier": "gold", "env": "dev" })),
+        ("b", json!({ "tier": "silver", "env": "prod" })),
+        ("c", json!({ "env": "test" })),
+    ] {
+        let put = test::TestRequest::put()
+            .uri(&format!("/api/mygroup/default/Widget/{name}"))
+            .set_json(json!({
+                "apiVersion": "mygroup/v1",
+                "kind": "Widget",
+                "metadata": { "name": name, "labels": labels },
+                "spec": {}
+            }))
+            .to_request();
+        assert_eq!(test::call_service(&app, put).await.status(), StatusCode::OK);
+    }
+
+    let names = |items: Vec<Value>| -> Vec<String> {
+        let mut names: Vec<String> = items
+            .iter()
+            .map(|o| o["metadata"]["name"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    };
+    let list = |query: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/mygroup/default/Widget?{query}"))
+            .to_request()
+    };
+
+    for (query, expected) in [
+        ("labelSelector=tier%3Dgold", vec!["a"]),
+        ("labelSelector=tier", vec!["a", "b"]),
+        ("labelSelector=!tier", vec!["c"]),
+        ("labelSelector=env%20in%20(dev,test)", vec!["a", "c"]),
+        ("labelSelector=tier!%3Dgold", vec!["b", "c"]),
+        ("fieldSelector=metadata.name%3Db", vec!["b"]),
+        (
+            "labelSelector=env%20notin%20(prod)&fieldSelector=metadata.name!%3Da",
+            vec!["c"],
+        ),
+        (
+            "fieldSelector=metadata.deletionTimestamp%3D",
+            vec!["a", "b", "c"],
+        ),
+    ] {
+        let items: Vec<Value> = test::call_and_read_body_json(&app, list(query)).await;
+        assert_eq!(names(items), expected, "{query}");
+    }
+
+    for query in [
+        "labelSelector=env%20in%20(dev",
+        "fieldSelector=spec.size%3D1",
+    ] {
+        let resp = test::call_service(&app, list(query)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
+
+    // Only the write of the gold widget reached the subscriber.
+    match rx.try_recv() {
+        Ok(ServerMessage::Event { object, .. }) => assert_eq!(object["metadata"]["name"], "a"),
+        other => panic!("expected an event, got {:?}", other),
+    }
+    assert!(rx.try_recv().is_err());
+}
+
+async fn index_entries(store: &Arc<RwLock<InMemoryStore>>) -> Vec<String> {
+    let store = store.read().await;
+    let mut keys = store
+        .list_keys(INDEX_CONTAINER, Some("default/"))
+        .await
+        .unwrap();
+    keys.sort();
+    keys
+}
+
+/// Writes keep the label and indexed-field entries of objects current, lists
+/// with selectors use them once they are complete, and rebuilding indexes
+/// objects written around them.
+#[actix_web::test]
+async fn test_secondary_indexes() {
+    let store = Arc::new(RwLock::new(InMemoryStore::new()));
+    let put_raw = |name: &'static str, tier: &'static str, size: u32| {
+        let store = store.clone();
+        async move {
+            let object = json!({
+                "apiVersion": "indexgroup/v1",
+                "kind": "Gizmo",
+                "metadata": { "name": name, "labels": { "tier": tier } },
+                "spec": { "size": size }
+            });
+            let store = store.write().await;
+            store
+                .put(
+                    "resource",
+                    &format!("default/indexgroup/gizmo/{name}"),
+                    serde_json::to_vec(&object).unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+    };
+
+    // Written before the indexes existed: they are not trusted until rebuilt.
+    store.write().await.new_container("resource").await.unwrap();
+    put_raw("legacy", "gold", 3).await;
+
+    let mut builder = KuiperRuntimeBuilder::new(store.clone());
+    builder.with_index_admin();
+    let rt = Arc::new(builder.build());
+    let subs: SubscriberMap = Arc::new(DashMap::new());
+    let sub_map: SubscriptionMap = Arc::new(DashMap::new());
+    define_kind(
+        &rt,
+        "indexgroup",
+        "Gizmo",
+        json!({ "versions": [{ "name": "v1", "enabled": true, "indexedFields": ["spec.size"] }] }),
+    )
+    .await;
+    let app = {
+        let rt = rt.clone();
+        test::init_service(App::new().configure(move |cfg| {
+            configure_app(cfg, rt.clone(), subs.clone(), sub_map.clone());
+            configure_admin(cfg);
+        }))
+        .await
+    };
+
+    let put = |name: &str, tier: &str, size: u32| {
+        test::TestRequest::put()
+            .uri(&format!("/api/indexgroup/default/Gizmo/{name}"))
+            .set_json(json!({
+                "apiVersion": "indexgroup/v1",
+                "kind": "Gizmo",
+                "metadata": { "name": name, "labels": { "tier": tier } },
+                "spec": { "size": size }
+            }))
+            .to_request()
+    };
+    let list = |query: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/indexgroup/default/Gizmo?{query}"))
+            .to_request()
+    };
+    let names = |items: Vec<Value>| -> Vec<String> {
+        let mut names: Vec<String> = items
+            .iter()
+            .map(|o| o["metadata"]["name"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    };
+    let rebuild = || {
+        test::TestRequest::post()
+            .uri("/admin/rebuild-indexes")
+            .to_request()
+    };
+
+    for (name, tier, size) in [("a", "gold", 1), ("b", "silver", 2)] {
+        let resp = test::call_service(&app, put(name, tier, size)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let items: Vec<Value> =
+        test::call_and_read_body_json(&app, list("labelSelector=tier%3Dgold")).await;
+    assert_eq!(names(items), ["a", "legacy"]);
+
+    let stats: Value = test::call_and_read_body_json(&app, rebuild()).await;
+    assert!(stats["objects"].as_u64().unwrap() >= 3);
+    assert_eq!(
+        index_entries(&store).await,
+        [
+            "default/fields/spec.size=1/indexgroup/gizmo/a",
+            "default/fields/spec.size=2/indexgroup/gizmo/b",
+            "default/fields/spec.size=3/indexgroup/gizmo/legacy",
+            "default/labels/tier=gold/indexgroup/gizmo/a",
+            "default/labels/tier=gold/indexgroup/gizmo/legacy",
+            "default/labels/tier=silver/indexgroup/gizmo/b",
+        ]
+    );
+
+    // Updates and deletes leave no stale entries behind.
+    let resp = test::call_service(&app, put("a", "silver", 2)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let delete = test::TestRequest::delete()
+        .uri("/api/indexgroup/default/Gizmo/b")
+        .to_request();
+    assert!(test::call_service(&app, delete).await.status().is_success());
+    assert_eq!(
+        index_entries(&store).await,
+        [
+            "default/fields/spec.size=2/indexgroup/gizmo/a",
+            "default/fields/spec.size=3/indexgroup/gizmo/legacy",
+            "default/labels/tier=gold/indexgroup/gizmo/legacy",
+            "default/labels/tier=silver/indexgroup/gizmo/a",
+        ]
+    );
+
+    for (query, expected) in [
+        ("labelSelector=tier%3Dsilver", vec!["a"]),
+        (
+            "labelSelector=tier%20in%20(gold,silver)",
+            vec!["a", "legacy"],
+        ),
+        ("fieldSelector=spec.size%3D3", vec!["legacy"]),
+        ("fieldSelector=spec.size%3D2,metadata.name!%3Da", vec![]),
+        (
+            "labelSelector=tier%3Dgold&fieldSelector=spec.size%3D2",
+            vec![],
+        ),
+    ] {
+        let items: Vec<Value> = test::call_and_read_body_json(&app, list(query)).await;
+        assert_eq!(names(items), expected, "{query}");
+    }
+
+    let across = test::TestRequest::get()
+        .uri("/api/indexgroup/*/Gizmo?labelSelector=tier%3Dsilver")
+        .to_request();
+    let items: Vec<Value> = test::call_and_read_body_json(&app, across).await;
+    assert_eq!(names(items), ["a"]);
+
+    // An object written around the indexes is only found by selectors after
+    // a rebuild.
+    put_raw("sneaky", "gold", 4).await;
+    let items: Vec<Value> =
+        test::call_and_read_body_json(&app, list("labelSelector=tier%3Dgold")).await;
+    assert_eq!(names(items), ["legacy"]);
+    let items: Vec<Value> = test::call_and_read_body_json(&app, list("")).await;
+    assert_eq!(names(items), ["a", "legacy", "sneaky"]);
+
+    let resp = test::call_service(&app, rebuild()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let items: Vec<Value> =
+        test::call_and_read_body_json(&app, list("labelSelector=tier%3Dgold")).await;
+    assert_eq!(names(items), ["legacy", "sneaky"]);
+
+    // Dropping the indexed field re-indexes the kind without it.
+    define_kind(
+        &rt,
+        "indexgroup",
+        "Gizmo",
+        json!({ "versions": [{ "name": "v1", "enabled": true }] }),
+    )
+    .await;
+    assert_eq!(
+        index_entries(&store).await,
+        [
+            "default/labels/tier=gold/indexgroup/gizmo/legacy",
+            "default/labels/tier=gold/indexgroup/gizmo/sneaky",
+            "default/labels/tier=silver/indexgroup/gizmo/a",
+        ]
+    );
+    let resp = test::call_service(&app, list("fieldSelector=spec.size%3D3")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Rebuilding is opt-in, for the runtime and for the routes.
+    let (rt, subs, sub_map) = build_runtime().await;
+    let err = rt.send(&RebuildIndexesRequest {}).await.unwrap_err();
+    assert!(err.to_string().contains("rebuild_indexes"), "{err:#}");
+    let app = init_app!(rt, subs, sub_map);
+    let resp = test::call_service(&app, rebuild()).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+async fn namespace_command(rt: &KuiperRuntime, mut ctx: CommandContext) -> anyhow::Result<Value> {
+    ctx.is_internal = true;
+    rt.execute(&mut ctx).await.map(Option::unwrap_or_default)
+}
+
+/// Objects are only created in existing namespaces that are not being
+/// deleted; deleting a namespace deletes its contents, and lists can span
+/// namespaces.
+#[actix_web::test]
+async fn test_namespace_lifecycle_and_all_namespaces_list() {
+    let (rt, subs, sub_map) =
+        build_runtime_with(&Arc::new(RwLock::new(InMemoryStore::new())), |builder| {
+            builder.with_garbage_collector_options(GarbageCollectorOptions {
+                interval: std::time::Duration::from_secs(3600),
+            });
+        })
+        .await;
+    let app = init_app!(rt, subs, sub_map);
+    let gc = rt.garbage_collector().expect("garbage collector enabled");
+
+    let put = |namespace: &str, name: &str| {
+        test::TestRequest::put()
+            .uri(&format!("/api/mygroup/{namespace}/Widget/{name}"))
+            .set_json(object("mygroup/v1", "Widget", name, json!({})))
+            .to_request()
+    };
+    let status = |namespace: &str, name: &str| {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/mygroup/{namespace}/Widget/{name}"))
+            .to_request();
+        async { test::call_service(&app, req).await.status() }
+    };
+
+    let resp = test::call_service(&app, put("team-a", "w1")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let create = SetRequest {
+        namespace: "global".to_string(),
+        resource: namespace_resource("team-a"),
+        value: serde_json::from_value(json!({
+            "apiVersion": "ext.api.cloud-api.dev/v1alpha1",
+            "kind": "Namespace",
+            "metadata": { "name": "team-a" }
+        }))
+        .unwrap(),
+    };
+    namespace_command(&rt, CommandContext::from_request(&create).unwrap())
+        .await
+        .unwrap();
+
+    for (namespace, name) in [("team-a", "w1"), ("team-a", "w2"), ("default", "w3")] {
+        let resp = test::call_service(&app, put(namespace, name)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let list = test::TestRequest::get()
+        .uri("/api/mygroup/*/Widget")
+        .to_request();
+    let items: Vec<Value> = test::call_and_read_body_json(&app, list).await;
+    let mut found: Vec<String> = items
+        .iter()
+        .map(|o| {
+            format!(
+                "{}/{}",
+                o["metadata"]["namespace"].as_str().unwrap(),
+                o["metadata"]["name"].as_str().unwrap()
+            )
+        })
+        .collect();
+    found.sort();
+    assert_eq!(found, ["default/w3", "team-a/w1", "team-a/w2"]);
+
+    let get = test::TestRequest::get()
+        .uri("/api/mygroup/*/Widget/w1")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, get).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    // Deleting the namespace holds it until its contents are gone, and
+    // nothing new can be created in it meanwhile.
+    let delete = DeleteRequest::new("global", &namespace_resource("team-a"));
+    let terminating = namespace_command(&rt, CommandContext::from_request(&delete).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(terminating["metadata"]["finalizers"], json!(["namespace"]));
+    let resp = test::call_service(&app, put("team-a", "w4")).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    while gc.run_pass().await.unwrap() > 0 {}
+    assert_eq!(status("team-a", "w1").await, StatusCode::NOT_FOUND);
+    assert_eq!(status("team-a", "w2").await, StatusCode::NOT_FOUND);
+    assert_eq!(status("default", "w3").await, StatusCode::OK);
+    let get = GetRequest {
+        namespace: "global".to_string(),
+        resource: namespace_resource("team-a"),
+        version: None,
+    };
+    assert!(
+        namespace_command(&rt, CommandContext::from_request(&get).unwrap())
+            .await
+            .is_err()
+    );
+    let resp = test::call_service(&app, put("team-a", "w1")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // The built-in namespaces stay.
+    let delete = DeleteRequest::new("global", &namespace_resource("default"));
+    let err = namespace_command(&rt, CommandContext::from_request(&delete).unwrap())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<KuiperError>(),
+        Some(KuiperError::Forbidden(_))
+    ));
+}
+
+/// Clients create and delete namespaces through the REST API; the rest of
+/// the system group stays reserved.
+#[actix_web::test]
+async fn test_namespace_created_and_deleted_over_rest() {
+    let (rt, subs, sub_map) =
+        build_runtime_with(&Arc::new(RwLock::new(InMemoryStore::new())), |builder| {
+            builder.with_garbage_collector_options(GarbageCollectorOptions {
+                interval: std::time::Duration::from_secs(3600),
+            });
+        })
+        .await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let put_namespace = |namespace: &str, name: &str| {
+        test::TestRequest::put()
+            .uri(&format!(
+                "/api/ext.api.cloud-api.dev/{namespace}/namespaces/{name}"
+            ))
+            .set_json(json!({
+                "apiVersion": "ext.api.cloud-api.dev/v1alpha1",
+                "kind": "Namespace",
+                "metadata": { "name": name }
+            }))
+            .to_request()
+    };
+    let put_widget = |name: &str| {
+        test::TestRequest::put()
+            .uri(&format!("/api/mygroup/team-b/Widget/{name}"))
+            .set_json(object("mygroup/v1", "Widget", name, json!({})))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, put_widget("w1")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = test::call_service(&app, put_namespace("global", "team-b")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, put_widget("w1")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Namespaces live in `global`; built-in namespaces and other system
+    // kinds are still off limits.
+    let resp = test::call_service(&app, put_namespace("default", "team-c")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(&app, put_namespace("global", "default")).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::put()
+        .uri("/api/ext.api.cloud-api.dev/global/resourcedefinitions/widgets")
+        .set_json(json!({
+            "apiVersion": "ext.api.cloud-api.dev/v1alpha1",
+            "kind": "ResourceDefinition",
+            "metadata": { "name": "widgets" }
+        }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let req = test::TestRequest::delete()
+        .uri("/api/ext.api.cloud-api.dev/global/namespaces/team-b")
+        .to_request();
+    let terminating: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(terminating["metadata"]["finalizers"], json!(["namespace"]));
+    let resp = test::call_service(&app, put_widget("w2")).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+/// Strict mode rejects writes of unregistered kinds and versions, and of
+/// kinds outside their scope; it can be turned off.
+#[actix_web::test]
+async fn test_strict_mode_rejects_unregistered_and_misplaced_writes() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    rt.initialize().await.unwrap();
+    define_kind(
+        &rt,
+        "strictgroup",
+        "Gizmo",
+        json!({ "versions": [
+            { "name": "v1", "enabled": true },
+            { "name": "v2", "enabled": false }
+        ] }),
+    )
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let put = |namespace: &str, api_version: &str, kind: &str| {
+        test::TestRequest::put()
+            .uri(&format!("/api/strictgroup/{}/{}/strict", namespace, kind))
+            .set_json(json!({
+                "apiVersion": api_version,
+                "kind": kind,
+                "metadata": { "name": "strict" }
+            }))
+            .to_request()
+    };
+    let status = |req| async { test::call_service(&app, req).await.status() };
+
+    assert_eq!(
+        status(put("default", "strictgroup/v1", "Gizmo")).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        status(put("default", "strictgroup/v1", "Gadget")).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status(put("default", "strictgroup/v3", "Gizmo")).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status(put("default", "strictgroup/v2", "Gizmo")).await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        status(put("global", "strictgroup/v1", "Gizmo")).await,
+        StatusCode::BAD_REQUEST
+    );
+
+    // The body has to be the object at the path: a registered kind cannot
+    // stand in for an unregistered one, another name or another group.
+    let put_at = |uri: &str, name: &str| {
+        test::TestRequest::put()
+            .uri(uri)
+            .set_json(json!({
+                "apiVersion": "strictgroup/v1",
+                "kind": "Gizmo",
+                "metadata": { "name": name }
+            }))
+            .to_request()
+    };
+    for (uri, name) in [
+        ("/api/strictgroup/default/Gadget/strict", "strict"),
+        ("/api/strictgroup/default/Gizmo/strict", "other"),
+        ("/api/othergroup/default/Gizmo/strict", "strict"),
+    ] {
+        assert_eq!(
+            status(put_at(uri, name)).await,
+            StatusCode::BAD_REQUEST,
+            "{uri} {name}"
+        );
+    }
+    let patch = test::TestRequest::patch()
+        .uri("/api/strictgroup/default/Gizmo/strict")
+        .insert_header(("Content-Type", "application/merge-patch+json"))
+        .set_payload(r#"{"metadata":{"name":"renamed"}}"#)
+        .to_request();
+    assert_eq!(status(patch).await, StatusCode::BAD_REQUEST);
+
+    // System kinds only live in `global`.
+    let err = rt
+        .send(&SetRequest {
+            namespace: "default".to_string(),
+            resource: namespace_resource("team-a"),
+            value: serde_json::from_value(json!({
+                "apiVersion": "ext.api.cloud-api.dev/v1alpha1",
+                "kind": "Namespace",
+                "metadata": { "name": "team-a" }
+            }))
+            .unwrap(),
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<KuiperError>(),
+        Some(KuiperError::Invalid(_))
+    ));
+
+    // Without strict mode any kind may be written.
+    let mut builder = KuiperRuntimeBuilder::new(Arc::new(RwLock::new(InMemoryStore::new())));
+    builder.with_strict_mode(false);
+    let lax = Arc::new(builder.build());
+    let app = init_app!(lax, subs, sub_map);
+    let resp = test::call_service(&app, put("default", "strictgroup/v1", "Gadget")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+/// The kind segment of a route may be the kind or its plural, singular or
+/// short name; objects are stored under the kind. Built-in kinds are
+/// reachable the same way.
+#[actix_web::test]
+async fn test_routes_resolve_kind_names() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    rt.initialize().await.unwrap();
+    let app = init_app!(rt, subs, sub_map);
+
+    for (kind, short_name) in [("Gizmo", "gz"), ("Gadget", "g"), ("Gauge", "g")] {
+        let plural = format!("{}s", kind.to_lowercase());
+        let mut ctx = CommandContext::from_request(&SetRequest {
+            namespace: "global".to_string(),
+            resource: format!("ext.api.cloud-api.dev/v1alpha1/ResourceDefinition/{plural}"),
+            value: serde_json::from_value(json!({
+                "apiVersion": "ext.api.cloud-api.dev/v1alpha1",
+                "kind": "ResourceDefinition",
+                "metadata": { "name": plural, "namespace": "global" },
+                "spec": {
+                    "group": "aliasgroup",
+                    "scope": "Namespace",
+                    "names": {
+                        "kind": kind,
+                        "singular": kind.to_lowercase(),
+                        "plural": plural,
+                        "shortNames": [short_name]
+                    },
+                    "versions": [{ "name": "v1", "enabled": true }]
+                }
+            }))
+            .unwrap(),
+        })
+        .unwrap();
+        ctx.is_internal = true;
+        rt.execute(&mut ctx).await.unwrap();
+    }
+
+    for alias in ["resourcedefinitions", "rd"] {
+        let get = test::TestRequest::get()
+            .uri(&format!(
+                "/api/ext.api.cloud-api.dev/global/{}/gizmos",
+                alias
+            ))
+            .to_request();
+        let definition: Value = test::call_and_read_body_json(&app, get).await;
+        assert_eq!(definition["spec"]["names"]["shortNames"], json!(["gz"]));
+    }
+
+    let list = test::TestRequest::get()
+        .uri("/api/ext.api.cloud-api.dev/global/resourcedefinitions")
+        .to_request();
+    let definitions: Vec<Value> = test::call_and_read_body_json(&app, list).await;
+    assert!(definitions
+        .iter()
+        .any(|d| d["metadata"]["name"] == "gizmos"));
+
+    let put = test::TestRequest::put()
+        .uri("/api/aliasgroup/default/gz/one")
+        .set_json(json!({
+            "apiVersion": "aliasgroup/v1",
+            "kind": "Gizmo",
+            "metadata": { "name": "one" }
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, put).await.status(), StatusCode::OK);
+
+    for alias in ["gizmos", "gizmo", "Gizmo", "GZ"] {
+        let get = test::TestRequest::get()
+            .uri(&format!("/api/aliasgroup/default/{}/one", alias))
+            .to_request();
+        let resp = test::call_service(&app, get).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{}", alias);
+    }
+    let stored = rt
+        .send(&GetRequest {
+            namespace: "default".to_string(),
+            resource: "aliasgroup/Gizmo/one".to_string(),
+            version: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(stored.metadata.name, "one");
+
+    let get = test::TestRequest::get()
+        .uri("/api/aliasgroup/default/g/one")
+        .to_request();
+    let resp = test::call_service(&app, get).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body = test::read_body(resp).await;
+    let body = std::str::from_utf8(&body).unwrap();
+    assert!(body.contains("ambiguous") && body.contains("Gadget, Gauge"));
+}
+
+/// `/apis` describes the registered kinds, and is regenerated, with WebSocket
+/// clients told so, when a `ResourceDefinition` is written.
+#[actix_web::test]
+async fn test_discovery_describes_registered_kinds() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    rt.initialize().await.unwrap();
+    let app = init_app!(rt, subs, sub_map);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    subs.insert("client".to_string(), tx);
+
+    let discover = || test::TestRequest::get().uri("/apis").to_request();
+    let discovery: ApiDiscovery = test::call_and_read_body_json(&app, discover()).await;
+
+    let widget = discovery.resource("mygroup", "v1", "Widget").unwrap();
+    assert_eq!(widget.plural, "widgets");
+    assert_eq!(widget.scope, "Namespace");
+    assert!(widget.verbs.iter().any(|v| v == "set"));
+    assert_eq!(widget.subresources, ["finalize"]);
+
+    let definitions = discovery
+        .resource("ext.api.cloud-api.dev", "v1alpha1", "ResourceDefinition")
+        .unwrap();
+    assert_eq!(definitions.scope, "System");
+    assert_eq!(definitions.short_names, ["rd"]);
+    assert!(!definitions.verbs.iter().any(|v| v == "set"));
+    let namespaces = discovery
+        .resource("ext.api.cloud-api.dev", "v1alpha1", "Namespace")
+        .unwrap();
+    assert!(namespaces.verbs.iter().any(|v| v == "set"));
+    assert!(discovery.resource("discogroup", "v1", "Gadget").is_none());
+
+    define_kind(
+        &rt,
+        "discogroup",
+        "Gadget",
+        json!({ "versions": [
+            { "name": "v1", "enabled": true, "subresources": { "status": {} } },
+            { "name": "v2", "enabled": false }
+        ] }),
+    )
+    .await;
+    assert!(matches!(rx.try_recv(), Ok(ServerMessage::DiscoveryChanged)));
+
+    // Clients write System-scoped kinds of their own too.
+    define_kind(
+        &rt,
+        "discogroup",
+        "Region",
+        json!({
+            "scope": "System",
+            "versions": [{ "name": "v1", "enabled": true, "subresources": { "status": {} } }]
+        }),
+    )
+    .await;
+    rx.try_recv().unwrap();
+
+    let discovery: ApiDiscovery = test::call_and_read_body_json(&app, discover()).await;
+    let gadget = discovery.resource("discogroup", "v1", "Gadget").unwrap();
+    assert_eq!(gadget.subresources, ["status", "finalize"]);
+    assert!(discovery.resource("discogroup", "v2", "Gadget").is_none());
+    let region = discovery.resource("discogroup", "v1", "Region").unwrap();
+    assert_eq!(region.scope, "System");
+    assert!(region.verbs.iter().any(|v| v == "set"));
+
+    assert_eq!(rt.send(&DiscoveryRequest {}).await.unwrap(), discovery);
+}
+
+/// Collects every `$ref` and `operationId` in an OpenAPI document.
+fn openapi_refs_and_operations(value: &Value, refs: &mut Vec<String>, ids: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value) {
+                    ("$ref", Value::String(r)) => refs.push(r.clone()),
+                    ("operationId", Value::String(id)) => ids.push(id.clone()),
+                    _ => openapi_refs_and_operations(value, refs, ids),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                openapi_refs_and_operations(item, refs, ids);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `/openapi/v3` covers the fixed routes and those of every registered kind,
+/// and is regenerated when a `ResourceDefinition` is written.
+#[actix_web::test]
+async fn test_openapi_document_covers_registered_kinds() {
+    let (rt, subs, sub_map) = build_runtime().await;
+    rt.initialize().await.unwrap();
+    let app = init_app!(rt, subs, sub_map);
+    let fetch = || test::TestRequest::get().uri("/openapi/v3").to_request();
+
+    let doc: Value = test::call_and_read_body_json(&app, fetch()).await;
+    assert_eq!(doc["openapi"], "3.0.3");
+    for path in ["/version", "/apis", "/openapi/v3", "/api/batch"] {
+        assert!(doc["paths"][path].is_object(), "{}", path);
+    }
+    let widget = &doc["paths"]["/api/mygroup/{namespace}/widgets/{name}"];
+    for method in ["get", "put", "patch", "delete"] {
+        assert!(widget[method].is_object(), "{}", method);
+    }
+    assert!(doc["paths"]["/api/mygroup/{namespace}/widgets/{name}/status"].is_null());
+    let definitions = &doc["paths"]["/api/ext.api.cloud-api.dev/global/resourcedefinitions/{name}"];
+    assert!(definitions["get"].is_object() && definitions["put"].is_null());
+
+    define_kind(
+        &rt,
+        "openapigroup",
+        "Gizmo",
+        json!({ "versions": [
+            {
+                "name": "v1",
+                "enabled": true,
+                "schema": {
+                    "type": "object",
+                    "required": ["size"],
+                    "properties": { "size": { "type": "integer" } }
+                },
+                "subresources": { "status": {} }
+            },
+            { "name": "v2", "enabled": true }
+        ] }),
+    )
+    .await;
+
+    let doc: Value = test::call_and_read_body_json(&app, fetch()).await;
+    let schemas = &doc["components"]["schemas"];
+    assert_eq!(
+        schemas["openapigroup.v1.Gizmo"]["properties"]["spec"]["required"],
+        json!(["size"])
+    );
+    assert_eq!(
+        schemas["openapigroup.v2.Gizmo"]["properties"]["apiVersion"]["enum"],
+        json!(["openapigroup/v2"])
+    );
+    let gizmo = "/api/openapigroup/{namespace}/gizmos/{name}";
+    assert_eq!(
+        doc["paths"][gizmo]["put"]["requestBody"]["content"]["application/json"]["schema"]["oneOf"]
+            .as_array()
+            .map(Vec::len),
+        Some(2)
+    );
+    assert!(doc["paths"][format!("{}/status", gizmo)]["put"].is_object());
+    let namespaces = &doc["paths"]["/api/ext.api.cloud-api.dev/global/namespaces/{name}"];
+    assert!(namespaces["put"].is_object() && namespaces["patch"].is_object());
+
+    define_kind(
+        &rt,
+        "openapigroup",
+        "Region",
+        json!({
+            "scope": "System",
+            "versions": [{ "name": "v1", "enabled": true, "subresources": { "status": {} } }]
+        }),
+    )
+    .await;
+    let doc: Value = test::call_and_read_body_json(&app, fetch()).await;
+    let region = "/api/openapigroup/global/regions/{name}";
+    for method in ["get", "put", "patch", "delete"] {
+        assert!(doc["paths"][region][method].is_object(), "{}", method);
+    }
+    assert!(doc["paths"][format!("{}/status", region)]["put"].is_object());
+
+    // Every reference resolves and every operation has its own id.
+    let (mut refs, mut ids) = (Vec::new(), Vec::new());
+    openapi_refs_and_operations(&doc, &mut refs, &mut ids);
+    for r in &refs {
+        let pointer = r.strip_prefix('#').unwrap();
+        assert!(doc.pointer(pointer).is_some(), "unresolved {}", r);
+    }
+    let count = ids.len();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), count, "duplicate operationId");
+}
+
+/// Objects are stored at the storage version, read at any version through
+/// the declared field mappings, and rewritten when the storage version
+/// changes.
+#[actix_web::test]
+async fn test_storage_version_conversion_and_migration() {
+    let (rt, subs, sub_map) =
+        build_runtime_with(&Arc::new(RwLock::new(InMemoryStore::new())), |builder| {
+            builder.with_storage_migration_options(StorageMigrationOptions {
+                interval: std::time::Duration::from_secs(3600),
+            });
+        })
+        .await;
+    let mappings = json!({
+        "strategy": "FieldMapping",
+        "mappings": [{
+            "from": "v1alpha1",
+            "to": "v1beta1",
+            "fields": [{ "from": "spec.size", "to": "spec.replicas" }]
+        }]
+    });
+    define_kind(
+        &rt,
+        "convgroup",
+        "Gear",
+        json!({
+            "versions": [
+                { "name": "v1alpha1", "enabled": true, "storage": true },
+                { "name": "v1beta1", "enabled": true, "storage": false }
+            ],
+            "conversion": mappings.clone()
+        }),
+    )
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+    let uri = |name: &str| format!("/api/convgroup/default/Gear/{name}");
+
+    let put = test::TestRequest::put()
+        .uri(&uri("a"))
+        .set_json(object(
+            "convgroup/v1alpha1",
+            "Gear",
+            "a",
+            json!({ "spec": { "size": 3 } }),
+        ))
+        .to_request();
+    assert_eq!(test::call_service(&app, put).await.status(), StatusCode::OK);
+
+    // Written at v1beta1, answered at v1beta1, stored at v1alpha1.
+    let put = test::TestRequest::put()
+        .uri(&uri("b"))
+        .set_json(object(
+            "convgroup/v1beta1",
+            "Gear",
+            "b",
+            json!({ "spec": { "replicas": 5 } }),
+        ))
+        .to_request();
+    let written: Value = test::call_and_read_body_json(&app, put).await;
+    assert_eq!(written["apiVersion"], "convgroup/v1beta1");
+    assert_eq!(written["spec"], json!({ "replicas": 5 }));
+
+    let get = |version: Option<&str>| GetRequest {
+        namespace: "default".to_string(),
+        resource: "convgroup/Gear/b".to_string(),
+        version: version.map(str::to_string),
+    };
+    let stored = rt.send(&get(None)).await.unwrap();
+    assert_eq!(stored.api_version, "convgroup/v1alpha1");
+    assert_eq!(stored.extension_data["spec"], json!({ "size": 5 }));
+    let b_version = stored.metadata.resource_version.clone();
+
+    let read = test::TestRequest::get()
+        .uri(&format!("{}?version=v1beta1", uri("a")))
+        .to_request();
+    let read: Value = test::call_and_read_body_json(&app, read).await;
+    assert_eq!(read["apiVersion"], "convgroup/v1beta1");
+    assert_eq!(read["spec"], json!({ "replicas": 3 }));
+
+    let list = test::TestRequest::get()
+        .uri("/api/convgroup/default/gears?version=v1beta1")
+        .to_request();
+    let listed: Vec<Value> = test::call_and_read_body_json(&app, list).await;
+    assert_eq!(listed.len(), 2);
+    assert!(listed
+        .iter()
+        .all(|g| g["apiVersion"] == "convgroup/v1beta1" && g["spec"]["replicas"].is_number()));
+
+    let unknown = test::TestRequest::get()
+        .uri(&format!("{}?version=v2", uri("a")))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, unknown).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    // Reads follow a new storage version before the migrator has run.
+    define_kind(
+        &rt,
+        "convgroup",
+        "Gear",
+        json!({
+            "versions": [
+                { "name": "v1alpha1", "enabled": true, "storage": false },
+                { "name": "v1beta1", "enabled": true, "storage": true }
+            ],
+            "conversion": mappings
+        }),
+    )
+    .await;
+    let stored = rt.send(&get(None)).await.unwrap();
+    assert_eq!(stored.api_version, "convgroup/v1beta1");
+    assert_eq!(stored.extension_data["spec"], json!({ "replicas": 5 }));
+
+    // Migration rewrites both objects once, keeping their resourceVersion.
+    let migrator = rt.storage_migrator().expect("storage migration enabled");
+    assert_eq!(migrator.run_pass().await.unwrap(), 2);
+    assert_eq!(migrator.run_pass().await.unwrap(), 0);
+    let migrated = rt.send(&get(Some("v1alpha1"))).await.unwrap();
+    assert_eq!(migrated.extension_data["spec"], json!({ "size": 5 }));
+    assert_eq!(migrated.metadata.resource_version, b_version);
+    assert_eq!(migrated.metadata.generation, Some(1));
+
+    // Patches and applies are made to the object at their own version.
+    let patch = test::TestRequest::patch()
+        .uri(&format!("{}?version=v1alpha1", uri("a")))
+        .insert_header(("Content-Type", "application/merge-patch+json"))
+        .set_payload(json!({ "spec": { "size": 7 } }).to_string())
+        .to_request();
+    let patched: Value = test::call_and_read_body_json(&app, patch).await;
+    assert_eq!(patched["apiVersion"], "convgroup/v1alpha1");
+    assert_eq!(patched["spec"], json!({ "size": 7 }));
+    let apply = test::TestRequest::patch()
+        .uri(&format!("{}?fieldManager=gearbox", uri("b")))
+        .insert_header(("Content-Type", "application/apply-patch+json"))
+        .set_payload(
+            object(
+                "convgroup/v1alpha1",
+                "Gear",
+                "b",
+                json!({ "spec": { "size": 9 } }),
+            )
+            .to_string(),
+        )
+        .to_request();
+    let applied: Value = test::call_and_read_body_json(&app, apply).await;
+    assert_eq!(applied["spec"], json!({ "size": 9 }));
+    let a = GetRequest {
+        resource: "convgroup/Gear/a".to_string(),
+        ..get(None)
+    };
+    let stored = rt.send(&a).await.unwrap();
+    assert_eq!(stored.extension_data["spec"], json!({ "replicas": 7 }));
+    let stored = rt.send(&get(None)).await.unwrap();
+    assert_eq!(stored.extension_data["spec"], json!({ "replicas": 9 }));
+
+    // A kind has at most one storage version, and mappings must reach every
+    // enabled version from it.
+    for (versions, conversion) in [
+        (
+            json!([
+                { "name": "v1alpha1", "enabled": true, "storage": true },
+                { "name": "v1beta1", "enabled": true, "storage": true }
+            ]),
+            Value::Null,
+        ),
+        (
+            json!([
+                { "name": "v1alpha1", "enabled": true, "storage": true },
+                { "name": "v1beta1", "enabled": true }
+            ]),
+            json!({ "strategy": "FieldMapping", "mappings": [] }),
+        ),
+    ] {
+        let mut ctx = CommandContext::from_request(&SetRequest {
+            namespace: "global".to_string(),
+            resource: "ext.api.cloud-api.dev/v1alpha1/ResourceDefinition/gears".to_string(),
+            value: serde_json::from_value(json!({
+                "apiVersion": "ext.api.cloud-api.dev/v1alpha1",
+                "kind": "ResourceDefinition",
+                "metadata": { "name": "gears", "namespace": "global" },
+                "spec": {
+                    "group": "convgroup",
+                    "scope": "Namespace",
+                    "names": { "kind": "Gear", "singular": "gear", "plural": "gears" },
+                    "versions": versions,
+                    "conversion": conversion
+                }
+            }))
+            .unwrap(),
+        })
+        .unwrap();
+        ctx.is_internal = true;
+        let err = rt.execute(&mut ctx).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<KuiperError>(),
+            Some(KuiperError::Invalid(_))
+        ));
+    }
+}
+
+/// A conversion webhook converts objects on write and read, and cannot
+/// change their metadata.
+#[actix_web::test]
+async fn test_conversion_webhook() {
+    async fn convert(body: actix_web::web::Json<Value>) -> actix_web::HttpResponse {
+        let desired = body["desiredAPIVersion"].as_str().unwrap().to_string();
+        let to_beta = desired.ends_with("v1beta1");
+        let objects: Vec<Value> = body["objects"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|object| {
+                let mut object = object.clone();
+                let spec = object["spec"].as_object_mut().unwrap();
+                let (from, to) = if to_beta {
+                    ("size", "replicas")
+                } else {
+                    ("replicas", "size")
+                };
+                if let Some(value) = spec.remove(from) {
+                    spec.insert(to.to_string(), value);
+                }
+                object["apiVersion"] = json!(desired);
+                object["metadata"]["name"] = json!("renamed");
+                object
+            })
+            .collect();
+        actix_web::HttpResponse::Ok().json(json!({ "objects": objects }))
+    }
+
+    let server = actix_web::HttpServer::new(|| {
+        App::new().route("/convert", actix_web::web::post().to(convert))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let address = server.addrs()[0];
+    let server = server.run();
+    let handle = server.handle();
+    actix_web::rt::spawn(server);
+
+    let (rt, subs, sub_map) =
+        build_runtime_with(&Arc::new(RwLock::new(InMemoryStore::new())), |builder| {
+            builder.with_storage_migration_options(StorageMigrationOptions {
+                interval: std::time::Duration::from_secs(3600),
+            });
+        })
+        .await;
+    let mut ctx = CommandContext::from_request(&SetRequest {
+        namespace: "global".to_string(),
+        resource: "ext.api.cloud-api.dev/v1alpha1/ServiceEndpoint/gear-converter".to_string(),
+        value: serde_json::from_value(json!({
+            "apiVersion": "ext.api.cloud-api.dev/v1alpha1",
+            "kind": "ServiceEndpoint",
+            "metadata": { "name": "gear-converter", "namespace": "global" },
+            "spec": { "url": format!("http://{address}") }
+        }))
+        .unwrap(),
+    })
+    .unwrap();
+    ctx.is_internal = true;
+    rt.execute(&mut ctx).await.unwrap();
+    define_kind(
+        &rt,
+        "convgroup",
+        "Gear",
+        json!({
+            "versions": [
+                { "name": "v1alpha1", "enabled": true, "storage": false },
+                { "name": "v1beta1", "enabled": true, "storage": true }
+            ],
+            "conversion": { "strategy": "Webhook", "serviceEndpoint": "gear-converter" }
+        }),
+    )
+    .await;
+    let app = init_app!(rt, subs, sub_map);
+
+    let put = test::TestRequest::put()
+        .uri("/api/convgroup/default/Gear/w")
+        .set_json(object(
+            "convgroup/v1alpha1",
+            "Gear",
+            "w",
+            json!({ "spec": { "size": 2 } }),
+        ))
+        .to_request();
+    let written: Value = test::call_and_read_body_json(&app, put).await;
+    assert_eq!(written["apiVersion"], "convgroup/v1alpha1");
+    assert_eq!(written["spec"], json!({ "size": 2 }));
+    assert_eq!(written["metadata"]["name"], "w");
+
+    let get = test::TestRequest::get()
+        .uri("/api/convgroup/default/Gear/w")
+        .to_request();
+    let stored: Value = test::call_and_read_body_json(&app, get).await;
+    assert_eq!(stored["apiVersion"], "convgroup/v1beta1");
+    assert_eq!(stored["spec"], json!({ "replicas": 2 }));
+    assert_eq!(stored["metadata"]["name"], "w");
+
+    handle.stop(true).await;
+}